@@ -56,5 +56,69 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Compares loading a bundle of modules into a fresh runtime with and without a warm code cache
+fn code_cache_benchmark(c: &mut Criterion) {
+    // A bundle of 20 modules, each with 200 small functions, all imported by one entrypoint
+    let mut modules: Vec<Module> = (0..20)
+        .map(|i| {
+            let functions: String = (0..200)
+                .map(|j| {
+                    format!("export function f{j}(a, b) {{ return a * {j} + b.length + {i}; }}\n")
+                })
+                .collect();
+            Module::new(format!("bundle/m{i}.js"), &functions)
+        })
+        .collect();
+    let imports: String = (0..20)
+        .map(|i| format!("import * as m{i} from './m{i}.js';\n"))
+        .collect();
+    modules.push(Module::new(
+        "bundle/main.js",
+        &format!("{imports}export default () => m0.f0(1, 'a');"),
+    ));
+
+    let dir = std::env::temp_dir().join("rustyscript_bench_code_cache");
+    std::fs::remove_dir_all(&dir).ok();
+
+    let load = |code_cache_dir: Option<std::path::PathBuf>| {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            code_cache_dir,
+            ..Default::default()
+        })
+        .expect("Could not create runtime");
+        runtime
+            .load_modules_lazy(&modules)
+            .expect("Could not load bundle");
+    };
+
+    c.bench_function("code_cache_cold", |b| b.iter(|| load(None)));
+
+    load(Some(dir.clone()));
+    c.bench_function("code_cache_warm", |b| b.iter(|| load(Some(dir.clone()))));
+
+    // A single large root module, loaded with `load_module`
+    let functions: String = (0..4000)
+        .map(|j| format!("export function f{j}(a, b) {{ return a * {j} + b.length; }}\n"))
+        .collect();
+    let root = Module::new("bundle/root.js", &functions);
+    let load_root = |code_cache_dir: Option<std::path::PathBuf>| {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            code_cache_dir,
+            ..Default::default()
+        })
+        .expect("Could not create runtime");
+        runtime.load_module(&root).expect("Could not load root");
+    };
+
+    c.bench_function("code_cache_root_cold", |b| b.iter(|| load_root(None)));
+
+    load_root(Some(dir.clone()));
+    c.bench_function("code_cache_root_warm", |b| {
+        b.iter(|| load_root(Some(dir.clone())))
+    });
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+criterion_group!(benches, criterion_benchmark, code_cache_benchmark);
 criterion_main!(benches);
@@ -0,0 +1,136 @@
+//! Cancels a runtime's in-flight call from another thread, independently of its timeout
+use crate::Error;
+use deno_core::v8;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, PoisonError,
+};
+use tokio_util::sync::CancellationToken;
+
+/// A cloneable, thread-safe handle that cancels whatever call a runtime is running
+/// See [`crate::Runtime::cancellation_handle`]
+///
+/// Cancelling terminates JS execution, and the in-flight call fails with [`Error::Cancelled`].
+/// The runtime stays usable for later calls - a cancellation only ever affects the call
+/// running at the moment it is requested, and is ignored if nothing is running
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ Runtime, Module, Error, json_args };
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let module = Module::new("test.js", "export function spin() { while (true) {} }");
+/// let module = runtime.load_module(&module)?;
+///
+/// let handle = runtime.cancellation_handle();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_millis(50));
+///     handle.cancel();
+/// });
+///
+/// let result = runtime.call_function::<()>(Some(&module), "spin", json_args!());
+/// assert!(matches!(result, Err(Error::Cancelled)));
+///
+/// // The runtime is still usable afterwards
+/// let value: usize = runtime.eval("1 + 1")?;
+/// assert_eq!(2, value);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CancellationHandle {
+    isolate: v8::IsolateHandle,
+    state: Arc<CancellationState>,
+}
+
+/// Shared between a runtime and its handles
+#[derive(Default)]
+struct CancellationState {
+    /// Cancelled to stop the running call, and replaced once it has stopped
+    token: Mutex<CancellationToken>,
+
+    /// How many cancellable calls are running - nested calls each count once
+    active: AtomicUsize,
+}
+
+impl CancellationHandle {
+    /// Creates a handle for the runtime owning the given isolate
+    pub(crate) fn new(isolate: v8::IsolateHandle) -> Self {
+        Self {
+            isolate,
+            state: Arc::default(),
+        }
+    }
+
+    /// Cancels the call the runtime is currently running, if any
+    /// The call fails with [`Error::Cancelled`]
+    pub fn cancel(&self) {
+        let token = self
+            .state
+            .token
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if self.is_running() && !token.is_cancelled() {
+            token.cancel();
+            self.isolate.terminate_execution();
+        }
+    }
+
+    /// Returns true if a call is currently running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.state.active.load(Ordering::SeqCst) > 0
+    }
+
+    /// Runs a call so that it can be cancelled through this handle
+    pub(crate) async fn run<T>(
+        &self,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let _guard = ActiveCall::enter(self);
+        let token = self.token();
+
+        let result = tokio::select! {
+            result = future => result,
+            () = token.cancelled() => Err(Error::Cancelled),
+        };
+
+        // Terminating the isolate can make the call itself fail first
+        if token.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            result
+        }
+    }
+
+    fn token(&self) -> CancellationToken {
+        self.state
+            .token
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Marks a cancellable call as running, and clears a cancellation once the outermost call ends
+struct ActiveCall<'a>(&'a CancellationHandle);
+impl<'a> ActiveCall<'a> {
+    fn enter(handle: &'a CancellationHandle) -> Self {
+        handle.state.active.fetch_add(1, Ordering::SeqCst);
+        Self(handle)
+    }
+}
+
+impl Drop for ActiveCall<'_> {
+    fn drop(&mut self) {
+        let state = &self.0.state;
+        let mut token = state.token.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.active.fetch_sub(1, Ordering::SeqCst) == 1 && token.is_cancelled() {
+            *token = CancellationToken::new();
+            self.0.isolate.cancel_terminate_execution();
+        }
+    }
+}
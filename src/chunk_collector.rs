@@ -0,0 +1,164 @@
+//! Assembles results too large to return in one value, which a script sends to the host in pieces
+use crate::{Error, Runtime};
+use deno_core::serde_json;
+use serde::de::DeserializeOwned;
+use std::{cell::RefCell, rc::Rc};
+
+/// Collects chunks sent by a script through a registered function
+///
+/// Each argument passed to the function from JS is stored as a chunk, in order.
+/// Once the script is done, the chunks can be assembled into a single typed value
+/// with [`ChunkCollector::collect_items`] or [`ChunkCollector::collect_json`]
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ ChunkCollector, Runtime, Module, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let collector = ChunkCollector::register(&mut runtime, "emit")?;
+///
+/// let module = Module::new("test.js", "
+///     for (let i = 0; i < 10; i += 5) {
+///         rustyscript.functions.emit([i, i + 1, i + 2, i + 3, i + 4]);
+///     }
+/// ");
+/// runtime.load_module(&module)?;
+///
+/// let items: Vec<u32> = collector.collect_items()?;
+/// assert_eq!((0..10).collect::<Vec<_>>(), items);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChunkCollector {
+    chunks: Rc<RefCell<Vec<serde_json::Value>>>,
+}
+
+impl ChunkCollector {
+    /// Registers a function under `name` that scripts call with chunks, as `rustyscript.functions[name](...chunks)`
+    ///
+    /// # Errors
+    /// Will return an error if the function cannot be registered
+    pub fn register(runtime: &mut Runtime, name: &str) -> Result<Self, Error> {
+        let collector = Self::default();
+        let chunks = collector.chunks.clone();
+        runtime.register_function(name, move |args| {
+            chunks.borrow_mut().extend_from_slice(args);
+            Ok(serde_json::Value::Null)
+        })?;
+
+        Ok(collector)
+    }
+
+    /// Number of chunks received, and not yet collected
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.chunks.borrow().len()
+    }
+
+    /// True if no chunks are waiting to be collected
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.chunks.borrow().is_empty()
+    }
+
+    /// Removes and returns the chunks received so far, as they were sent
+    #[must_use]
+    pub fn take_chunks(&self) -> Vec<serde_json::Value> {
+        std::mem::take(&mut *self.chunks.borrow_mut())
+    }
+
+    /// Assembles the chunks into a list of items
+    /// A chunk that is an array contributes each of its elements; any other chunk is a single item
+    ///
+    /// The chunks are removed, so the collector can be reused
+    ///
+    /// # Errors
+    /// Will return an error if an item cannot be deserialized into `T`
+    pub fn collect_items<T>(&self) -> Result<Vec<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut items = Vec::new();
+        for chunk in self.take_chunks() {
+            match chunk {
+                serde_json::Value::Array(values) => {
+                    for value in values {
+                        items.push(serde_json::from_value(value)?);
+                    }
+                }
+                value => items.push(serde_json::from_value(value)?),
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Assembles the chunks, which must be strings, into one JSON document, then deserializes it
+    /// Lets a script send `JSON.stringify(result)` in slices, without ever building a single large value
+    ///
+    /// The chunks are removed, so the collector can be reused
+    ///
+    /// # Errors
+    /// Will return an error if a chunk is not a string, or if the assembled text cannot be deserialized into `T`
+    pub fn collect_json<T>(&self) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut text = String::new();
+        for chunk in self.take_chunks() {
+            match chunk {
+                serde_json::Value::String(s) => text.push_str(&s),
+                other => {
+                    return Err(Error::JsonDecode(format!(
+                        "Expected a string chunk, found `{other}`"
+                    )))
+                }
+            }
+        }
+
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod test_chunk_collector {
+    use super::*;
+    use crate::{Module, RuntimeOptions};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_collect_json() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        let collector =
+            ChunkCollector::register(&mut runtime, "emit").expect("Could not register collector");
+
+        let module = Module::new(
+            "test.js",
+            "
+            const text = JSON.stringify({ a: 1, b: 'two', c: [3] });
+            for (let i = 0; i < text.length; i += 4) {
+                rustyscript.functions.emit(text.slice(i, i + 4));
+            }
+            ",
+        );
+        runtime.load_module(&module).expect("Could not load module");
+        assert!(collector.len() > 1);
+
+        let value: HashMap<String, serde_json::Value> =
+            collector.collect_json().expect("Could not collect chunks");
+        assert_eq!(serde_json::json!(1), value["a"]);
+        assert_eq!(serde_json::json!("two"), value["b"]);
+        assert_eq!(serde_json::json!([3]), value["c"]);
+        assert!(collector.is_empty());
+
+        runtime
+            .eval::<crate::Undefined>("rustyscript.functions.emit(1, 2)")
+            .expect("Could not send chunks");
+        collector
+            .collect_json::<u32>()
+            .expect_err("Chunks were not strings");
+    }
+}
@@ -1,6 +1,7 @@
 //! Contains the error type for the runtime
 //! And some associated utilities
 use crate::Module;
+use deno_core::{serde_json, v8};
 use thiserror::Error;
 
 /// Options for [`Error::as_highlighted`]
@@ -28,6 +29,27 @@ impl Default for ErrorFormattingOptions {
     }
 }
 
+/// How an [`Error`] is rendered by [`Error::format`] and [`crate::Runtime::format_error`]
+#[derive(Clone, Default)]
+pub enum ErrorFormat {
+    /// The error's usual `Display` output
+    #[default]
+    Display,
+
+    /// Only the message - for JS exceptions, without the error's name or stack
+    Message,
+
+    /// A single line of JSON, with `name`, `message`, `stack` and `properties` fields
+    /// See [`Error::to_json`]
+    Json,
+
+    /// The source line highlighted, as in [`Error::as_highlighted`]
+    Highlighted(ErrorFormattingOptions),
+
+    /// A custom formatter
+    Custom(std::rc::Rc<dyn Fn(&Error) -> String>),
+}
+
 /// Represents the errors that can occur during execution of a module
 #[derive(Error, Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Error {
@@ -65,7 +87,7 @@ pub enum Error {
 
     /// Runtime error we successfully downcast
     #[error("{0}")]
-    JsError(#[from] deno_core::error::JsError),
+    JsError(JsError),
 
     /// Triggers when a module times out before finishing
     #[error("Module timed out: {0}")]
@@ -74,6 +96,33 @@ pub enum Error {
     /// Triggers when the heap (via `max_heap_size`) is exhausted during execution
     #[error("Heap exhausted")]
     HeapExhausted,
+
+    /// Triggers when a script recurses past the isolate's stack limit, and does not catch the resulting error
+    /// See [`crate::RuntimeOptions::max_stack_size`]
+    #[error("Maximum call stack size exceeded")]
+    StackOverflow,
+
+    /// Triggers when registering a rust function under a name already in use, if duplicates are rejected
+    /// See [`crate::DuplicateRegistration`]
+    #[error("A function named `{0}` is already registered")]
+    FunctionAlreadyRegistered(String),
+
+    /// Triggers when a call is stopped through a [`crate::CancellationHandle`]
+    #[error("Execution was cancelled")]
+    Cancelled,
+
+    /// Triggers when attempting to use a runtime after [`crate::Runtime::abort_all`]
+    #[error("Runtime execution was aborted")]
+    Aborted,
+
+    /// Triggers when attempting to use a runtime older than its `max_lifetime`
+    /// The runtime should be dropped and recreated
+    #[error("Runtime has exceeded its maximum lifetime, and must be recreated")]
+    LifetimeExceeded,
+
+    /// A structured error with a code and data payload, raised in JS as a `rustyscript.OpError`
+    #[error("{0}")]
+    OpError(#[from] OpError),
 }
 
 impl Error {
@@ -169,6 +218,316 @@ impl Error {
             self.to_string()
         }
     }
+
+    /// Renders the error in the given format
+    /// Useful to give errors the shape a structured logger expects
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Undefined, Error, error::ErrorFormat, serde_json };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let e = runtime.eval::<Undefined>("throw new TypeError('bad input')").unwrap_err();
+    ///
+    /// assert_eq!("bad input", e.format(&ErrorFormat::Message));
+    ///
+    /// let json: serde_json::Value = serde_json::from_str(&e.format(&ErrorFormat::Json))?;
+    /// assert_eq!("TypeError", json["name"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn format(&self, format: &ErrorFormat) -> String {
+        match format {
+            ErrorFormat::Display => self.to_string(),
+            ErrorFormat::Message => match self {
+                Error::JsError(e) => e.message.clone(),
+                Error::OpError(e) => e.message.clone(),
+                _ => self.to_string(),
+            },
+            ErrorFormat::Json => self.to_json().to_string(),
+            ErrorFormat::Highlighted(options) => self.as_highlighted(*options),
+            ErrorFormat::Custom(formatter) => formatter(self),
+        }
+    }
+
+    /// Describes the error as a JSON object, with `name`, `message`, `stack` and `properties` fields
+    ///
+    /// For JS exceptions these are read from the exception. For an [`Error::OpError`], `name` is `OpError`,
+    /// and `properties` holds its `code` and `data`. Other errors are named after their variant, with no stack or properties
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        let (name, message, stack, properties) = match self {
+            Error::JsError(e) => (
+                e.name.as_str(),
+                e.message.clone(),
+                e.stack.clone(),
+                e.properties.clone(),
+            ),
+            Error::OpError(e) => (
+                "OpError",
+                e.message.clone(),
+                None,
+                serde_json::json!({ "code": e.code, "data": e.data }),
+            ),
+            _ => (
+                self.variant_name(),
+                self.to_string(),
+                None,
+                serde_json::json!({}),
+            ),
+        };
+
+        serde_json::json!({
+            "name": name,
+            "message": message,
+            "stack": stack,
+            "properties": properties,
+        })
+    }
+
+    /// The name of the error's variant
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Error::MissingEntrypoint(_) => "MissingEntrypoint",
+            Error::ValueNotFound(_) => "ValueNotFound",
+            Error::ValueNotCallable(_) => "ValueNotCallable",
+            Error::V8Encoding(_) => "V8Encoding",
+            Error::JsonDecode(_) => "JsonDecode",
+            Error::ModuleNotFound(_) => "ModuleNotFound",
+            Error::WorkerHasStopped => "WorkerHasStopped",
+            Error::Runtime(_) => "Runtime",
+            Error::JsError(_) => "JsError",
+            Error::Timeout(_) => "Timeout",
+            Error::HeapExhausted => "HeapExhausted",
+            Error::StackOverflow => "StackOverflow",
+            Error::FunctionAlreadyRegistered(_) => "FunctionAlreadyRegistered",
+            Error::Cancelled => "Cancelled",
+            Error::Aborted => "Aborted",
+            Error::LifetimeExceeded => "LifetimeExceeded",
+            Error::OpError(_) => "OpError",
+        }
+    }
+}
+
+/// The JS error class that [`OpError`]s are built as
+const OP_ERROR_CLASS: &str = "RustyscriptOpError";
+
+/// A structured error for extension ops to return, with a code and data payload
+///
+/// It arrives in JS as an instance of `rustyscript.OpError`, with `code` and `data`
+/// properties, so scripts can handle specific failures from host ops programmatically
+///
+/// Registered functions can raise one too, by returning it as an [`Error::OpError`]
+///
+/// Formats as JSON, so the payload survives the trip to JS
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ OpError, serde_json::json };
+///
+/// #[deno_core::op2(fast)]
+/// fn op_reserve(#[smi] amount: u32) -> Result<(), OpError> {
+///     Err(OpError::new("QUOTA_EXCEEDED", "Not enough quota").with_data(json!({ "requested": amount })))
+/// }
+///
+/// // In JS: try { ... } catch (e) { if (e.code === 'QUOTA_EXCEEDED') console.log(e.data.requested) }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OpError {
+    /// A machine-readable code identifying the failure
+    pub code: String,
+
+    /// A human-readable description of the failure
+    pub message: String,
+
+    /// Additional data about the failure
+    pub data: deno_core::serde_json::Value,
+}
+
+impl OpError {
+    /// Create a new error with the given code and message, and no data
+    pub fn new(code: impl ToString, message: impl ToString) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.to_string(),
+            data: deno_core::serde_json::Value::Null,
+        }
+    }
+
+    /// Attach a data payload to the error
+    /// Data that cannot be serialized is replaced with `null`
+    #[must_use]
+    pub fn with_data(mut self, data: impl serde::Serialize) -> Self {
+        self.data = deno_core::serde_json::to_value(data).unwrap_or_default();
+        self
+    }
+}
+
+impl std::fmt::Display for OpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match deno_core::serde_json::to_string(self) {
+            Ok(json) => write!(f, "{json}"),
+            Err(_) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for OpError {}
+
+/// An exception thrown or rejected by JS, with the details needed to react to it from rust
+///
+/// Derefs to the underlying [`deno_core::error::JsError`], for its stack frames and source line
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ Runtime, Module, Undefined, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// let module = Module::new("test.js", "
+///     class ValidationError extends Error {
+///         constructor(field) {
+///             super(`${field} is invalid`);
+///             this.name = 'ValidationError';
+///             this.field = field;
+///         }
+///     }
+///     export const validate = () => { throw new ValidationError('email'); };
+/// ");
+/// let module = runtime.load_module(&module)?;
+///
+/// match runtime.call_function::<Undefined>(Some(&module), "validate", &()) {
+///     Err(Error::JsError(e)) if e.name == "ValidationError" => {
+///         assert_eq!("email is invalid", e.message);
+///         assert_eq!("email", e.properties["field"]);
+///     }
+///     other => panic!("Unexpected result: {other:?}"),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JsError {
+    /// The error's `name`, such as `TypeError` or a custom `ValidationError`
+    /// `Error` if the thrown value was not an error
+    pub name: String,
+
+    /// The error's `message`, or a description of the thrown value if it was not an error
+    pub message: String,
+
+    /// The error's stack trace, if it had one
+    pub stack: Option<String>,
+
+    /// The thrown value's own enumerable properties, such as data attached by a custom error class
+    /// Always an object - empty if there were none, or they could not be read
+    pub properties: serde_json::Value,
+
+    details: deno_core::error::JsError,
+}
+
+impl From<JsError> for Error {
+    fn from(e: JsError) -> Self {
+        if e.is_stack_overflow() {
+            Error::StackOverflow
+        } else {
+            Error::JsError(e)
+        }
+    }
+}
+
+impl JsError {
+    /// True if this is the `RangeError` v8 throws once the stack limit is reached
+    fn is_stack_overflow(&self) -> bool {
+        self.name == "RangeError" && self.message == "Maximum call stack size exceeded"
+    }
+
+    /// Builds an error from a thrown value, including its properties
+    pub(crate) fn from_exception(
+        scope: &mut v8::HandleScope,
+        exception: v8::Local<v8::Value>,
+    ) -> Self {
+        let details = deno_core::error::JsError::from_v8_exception(scope, exception);
+        Self::from(details).with_properties(scope, exception)
+    }
+
+    /// Reads the own enumerable properties of the thrown value into `properties`
+    /// Properties that cannot be represented as JSON are skipped
+    pub(crate) fn with_properties(
+        mut self,
+        scope: &mut v8::HandleScope,
+        exception: v8::Local<v8::Value>,
+    ) -> Self {
+        let mut properties = serde_json::Map::new();
+        let object = v8::Local::<v8::Object>::try_from(exception).ok();
+        let keys = object.and_then(|object| {
+            object.get_own_property_names(scope, v8::GetPropertyNamesArgs::default())
+        });
+        if let (Some(object), Some(keys)) = (object, keys) {
+            for i in 0..keys.length() {
+                let Some(key) = keys.get_index(scope, i) else {
+                    continue;
+                };
+                let Some(value) = object.get(scope, key) else {
+                    continue;
+                };
+                if let Ok(value) = deno_core::serde_v8::from_v8::<serde_json::Value>(scope, value) {
+                    properties.insert(key.to_rust_string_lossy(scope), value);
+                }
+            }
+        }
+
+        self.properties = serde_json::Value::Object(properties);
+        self
+    }
+}
+
+impl From<deno_core::error::JsError> for JsError {
+    fn from(details: deno_core::error::JsError) -> Self {
+        Self {
+            name: details.name.clone().unwrap_or_else(|| "Error".to_string()),
+            message: details
+                .message
+                .clone()
+                .unwrap_or_else(|| details.exception_message.clone()),
+            stack: details.stack.clone(),
+            properties: serde_json::Value::Object(serde_json::Map::new()),
+            details,
+        }
+    }
+}
+
+impl std::ops::Deref for JsError {
+    type Target = deno_core::error::JsError;
+    fn deref(&self) -> &Self::Target {
+        &self.details
+    }
+}
+
+impl std::fmt::Display for JsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.details.fmt(f)
+    }
+}
+
+impl std::error::Error for JsError {}
+
+impl From<deno_core::error::JsError> for Error {
+    fn from(e: deno_core::error::JsError) -> Self {
+        JsError::from(e).into()
+    }
+}
+
+/// Picks the JS class for an error returned by an op
+pub(crate) fn get_error_class(error: &deno_core::error::AnyError) -> &'static str {
+    let is_op_error = error.downcast_ref::<OpError>().is_some()
+        || matches!(error.downcast_ref::<Error>(), Some(Error::OpError(_)));
+    if is_op_error {
+        OP_ERROR_CLASS
+    } else {
+        deno_core::error::get_custom_error_class(error).unwrap_or("Error")
+    }
 }
 
 #[macro_use]
@@ -187,6 +546,7 @@ mod error_macro {
 }
 
 map_error!(std::cell::BorrowMutError, |e| Error::Runtime(e.to_string()));
+map_error!(std::cell::BorrowError, |e| Error::Runtime(e.to_string()));
 map_error!(std::io::Error, |e| Error::ModuleNotFound(e.to_string()));
 map_error!(deno_core::v8::DataError, |e| Error::Runtime(e.to_string()));
 map_error!(deno_core::ModuleResolutionError, |e| Error::Runtime(
@@ -206,7 +566,7 @@ map_error!(deno_core::anyhow::Error, |e| {
     // trydowncast to deno_core::error::JsError
     let s = e.to_string();
     match e.downcast::<deno_core::error::JsError>() {
-        Ok(js_error) => Error::JsError(js_error),
+        Ok(js_error) => JsError::from(js_error).into(),
         Err(_) => Error::Runtime(s),
     }
 });
@@ -223,7 +583,10 @@ map_error!(deno_core::futures::channel::oneshot::Canceled, |e| {
 
 #[cfg(test)]
 mod test {
-    use crate::{error::ErrorFormattingOptions, Module, Runtime, RuntimeOptions, Undefined};
+    use crate::{
+        error::{ErrorFormat, ErrorFormattingOptions, OpError},
+        json_args, Error, Module, Runtime, RuntimeOptions, Undefined,
+    };
 
     #[test]
     #[rustfmt::skip]
@@ -248,4 +611,154 @@ mod test {
             "= Uncaught (in promise) ReferenceError: x is not defined"
         ));
     }
+
+    #[test]
+    fn test_error_format() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let js_error = runtime
+            .eval::<Undefined>("const e = new RangeError('too big'); e.limit = 3; throw e;")
+            .unwrap_err();
+
+        assert_eq!(js_error.to_string(), js_error.format(&ErrorFormat::Display));
+        assert_eq!("too big", js_error.format(&ErrorFormat::Message));
+        assert_eq!(runtime.format_error(&js_error), js_error.to_string());
+
+        let json = js_error.to_json();
+        assert_eq!("RangeError", json["name"]);
+        assert_eq!("too big", json["message"]);
+        assert!(json["stack"]
+            .as_str()
+            .is_some_and(|s| s.contains("too big")));
+        assert_eq!(3, json["properties"]["limit"]);
+
+        let op_error = Error::OpError(OpError::new("QUOTA", "no quota").with_data(5));
+        let json = op_error.to_json();
+        assert_eq!("OpError", json["name"]);
+        assert_eq!("QUOTA", json["properties"]["code"]);
+        assert_eq!(5, json["properties"]["data"]);
+
+        let json = Error::ValueNotFound("x".to_string()).to_json();
+        assert_eq!("ValueNotFound", json["name"]);
+        assert!(json["stack"].is_null());
+
+        let custom = ErrorFormat::Custom(std::rc::Rc::new(|e: &Error| {
+            format!("[{}]", e.to_json()["name"].as_str().unwrap_or_default())
+        }));
+        assert_eq!("[RangeError]", js_error.format(&custom));
+    }
+
+    #[test]
+    fn test_js_error_details() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            class ValidationError extends Error {
+                constructor(field) {
+                    super(`${field} is invalid`);
+                    this.name = 'ValidationError';
+                    this.field = field;
+                    this.limits = { min: 1, max: 3 };
+                }
+            }
+            export const validate = (field) => { throw new ValidationError(field); };
+            export const validateLater = async (field) => { throw new ValidationError(field); };
+            export const throwString = () => { throw 'plain'; };
+            ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        for name in ["validate", "validateLater"] {
+            let e = runtime
+                .call_function::<Undefined>(Some(&module), name, json_args!("email"))
+                .unwrap_err();
+            let Error::JsError(e) = e else {
+                panic!("Expected a JS error, got {e:?}");
+            };
+            assert_eq!("ValidationError", e.name);
+            assert_eq!("email is invalid", e.message);
+            assert!(e.stack.as_deref().is_some_and(|s| s.contains("validate")));
+            assert_eq!(
+                deno_core::serde_json::json!({
+                    "name": "ValidationError",
+                    "field": "email",
+                    "limits": { "min": 1, "max": 3 }
+                }),
+                e.properties
+            );
+            assert!(!e.frames.is_empty());
+        }
+
+        let e = runtime
+            .call_function::<Undefined>(Some(&module), "throwString", json_args!())
+            .unwrap_err();
+        let Error::JsError(e) = e else {
+            panic!("Expected a JS error, got {e:?}");
+        };
+        assert_eq!("Error", e.name);
+        assert!(e.message.contains("plain"));
+        assert_eq!(deno_core::serde_json::json!({}), e.properties);
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let module = Module::new(
+            "test.js",
+            "
+            export const recurse = (n) => recurse(n + 1) + 1;
+            export const recurseCaught = () => {
+                try {
+                    return recurse(0);
+                } catch (e) {
+                    return e.name;
+                }
+            };
+            ",
+        );
+        let module = runtime.load_module(&module).unwrap();
+
+        let e = runtime
+            .call_function::<Undefined>(Some(&module), "recurse", json_args!(0))
+            .unwrap_err();
+        assert!(matches!(e, Error::StackOverflow), "{e:?}");
+        assert_eq!("StackOverflow", e.to_json()["name"]);
+
+        // The runtime unwinds, and remains usable
+        let name: String = runtime
+            .call_function(Some(&module), "recurseCaught", json_args!())
+            .unwrap();
+        assert_eq!("RangeError", name);
+    }
+
+    #[test]
+    fn test_stack_overflow_small_thread() {
+        // v8's default limit is close to 1MiB, so without max_stack_size this would overflow the thread itself
+        let handle = std::thread::Builder::new()
+            .stack_size(1024 * 1024)
+            .spawn(|| {
+                let mut runtime = crate::RuntimeBuilder::new()
+                    .with_max_stack_size(256 * 1024)
+                    .build()
+                    .unwrap();
+                let module = Module::new(
+                    "test.js",
+                    "export const recurse = (n) => recurse(n + 1) + 1;",
+                );
+                let module = runtime.load_module(&module).unwrap();
+                runtime
+                    .call_function::<Undefined>(Some(&module), "recurse", json_args!(0))
+                    .unwrap_err()
+            })
+            .unwrap();
+
+        let e = handle.join().unwrap();
+        assert!(matches!(e, Error::StackOverflow), "{e:?}");
+
+        // The size is process-wide, so a runtime cannot ask for a different one
+        let result = crate::RuntimeBuilder::new()
+            .with_max_stack_size(512 * 1024)
+            .build();
+        assert!(matches!(result, Err(Error::Runtime(_))));
+    }
 }
@@ -1,12 +1,36 @@
 import * as console from 'ext:deno_console/01_console.js';
 
 import { applyToGlobal, nonEnumerable } from 'ext:rustyscript/rustyscript.js';
+
+// Finds the module that called into the console, using the call stack
+// Frames belonging to extensions are skipped
+const callerSpecifier = () => {
+    const stack = new Error().stack ?? '';
+    for (const line of stack.split('\n').slice(1)) {
+        const match = line.match(/([a-zA-Z][\w+.-]*:\S+?):\d+:\d+\)?$/);
+        if (match && !match[1].startsWith('ext:')) {
+            return match[1];
+        }
+    }
+    return null;
+};
+
 applyToGlobal({
     console: nonEnumerable(
-      new console.Console((msg, _level) => {
-          // TODO(ysh)
-          // This is a temporary solution to get rid of non-JSON output from log
-          rustyscript.functions['console.log'](msg);
+      new console.Console((msg, level) => {
+          switch (Deno.core.ops.op_console_mode()) {
+              // Kept by the runtime - only look up the caller when the host asked for it
+              case 2:
+                  Deno.core.ops.op_console_event(msg, level, callerSpecifier());
+                  break;
+              case 1:
+                  Deno.core.ops.op_console_event(msg, level, null);
+                  break;
+              default:
+                  // TODO(ysh)
+                  // This is a temporary solution to get rid of non-JSON output from log
+                  rustyscript.functions['console.log'](msg);
+          }
       }),
     ),
 });
\ No newline at end of file
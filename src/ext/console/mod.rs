@@ -1,9 +1,39 @@
-use super::ExtensionTrait;
-use deno_core::{extension, Extension};
+use super::{rustyscript::ConsoleHook, ExtensionTrait};
+use deno_core::{extension, op2, Extension, OpState};
+use std::{cell::RefCell, rc::Rc};
+
+/// How console output is delivered:
+/// 0 - the message alone, to the `console.log` function registered by the host
+/// 1 - the message and its level, to the runtime's console hook
+/// 2 - the same, with the specifier of the module that wrote it
+#[op2(fast)]
+fn op_console_mode(state: &OpState) -> u32 {
+    match state.try_borrow::<ConsoleHook>() {
+        None => 0,
+        Some(hook) if hook.locations => 2,
+        Some(_) => 1,
+    }
+}
+
+/// Passes a console message to the runtime's console hook
+#[op2]
+fn op_console_event(
+    state: Rc<RefCell<OpState>>,
+    #[string] message: &str,
+    level: u32,
+    #[serde] specifier: Option<String>,
+) {
+    // Released before the handler runs, so it is free to use the state itself
+    let hook = state.borrow().try_borrow::<ConsoleHook>().cloned();
+    if let Some(hook) = hook {
+        (hook.handler)(crate::ConsoleEvent::new(message, level, specifier));
+    }
+}
 
 extension!(
     init_console,
     deps = [rustyscript],
+    ops = [op_console_mode, op_console_event],
     esm_entry_point = "ext:init_console/init_console.js",
     esm = [ dir "src/ext/console", "init_console.js" ],
 );
@@ -1 +1,8 @@
-import * as io from "ext:deno_io/12_io.js";
\ No newline at end of file
+import * as io from "ext:deno_io/12_io.js";
+
+// Scripts reach stdio through the `Deno` namespace - the host may redirect it, see `IoOptions`
+Object.defineProperties(globalThis.Deno, {
+    stdin: { value: io.stdin, enumerable: true },
+    stdout: { value: io.stdout, enumerable: true },
+    stderr: { value: io.stderr, enumerable: true },
+});
\ No newline at end of file
@@ -1,6 +1,10 @@
 use super::ExtensionTrait;
 use deno_core::{extension, Extension};
 
+mod options;
+pub use options::IoOptions;
+pub(crate) use options::IoThreads;
+
 #[cfg(windows)]
 mod tty_windows;
 #[cfg(windows)]
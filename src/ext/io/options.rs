@@ -0,0 +1,132 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    thread::JoinHandle,
+};
+
+/// Replacement stdio streams for scripts, used by the `io` extension
+///
+/// Streams left unset use the process's own stdio. Each stream that is set is connected to the
+/// runtime through an OS pipe, with a background thread copying data between the two - which
+/// is why they must be `Send`
+///
+/// Dropping the runtime waits for everything scripts wrote to `stdout` and `stderr` to reach
+/// the host's writers, so output is complete once the runtime is gone. The thread feeding `stdin`
+/// stops at its next write once the runtime is dropped - it is not waited for, since the host's
+/// reader may block indefinitely
+///
+/// ```rust
+/// use rustyscript::{ IoOptions, RuntimeBuilder, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let runtime = RuntimeBuilder::new()
+///     .with_io(IoOptions {
+///         stdin: Some(Box::new(std::io::Cursor::new(b"input".to_vec()))),
+///         stdout: Some(Box::new(std::io::sink())),
+///         ..Default::default()
+///     })
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct IoOptions {
+    /// Source of the data scripts read from `Deno.stdin`
+    pub stdin: Option<Box<dyn Read + Send>>,
+
+    /// Destination of the data scripts write to `Deno.stdout`
+    /// Written data is flushed as it arrives
+    pub stdout: Option<Box<dyn Write + Send>>,
+
+    /// Destination of the data scripts write to `Deno.stderr`
+    /// Written data is flushed as it arrives
+    pub stderr: Option<Box<dyn Write + Send>>,
+}
+
+impl IoOptions {
+    /// Returns true if any of the streams is replaced
+    #[must_use]
+    pub fn is_set(&self) -> bool {
+        self.stdin.is_some() || self.stdout.is_some() || self.stderr.is_some()
+    }
+
+    /// Connects the streams to pipes the `deno_io` extension can use, starting the threads feeding them
+    pub(crate) fn into_stdio(self) -> std::io::Result<(deno_io::Stdio, IoThreads)> {
+        let mut threads = IoThreads::default();
+        let mut connect = |writer| {
+            let (file, thread) = pipe_to(writer)?;
+            threads.0.push(thread);
+            Ok::<_, std::io::Error>(deno_io::StdioPipe::file(file))
+        };
+
+        let stdio = deno_io::Stdio {
+            stdin: match self.stdin {
+                Some(reader) => deno_io::StdioPipe::file(pipe_from(reader)?),
+                None => deno_io::StdioPipe::inherit(),
+            },
+            stdout: match self.stdout {
+                Some(writer) => connect(writer)?,
+                None => deno_io::StdioPipe::inherit(),
+            },
+            stderr: match self.stderr {
+                Some(writer) => connect(writer)?,
+                None => deno_io::StdioPipe::inherit(),
+            },
+        };
+        Ok((stdio, threads))
+    }
+}
+
+/// The threads draining the runtime's output pipes into the host's writers
+///
+/// Dropping it waits for them to finish, which they do once the runtime has closed its end of the pipes -
+/// so it must be dropped after the runtime
+#[derive(Default)]
+pub(crate) struct IoThreads(Vec<JoinHandle<()>>);
+
+impl Drop for IoThreads {
+    fn drop(&mut self) {
+        for thread in self.0.drain(..) {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Returns the read end of a pipe fed from `reader`
+fn pipe_from(mut reader: Box<dyn Read + Send>) -> std::io::Result<File> {
+    let (read, mut write) = os_pipe::pipe()?;
+    std::thread::spawn(move || {
+        // Ends once the reader is exhausted, or at the first write after the runtime closes its end
+        std::io::copy(&mut reader, &mut write).ok();
+    });
+    Ok(into_file(read))
+}
+
+/// Returns the write end of a pipe drained into `writer`, and the thread draining it
+fn pipe_to(mut writer: Box<dyn Write + Send>) -> std::io::Result<(File, JoinHandle<()>)> {
+    let (mut read, write) = os_pipe::pipe()?;
+    let thread = std::thread::spawn(move || {
+        // Ends once the runtime closes its end, or the writer fails
+        let mut buffer = [0; 8192];
+        while let Ok(n @ 1..) = read.read(&mut buffer) {
+            if writer
+                .write_all(&buffer[..n])
+                .and_then(|()| writer.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    Ok((into_file(write), thread))
+}
+
+#[cfg(unix)]
+fn into_file(pipe: impl Into<std::os::fd::OwnedFd>) -> File {
+    File::from(pipe.into())
+}
+
+#[cfg(windows)]
+fn into_file(pipe: impl Into<std::os::windows::io::OwnedHandle>) -> File {
+    File::from(pipe.into())
+}
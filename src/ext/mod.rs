@@ -61,6 +61,9 @@ pub struct ExtensionOptions {
     pub web: web::WebOptions,
 
     /// Optional seed for the `deno_crypto` extension
+    ///
+    /// INSECURE: when set, `crypto.getRandomValues`, `crypto.randomUUID` and generated keys are
+    /// deterministic. Only use this for reproducible tests - never in production
     #[cfg(feature = "crypto")]
     pub crypto_seed: Option<u64>,
 
@@ -68,10 +71,19 @@ pub struct ExtensionOptions {
     #[cfg(feature = "io")]
     pub io_pipes: Option<deno_io::Stdio>,
 
+    /// Host-controlled replacements for the stdio streams, used instead of `io_pipes` if any are set
+    #[cfg(feature = "io")]
+    pub io: io::IoOptions,
+
     /// Optional path to the directory where the webstorage extension will store its data
     #[cfg(feature = "webstorage")]
     pub webstorage_origin_storage_dir: Option<std::path::PathBuf>,
 
+    /// Optional limit on the size of each webstorage area
+    /// Untrusted scripts can otherwise fill the backing store without bound
+    #[cfg(feature = "webstorage")]
+    pub webstorage_quota: Option<webstorage::WebStorageQuota>,
+
     /// Optional cache configuration for the `deno_cache` extension
     #[cfg(feature = "cache")]
     pub cache: Option<deno_cache::CreateCache<deno_cache::SqliteBackedCache>>,
@@ -89,15 +101,39 @@ impl Default for ExtensionOptions {
             #[cfg(feature = "io")]
             io_pipes: Some(deno_io::Stdio::default()),
 
+            #[cfg(feature = "io")]
+            io: io::IoOptions::default(),
+
             #[cfg(feature = "webstorage")]
             webstorage_origin_storage_dir: None,
 
+            #[cfg(feature = "webstorage")]
+            webstorage_quota: None,
+
             #[cfg(feature = "cache")]
             cache: None,
         }
     }
 }
 
+impl ExtensionOptions {
+    /// Connects the stdio streams given in `io`, if any, replacing `io_pipes`
+    /// The returned threads must outlive the runtime - see [`io::IoThreads`]
+    #[cfg(feature = "io")]
+    pub(crate) fn connect_io(&mut self) -> Result<io::IoThreads, crate::Error> {
+        let io = std::mem::take(&mut self.io);
+        if !io.is_set() {
+            return Ok(io::IoThreads::default());
+        }
+
+        let (pipes, threads) = io
+            .into_stdio()
+            .map_err(|e| crate::Error::Runtime(format!("Could not connect stdio: {e}")))?;
+        self.io_pipes = Some(pipes);
+        Ok(threads)
+    }
+}
+
 pub(crate) fn all_extensions(
     user_extensions: Vec<Extension>,
     options: ExtensionOptions,
@@ -132,6 +168,7 @@ pub(crate) fn all_extensions(
     #[cfg(feature = "webstorage")]
     extensions.extend(webstorage::extensions(
         options.webstorage_origin_storage_dir,
+        options.webstorage_quota,
         is_snapshot,
     ));
 
@@ -141,3 +178,46 @@ pub(crate) fn all_extensions(
     extensions.extend(user_extensions);
     extensions
 }
+
+/// Checks that a set of runtime-only extensions can be added on top of an existing set of extensions
+/// (usually ones frozen into a snapshot) without conflicting with them
+///
+/// Extensions must have unique names, must not redefine existing ops, and must only depend on
+/// extensions that are present
+pub(crate) fn validate_runtime_extensions(
+    base: &[Extension],
+    additions: &[Extension],
+) -> Result<(), crate::Error> {
+    let mut names: std::collections::HashSet<&str> = base.iter().map(|e| e.name).collect();
+    let mut ops: std::collections::HashSet<&str> = base
+        .iter()
+        .flat_map(|e| e.ops.iter().map(|op| op.name))
+        .collect();
+
+    for extension in additions {
+        if let Some(dep) = extension.deps.iter().find(|dep| !names.contains(*dep)) {
+            return Err(crate::Error::Runtime(format!(
+                "Runtime extension `{}` depends on missing extension `{dep}`",
+                extension.name
+            )));
+        }
+
+        if !names.insert(extension.name) {
+            return Err(crate::Error::Runtime(format!(
+                "Runtime extension `{}` is already loaded",
+                extension.name
+            )));
+        }
+
+        for op in extension.ops.iter() {
+            if !ops.insert(op.name) {
+                return Err(crate::Error::Runtime(format!(
+                    "Runtime extension `{}` redefines op `{}`",
+                    extension.name, op.name
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -83,6 +83,24 @@ export const whitelist = {
     "op_register_entrypoint": "Rustyscript builtin",
     "call_registered_function": "Rustyscript builtin",
     "call_registered_function_async": "Rustyscript builtin",
+    "op_get_blob": "Rustyscript builtin",
+    "op_open_stream": "Rustyscript builtin",
+    "op_stream_next": "Rustyscript builtin",
+    "op_stream_close": "Rustyscript builtin",
+    "op_open_iterator": "Rustyscript builtin",
+    "op_iterator_next": "Rustyscript builtin",
+    "op_iterator_close": "Rustyscript builtin",
+    "op_set_exit_code": "Rustyscript builtin",
+    "op_console_mode": "Rustyscript builtin",
+    "op_console_event": "Rustyscript builtin",
+    "op_script_exit": "Rustyscript builtin",
+    "op_report_unhandled_rejection": "Rustyscript builtin",
+    "op_fetch_concurrency_limit": "Rustyscript builtin",
+    "op_wasm_imports": "Rustyscript builtin",
+    "op_wasm_memory_limit": "Rustyscript builtin",
+    "op_wasm_check_module": "Rustyscript builtin",
+    "op_web_caller": "Rustyscript builtin",
+    "op_set_web_caller": "Rustyscript builtin",
     "op_panic2": "Panic stub to replace op_panic",
 
     //
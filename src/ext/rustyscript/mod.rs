@@ -1,12 +1,75 @@
 use super::ExtensionTrait;
-use crate::{error::Error, RsAsyncFunction, RsFunction};
-use deno_core::{anyhow::anyhow, extension, op2, serde_json, v8, Extension, OpState};
-use std::collections::HashMap;
+use crate::{
+    error::Error, JsIteratorSource, JsStreamSource, RsAsyncFunction, RsFunction,
+    RsIteratorFunction, RsStreamFunction,
+};
+use deno_core::{
+    anyhow::anyhow, extension, futures::StreamExt, op2, serde_json, v8, Extension, OpState,
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 type FnCache = HashMap<String, Box<dyn RsFunction>>;
 type AsyncFnCache = HashMap<String, Box<dyn RsAsyncFunction>>;
+type StreamFnCache = HashMap<String, Box<dyn RsStreamFunction>>;
+type IteratorFnCache = HashMap<String, Box<dyn RsIteratorFunction>>;
+
+/// Streams opened from JS, by id
+/// A stream is taken out of its slot while a value is being read from it
+#[derive(Default)]
+struct StreamPool {
+    next_id: u32,
+    streams: HashMap<u32, Option<JsStreamSource>>,
+}
+
+/// Iterators opened from JS, by id
+#[derive(Default)]
+struct IteratorPool {
+    next_id: u32,
+    iterators: HashMap<u32, JsIteratorSource>,
+}
+
+/// Named byte blobs registered by the host, handed to JS as copies so the originals stay read-only
+pub(crate) type BlobPool = HashMap<String, Vec<u8>>;
+
+/// Bounds how many calls to registered async functions may be in flight at once
+/// Calls past the limit wait for a permit before the function's future is polled
+pub(crate) struct AsyncCallLimit(pub Rc<tokio::sync::Semaphore>);
+
+/// The exit status requested by a script through `rustyscript.exit` or `rustyscript.setExitCode`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ExitCode(pub i32);
+
+/// Handles console output kept by the runtime
+pub(crate) type ConsoleHandler = Rc<dyn Fn(crate::ConsoleEvent)>;
+
+/// Receives console output in place of a `console.log` function registered by the host
+#[derive(Clone)]
+pub(crate) struct ConsoleHook {
+    pub handler: ConsoleHandler,
+
+    /// Whether to find the module that wrote each message, which walks the call stack
+    pub locations: bool,
+}
+
+/// Marks that `rustyscript.exit` terminated execution, which must be cancelled before the next call
+pub(crate) struct ScriptExited;
+
+/// The host's handler for promise rejections that went unhandled
+#[derive(Clone)]
+pub(crate) struct UnhandledRejectionHandler(pub Rc<dyn Fn(crate::JsError)>);
 
 mod callbacks;
+pub(crate) mod wasm;
+
+/// Host functions exposed to WASM modules, as the names registered under each import namespace
+/// Each is registered as a sync function named `namespace.name`
+///
+/// The generation goes up with each registration, so JS only fetches the names again once they change
+#[derive(Default, Clone, serde::Serialize)]
+pub(crate) struct WasmImports {
+    pub generation: u32,
+    pub namespaces: HashMap<String, Vec<String>>,
+}
 
 /// Registers a JS function with the runtime as being the entrypoint for the module
 ///
@@ -43,14 +106,248 @@ fn call_registered_function_async(
     #[serde] args: Vec<serde_json::Value>,
     state: &mut OpState,
 ) -> impl std::future::Future<Output = Result<serde_json::Value, Error>> {
-    if state.has::<AsyncFnCache>() {
-        let table = state.borrow_mut::<AsyncFnCache>();
-        if let Some(callback) = table.get(&name) {
-            return callback(args);
+    let future = state
+        .try_borrow::<AsyncFnCache>()
+        .and_then(|table| table.get(&name))
+        .map(|callback| callback(args));
+    let limit = state
+        .try_borrow::<AsyncCallLimit>()
+        .map(|limit| limit.0.clone());
+
+    async move {
+        let future = future.ok_or(Error::ValueNotCallable(name))?;
+
+        // Hold a permit until the call completes, if concurrency is limited
+        let _permit = match &limit {
+            Some(limit) => Some(
+                limit
+                    .acquire()
+                    .await
+                    .map_err(|e| Error::Runtime(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        future.await
+    }
+}
+
+/// Calls a registered stream function, returning the id of the opened stream
+#[op2]
+#[allow(clippy::needless_pass_by_value)]
+fn op_open_stream(
+    #[string] name: &str,
+    #[serde] args: Vec<serde_json::Value>,
+    state: &mut OpState,
+) -> Result<u32, Error> {
+    let stream = match state
+        .try_borrow::<StreamFnCache>()
+        .and_then(|table| table.get(name))
+    {
+        Some(callback) => callback(&args)?,
+        None => return Err(Error::ValueNotCallable(name.to_string())),
+    };
+
+    if !state.has::<StreamPool>() {
+        state.put(StreamPool::default());
+    }
+    let pool = state.borrow_mut::<StreamPool>();
+    let id = pool.next_id;
+    pool.next_id = pool.next_id.wrapping_add(1);
+    pool.streams.insert(id, Some(stream));
+
+    Ok(id)
+}
+
+/// Reads the next value from an opened stream, as `{ done, value }`
+#[op2(async)]
+#[serde]
+async fn op_stream_next(
+    state: Rc<RefCell<OpState>>,
+    #[smi] id: u32,
+) -> Result<serde_json::Value, Error> {
+    let stream = state
+        .borrow_mut()
+        .try_borrow_mut::<StreamPool>()
+        .and_then(|pool| pool.streams.get_mut(&id))
+        .and_then(Option::take);
+    let Some(mut stream) = stream else {
+        return Ok(serde_json::json!({ "done": true }));
+    };
+
+    let next = stream.next().await;
+
+    let mut state = state.borrow_mut();
+    let Some(pool) = state.try_borrow_mut::<StreamPool>() else {
+        return Ok(serde_json::json!({ "done": true }));
+    };
+    match next {
+        Some(Ok(value)) => {
+            // Put the stream back, unless it was closed in the meantime
+            if let Some(slot) = pool.streams.get_mut(&id) {
+                *slot = Some(stream);
+            }
+            Ok(serde_json::json!({ "done": false, "value": value }))
+        }
+
+        Some(Err(e)) => {
+            pool.streams.remove(&id);
+            Err(e)
+        }
+
+        None => {
+            pool.streams.remove(&id);
+            Ok(serde_json::json!({ "done": true }))
         }
     }
+}
 
-    Box::pin(std::future::ready(Err(Error::ValueNotCallable(name))))
+/// Closes an opened stream, dropping it on the rust side
+#[op2(fast)]
+fn op_stream_close(state: &mut OpState, #[smi] id: u32) {
+    if let Some(pool) = state.try_borrow_mut::<StreamPool>() {
+        pool.streams.remove(&id);
+    }
+}
+
+/// Calls a registered iterator function, returning the id of the opened iterator
+#[op2]
+#[allow(clippy::needless_pass_by_value)]
+fn op_open_iterator(
+    #[string] name: &str,
+    #[serde] args: Vec<serde_json::Value>,
+    state: &mut OpState,
+) -> Result<u32, Error> {
+    let iterator = match state
+        .try_borrow::<IteratorFnCache>()
+        .and_then(|table| table.get(name))
+    {
+        Some(callback) => callback(&args)?,
+        None => return Err(Error::ValueNotCallable(name.to_string())),
+    };
+
+    if !state.has::<IteratorPool>() {
+        state.put(IteratorPool::default());
+    }
+    let pool = state.borrow_mut::<IteratorPool>();
+    let id = pool.next_id;
+    pool.next_id = pool.next_id.wrapping_add(1);
+    pool.iterators.insert(id, iterator);
+
+    Ok(id)
+}
+
+/// Pulls the next value from an opened iterator, as `{ done, value }`
+#[op2]
+#[serde]
+fn op_iterator_next(state: &mut OpState, #[smi] id: u32) -> Result<serde_json::Value, Error> {
+    let Some(pool) = state.try_borrow_mut::<IteratorPool>() else {
+        return Ok(serde_json::json!({ "done": true }));
+    };
+    let next = pool.iterators.get_mut(&id).and_then(Iterator::next);
+    match next {
+        Some(Ok(value)) => Ok(serde_json::json!({ "done": false, "value": value })),
+
+        Some(Err(e)) => {
+            pool.iterators.remove(&id);
+            Err(e)
+        }
+
+        None => {
+            pool.iterators.remove(&id);
+            Ok(serde_json::json!({ "done": true }))
+        }
+    }
+}
+
+/// Closes an opened iterator, dropping it on the rust side
+#[op2(fast)]
+fn op_iterator_close(state: &mut OpState, #[smi] id: u32) {
+    if let Some(pool) = state.try_borrow_mut::<IteratorPool>() {
+        pool.iterators.remove(&id);
+    }
+}
+
+/// Returns a blob registered with `Runtime::register_blob`
+/// Each call gets its own copy, so writes from one script cannot change what later readers see
+#[op2]
+fn op_get_blob<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    state: Rc<RefCell<OpState>>,
+    #[string] name: &str,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    let data = state
+        .borrow()
+        .try_borrow::<BlobPool>()
+        .and_then(|pool| pool.get(name).cloned())
+        .ok_or_else(|| Error::ValueNotFound(name.to_string()))?;
+
+    let store = v8::ArrayBuffer::new_backing_store_from_vec(data).make_shared();
+    Ok(v8::ArrayBuffer::with_backing_store(scope, &store).into())
+}
+
+/// Returns the host functions to add to the imports of WASM modules instantiated from JS,
+/// or `None` if they are unchanged since the given generation
+#[op2]
+#[serde]
+fn op_wasm_imports(state: &mut OpState, #[smi] generation: u32) -> Option<WasmImports> {
+    state
+        .try_borrow::<WasmImports>()
+        .filter(|imports| imports.generation != generation)
+        .cloned()
+}
+
+/// Returns the most pages of memory a WASM memory may grow to, if limited
+#[op2]
+#[serde]
+fn op_wasm_memory_limit(state: &mut OpState) -> Option<u32> {
+    state
+        .try_borrow::<wasm::WasmMemoryLimit>()
+        .map(|limit| limit.0)
+}
+
+/// Rejects a WASM binary whose memory could grow past the configured limit
+#[op2]
+fn op_wasm_check_module(state: &mut OpState, #[anybuffer] bytes: &[u8]) -> Result<(), Error> {
+    match state.try_borrow::<wasm::WasmMemoryLimit>() {
+        Some(limit) => wasm::check_memory(bytes, limit.0),
+        None => Ok(()),
+    }
+}
+
+/// Passes a promise rejection that went unhandled to the host's handler
+/// Returns false if there is none, leaving the rejection to fail the event loop
+#[op2(reentrant)]
+fn op_report_unhandled_rejection(
+    scope: &mut v8::HandleScope,
+    state: Rc<RefCell<OpState>>,
+    reason: v8::Local<v8::Value>,
+) -> bool {
+    let handler = state
+        .borrow()
+        .try_borrow::<UnhandledRejectionHandler>()
+        .cloned();
+    let Some(handler) = handler else {
+        return false;
+    };
+
+    (handler.0)(crate::JsError::from_exception(scope, reason));
+    true
+}
+
+/// Records the exit status requested by the script
+#[op2(fast)]
+fn op_set_exit_code(state: &mut OpState, code: i32) {
+    state.put(ExitCode(code));
+}
+
+/// Records the exit status, then terminates execution so the script cannot catch it
+#[op2]
+fn op_script_exit(scope: &mut v8::HandleScope, state: Rc<RefCell<OpState>>, code: i32) {
+    let mut state = state.borrow_mut();
+    state.put(ExitCode(code));
+    state.put(ScriptExited);
+    scope.terminate_execution();
 }
 
 #[op2(fast)]
@@ -60,7 +357,12 @@ fn op_panic2(#[string] msg: &str) -> Result<(), deno_core::anyhow::Error> {
 
 extension!(
     rustyscript,
-    ops = [op_register_entrypoint, call_registered_function, call_registered_function_async],
+    ops = [
+        op_register_entrypoint, call_registered_function, call_registered_function_async, op_get_blob,
+        op_open_stream, op_stream_next, op_stream_close, op_open_iterator, op_iterator_next,
+        op_iterator_close, op_set_exit_code, op_script_exit, op_wasm_imports, op_wasm_memory_limit, op_wasm_check_module,
+        op_report_unhandled_rejection
+    ],
     esm_entry_point = "ext:rustyscript/rustyscript.js",
     esm = [ dir "src/ext/rustyscript", "rustyscript.js" ],
     middleware = |op| match op.name {
@@ -32,10 +32,132 @@ const getterOnly = (getter) => {
 }
 const applyToGlobal = (properties) => Object.defineProperties(globalThis, properties);
 
+// Structured errors returned by host ops
+class OpError extends Error {
+    constructor(message, code, data) {
+        super(message);
+        this.name = 'OpError';
+        this.code = code;
+        this.data = data;
+    }
+}
+
+Deno.core.registerErrorBuilder('RustyscriptOpError', (json) => {
+    try {
+        const { message, code, data } = JSON.parse(json);
+        return new OpError(message, code, data);
+    } catch {
+        return new OpError(json, 'UNKNOWN', null);
+    }
+});
+
+// Bridge host functions registered with `Runtime::register_wasm_imports` into WASM imports,
+// and hold WASM memories to the limit set by the host, if any
+const WasmOriginals = {
+    compile: WebAssembly.compile,
+    instantiate: WebAssembly.instantiate,
+    compileStreaming: WebAssembly.compileStreaming,
+    instantiateStreaming: WebAssembly.instantiateStreaming,
+};
+
+// The bridged imports are rebuilt only when the host registers more of them
+let hostImports = { generation: 0, namespaces: {} };
+
+const withHostImports = (imports = {}) => {
+    const update = Deno.core.ops.op_wasm_imports(hostImports.generation);
+    if (update !== null) {
+        const namespaces = {};
+        for (const [namespace, names] of Object.entries(update.namespaces)) {
+            namespaces[namespace] = {};
+            for (const name of names) {
+                namespaces[namespace][name] = (...args) => Deno.core.ops.call_registered_function(`${namespace}.${name}`, args);
+            }
+        }
+        hostImports = { generation: update.generation, namespaces };
+    }
+
+    const merged = { ...imports };
+    for (const [namespace, functions] of Object.entries(hostImports.namespaces)) {
+        merged[namespace] = { ...merged[namespace], ...functions };
+    }
+    return merged;
+};
+
+const isWasmModule = (source) => source instanceof WebAssembly.Module;
+
+WebAssembly.compile = async (bytes) => {
+    Deno.core.ops.op_wasm_check_module(bytes);
+    return WasmOriginals.compile(bytes);
+};
+
+WebAssembly.instantiate = async (source, imports) => {
+    if (!isWasmModule(source)) Deno.core.ops.op_wasm_check_module(source);
+    return WasmOriginals.instantiate(source, withHostImports(imports));
+};
+
+if (WasmOriginals.compileStreaming) {
+    WebAssembly.compileStreaming = async (source) => WebAssembly.compile(await (await source).arrayBuffer());
+    WebAssembly.instantiateStreaming = async (source, imports) => WebAssembly.instantiate(await (await source).arrayBuffer(), imports);
+}
+
+// The wrapped constructors replace the originals everywhere they can be reached from,
+// including `prototype.constructor`, and cannot be swapped back
+const replaceConstructor = (name, handler) => {
+    const original = WebAssembly[name];
+    const wrapped = new Proxy(original, handler);
+    for (const [target, key] of [[WebAssembly, name], [original.prototype, 'constructor']]) {
+        Object.defineProperty(target, key, { value: wrapped, writable: false, configurable: false });
+    }
+};
+
+replaceConstructor('Module', {
+    construct(target, [bytes], newTarget) {
+        Deno.core.ops.op_wasm_check_module(bytes);
+        return Reflect.construct(target, [bytes], newTarget);
+    }
+});
+
+replaceConstructor('Instance', {
+    construct(target, [module, imports], newTarget) {
+        return Reflect.construct(target, [module, withHostImports(imports)], newTarget);
+    }
+});
+
+// Memories created from JS default to the limit as their maximum, instead of being unbounded
+// Sizes are read and converted once, so getters cannot report different sizes to the check and to v8
+replaceConstructor('Memory', {
+    construct(target, [descriptor], newTarget) {
+        const limit = Deno.core.ops.op_wasm_memory_limit();
+        if (limit !== null && descriptor) {
+            const initial = Number(descriptor.initial);
+            const maximum = descriptor.maximum === undefined ? limit : Number(descriptor.maximum);
+            if (!(initial <= limit && maximum <= limit)) {
+                throw new RangeError(`WASM memory may not exceed ${limit} pages`);
+            }
+            descriptor = { ...descriptor, initial, maximum };
+        }
+        return Reflect.construct(target, [descriptor], newTarget);
+    }
+});
+
+for (const name of ['compile', 'instantiate', 'compileStreaming', 'instantiateStreaming']) {
+    if (WebAssembly[name]) {
+        Object.defineProperty(WebAssembly, name, { writable: false, configurable: false });
+    }
+}
+
 // Populate the global object
 globalThis.rustyscript = {
     'register_entrypoint': (f) => Deno.core.ops.op_register_entrypoint(f),
     'bail': (msg) => { throw new Error(msg) },
+    'blob': (name) => Deno.core.ops.op_get_blob(name),
+    'OpError': OpError,
+    'setExitCode': (code) => Deno.core.ops.op_set_exit_code(code),
+    'exit': (code = 0) => {
+        Deno.core.ops.op_script_exit(code);
+        // Termination lands at the next interrupt check, which a loop is guaranteed to reach
+        while (true) {}
+    },
     
     'functions': new Proxy({}, {
         get: function(_target, name) {
@@ -45,7 +167,50 @@ globalThis.rustyscript = {
 
     'async_functions': new Proxy({}, {
         get: function(_target, name) {
-            return (...args) => Deno.core.ops.call_registered_function_async(name, args);
+            // Always returns a promise - failures reject it instead of throwing
+            return async (...args) => Deno.core.ops.call_registered_function_async(name, args);
+        }
+    }),
+
+    'stream_functions': new Proxy({}, {
+        get: function(_target, name) {
+            return (...args) => {
+                if (typeof ReadableStream === 'undefined') {
+                    throw new Error('ReadableStream is not enabled in this environment');
+                }
+
+                const id = Deno.core.ops.op_open_stream(name, args);
+                return new ReadableStream({
+                    async pull(controller) {
+                        const chunk = await Deno.core.ops.op_stream_next(id);
+                        if (chunk.done) {
+                            controller.close();
+                        } else {
+                            controller.enqueue(chunk.value);
+                        }
+                    },
+                    cancel() {
+                        Deno.core.ops.op_stream_close(id);
+                    }
+                }, { highWaterMark: 0 });
+            };
+        }
+    }),
+
+    'iterator_functions': new Proxy({}, {
+        get: function(_target, name) {
+            return function* (...args) {
+                const id = Deno.core.ops.op_open_iterator(name, args);
+                try {
+                    while (true) {
+                        const item = Deno.core.ops.op_iterator_next(id);
+                        if (item.done) return;
+                        yield item.value;
+                    }
+                } finally {
+                    Deno.core.ops.op_iterator_close(id);
+                }
+            };
         }
     })
 };
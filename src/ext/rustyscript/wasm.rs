@@ -0,0 +1,86 @@
+//! Checks applied to WASM modules compiled from JS
+use crate::Error;
+
+/// Size of a WASM memory page, in bytes
+pub const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+/// The most pages of memory a WASM module may declare, set by `RuntimeOptions::max_wasm_memory`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WasmMemoryLimit(pub u32);
+
+/// Ensures every memory defined by a WASM binary has a maximum size within `limit` pages
+/// Memories without a maximum are rejected, since nothing would stop them from growing
+/// Imported memories are checked when created through `WebAssembly.Memory` instead
+///
+/// Malformed binaries are let through, and left for v8 to reject on compilation
+pub(crate) fn check_memory(bytes: &[u8], limit: u32) -> Result<(), Error> {
+    let Some(mut reader) = bytes.strip_prefix(b"\0asm").and_then(|b| b.get(4..)) else {
+        return Ok(());
+    };
+
+    while let Some((&id, rest)) = reader.split_first() {
+        reader = rest;
+        let Some(size) = read_leb(&mut reader) else {
+            return Ok(());
+        };
+        let Some(mut section) = usize::try_from(size).ok().and_then(|s| reader.get(..s)) else {
+            return Ok(());
+        };
+        reader = &reader[section.len()..];
+
+        // Only the memory section is of interest
+        if id != 5 {
+            continue;
+        }
+
+        let count = read_leb(&mut section).unwrap_or_default();
+        for _ in 0..count {
+            let Some((&flags, rest)) = section.split_first() else {
+                return Ok(());
+            };
+            section = rest;
+
+            let initial = read_leb(&mut section).unwrap_or_default();
+            let maximum = if flags & 1 == 1 {
+                read_leb(&mut section)
+            } else {
+                None
+            };
+
+            match maximum {
+                _ if initial > u64::from(limit) => {
+                    return Err(Error::Runtime(format!(
+                        "WASM module requires {initial} pages of memory, but the limit is {limit}"
+                    )))
+                }
+                None => {
+                    return Err(Error::Runtime(format!(
+                        "WASM module memory must declare a maximum of at most {limit} pages"
+                    )))
+                }
+                Some(maximum) if maximum > u64::from(limit) => {
+                    return Err(Error::Runtime(format!(
+                        "WASM module allows {maximum} pages of memory, but the limit is {limit}"
+                    )))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads an unsigned LEB128 integer, advancing the slice past it
+fn read_leb(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Some(value);
+        }
+    }
+
+    None
+}
@@ -4,13 +4,135 @@ import * as request from "ext:deno_fetch/23_request.js";
 import * as response from "ext:deno_fetch/23_response.js";
 import * as fetch from "ext:deno_fetch/26_fetch.js";
 import * as eventSource from "ext:deno_fetch/27_eventsource.js";
+import { ReadableStream } from "ext:deno_web/06_streams.js";
 
 Deno.core.setWasmStreamingCallback(fetch.handleWasmStreaming);
 
 import {applyToGlobal, writeable, nonEnumerable} from 'ext:rustyscript/rustyscript.js';
 
+// Limits how many fetches can be in flight at once - excess requests wait in a queue
+// A fetch is in flight until its response body is done with, not just until the headers arrive
+// The limit is read on first use, since it is not known when building a snapshot
+let fetchLimit;
+let activeFetches = 0;
+const fetchQueue = [];
+
+async function acquireFetchSlot() {
+    fetchLimit ??= Deno.core.ops.op_fetch_concurrency_limit();
+    if (fetchLimit === 0 || activeFetches < fetchLimit) {
+        activeFetches++;
+        return;
+    }
+
+    // The slot is handed over directly by the fetch that releases it
+    await new Promise((resolve) => fetchQueue.push(resolve));
+}
+
+function releaseFetchSlot() {
+    const next = fetchQueue.shift();
+    if (next) {
+        next();
+    } else {
+        activeFetches--;
+    }
+}
+
+// Frees the slot of a response whose body was dropped without being read to the end
+const unreadBodies = new FinalizationRegistry((release) => release());
+
+// Keeps the fetch's slot until the response body is fully read, cancelled or errors,
+// or is garbage-collected
+function holdSlotForBody(res) {
+    let released = false;
+    const release = () => {
+        if (!released) {
+            released = true;
+            releaseFetchSlot();
+        }
+    };
+
+    const inner = response.toInnerResponse(res);
+    const body = inner.body?.streamOrStatic;
+    if (fetchLimit === 0 || !(body instanceof ReadableStream)) {
+        release();
+        return res;
+    }
+
+    const reader = body.getReader();
+    const tracked = new ReadableStream({
+        async pull(controller) {
+            try {
+                const { done, value } = await reader.read();
+                if (done) {
+                    release();
+                    controller.close();
+                } else {
+                    controller.enqueue(value);
+                }
+            } catch (e) {
+                release();
+                controller.error(e);
+            }
+        },
+        cancel(reason) {
+            release();
+            return reader.cancel(reason);
+        },
+    }, { highWaterMark: 0 });
+
+    inner.body.streamOrStatic = tracked;
+    unreadBodies.register(tracked, release);
+    return res;
+}
+
+// The module whose fetches are in flight - its permissions apply to every check they make,
+// including those after an await, such as for each redirect followed
+// Fetches from other modules wait until it has none left, so checks are never applied to the wrong module
+let activeCaller = null;
+let callerFetches = 0;
+const callerQueue = [];
+
+async function enterCaller(caller) {
+    while (callerFetches > 0 && activeCaller !== caller) {
+        await new Promise((resolve) => callerQueue.push(resolve));
+    }
+    activeCaller = caller;
+    callerFetches++;
+    Deno.core.ops.op_set_web_caller(caller);
+}
+
+function leaveCaller() {
+    callerFetches--;
+    if (callerFetches === 0) {
+        activeCaller = null;
+        Deno.core.ops.op_set_web_caller("");
+        for (const resolve of callerQueue.splice(0)) {
+            resolve();
+        }
+    }
+}
+
+async function limitedFetch(input, init = undefined) {
+    // The caller must be found before the first await, while its frame is still on the stack
+    const caller = Deno.core.ops.op_web_caller();
+    await acquireFetchSlot();
+    let res;
+    try {
+        await enterCaller(caller);
+        try {
+            res = await fetch.fetch(input, init);
+        } finally {
+            leaveCaller();
+        }
+    } catch (e) {
+        releaseFetchSlot();
+        throw e;
+    }
+    return holdSlotForBody(res);
+}
+
 applyToGlobal({
-    fetch: writeable(fetch.fetch),
+    fetch: writeable(limitedFetch),
     Request: nonEnumerable(request.Request),
     Response: nonEnumerable(response.Response),
     Headers: nonEnumerable(headers.Headers),
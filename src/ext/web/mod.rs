@@ -1,23 +1,94 @@
 use super::ExtensionTrait;
-use deno_core::{extension, Extension};
+use deno_core::{extension, op2, v8, Extension, OpState};
 use std::{rc::Rc, sync::Arc};
 
 mod options;
 pub use options::WebOptions;
 
 mod permissions;
-pub(crate) use permissions::PermissionsContainer;
 pub use permissions::{AllowlistWebPermissions, DefaultWebPermissions, WebPermissions};
+pub(crate) use permissions::{LoggingWebPermissions, ModuleWebPermissions, PermissionsContainer};
+
+/// The most fetches that can be in flight at once, if limited
+struct FetchConcurrencyLimit(Option<usize>);
+
+/// Returns the most fetches that can be in flight at once, or 0 for no limit
+#[op2(fast)]
+#[smi]
+fn op_fetch_concurrency_limit(state: &mut OpState) -> u32 {
+    state
+        .try_borrow::<FetchConcurrencyLimit>()
+        .and_then(|limit| limit.0)
+        .map_or(0, |limit| u32::try_from(limit).unwrap_or(u32::MAX))
+}
+
+/// The only script allowed to set the module making a request
+const WEB_CALLER_SCRIPT: &str = "ext:init_fetch/init_fetch.js";
+
+/// Returns the names of the scripts on the stack, innermost first
+/// Frames without a script name, such as code run by `eval`, are skipped
+fn stack_script_names(scope: &mut v8::HandleScope) -> Vec<String> {
+    let Some(trace) = v8::StackTrace::current_stack_trace(scope, 64) else {
+        return Vec::new();
+    };
+
+    let mut names = Vec::with_capacity(trace.get_frame_count());
+    for i in 0..trace.get_frame_count() {
+        let Some(frame) = trace.get_frame(scope, i) else {
+            continue;
+        };
+        if let Some(name) = frame.get_script_name(scope) {
+            names.push(name.to_rust_string_lossy(scope));
+        }
+    }
+    names
+}
+
+/// Returns the specifier of the innermost non-extension script on the stack, or an empty string
+/// Used to find which module is making a request, so that its own permissions can be applied
+#[op2]
+#[string]
+fn op_web_caller(scope: &mut v8::HandleScope) -> String {
+    stack_script_names(scope)
+        .into_iter()
+        .find(|name| !name.starts_with("ext:"))
+        .unwrap_or_default()
+}
+
+/// Sets the module whose fetches are in flight, or clears it if empty
+/// Only `fetch` may set it - a script calling this directly could otherwise claim another module's permissions
+#[op2]
+fn op_set_web_caller(
+    scope: &mut v8::HandleScope,
+    state: &mut OpState,
+    #[string] caller: &str,
+) -> Result<(), crate::Error> {
+    if stack_script_names(scope).first().map(String::as_str) != Some(WEB_CALLER_SCRIPT) {
+        return Err(crate::Error::Runtime(
+            "The module making a request cannot be set by scripts".to_string(),
+        ));
+    }
+
+    if let Some(permissions) = state.try_borrow::<ModuleWebPermissions>() {
+        permissions.set_caller((!caller.is_empty()).then(|| caller.to_string()));
+    }
+    Ok(())
+}
 
 extension!(
     init_fetch,
     deps = [rustyscript],
+    ops = [op_fetch_concurrency_limit, op_web_caller, op_set_web_caller],
     esm_entry_point = "ext:init_fetch/init_fetch.js",
     esm = [ dir "src/ext/web", "init_fetch.js" ],
+    options = {
+        max_concurrent_fetches: Option<usize>
+    },
+    state = |state, config| state.put(FetchConcurrencyLimit(config.max_concurrent_fetches)),
 );
 impl ExtensionTrait<WebOptions> for init_fetch {
     fn init(options: WebOptions) -> Extension {
-        init_fetch::init_ops_and_esm()
+        init_fetch::init_ops_and_esm(options.max_concurrent_fetches)
     }
 }
 impl ExtensionTrait<WebOptions> for deno_fetch::deno_fetch {
@@ -35,6 +35,13 @@ pub struct WebOptions {
 
     /// Permissions manager for the web related extensions
     pub permissions: Rc<dyn WebPermissions>,
+
+    /// Maximum number of fetches that can be in flight at once
+    /// Further fetches are queued until a running one finishes. Defaults to no limit
+    ///
+    /// A fetch keeps its slot until its response body has been read to the end or cancelled,
+    /// or the response is garbage-collected - so a script holding unread responses can block its own fetches
+    pub max_concurrent_fetches: Option<usize>,
 }
 
 impl Default for WebOptions {
@@ -49,6 +56,7 @@ impl Default for WebOptions {
             client_cert_chain_and_key: deno_tls::TlsKeys::Null,
             file_fetch_handler: std::rc::Rc::new(deno_fetch::DefaultFileFetchHandler),
             permissions: Rc::new(DefaultWebPermissions),
+            max_concurrent_fetches: None,
         }
     }
 }
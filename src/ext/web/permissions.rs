@@ -1,8 +1,11 @@
+use crate::{LogLevel, Logger};
 use deno_core::anyhow::anyhow;
+use deno_core::url::Host;
 use std::{
     borrow::Cow,
     cell::RefCell,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    net::IpAddr,
     path::{Path, PathBuf},
     rc::Rc,
 };
@@ -20,6 +23,146 @@ struct AllowlistWebPermissionsSet {
     pub read_paths: HashSet<String>,
     pub write_paths: HashSet<String>,
     pub hosts: HashSet<String>,
+    pub host_patterns: Vec<HostPattern>,
+    pub cidrs: Vec<IpCidr>,
+}
+
+impl AllowlistWebPermissionsSet {
+    /// Checks a host against the exact hosts, the host patterns and the CIDR ranges
+    fn allows_host(&self, host: &str, port: Option<u16>) -> bool {
+        if self.hosts.contains(host) {
+            return true;
+        }
+
+        match parse_host(host) {
+            Some(Host::Domain(domain)) => self
+                .host_patterns
+                .iter()
+                .any(|pattern| pattern.matches(&domain, port)),
+            Some(Host::Ipv4(ip)) => self.cidrs.iter().any(|cidr| cidr.contains(ip.into())),
+            Some(Host::Ipv6(ip)) => self.cidrs.iter().any(|cidr| cidr.contains(ip.into())),
+            None => false,
+        }
+    }
+}
+
+/// Parses a host as given to `check_host` - a domain (converted to its ASCII form), or an IP address,
+/// with or without the brackets around an IPv6 address
+fn parse_host(host: &str) -> Option<Host<String>> {
+    let unbracketed = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    match unbracketed.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => Some(Host::Ipv4(ip)),
+        Ok(IpAddr::V6(ip)) => Some(Host::Ipv6(ip)),
+        Err(_) => Host::parse(host).ok(),
+    }
+}
+
+/// A domain allowed by [`AllowlistWebPermissions::allow_host_pattern`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct HostPattern {
+    /// The domain, in its ASCII form, without the wildcard
+    domain: String,
+
+    /// If true, matches subdomains of `domain` rather than `domain` itself
+    wildcard: bool,
+
+    /// If set, only this port is allowed
+    port: Option<u16>,
+}
+
+impl HostPattern {
+    fn parse(pattern: &str) -> Result<Self, crate::Error> {
+        let invalid = || crate::Error::Runtime(format!("Invalid host pattern: '{pattern}'"));
+
+        let (host, port) = match pattern.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse::<u16>().map_err(|_| invalid())?)),
+            None => (pattern, None),
+        };
+        let (host, wildcard) = match host.strip_prefix("*.") {
+            Some(host) => (host, true),
+            None => (host, false),
+        };
+
+        match Host::parse(host) {
+            Ok(Host::Domain(domain)) if !domain.contains('*') => Ok(Self {
+                domain,
+                wildcard,
+                port,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn matches(&self, domain: &str, port: Option<u16>) -> bool {
+        if self.port.is_some() && self.port != port {
+            return false;
+        }
+
+        let domain = domain.strip_suffix('.').unwrap_or(domain);
+        if self.wildcard {
+            domain
+                .strip_suffix(self.domain.as_str())
+                .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.'))
+        } else {
+            domain == self.domain
+        }
+    }
+}
+
+/// A range of IP addresses allowed by [`AllowlistWebPermissions::allow_cidr`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct IpCidr {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl IpCidr {
+    fn parse(cidr: &str) -> Result<Self, crate::Error> {
+        let invalid = || crate::Error::Runtime(format!("Invalid CIDR range: '{cidr}'"));
+
+        let (network, prefix) = match cidr.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix)),
+            None => (cidr, None),
+        };
+        let network: IpAddr = network.parse().map_err(|_| invalid())?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| invalid())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(invalid());
+        }
+
+        Ok(Self { network, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 addresses written as IPv4-mapped IPv6 are matched as IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Permissions manager for the web related extensions
@@ -79,6 +222,34 @@ impl AllowlistWebPermissions {
     pub fn deny_host(&self, host: &str) {
         self.0.borrow_mut().hosts.remove(host);
     }
+
+    /// Whitelist every host matching a pattern, for both hosts and URLs
+    ///
+    /// - `*.example.com` matches any subdomain of `example.com`, but not `example.com` itself
+    /// - `example.com` matches only that domain - useful for internationalized names,
+    ///   which are compared in their ASCII (punycode) form
+    /// - A `:port` suffix, such as `*.example.com:8443`, restricts the pattern to that port
+    ///
+    /// # Errors
+    /// Fails if the pattern is not a valid domain
+    pub fn allow_host_pattern(&self, pattern: &str) -> Result<(), crate::Error> {
+        let pattern = HostPattern::parse(pattern)?;
+        self.0.borrow_mut().host_patterns.push(pattern);
+        Ok(())
+    }
+
+    /// Whitelist every IP address in a range, such as `10.0.0.0/8` or `fd00::/8`, for both hosts and URLs
+    /// A single address, without a prefix length, is also accepted
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:10.0.0.1`) are matched against IPv4 ranges
+    ///
+    /// # Errors
+    /// Fails if the range is not valid
+    pub fn allow_cidr(&self, cidr: &str) -> Result<(), crate::Error> {
+        let cidr = IpCidr::parse(cidr)?;
+        self.0.borrow_mut().cidrs.push(cidr);
+        Ok(())
+    }
 }
 impl WebPermissions for AllowlistWebPermissions {
     fn allow_hrtime(&self) -> bool {
@@ -91,7 +262,7 @@ impl WebPermissions for AllowlistWebPermissions {
         port: Option<u16>,
         api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
-        if self.0.borrow().hosts.contains(host) {
+        if self.0.borrow().allows_host(host, port) {
             Ok(())
         } else {
             Err(anyhow!("Host '{}' is not allowed", host))
@@ -103,7 +274,18 @@ impl WebPermissions for AllowlistWebPermissions {
         url: &deno_core::url::Url,
         api_name: &str,
     ) -> Result<(), deno_core::error::AnyError> {
-        if self.0.borrow().url.contains(url.as_str()) {
+        let set = self.0.borrow();
+        let host_allowed = match url.host() {
+            Some(Host::Domain(domain)) => set
+                .host_patterns
+                .iter()
+                .any(|pattern| pattern.matches(domain, url.port_or_known_default())),
+            Some(Host::Ipv4(ip)) => set.cidrs.iter().any(|cidr| cidr.contains(ip.into())),
+            Some(Host::Ipv6(ip)) => set.cidrs.iter().any(|cidr| cidr.contains(ip.into())),
+            None => false,
+        };
+
+        if host_allowed || set.url.contains(url.as_str()) {
             Ok(())
         } else {
             Err(anyhow!("URL '{}' is not allowed", url))
@@ -195,6 +377,220 @@ pub trait WebPermissions {
     ) -> Result<(), deno_core::error::AnyError> {
         Ok(())
     }
+
+    /// Query whether a connection to a host would be allowed, without performing it
+    /// Useful to display or pre-check a script's capabilities
+    fn would_allow_net(&self, host: &str, port: Option<u16>) -> bool {
+        self.check_host(host, port, "would_allow_net").is_ok()
+    }
+
+    /// Query whether a URL would be allowed for fetch or websocket, without performing the request
+    fn would_allow_url(&self, url: &deno_core::url::Url) -> bool {
+        self.check_url(url, "would_allow_url").is_ok()
+    }
+
+    /// Query whether a path would be allowed to be read, without reading it
+    fn would_allow_read(&self, p: &Path) -> bool {
+        self.check_read(p, "would_allow_read").is_ok()
+    }
+
+    /// Query whether a path would be allowed to be written to, without writing it
+    fn would_allow_write(&self, p: &Path) -> bool {
+        self.check_write(p, "would_allow_write").is_ok()
+    }
+}
+
+/// Wraps another permissions manager, reporting any denials to a logger
+pub(crate) struct LoggingWebPermissions {
+    pub inner: Rc<dyn WebPermissions>,
+    pub logger: Rc<dyn Logger>,
+}
+impl LoggingWebPermissions {
+    fn report<T>(
+        &self,
+        api_name: &str,
+        result: Result<T, deno_core::error::AnyError>,
+    ) -> Result<T, deno_core::error::AnyError> {
+        if let Err(e) = &result {
+            self.logger.log(
+                LogLevel::Warn,
+                &format!("Permission denied for {api_name}: {e}"),
+            );
+        }
+        result
+    }
+}
+impl WebPermissions for LoggingWebPermissions {
+    fn allow_hrtime(&self) -> bool {
+        self.inner.allow_hrtime()
+    }
+
+    fn check_url(
+        &self,
+        url: &deno_core::url::Url,
+        api_name: &str,
+    ) -> Result<(), deno_core::error::AnyError> {
+        self.report(api_name, self.inner.check_url(url, api_name))
+    }
+
+    fn check_read<'a>(
+        &self,
+        p: &'a Path,
+        api_name: &str,
+    ) -> Result<Cow<'a, Path>, deno_core::error::AnyError> {
+        self.report(api_name, self.inner.check_read(p, api_name))
+    }
+
+    fn check_write<'a>(
+        &self,
+        p: &'a Path,
+        api_name: &str,
+    ) -> Result<Cow<'a, Path>, deno_core::error::AnyError> {
+        self.report(api_name, self.inner.check_write(p, api_name))
+    }
+
+    fn check_host(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        api_name: &str,
+    ) -> Result<(), deno_core::error::AnyError> {
+        self.report(api_name, self.inner.check_host(host, port, api_name))
+    }
+
+    // Queries are not attempts, so they are not reported as denials
+    fn would_allow_net(&self, host: &str, port: Option<u16>) -> bool {
+        self.inner.would_allow_net(host, port)
+    }
+
+    fn would_allow_url(&self, url: &deno_core::url::Url) -> bool {
+        self.inner.would_allow_url(url)
+    }
+
+    fn would_allow_read(&self, p: &Path) -> bool {
+        self.inner.would_allow_read(p)
+    }
+
+    fn would_allow_write(&self, p: &Path) -> bool {
+        self.inner.would_allow_write(p)
+    }
+}
+
+/// The API name `deno_fetch` gives its permission checks
+const FETCH_API_NAME: &str = "fetch()";
+
+/// Routes permission checks to the permissions set for the module making the request
+/// Unscoped modules get the runtime-wide permissions. Once any module is scoped, checks made
+/// without a known caller are denied, since they could have been started by a scoped module
+///
+/// The caller is only known to `fetch`, which sets it for as long as the module's requests are in flight -
+/// checks made by other APIs in the meantime are not attributed to it
+#[derive(Clone)]
+pub(crate) struct ModuleWebPermissions {
+    default: Rc<dyn WebPermissions>,
+    modules: Rc<RefCell<HashMap<String, Rc<dyn WebPermissions>>>>,
+    caller: Rc<RefCell<Option<String>>>,
+}
+impl ModuleWebPermissions {
+    pub fn new(default: Rc<dyn WebPermissions>) -> Self {
+        Self {
+            default,
+            modules: Rc::default(),
+            caller: Rc::default(),
+        }
+    }
+
+    /// Set the permissions for requests made by the module with the given specifier
+    pub fn set(&self, specifier: &str, permissions: Rc<dyn WebPermissions>) {
+        self.modules
+            .borrow_mut()
+            .insert(specifier.to_string(), permissions);
+    }
+
+    /// Set the module whose fetches are in flight, or `None` once they are done
+    pub fn set_caller(&self, caller: Option<String>) {
+        *self.caller.borrow_mut() = caller;
+    }
+
+    /// The permissions for a check made by the given API
+    fn for_api(&self, api_name: &str) -> Rc<dyn WebPermissions> {
+        if api_name == FETCH_API_NAME {
+            self.active()
+        } else {
+            self.for_caller(None)
+        }
+    }
+
+    fn active(&self) -> Rc<dyn WebPermissions> {
+        self.for_caller(self.caller.borrow().as_deref())
+    }
+
+    fn for_caller(&self, caller: Option<&str>) -> Rc<dyn WebPermissions> {
+        let modules = self.modules.borrow();
+        match caller {
+            Some(caller) => modules
+                .get(caller)
+                .cloned()
+                .unwrap_or_else(|| self.default.clone()),
+            None if modules.is_empty() => self.default.clone(),
+            None => Rc::new(AllowlistWebPermissions::new()),
+        }
+    }
+}
+impl WebPermissions for ModuleWebPermissions {
+    // Timer precision is not a request, so the runtime-wide setting always applies
+    fn allow_hrtime(&self) -> bool {
+        self.default.allow_hrtime()
+    }
+
+    fn check_url(
+        &self,
+        url: &deno_core::url::Url,
+        api_name: &str,
+    ) -> Result<(), deno_core::error::AnyError> {
+        self.for_api(api_name).check_url(url, api_name)
+    }
+
+    fn check_read<'a>(
+        &self,
+        p: &'a Path,
+        api_name: &str,
+    ) -> Result<Cow<'a, Path>, deno_core::error::AnyError> {
+        self.for_api(api_name).check_read(p, api_name)
+    }
+
+    fn check_write<'a>(
+        &self,
+        p: &'a Path,
+        api_name: &str,
+    ) -> Result<Cow<'a, Path>, deno_core::error::AnyError> {
+        self.for_api(api_name).check_write(p, api_name)
+    }
+
+    fn check_host(
+        &self,
+        host: &str,
+        port: Option<u16>,
+        api_name: &str,
+    ) -> Result<(), deno_core::error::AnyError> {
+        self.for_api(api_name).check_host(host, port, api_name)
+    }
+
+    fn would_allow_net(&self, host: &str, port: Option<u16>) -> bool {
+        self.active().would_allow_net(host, port)
+    }
+
+    fn would_allow_url(&self, url: &deno_core::url::Url) -> bool {
+        self.active().would_allow_url(url)
+    }
+
+    fn would_allow_read(&self, p: &Path) -> bool {
+        self.active().would_allow_read(p)
+    }
+
+    fn would_allow_write(&self, p: &Path) -> bool {
+        self.active().would_allow_write(p)
+    }
 }
 
 #[derive(Clone)]
@@ -258,3 +654,118 @@ impl deno_net::NetPermissions for PermissionsContainer {
         self.0.check_write(p, api_name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_would_allow() {
+        let permissions = AllowlistWebPermissions::new();
+        permissions.allow_host("example.com");
+        permissions.allow_url("https://example.com/data.json");
+        permissions.allow_read("/srv/data");
+
+        assert!(permissions.would_allow_net("example.com", Some(443)));
+        assert!(!permissions.would_allow_net("evil.com", None));
+
+        let url = deno_core::url::Url::parse("https://example.com/data.json").unwrap();
+        assert!(permissions.would_allow_url(&url));
+        let url = deno_core::url::Url::parse("https://example.com/other.json").unwrap();
+        assert!(!permissions.would_allow_url(&url));
+
+        assert!(permissions.would_allow_read(Path::new("/srv/data")));
+        assert!(!permissions.would_allow_write(Path::new("/srv/data")));
+
+        assert!(DefaultWebPermissions.would_allow_net("evil.com", None));
+    }
+
+    #[test]
+    fn test_host_patterns() {
+        let permissions = AllowlistWebPermissions::new();
+        permissions.allow_host_pattern("*.example.com").unwrap();
+        permissions.allow_host_pattern("api.internal:8443").unwrap();
+        permissions.allow_host_pattern("bücher.example").unwrap();
+        assert!(permissions.allow_host_pattern("*.").is_err());
+        assert!(permissions.allow_host_pattern("example.com:http").is_err());
+
+        assert!(permissions.would_allow_net("cdn.example.com", Some(443)));
+        assert!(permissions.would_allow_net("A.B.EXAMPLE.COM", None));
+        assert!(!permissions.would_allow_net("example.com", None));
+        assert!(!permissions.would_allow_net("badexample.com", None));
+
+        assert!(permissions.would_allow_net("api.internal", Some(8443)));
+        assert!(!permissions.would_allow_net("api.internal", Some(80)));
+
+        assert!(permissions.would_allow_net("xn--bcher-kva.example", None));
+        assert!(permissions.would_allow_net("bücher.example", None));
+
+        let url = deno_core::url::Url::parse("https://cdn.example.com/lib.js").unwrap();
+        assert!(permissions.would_allow_url(&url));
+        let url = deno_core::url::Url::parse("http://api.internal:8443/v1").unwrap();
+        assert!(permissions.would_allow_url(&url));
+        let url = deno_core::url::Url::parse("http://api.internal/v1").unwrap();
+        assert!(!permissions.would_allow_url(&url));
+    }
+
+    #[test]
+    fn test_cidr() {
+        let permissions = AllowlistWebPermissions::new();
+        permissions.allow_cidr("10.0.0.0/8").unwrap();
+        permissions.allow_cidr("fd00::/8").unwrap();
+        permissions.allow_cidr("192.168.1.1").unwrap();
+        assert!(permissions.allow_cidr("10.0.0.0/33").is_err());
+        assert!(permissions.allow_cidr("example.com/8").is_err());
+
+        assert!(permissions.would_allow_net("10.20.30.40", None));
+        assert!(!permissions.would_allow_net("11.0.0.1", None));
+        assert!(permissions.would_allow_net("192.168.1.1", Some(80)));
+        assert!(!permissions.would_allow_net("192.168.1.2", None));
+
+        assert!(permissions.would_allow_net("fd12::1", None));
+        assert!(permissions.would_allow_net("[fd12::1]", None));
+        assert!(!permissions.would_allow_net("fe80::1", None));
+        assert!(permissions.would_allow_net("::ffff:10.0.0.1", None));
+
+        let url = deno_core::url::Url::parse("http://[fd00::1]:8080/").unwrap();
+        assert!(permissions.would_allow_url(&url));
+        let url = deno_core::url::Url::parse("http://10.1.2.3/").unwrap();
+        assert!(permissions.would_allow_url(&url));
+        let url = deno_core::url::Url::parse("http://8.8.8.8/").unwrap();
+        assert!(!permissions.would_allow_url(&url));
+    }
+
+    #[test]
+    fn test_module_permissions() {
+        let scoped = AllowlistWebPermissions::new();
+        scoped.allow_host("example.com");
+
+        let permissions = ModuleWebPermissions::new(Rc::new(DefaultWebPermissions));
+        permissions.set("file:///scoped.js", Rc::new(scoped));
+
+        // Unscoped callers get the runtime-wide permissions
+        permissions.set_caller(Some("file:///other.js".to_string()));
+        assert!(permissions.would_allow_net("evil.com", None));
+
+        permissions.set_caller(Some("file:///scoped.js".to_string()));
+        assert!(permissions.would_allow_net("example.com", None));
+        assert!(!permissions.would_allow_net("evil.com", None));
+
+        // The caller only applies to fetch
+        assert!(permissions
+            .check_host("example.com", None, "fetch()")
+            .is_ok());
+        assert!(permissions
+            .check_host("example.com", None, "Deno.connect()")
+            .is_err());
+
+        // Unknown callers are denied once a module is scoped
+        permissions.set_caller(None);
+        assert!(!permissions.would_allow_net("evil.com", None));
+        assert!(!permissions.would_allow_net("example.com", None));
+        assert!(permissions.allow_hrtime());
+
+        let permissions = ModuleWebPermissions::new(Rc::new(DefaultWebPermissions));
+        assert!(permissions.would_allow_net("evil.com", None));
+    }
+}
@@ -1,6 +1,19 @@
 import * as webStorage from "ext:deno_webstorage/01_webstorage.js";
 
 import { applyToGlobal, getterOnly, nonEnumerable } from 'ext:rustyscript/rustyscript.js';
+
+// The quota is enforced by the storage ops - this only builds the error they report
+// Without the web extension there is no `DOMException`, so a plain error is given the same name
+Deno.core.registerErrorBuilder('DOMExceptionQuotaExceededError', (message) => {
+    if (typeof DOMException === 'function') {
+        return new DOMException(message, 'QuotaExceededError');
+    }
+
+    const error = new Error(message);
+    error.name = 'QuotaExceededError';
+    return error;
+});
+
 applyToGlobal({
     Storage: nonEnumerable(webStorage.Storage),
     sessionStorage: getterOnly(webStorage.sessionStorage),
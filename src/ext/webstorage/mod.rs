@@ -1,16 +1,212 @@
 use super::ExtensionTrait;
-use deno_core::{extension, Extension};
-use std::path::PathBuf;
+use deno_core::{
+    error::{custom_error, AnyError},
+    extension, op2, Extension, OpState,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+/// What happens when a write would take a storage area over its quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageQuotaPolicy {
+    /// The write fails with a `QuotaExceededError` `DOMException`
+    #[default]
+    Reject,
+
+    /// The least recently written entries are removed until the write fits
+    /// A single entry larger than the quota is still rejected
+    EvictOldest,
+}
+
+/// A limit on the size of each webstorage area (`localStorage` and `sessionStorage`)
+/// Size is measured as the UTF-8 length of every key and value in the area
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebStorageQuota {
+    /// The most bytes a single storage area may hold
+    pub max_bytes: usize,
+
+    /// What to do when a write would exceed `max_bytes`
+    pub policy: StorageQuotaPolicy,
+}
+
+/// Running size of a storage area, and the order its entries were last written in
+/// Built from the stored entries on first use, so data persisted by earlier runs counts towards the quota
+#[derive(Default)]
+struct StorageUsage {
+    total: usize,
+    next_write: u64,
+    entries: HashMap<String, (u64, usize)>,
+    writes: BTreeMap<u64, String>,
+}
+impl StorageUsage {
+    fn size_of(&self, key: &str) -> usize {
+        self.entries.get(key).map_or(0, |(_, size)| *size)
+    }
+
+    fn insert(&mut self, key: &str, size: usize) {
+        self.remove(key);
+        self.entries
+            .insert(key.to_string(), (self.next_write, size));
+        self.writes.insert(self.next_write, key.to_string());
+        self.next_write += 1;
+        self.total += size;
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((write, size)) = self.entries.remove(key) {
+            self.writes.remove(&write);
+            self.total -= size;
+        }
+    }
+
+    /// The least recently written entry, other than `key`
+    fn oldest_except(&self, key: &str) -> Option<String> {
+        self.writes.values().find(|k| *k != key).cloned()
+    }
+}
+
+/// Usage of each storage area, keyed by whether it is persistent (`localStorage`)
+#[derive(Default)]
+struct StorageAreas(HashMap<bool, StorageUsage>);
+
+fn quota_exceeded(max_bytes: usize) -> AnyError {
+    custom_error(
+        "DOMExceptionQuotaExceededError",
+        format!("Storage quota of {max_bytes} bytes exceeded"),
+    )
+}
+
+/// Takes a storage area's usage out of the state, reading it from storage the first time
+/// Existing entries are ordered as storage lists them, which is the order they were written in
+fn take_usage(state: &mut OpState, persistent: bool) -> Result<StorageUsage, AnyError> {
+    if let Some(usage) = state
+        .try_borrow_mut::<StorageAreas>()
+        .and_then(|areas| areas.0.remove(&persistent))
+    {
+        return Ok(usage);
+    }
+
+    let mut usage = StorageUsage::default();
+    for key in deno_webstorage::op_webstorage_iterate_keys::call(state, persistent)? {
+        let value = deno_webstorage::op_webstorage_get::call(state, key.clone(), persistent)?;
+        let size = key.len() + value.map_or(0, |v| v.len());
+        usage.insert(&key, size);
+    }
+    Ok(usage)
+}
+
+fn put_usage(state: &mut OpState, persistent: bool, usage: StorageUsage) {
+    if !state.has::<StorageAreas>() {
+        state.put(StorageAreas::default());
+    }
+    state
+        .borrow_mut::<StorageAreas>()
+        .0
+        .insert(persistent, usage);
+}
+
+/// Writes an entry, evicting the least recently written ones first if the policy allows it
+fn set_within_quota(
+    state: &mut OpState,
+    usage: &mut StorageUsage,
+    quota: WebStorageQuota,
+    key: &str,
+    value: &str,
+    persistent: bool,
+) -> Result<(), AnyError> {
+    let size = key.len() + value.len();
+    while usage.total - usage.size_of(key) + size > quota.max_bytes {
+        let oldest = match quota.policy {
+            StorageQuotaPolicy::EvictOldest => usage.oldest_except(key),
+            StorageQuotaPolicy::Reject => None,
+        };
+        let Some(oldest) = oldest else {
+            return Err(quota_exceeded(quota.max_bytes));
+        };
+
+        deno_webstorage::op_webstorage_remove::call(state, &oldest, persistent)?;
+        usage.remove(&oldest);
+    }
+
+    deno_webstorage::op_webstorage_set::call(state, key, value, persistent)?;
+    usage.insert(key, size);
+    Ok(())
+}
+
+/// Replaces `op_webstorage_set`, enforcing the quota if there is one
+/// Size is the UTF-8 length of the key and value, kept as a running total for each area
+#[op2(fast)]
+fn op_webstorage_set_with_quota(
+    state: &mut OpState,
+    #[string] key: &str,
+    #[string] value: &str,
+    persistent: bool,
+) -> Result<(), AnyError> {
+    let Some(quota) = state
+        .try_borrow::<Option<WebStorageQuota>>()
+        .copied()
+        .flatten()
+    else {
+        return deno_webstorage::op_webstorage_set::call(state, key, value, persistent);
+    };
+    if key.len() + value.len() > quota.max_bytes {
+        return Err(quota_exceeded(quota.max_bytes));
+    }
+
+    let mut usage = take_usage(state, persistent)?;
+    let result = set_within_quota(state, &mut usage, quota, key, value, persistent);
+    put_usage(state, persistent, usage);
+    result
+}
+
+/// Replaces `op_webstorage_remove`, keeping the running size up to date
+#[op2(fast)]
+fn op_webstorage_remove_with_quota(
+    state: &mut OpState,
+    #[string] key: &str,
+    persistent: bool,
+) -> Result<(), AnyError> {
+    deno_webstorage::op_webstorage_remove::call(state, key, persistent)?;
+    if let Some(usage) = state
+        .try_borrow_mut::<StorageAreas>()
+        .and_then(|areas| areas.0.get_mut(&persistent))
+    {
+        usage.remove(key);
+    }
+    Ok(())
+}
+
+/// Replaces `op_webstorage_clear`, keeping the running size up to date
+#[op2(fast)]
+fn op_webstorage_clear_with_quota(state: &mut OpState, persistent: bool) -> Result<(), AnyError> {
+    deno_webstorage::op_webstorage_clear::call(state, persistent)?;
+    if let Some(areas) = state.try_borrow_mut::<StorageAreas>() {
+        areas.0.insert(persistent, StorageUsage::default());
+    }
+    Ok(())
+}
 
 extension!(
     init_webstorage,
     deps = [rustyscript],
     esm_entry_point = "ext:init_webstorage/init_webstorage.js",
     esm = [ dir "src/ext/webstorage", "init_webstorage.js" ],
+    options = {
+        quota: Option<WebStorageQuota>
+    },
+    middleware = |op| match op.name {
+        "op_webstorage_set" => op.with_implementation_from(&op_webstorage_set_with_quota()),
+        "op_webstorage_remove" => op.with_implementation_from(&op_webstorage_remove_with_quota()),
+        "op_webstorage_clear" => op.with_implementation_from(&op_webstorage_clear_with_quota()),
+        _ => op,
+    },
+    state = |state, config| state.put(config.quota),
 );
-impl ExtensionTrait<()> for init_webstorage {
-    fn init(_: ()) -> Extension {
-        init_webstorage::init_ops_and_esm()
+impl ExtensionTrait<Option<WebStorageQuota>> for init_webstorage {
+    fn init(quota: Option<WebStorageQuota>) -> Extension {
+        init_webstorage::init_ops_and_esm(quota)
     }
 }
 impl ExtensionTrait<Option<PathBuf>> for deno_webstorage::deno_webstorage {
@@ -19,9 +215,13 @@ impl ExtensionTrait<Option<PathBuf>> for deno_webstorage::deno_webstorage {
     }
 }
 
-pub fn extensions(origin_storage_dir: Option<PathBuf>, is_snapshot: bool) -> Vec<Extension> {
+pub fn extensions(
+    origin_storage_dir: Option<PathBuf>,
+    quota: Option<WebStorageQuota>,
+    is_snapshot: bool,
+) -> Vec<Extension> {
     vec![
         deno_webstorage::deno_webstorage::build(origin_storage_dir, is_snapshot),
-        init_webstorage::build((), is_snapshot),
+        init_webstorage::build(quota, is_snapshot),
     ]
 }
@@ -2,8 +2,12 @@ use crate::{
     ext,
     module_loader::{LoaderOptions, RustyLoader},
     traits::{ToDefinedValue, ToModuleSpecifier, ToV8String},
-    transpiler::{transpile, transpile_extension},
-    Error, ExtensionOptions, Module, ModuleHandle,
+    transpiler::{
+        find_isolation_violation, transpile, transpile_extension, transpile_with_diagnostics,
+    },
+    type_registry::{self, JsTypeConversion, TypeRegistry},
+    Error, ExportKind, ExportSignature, ExtensionOptions, FunctionKind, LogLevel, Logger, Module,
+    ModuleGraphSize, ModuleHandle,
 };
 use deno_core::{
     futures::FutureExt, serde_json, serde_v8::from_v8, v8, JsRuntime, PollEventLoopOptions,
@@ -11,6 +15,7 @@ use deno_core::{
 use serde::de::DeserializeOwned;
 use std::{
     collections::{HashMap, HashSet},
+    path::Path,
     pin::Pin,
     rc::Rc,
     task::Poll,
@@ -18,6 +23,22 @@ use std::{
 };
 use tokio_util::sync::CancellationToken;
 
+/// Host side of v8's structured clone algorithm
+/// Shared array buffers, host objects and WebAssembly modules are not supported,
+/// so v8 reports them as non-cloneable
+struct StructuredClone;
+impl v8::ValueSerializerImpl for StructuredClone {
+    fn throw_data_clone_error<'s>(
+        &self,
+        scope: &mut v8::HandleScope<'s>,
+        message: v8::Local<'s, v8::String>,
+    ) {
+        let error = v8::Exception::type_error(scope, message);
+        scope.throw_exception(error);
+    }
+}
+impl v8::ValueDeserializerImpl for StructuredClone {}
+
 /// Represents a function that can be registered with the runtime
 pub trait RsFunction:
     Fn(&[serde_json::Value]) -> Result<serde_json::Value, Error> + 'static
@@ -44,6 +65,303 @@ impl<F> RsAsyncFunction for F where
 {
 }
 
+/// A stream of values produced by the host
+/// Returned by an [`RsStreamFunction`], and consumed in JS as a `ReadableStream`
+pub type JsStreamSource =
+    Pin<Box<dyn deno_core::futures::Stream<Item = Result<serde_json::Value, Error>>>>;
+
+/// Represents a function returning a stream that can be registered with the runtime
+pub trait RsStreamFunction:
+    Fn(&[serde_json::Value]) -> Result<JsStreamSource, Error> + 'static
+{
+}
+impl<F> RsStreamFunction for F where
+    F: Fn(&[serde_json::Value]) -> Result<JsStreamSource, Error> + 'static
+{
+}
+
+/// A sequence of values produced by the host, pulled one at a time
+/// Returned by an [`RsIteratorFunction`], and consumed in JS as a generator
+pub type JsIteratorSource = Box<dyn Iterator<Item = Result<serde_json::Value, Error>>>;
+
+/// Represents a function returning an iterator that can be registered with the runtime
+pub trait RsIteratorFunction:
+    Fn(&[serde_json::Value]) -> Result<JsIteratorSource, Error> + 'static
+{
+}
+impl<F> RsIteratorFunction for F where
+    F: Fn(&[serde_json::Value]) -> Result<JsIteratorSource, Error> + 'static
+{
+}
+
+/// Progress information for a running event loop
+/// See [`crate::Runtime::await_event_loop_with_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EventLoopProgress {
+    /// Number of times the event loop has been polled so far
+    pub ticks: u64,
+
+    /// Time since the event loop started running
+    pub elapsed: Duration,
+}
+
+/// The kind of an outstanding operation - see [`OpInfo`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    /// An async op that has not completed, such as a fetch
+    AsyncOp,
+
+    /// An open resource, such as a socket or file handle
+    Resource,
+
+    /// A timer created with `setTimeout`
+    Timer,
+
+    /// A repeating timer created with `setInterval`
+    Interval,
+}
+
+/// An outstanding operation that is keeping the event loop alive
+/// See [`crate::Runtime::pending_ops`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpInfo {
+    /// What kind of operation this is
+    pub kind: OpKind,
+
+    /// The name of the op or resource, or of the function that created the timer
+    pub name: String,
+}
+
+/// The severity of a message written to the JS console
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConsoleLevel {
+    /// `console.debug`
+    Debug,
+
+    /// `console.log` and `console.info`
+    Info,
+
+    /// `console.warn`
+    Warn,
+
+    /// `console.error`
+    Error,
+}
+
+/// A message written to the JS console, captured by the runtime
+/// See [`RuntimeOptions::capture_console`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleEvent {
+    /// The formatted message, without its trailing newline
+    pub message: String,
+
+    /// The severity of the message
+    pub level: ConsoleLevel,
+
+    /// Specifier of the module that wrote the message
+    /// Only looked up if [`RuntimeOptions::console_locations`] is set, and `None` if it could not be found
+    pub specifier: Option<String>,
+}
+
+impl ConsoleEvent {
+    /// Builds an event from a message and level as reported by the console extension
+    pub(crate) fn new(message: &str, level: u32, specifier: Option<String>) -> Self {
+        let level = match level {
+            0 => ConsoleLevel::Debug,
+            2 => ConsoleLevel::Warn,
+            3 => ConsoleLevel::Error,
+            _ => ConsoleLevel::Info,
+        };
+
+        Self {
+            message: message.strip_suffix('\n').unwrap_or(message).to_string(),
+            level,
+            specifier,
+        }
+    }
+}
+
+/// Console output collected by [`crate::Runtime::run_captured`], split into streams
+/// Each stream keeps at most the given number of bytes - output past that is dropped,
+/// and the stream is marked as truncated
+///
+/// The streams are named after where the console would print each message - they hold
+/// console messages only, not data written to the process's stdout and stderr
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedOutput {
+    /// Messages written with `console.log`, `console.info` and `console.debug`, one per line
+    pub stdout: String,
+
+    /// Messages written with `console.warn` and `console.error`, one per line
+    pub stderr: String,
+
+    /// True if output to stdout exceeded the limit, and was cut short
+    pub stdout_truncated: bool,
+
+    /// True if output to stderr exceeded the limit, and was cut short
+    pub stderr_truncated: bool,
+}
+
+impl CapturedOutput {
+    /// Appends a console message to the matching stream, keeping it within `limit` bytes
+    fn push(&mut self, event: &ConsoleEvent, limit: usize) {
+        let (stream, truncated) = match event.level {
+            ConsoleLevel::Debug | ConsoleLevel::Info => {
+                (&mut self.stdout, &mut self.stdout_truncated)
+            }
+            ConsoleLevel::Warn | ConsoleLevel::Error => {
+                (&mut self.stderr, &mut self.stderr_truncated)
+            }
+        };
+        if *truncated {
+            return;
+        }
+
+        let line = format!("{}\n", event.message);
+        let room = limit.saturating_sub(stream.len());
+        if line.len() <= room {
+            stream.push_str(&line);
+        } else {
+            let mut end = room;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            stream.push_str(&line[..end]);
+            *truncated = true;
+        }
+    }
+}
+
+/// What happens when a rust function is registered under a name that is already in use
+/// Applies to each kind of function separately - a sync and an async function may share a name
+/// See [`RuntimeOptions::duplicate_registration`]
+///
+/// Only functions registered one at a time follow this policy. Batch registration - `register_functions`,
+/// `register_async_functions` and `register_wasm_imports` - always rejects a batch containing a name in use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DuplicateRegistration {
+    /// The new function replaces the earlier one
+    #[default]
+    Overwrite,
+
+    /// Registration fails with [`Error::FunctionAlreadyRegistered`]
+    Reject,
+
+    /// The earlier function is kept, and the new one is silently dropped
+    KeepFirst,
+}
+
+/// A snapshot of the isolate's memory use, mirroring v8's `HeapStatistics`
+/// See [`crate::Runtime::heap_stats`]
+///
+/// All sizes are in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct HeapStats {
+    /// Memory reserved for the JS heap
+    pub total_heap_size: usize,
+
+    /// Memory reserved for executable code in the JS heap
+    pub total_heap_size_executable: usize,
+
+    /// Memory committed to the JS heap by the operating system
+    pub total_physical_size: usize,
+
+    /// Memory still available to the JS heap before it reaches its limit
+    pub total_available_size: usize,
+
+    /// Memory used by live objects in the JS heap
+    pub used_heap_size: usize,
+
+    /// The limit the JS heap may grow to
+    pub heap_size_limit: usize,
+
+    /// Memory allocated by v8 outside the JS heap
+    pub malloced_memory: usize,
+
+    /// The most memory v8 has allocated outside the JS heap
+    pub peak_malloced_memory: usize,
+
+    /// Memory held by objects outside the heap, such as array buffers, that v8 was told about
+    pub external_memory: usize,
+
+    /// The number of live contexts - a count that keeps growing suggests a leak
+    pub number_of_native_contexts: usize,
+
+    /// The number of contexts that were detached but not yet collected
+    pub number_of_detached_contexts: usize,
+}
+
+impl HeapStats {
+    /// Reads the statistics of an isolate
+    fn from_isolate(isolate: &mut v8::Isolate) -> Self {
+        let mut stats = v8::HeapStatistics::default();
+        isolate.get_heap_statistics(&mut stats);
+
+        Self {
+            total_heap_size: stats.total_heap_size(),
+            total_heap_size_executable: stats.total_heap_size_executable(),
+            total_physical_size: stats.total_physical_size(),
+            total_available_size: stats.total_available_size(),
+            used_heap_size: stats.used_heap_size(),
+            heap_size_limit: stats.heap_size_limit(),
+            malloced_memory: stats.malloced_memory(),
+            peak_malloced_memory: stats.peak_malloced_memory(),
+            external_memory: stats.external_memory(),
+            number_of_native_contexts: stats.number_of_native_contexts(),
+            number_of_detached_contexts: stats.number_of_detached_contexts(),
+        }
+    }
+}
+
+/// A read-only stand-in for the common parts of the `Deno` namespace
+/// Eases running scripts written for Deno, without granting them access to the host
+///
+/// Every value is supplied by the host - `Deno.env` never reads the real environment,
+/// and `Deno.cwd()` never reads the real working directory. See [`RuntimeOptions::deno_stub`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct DenoStub {
+    /// Variables returned by `Deno.env.get`, `Deno.env.has` and `Deno.env.toObject`
+    pub env: HashMap<String, String>,
+
+    /// Returned by `Deno.cwd()`
+    pub cwd: String,
+
+    /// Returned by `Deno.args`
+    pub args: Vec<String>,
+}
+
+impl DenoStub {
+    /// Create an empty stub - no variables, no arguments, and `/` as the working directory
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            cwd: "/".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a variable visible through `Deno.env`
+    #[must_use]
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Set the path returned by `Deno.cwd()`
+    #[must_use]
+    pub fn with_cwd(mut self, cwd: &str) -> Self {
+        self.cwd = cwd.to_string();
+        self
+    }
+
+    /// Set the arguments returned by `Deno.args`
+    #[must_use]
+    pub fn with_args(mut self, args: &[&str]) -> Self {
+        self.args = args.iter().map(ToString::to_string).collect();
+        self
+    }
+}
+
 /// Decodes a set of arguments into a vector of v8 values
 /// This is used to pass arguments to a javascript function
 /// And is faster and more flexible than using `json_args!`
@@ -77,11 +395,405 @@ fn decode_args<'a>(
     }
 }
 
+/// Visits a JSON array of arguments, converting each element into a v8 value as it is read
+struct StreamedArgsVisitor<'s, 'a> {
+    scope: &'s mut v8::HandleScope<'a>,
+}
+impl<'de, 's, 'a> serde::de::Visitor<'de> for StreamedArgsVisitor<'s, 'a> {
+    type Value = Vec<v8::Local<'a, v8::Value>>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a JSON array of arguments")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let scope = self.scope;
+        let mut result = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(arg) = seq.next_element::<serde_json::Value>()? {
+            let arg =
+                deno_core::serde_v8::to_v8(&mut *scope, arg).map_err(serde::de::Error::custom)?;
+            result.push(arg);
+        }
+        Ok(result)
+    }
+}
+
+/// Decodes a JSON array of arguments from a reader into a vector of v8 values
+/// Only one argument is held as JSON at a time, so the full argument list
+/// is never built in memory before being handed to v8
+fn decode_streamed_args<'a>(
+    reader: impl std::io::Read,
+    scope: &mut v8::HandleScope<'a>,
+) -> Result<Vec<v8::Local<'a, v8::Value>>, Error> {
+    use serde::de::Deserializer as _;
+
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let args = (&mut deserializer).deserialize_seq(StreamedArgsVisitor { scope })?;
+    deserializer.end()?;
+    Ok(args)
+}
+
+/// A module loaded by [`InnerRuntime::load_modules_lazy`]
+enum LazyModule {
+    /// Compiled and linked, but its top-level code has not been run
+    Compiled(Module, deno_core::ModuleId),
+
+    /// Evaluated, with the resulting handle
+    Evaluated(ModuleHandle),
+}
+
+/// Prepares a new context for a sandboxed module, leaving only the allowed globals on its global object
+/// Builtins are the context's own copies. `globalThis` is always kept, since it only leads back to the
+/// sandbox's own global object
+///
+/// Other allowed globals come from the runtime's context, but are never handed over as they are - any object
+/// from that context leads back to its global object. Functions are replaced by wrappers made here, which
+/// pass arguments, results and errors across as JSON, and objects by copies holding such wrappers
+///
+/// Code generation from strings would bypass the checks made on the module's source, so `eval` and the
+/// `Function` constructors are never available. Promises are left out as well, since the sandbox has no event loop
+const SANDBOX_SETUP_SOURCE: &str = "((allowed, host, hostJSON) => {
+    const global = globalThis;
+    const { defineProperty, getOwnPropertyNames, getPrototypeOf, hasOwn } = Object;
+    const { apply } = Reflect;
+    const { parse, stringify } = JSON;
+    const { Error, EvalError, String } = global;
+    const { parse: hostParse, stringify: hostStringify } = hostJSON;
+    const unavailable = ['eval', 'Function', 'Promise', 'WebAssembly'];
+
+    const wrapFunction = (f, owner) => function (...args) {
+        let result;
+        try {
+            const hostArgs = apply(hostParse, hostJSON, [stringify(args)]);
+            result = apply(hostStringify, hostJSON, [apply(f, owner, hostArgs)]);
+        } catch (e) {
+            let message;
+            try {
+                message = String(e?.message ?? e);
+            } catch {
+                message = 'Host function failed';
+            }
+            throw new Error(message);
+        }
+        return result === undefined ? undefined : parse(result);
+    };
+
+    const copyValue = (value, owner, depth) => {
+        if (typeof value === 'function') {
+            return wrapFunction(value, owner);
+        }
+        if (value === null || typeof value !== 'object') {
+            return value;
+        }
+        if (depth > 4) {
+            return undefined;
+        }
+
+        // Own and inherited properties, short of Object.prototype
+        const copy = {};
+        for (let proto = value; proto !== null && getPrototypeOf(proto) !== null; proto = getPrototypeOf(proto)) {
+            for (const key of getOwnPropertyNames(proto)) {
+                if (key === 'constructor' || hasOwn(copy, key)) {
+                    continue;
+                }
+                try {
+                    const property = copyValue(value[key], value, depth + 1);
+                    defineProperty(copy, key, { value: property, writable: true, enumerable: true, configurable: true });
+                } catch {}
+            }
+        }
+        return copy;
+    };
+
+    const blocked = () => {
+        throw new EvalError('Code generation from strings is disabled in sandboxed modules');
+    };
+    for (const f of [function () {}, function* () {}, async function () {}, async function* () {}]) {
+        defineProperty(getPrototypeOf(f), 'constructor', { value: blocked });
+    }
+    delete Array.fromAsync;
+    delete Atomics.waitAsync;
+
+    const builtins = getOwnPropertyNames(global);
+    for (const name of builtins) {
+        if (name === 'globalThis') {
+            continue;
+        }
+        if (!allowed.includes(name) || unavailable.includes(name)) {
+            delete global[name];
+        }
+    }
+    for (const name of getOwnPropertyNames(host)) {
+        if (!builtins.includes(name)) {
+            const value = copyValue(host[name], undefined, 0);
+            defineProperty(global, name, { value, writable: true, configurable: true });
+        }
+    }
+})";
+
+/// Global holding a sandboxed module's namespace while the module re-exporting it is loaded
+const SANDBOX_NAMESPACE_GLOBAL: &str = "__rustyscript_sandbox_namespace";
+
+/// The only globals visible to a pure module - deterministic builtins without side effects
+/// `Promise`, timers and `Date` are deliberately absent
+const PURE_GLOBALS: [&str; 24] = [
+    "Object",
+    "Array",
+    "String",
+    "Number",
+    "Boolean",
+    "BigInt",
+    "Symbol",
+    "Math",
+    "JSON",
+    "Map",
+    "Set",
+    "RegExp",
+    "Error",
+    "TypeError",
+    "RangeError",
+    "SyntaxError",
+    "ReferenceError",
+    "parseInt",
+    "parseFloat",
+    "isNaN",
+    "isFinite",
+    "encodeURIComponent",
+    "decodeURIComponent",
+    "structuredClone",
+];
+
+/// Replaces the timer globals with a queue that only advances through `Runtime::tick` or `Runtime::advance_clock`
+/// Tasks run in order of their due time (in virtual milliseconds), then in the order they were scheduled
+/// Takes the virtual clock's start as milliseconds since the unix epoch, or null to leave `Date.now` alone
+/// Returns the functions running the next task, advancing the clock by a number of milliseconds,
+/// and clearing the queue
+const MANUAL_TICKS_SOURCE: &str = "((epoch) => {
+    const queue = [];
+    let nextId = 1;
+    let now = 0;
+    const schedule = (callback, delay, args, repeat) => {
+        const id = nextId++;
+        delay = Math.max(1, Number(delay) || 0);
+        queue.push({ id, seq: id, time: now + delay, callback, delay, args, repeat });
+        return id;
+    };
+    const clear = (id) => {
+        const index = queue.findIndex((task) => task.id === id);
+        if (index !== -1) queue.splice(index, 1);
+    };
+
+    globalThis.setTimeout = (callback, delay = 0, ...args) => schedule(callback, delay, args, false);
+    globalThis.setInterval = (callback, delay = 0, ...args) => schedule(callback, delay, args, true);
+    globalThis.clearTimeout = clear;
+    globalThis.clearInterval = clear;
+
+    if (epoch !== null) {
+        // `Date` called without a time reads the virtual clock too, whether constructed or called
+        const RealDate = Date;
+        const VirtualDate = function Date(...args) {
+            if (new.target === undefined) return new RealDate(epoch + now).toString();
+            return Reflect.construct(RealDate, args.length === 0 ? [epoch + now] : args, new.target);
+        };
+        Object.setPrototypeOf(VirtualDate, RealDate);
+        Object.defineProperty(VirtualDate, 'length', { value: RealDate.length });
+        Object.defineProperty(VirtualDate, 'prototype', { value: RealDate.prototype });
+        Object.defineProperty(RealDate.prototype, 'constructor', {
+            value: VirtualDate, writable: true, configurable: true,
+        });
+        VirtualDate.now = () => epoch + now;
+        globalThis.Date = VirtualDate;
+
+        if (globalThis.performance) {
+            Object.defineProperty(performance, 'now', { value: () => now, configurable: true });
+        }
+    }
+
+    const next = () => {
+        let index = 0;
+        for (let i = 1; i < queue.length; i++) {
+            const [a, b] = [queue[i], queue[index]];
+            if (a.time < b.time || (a.time === b.time && a.seq < b.seq)) index = i;
+        }
+        return index;
+    };
+
+    const run = (index) => {
+        const [task] = queue.splice(index, 1);
+        now = task.time;
+
+        // Intervals are rescheduled first, so they can clear themselves
+        if (task.repeat) {
+            queue.push({ ...task, seq: nextId++, time: now + task.delay });
+        }
+
+        if (typeof task.callback === 'function') {
+            task.callback(...task.args);
+        } else {
+            (0, eval)(String(task.callback));
+        }
+    };
+
+    const tick = () => {
+        if (queue.length === 0) return 0;
+        run(next());
+        return queue.length;
+    };
+
+    // Runs the next task due within `ms`, returning the time left to advance by
+    // Once no task is due, moves the clock forward and returns -1
+    const advance = (ms) => {
+        const target = now + Math.max(0, ms);
+        const index = next();
+        if (queue.length === 0 || queue[index].time > target) {
+            now = target;
+            return -1;
+        }
+
+        run(index);
+        return target - now;
+    };
+
+    // Drops every queued task, returning how many there were
+    const clearAll = () => queue.splice(0).length;
+
+    return [tick, advance, clearAll];
+})";
+
+/// Hands unhandled promise rejections to the host's handler
+/// Dispatches the `unhandledrejection` event first where there is one, so listeners in the script can handle them
+const UNHANDLED_REJECTION_SOURCE: &str = "Deno.core.setUnhandledPromiseRejectionHandler((promise, reason) => {
+    if (typeof PromiseRejectionEvent === 'function') {
+        const event = new PromiseRejectionEvent('unhandledrejection', { cancelable: true, promise, reason });
+        globalThis.dispatchEvent(event);
+        if (event.defaultPrevented) return true;
+    }
+    return Deno.core.ops.op_report_unhandled_rejection(reason);
+});";
+
+/// Replaces `Math.random` with a seeded generator (sfc32), so that every runtime given the same seed
+/// produces the same sequence. Takes the low and high 32 bits of the seed
+const SEEDED_RANDOM_SOURCE: &str = "((a, b) => {
+    let c = 0x9e3779b9;
+    let d = 1;
+    const next = () => {
+        let t = (a + b) | 0;
+        a = b ^ (b >>> 9);
+        b = (c + (c << 3)) | 0;
+        c = (c << 21) | (c >>> 11);
+        d = (d + 1) | 0;
+        t = (t + d) | 0;
+        c = (c + t) | 0;
+        return (t >>> 0) / 4294967296;
+    };
+
+    // The first outputs still resemble the seed
+    for (let i = 0; i < 16; i++) next();
+    Math.random = next;
+})";
+
+/// Adds a read-only stand-in for common `Deno` APIs to the `Deno` global
+/// Takes the host-supplied values of a [`DenoStub`], and the platform as `Deno.build` reports it
+const DENO_STUB_SOURCE: &str = "((config, build) => {
+    class PermissionDenied extends Error {
+        constructor(message) {
+            super(message);
+            this.name = 'PermissionDenied';
+        }
+    }
+    const denied = (name) => () => {
+        throw new PermissionDenied(`${name} is not available in this environment`);
+    };
+
+    const env = Object.freeze({ ...config.env });
+    const stub = {
+        env: Object.freeze({
+            get: (key) => (Object.hasOwn(env, key) ? env[key] : undefined),
+            has: (key) => Object.hasOwn(env, key),
+            toObject: () => ({ ...env }),
+            set: denied('Deno.env.set'),
+            delete: denied('Deno.env.delete'),
+        }),
+        cwd: () => config.cwd,
+        chdir: denied('Deno.chdir'),
+        args: Object.freeze([...config.args]),
+        build: Object.freeze(build),
+        noColor: true,
+        exit: (code = 0) => rustyscript.exit(code),
+        errors: Object.freeze({ PermissionDenied }),
+    };
+
+    globalThis.Deno ??= {};
+    for (const [name, value] of Object.entries(stub)) {
+        Object.defineProperty(globalThis.Deno, name, { value, enumerable: true });
+    }
+})";
+
+/// Module resolution for sandboxed modules
+/// Their source is checked for imports before it is compiled, so this only runs if that check is bypassed
+fn reject_sandbox_import<'s>(
+    context: v8::Local<'s, v8::Context>,
+    specifier: v8::Local<'s, v8::String>,
+    _import_attributes: v8::Local<'s, v8::FixedArray>,
+    _referrer: v8::Local<'s, v8::Module>,
+) -> Option<v8::Local<'s, v8::Module>> {
+    // SAFETY: v8 only calls this while instantiating a module in `context`
+    let scope = &mut unsafe { v8::CallbackScope::new(context) };
+    let message = format!(
+        "Sandboxed modules cannot import {}",
+        specifier.to_rust_string_lossy(scope)
+    );
+    if let Some(message) = v8::String::new(scope, &message) {
+        let exception = v8::Exception::error(scope, message);
+        scope.throw_exception(exception);
+    }
+    None
+}
+
+/// Private property recording the module, and its id at the time, that a function from `get_function` was resolved from
+const FUNCTION_ORIGIN_KEY: &str = "rustyscript.function_origin";
+
+/// The JS stack size set for the process, in bytes
+static PROCESS_STACK_SIZE: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+
+/// Sets v8's stack limit the first time it is asked for
+/// v8 reads the flag whenever an isolate is created, so changing it again would race with runtimes
+/// being created on other threads - later requests must match the first one instead
+fn set_process_stack_size(max_stack_size: usize) -> Result<(), Error> {
+    let stack_size = *PROCESS_STACK_SIZE.get_or_init(|| {
+        deno_core::v8_set_flags(vec![
+            String::new(),
+            format!("--stack-size={}", max_stack_size / 1024),
+        ]);
+        max_stack_size
+    });
+
+    if stack_size == max_stack_size {
+        Ok(())
+    } else {
+        Err(Error::Runtime(format!(
+            "max_stack_size is process-wide, and was already set to {stack_size} bytes"
+        )))
+    }
+}
+
 /// Represents the set of options accepted by the runtime constructor
 pub struct RuntimeOptions {
     /// A set of `deno_core` extensions to add to the runtime
     pub extensions: Vec<deno_core::Extension>,
 
+    /// Extensions to add on top of the startup snapshot, which were not part of it
+    ///
+    /// Unlike `extensions`, these are always fully initialized (create them with `init_ops_and_esm`),
+    /// so a frozen snapshot can be combined with host-specific capabilities decided at runtime.
+    ///
+    /// They are validated before the runtime starts: names and ops must not clash with any
+    /// other extension, and dependencies must be present. Ignored by `SnapshotBuilder`.
+    pub runtime_extensions: Vec<deno_core::Extension>,
+
     /// Additional options for the built-in extensions
     pub extension_options: ext::ExtensionOptions,
 
@@ -92,8 +804,22 @@ pub struct RuntimeOptions {
     pub timeout: Duration,
 
     /// Optional maximum heap size for the runtime
+    /// When it is approached, the running script is terminated and the call fails with [`Error::HeapExhausted`]
     pub max_heap_size: Option<usize>,
 
+    /// Called once the heap approaches `max_heap_size`, just before the script is terminated
+    /// Receives the current and the initial heap limits in bytes - useful for logging which script ran out
+    pub on_heap_limit: Option<Rc<dyn Fn(usize, usize)>>,
+
+    /// Optional stack size for JS, in bytes - v8 defaults to just under 1MiB
+    /// Scripts recursing past it fail with [`Error::StackOverflow`], rather than crashing the process
+    ///
+    /// Must stay below the stack size of the thread running the runtime.
+    /// This is a process-wide v8 flag, so it can only be set once: the first runtime to set it
+    /// fixes it for every runtime created after it, and asking for a different size later
+    /// fails with [`Error::Runtime`]
+    pub max_stack_size: Option<usize>,
+
     /// Optional cache provider for the module loader
     #[allow(deprecated)]
     pub module_cache: Option<Box<dyn crate::module_loader::ModuleCacheProvider>>,
@@ -101,6 +827,14 @@ pub struct RuntimeOptions {
     /// Optional import provider for the module loader
     pub import_provider: Option<Box<dyn crate::module_loader::ImportProvider>>,
 
+    /// Optional hook replacing the default resolution of import specifiers into URLs
+    /// Consulted before the loader's own checks - see [`crate::module_loader::ModuleResolver`]
+    pub module_resolver: Option<crate::module_loader::ModuleResolver>,
+
+    /// Optional import map, aliasing bare specifiers such as `@app/utils` to module URLs
+    /// Applied to every import before the loader's own checks - see [`crate::module_loader::ImportMap`]
+    pub import_map: Option<crate::module_loader::ImportMap>,
+
     /// Optional snapshot to load into the runtime
     /// This will reduce load times, but requires the same extensions to be loaded
     /// as when the snapshot was created
@@ -109,6 +843,13 @@ pub struct RuntimeOptions {
     /// WARNING: Snapshots MUST be used on the same system they were created on
     pub startup_snapshot: Option<&'static [u8]>,
 
+    /// If true, the runtime is created so that its state can later be captured with
+    /// [`crate::Runtime::take_snapshot`], and used to seed other runtimes
+    ///
+    /// Requires the `snapshot_builder` feature
+    #[cfg(feature = "snapshot_builder")]
+    pub snapshottable: bool,
+
     /// Optional configuration parameters for building the underlying v8 isolate
     /// This can be used to alter the behavior of the runtime.
     /// See the `rusty_v8` documentation for more information
@@ -120,27 +861,243 @@ pub struct RuntimeOptions {
 
     /// A whitelist of custom schema prefixes that are allowed to be loaded
     pub schema_whlist: HashSet<String>,
+
+    /// Registry used to resolve `npm:` specifiers
+    /// Only available with the `npm_import` feature
+    #[cfg(feature = "npm_import")]
+    pub npm_registry: crate::module_loader::NpmRegistry,
+
+    /// If true, exceptions thrown while evaluating a module's top-level code will not fail the load
+    /// Instead, the error is recorded on the returned handle, see [`ModuleHandle::load_error`]
+    /// and [`ModuleHandle::side_module_errors`]
+    ///
+    /// Useful for best-effort loading of many scripts
+    pub capture_load_errors: bool,
+
+    /// Optional logger for internal diagnostics (module loads, timeouts, permission denials)
+    /// Also made available to ops through the state, and to the host through [`crate::Runtime::logger`]
+    pub logger: Option<Rc<dyn Logger>>,
+
+    /// Optional set of modules to make available to the runtime at creation
+    /// Modules in the manifest can be imported without filesystem or network access,
+    /// and those marked with `preload` are evaluated before the runtime is returned
+    pub manifest: Option<crate::module_loader::ModuleManifest>,
+
+    /// Optional in-memory filesystem consulted for `file:` imports before the disk
+    /// Files it serves can be imported even without the `fs_import` feature
+    pub virtual_fs: Option<Rc<dyn crate::module_loader::VirtualFileSystem>>,
+
+    /// If true, timers (`setTimeout`, `setInterval`) never fire on their own - each call
+    /// to [`crate::Runtime::tick`] runs the next one, then drains the microtask queue
+    ///
+    /// Useful for deterministic tests of async scripts, asserting state between scheduled tasks
+    pub manual_ticks: bool,
+
+    /// If set, common `Deno` APIs (`Deno.env`, `Deno.cwd()`, `Deno.args`, `Deno.build`, `Deno.exit`)
+    /// are added to the `Deno` global, backed only by the values in the stub
+    ///
+    /// Off by default, so scripts only see a `Deno` namespace when the host opts in
+    pub deno_stub: Option<DenoStub>,
+
+    /// If true, console output is kept in a buffer instead of being passed to a `console.log`
+    /// function registered by the host. Read it with [`crate::Runtime::take_console_output`]
+    ///
+    /// Requires the `console` feature
+    pub capture_console: bool,
+
+    /// If set, each console message is passed to this callback, with its level and formatted text,
+    /// instead of a `console.log` function registered by the host
+    ///
+    /// Takes precedence over `capture_console`. Requires the `console` feature
+    pub console_sink: Option<Box<dyn Fn(ConsoleLevel, &str)>>,
+
+    /// If true, output kept by the runtime - see `capture_console` and [`crate::Runtime::run_captured`] -
+    /// records the specifier of the module that wrote it, in [`ConsoleEvent::specifier`]
+    ///
+    /// Finding it walks the call stack on every console call, so it is off by default.
+    /// A `console.log` function registered by the host always receives the message alone
+    pub console_locations: bool,
+
+    /// If set, each promise rejection that goes unhandled is passed to this callback while the event loop runs,
+    /// instead of failing the event loop with an `Uncaught (in promise)` error
+    ///
+    /// With the `web` feature, an `unhandledrejection` listener that calls `preventDefault` stops it being reported
+    pub on_unhandled_rejection: Option<Box<dyn Fn(crate::JsError)>>,
+
+    /// If set, timers, `Date.now` and `new Date()` follow a virtual clock starting at this time,
+    /// which only moves through [`crate::Runtime::advance_clock`] or [`crate::Runtime::tick`]
+    ///
+    /// Lets tests trigger scheduled callbacks deterministically, without sleeping
+    /// As in Node, timer delays below 1ms are raised to 1ms, so a timer that keeps rescheduling itself
+    /// cannot stop the clock from advancing
+    pub virtual_clock: Option<std::time::SystemTime>,
+
+    /// How remote imports (`url_import` and `npm_import` features) are retried after transient failures
+    /// Defaults to no retries
+    pub import_retry: crate::module_loader::ImportRetryPolicy,
+
+    /// Separate time budgets for `file` and `http(s)` imports
+    /// Defaults to no limits
+    pub import_timeouts: crate::module_loader::ImportTimeouts,
+
+    /// Compilers for additional source languages, keyed by file extension (without the dot)
+    /// See [`crate::module_loader::LanguagePlugin`]
+    pub language_plugins: HashMap<String, Rc<dyn crate::module_loader::LanguagePlugin>>,
+
+    /// Compile-time constants, by name. Every use of a constant in a module's source
+    /// is replaced by its value before the module is compiled, so `if (FLAG_X) { ... }`
+    /// can be switched per deployment without the script reading any configuration
+    ///
+    /// Only references to an undeclared name are replaced - property accesses, object keys and
+    /// bindings that shadow a constant, such as parameters, are left untouched
+    pub compile_constants: HashMap<String, serde_json::Value>,
+
+    /// How modules written as CommonJS (`module.exports` and `require`) are handled
+    /// Defaults to loading them as-is - see [`crate::module_loader::CommonJsMode`]
+    pub commonjs: crate::module_loader::CommonJsMode,
+
+    /// The longest chain of imports allowed below a module loaded from rust, guarding against
+    /// deeply nested or recursive import graphs. An import past this depth fails to load
+    ///
+    /// Defaults to no limit
+    pub max_import_depth: Option<usize>,
+
+    /// The largest `text` or `bytes` import allowed, in bytes. A larger import fails to load,
+    /// and remote contents stop downloading once they pass the limit
+    ///
+    /// Defaults to no limit
+    pub max_raw_import_size: Option<usize>,
+
+    /// What happens when a rust function is registered under a name already in use
+    /// Defaults to replacing the earlier function - see [`DuplicateRegistration`]
+    ///
+    /// Batch registration ignores this, and always fails with [`Error::FunctionAlreadyRegistered`]
+    /// without registering anything if any name in the batch is already in use
+    pub duplicate_registration: DuplicateRegistration,
+
+    /// How [`crate::Runtime::format_error`] renders errors
+    /// Defaults to their usual `Display` output - see [`crate::error::ErrorFormat`]
+    pub error_format: crate::error::ErrorFormat,
+
+    /// The most calls to registered async functions that may be in flight at once
+    /// Further calls wait in a queue until a running one completes, bounding the fan-out
+    /// a single script can cause on the host's backends. Defaults to no limit
+    ///
+    /// Must be at least 1 - a limit of 0 would leave every call waiting forever, so it is rejected
+    pub max_concurrent_async_calls: Option<usize>,
+
+    /// Wall-clock time after which the runtime refuses new calls with [`Error::LifetimeExceeded`],
+    /// prompting the host to recreate it. Defaults to no limit
+    pub max_lifetime: Option<Duration>,
+
+    /// The most memory, in bytes, that a single WASM memory may grow to
+    /// Rounded down to whole 64KiB pages. Modules whose memories could outgrow it fail to compile,
+    /// including any that do not declare a maximum size. Defaults to no limit
+    pub max_wasm_memory: Option<usize>,
+
+    /// A directory in which to persist the bytecode v8 compiles for modules, across runs
+    /// Every module loaded from source - root modules and their imports - is compiled once,
+    /// then loaded from the directory by later runtimes and processes, until its code changes
+    ///
+    /// With a [`crate::Logger`], each use of stored bytecode is logged at [`crate::LogLevel::Debug`]
+    ///
+    /// `load_modules_lazy` compiles a module graph without running it, so it can be used to warm the cache.
+    /// The `code_cache` benchmarks (`cargo bench -- code_cache`) compare loading a bundle of imports,
+    /// and a single large root module, with and without it
+    pub code_cache_dir: Option<std::path::PathBuf>,
+
+    /// Makes randomness reproducible - `Math.random` is replaced by a generator seeded with this value,
+    /// and, with the `crypto` feature, it also seeds `crypto.getRandomValues` unless
+    /// `extension_options.crypto_seed` is set. Runtimes given the same seed produce the same sequences
+    ///
+    /// INSECURE: random values become predictable. Only use this for tests and fuzzing. Defaults to `None`
+    pub deterministic_seed: Option<u64>,
 }
 
 impl Default for RuntimeOptions {
     fn default() -> Self {
         Self {
             extensions: Vec::default(),
+            runtime_extensions: Vec::default(),
             default_entrypoint: None,
             timeout: Duration::MAX,
             max_heap_size: None,
+            on_heap_limit: None,
+            max_stack_size: None,
             module_cache: None,
             import_provider: None,
+            module_resolver: None,
+            import_map: None,
             startup_snapshot: None,
+
+            #[cfg(feature = "snapshot_builder")]
+            snapshottable: false,
             isolate_params: None,
             shared_array_buffer_store: None,
             schema_whlist: HashSet::default(),
+            #[cfg(feature = "npm_import")]
+            npm_registry: crate::module_loader::NpmRegistry::default(),
+            capture_load_errors: false,
+            logger: None,
+            manifest: None,
+            virtual_fs: None,
+            manual_ticks: false,
+            deno_stub: None,
+            capture_console: false,
+            console_sink: None,
+            console_locations: false,
+            on_unhandled_rejection: None,
+            virtual_clock: None,
+            import_retry: crate::module_loader::ImportRetryPolicy::default(),
+            import_timeouts: crate::module_loader::ImportTimeouts::default(),
+            language_plugins: HashMap::new(),
+            compile_constants: HashMap::new(),
+            commonjs: crate::module_loader::CommonJsMode::default(),
+            max_import_depth: None,
+            max_raw_import_size: None,
+            max_concurrent_async_calls: None,
+            duplicate_registration: DuplicateRegistration::default(),
+            error_format: crate::error::ErrorFormat::default(),
+            max_lifetime: None,
+            max_wasm_memory: None,
+            code_cache_dir: None,
+            deterministic_seed: None,
 
             extension_options: ExtensionOptions::default(),
         }
     }
 }
 
+/// The deno runtime behind an `InnerRuntime`
+/// Only a runtime created for snapshotting can be snapshotted, so one is used when requested
+pub enum DenoRuntime {
+    Standard(JsRuntime),
+
+    #[cfg(feature = "snapshot_builder")]
+    Snapshottable(deno_core::JsRuntimeForSnapshot),
+}
+impl std::ops::Deref for DenoRuntime {
+    type Target = JsRuntime;
+    fn deref(&self) -> &JsRuntime {
+        match self {
+            Self::Standard(runtime) => runtime,
+
+            #[cfg(feature = "snapshot_builder")]
+            Self::Snapshottable(runtime) => runtime,
+        }
+    }
+}
+impl std::ops::DerefMut for DenoRuntime {
+    fn deref_mut(&mut self) -> &mut JsRuntime {
+        match self {
+            Self::Standard(runtime) => runtime,
+
+            #[cfg(feature = "snapshot_builder")]
+            Self::Snapshottable(runtime) => runtime,
+        }
+    }
+}
+
 /// Deno `JsRuntime` wrapper providing helper functions needed
 /// by the public-facing Runtime API
 ///
@@ -149,28 +1106,139 @@ impl Default for RuntimeOptions {
 /// underlying deno runtime instance
 pub struct InnerRuntime {
     pub module_loader: Rc<RustyLoader>,
-    pub deno_runtime: JsRuntime,
+    pub deno_runtime: DenoRuntime,
 
     pub default_entrypoint: Option<String>,
+    pub capture_load_errors: bool,
+    pub logger: Option<Rc<dyn Logger>>,
+
+    // Created the first time a type conversion is registered
+    type_registry: Option<TypeRegistry>,
+
+    // Manifest modules waiting to be evaluated by `preload_manifest`
+    manifest_preloads: Vec<deno_core::ModuleSpecifier>,
+
+    // Modules compiled by `load_modules_lazy`, and their handles once evaluated
+    lazy_modules: HashMap<deno_core::ModuleSpecifier, LazyModule>,
+
+    // Console output captured since it was last taken, if `capture_console` is set
+    console_output: Option<Rc<std::cell::RefCell<Vec<ConsoleEvent>>>>,
+
+    // Whether console output kept by the runtime records the module that wrote it
+    console_locations: bool,
+
+    // The id each module filename was most recently loaded under
+    module_ids: HashMap<std::path::PathBuf, deno_core::ModuleId>,
+
+    // Runs the next queued timer, if `manual_ticks` or `virtual_clock` is set
+    tick_fn: Option<v8::Global<v8::Function>>,
+
+    // Advances the virtual clock, if `manual_ticks` or `virtual_clock` is set
+    advance_fn: Option<v8::Global<v8::Function>>,
+
+    // Empties the timer queue, if `manual_ticks` or `virtual_clock` is set
+    clear_timers_fn: Option<v8::Global<v8::Function>>,
+
+    // What registering a function under a name already in use does
+    duplicate_registration: DuplicateRegistration,
+
+    // Closures behind the callbacks created so far, shared with the ops through the state
+    callbacks: Rc<std::cell::RefCell<crate::js_value::CallbackPool>>,
+
+    // Waited for on drop - declared after `deno_runtime`, which closes the pipes, so it is dropped after it
+    #[cfg(feature = "io")]
+    _io_threads: ext::io::IoThreads,
 }
 impl InnerRuntime {
     pub fn new(
         options: RuntimeOptions,
         heap_exhausted_token: CancellationToken,
     ) -> Result<Self, Error> {
+        // Apply per-module permissions, and report permission denials to the logger, if there is one
+        #[cfg(feature = "web")]
+        let (options, module_web_permissions) = {
+            let mut options = options;
+            let module_web_permissions =
+                ext::web::ModuleWebPermissions::new(options.extension_options.web.permissions);
+            options.extension_options.web.permissions = Rc::new(module_web_permissions.clone());
+
+            if let Some(logger) = &options.logger {
+                options.extension_options.web.permissions =
+                    Rc::new(ext::web::LoggingWebPermissions {
+                        inner: options.extension_options.web.permissions.clone(),
+                        logger: logger.clone(),
+                    });
+            }
+            (options, module_web_permissions)
+        };
+
+        // Connect the host's stdio streams, if it provided any
+        #[cfg(feature = "io")]
+        let (options, io_threads) = {
+            let mut options = options;
+            let io_threads = options.extension_options.connect_io()?;
+            (options, io_threads)
+        };
+
+        // The deterministic seed also seeds the crypto extension, unless it was given its own seed
+        #[cfg(feature = "crypto")]
+        let options = {
+            let mut options = options;
+            if let Some(seed) = options.deterministic_seed {
+                options.extension_options.crypto_seed.get_or_insert(seed);
+            }
+            options
+        };
+
+        // Resolve the manifest, if any, into sources for the loader
+        let mut manifest_sources = HashMap::new();
+        let mut manifest_preloads = Vec::new();
+        if let Some(manifest) = &options.manifest {
+            for (specifier, entry) in manifest.resolve()? {
+                if entry.preload {
+                    manifest_preloads.push(specifier.clone());
+                }
+                manifest_sources.insert(specifier, entry.source.clone());
+            }
+        }
+
         let module_loader = Rc::new(RustyLoader::new(LoaderOptions {
             cache_provider: options.module_cache,
             import_provider: options.import_provider,
+            module_resolver: options.module_resolver,
+            import_map: options.import_map,
             schema_whlist: options.schema_whlist,
+            manifest_sources,
+            virtual_fs: options.virtual_fs,
+            import_retry: options.import_retry,
+            import_timeouts: options.import_timeouts,
+            language_plugins: options.language_plugins,
+            compile_constants: options.compile_constants,
+            commonjs: options.commonjs,
+            max_import_depth: options.max_import_depth,
+            max_raw_import_size: options.max_raw_import_size,
+            logger: options.logger.clone(),
+            code_cache_dir: options.code_cache_dir,
+
+            #[cfg(feature = "npm_import")]
+            npm_registry: options.npm_registry,
 
             ..Default::default()
         }));
 
         // If a snapshot is provided, do not reload ESM for extensions
         let is_snapshot = options.startup_snapshot.is_some();
-        let extensions =
+        let mut extensions =
             ext::all_extensions(options.extensions, options.extension_options, is_snapshot);
 
+        // Runtime-only extensions go last, so they cannot disturb the snapshot's layout
+        ext::validate_runtime_extensions(&extensions, &options.runtime_extensions)?;
+        extensions.extend(options.runtime_extensions);
+
+        if let Some(max_stack_size) = options.max_stack_size {
+            set_process_stack_size(max_stack_size)?;
+        }
+
         // If a heap size is provided, set the isolate params (preserving any user-provided params otherwise)
         let isolate_params = match options.isolate_params {
             Some(params) => {
@@ -190,44 +1258,514 @@ impl InnerRuntime {
             }
         };
 
-        let mut deno_runtime = JsRuntime::try_new(deno_core::RuntimeOptions {
-            module_loader: Some(module_loader.clone()),
+        let deno_options = deno_core::RuntimeOptions {
+            module_loader: Some(module_loader.clone()),
+
+            extension_transpiler: Some(Rc::new(|specifier, code| {
+                transpile_extension(&specifier, &code)
+            })),
+
+            get_error_class_fn: Some(&crate::error::get_error_class),
+
+            // Allow `text` and `bytes` imports
+            validate_import_attributes_cb: Some(Box::new(
+                crate::module_loader::raw_imports::validate_import_attributes,
+            )),
+            custom_module_evaluation_cb: Some(Box::new(
+                crate::module_loader::raw_imports::evaluate_raw_module,
+            )),
+
+            create_params: isolate_params,
+            shared_array_buffer_store: options.shared_array_buffer_store.clone(),
+
+            startup_snapshot: options.startup_snapshot,
+            extensions,
+
+            ..Default::default()
+        };
+
+        #[cfg(feature = "snapshot_builder")]
+        let mut deno_runtime = if options.snapshottable {
+            DenoRuntime::Snapshottable(deno_core::JsRuntimeForSnapshot::try_new(deno_options)?)
+        } else {
+            DenoRuntime::Standard(JsRuntime::try_new(deno_options)?)
+        };
+
+        #[cfg(not(feature = "snapshot_builder"))]
+        let mut deno_runtime = DenoRuntime::Standard(JsRuntime::try_new(deno_options)?);
+
+        // Add a callback to terminate the runtime if the max_heap_size limit is approached
+        if options.max_heap_size.is_some() {
+            let isolate_handle = deno_runtime.v8_isolate().thread_safe_handle();
+            let on_heap_limit = options.on_heap_limit.clone();
+
+            deno_runtime.add_near_heap_limit_callback(move |current_value, initial_value| {
+                // Report only the first approach, not the spikes granted while terminating
+                if !heap_exhausted_token.is_cancelled() {
+                    if let Some(on_heap_limit) = &on_heap_limit {
+                        on_heap_limit(current_value, initial_value);
+                    }
+                }
+
+                isolate_handle.terminate_execution();
+
+                // Signal the outer runtime to cancel block_on future (avoid hanging) and return friendly error
+                heap_exhausted_token.cancel();
+
+                // Spike the heap limit while terminating to avoid segfaulting
+                // Callback may fire multiple times if memory usage increases quicker then termination finalizes
+                5 * current_value
+            });
+        }
+
+        // Bound the number of registered async functions running at once
+        if let Some(limit) = options.max_concurrent_async_calls {
+            if limit == 0 {
+                return Err(Error::Runtime(
+                    "max_concurrent_async_calls must be at least 1".to_string(),
+                ));
+            }
+            deno_runtime
+                .op_state()
+                .borrow_mut()
+                .put(ext::rustyscript::AsyncCallLimit(Rc::new(
+                    tokio::sync::Semaphore::new(limit),
+                )));
+        }
+
+        // Bound the memory WASM modules may use
+        if let Some(limit) = options.max_wasm_memory {
+            let pages = limit / ext::rustyscript::wasm::WASM_PAGE_SIZE;
+            deno_runtime
+                .op_state()
+                .borrow_mut()
+                .put(ext::rustyscript::wasm::WasmMemoryLimit(
+                    u32::try_from(pages).unwrap_or(u32::MAX),
+                ));
+        }
+
+        // Report unhandled rejections to the host
+        if let Some(handler) = options.on_unhandled_rejection {
+            deno_runtime
+                .op_state()
+                .borrow_mut()
+                .put(ext::rustyscript::UnhandledRejectionHandler(Rc::from(
+                    handler,
+                )));
+            deno_runtime.execute_script("", UNHANDLED_REJECTION_SOURCE)?;
+        }
+
+        // Make the per-module permissions available to ops, and to the runtime
+        #[cfg(feature = "web")]
+        deno_runtime
+            .op_state()
+            .borrow_mut()
+            .put(module_web_permissions);
+
+        // Make the logger available to ops
+        if let Some(logger) = &options.logger {
+            deno_runtime.op_state().borrow_mut().put(logger.clone());
+
+            #[cfg(feature = "crypto")]
+            if options.extension_options.crypto_seed.is_some() {
+                logger.log(
+                    LogLevel::Warn,
+                    "crypto random values are seeded and predictable - this is insecure outside of tests",
+                );
+            }
+        }
+
+        // Add the `Deno` namespace stand-in, if requested
+        if let Some(stub) = &options.deno_stub {
+            let os = match std::env::consts::OS {
+                "macos" => "darwin",
+                os => os,
+            };
+            let build = serde_json::json!({
+                "os": os,
+                "arch": std::env::consts::ARCH,
+            });
+            let config = serde_json::to_string(stub)?;
+            deno_runtime.execute_script("", format!("{DENO_STUB_SOURCE}({config}, {build})"))?;
+        }
+
+        // Make `Math.random` reproducible, if requested
+        if let Some(seed) = options.deterministic_seed {
+            deno_runtime.execute_script(
+                "",
+                format!(
+                    "{SEEDED_RANDOM_SOURCE}({}, {})",
+                    seed & 0xffff_ffff,
+                    seed >> 32
+                ),
+            )?;
+        }
+
+        // Take over the timers, if they are to be stepped manually
+        let (tick_fn, advance_fn, clear_timers_fn) =
+            if options.manual_ticks || options.virtual_clock.is_some() {
+                let epoch = match options.virtual_clock {
+                    Some(start) => start
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_err(|e| Error::Runtime(e.to_string()))?
+                        .as_millis()
+                        .to_string(),
+                    None => "null".to_string(),
+                };
+
+                let clock =
+                    deno_runtime.execute_script("", format!("{MANUAL_TICKS_SOURCE}({epoch})"))?;
+                let mut scope = deno_runtime.handle_scope();
+                let clock = v8::Local::new(&mut scope, clock);
+                let clock = v8::Local::<v8::Array>::try_from(clock)?;
+
+                let mut get_fn = |index| -> Result<v8::Global<v8::Function>, Error> {
+                    let f = clock.get_index(&mut scope, index).ok_or_else(|| {
+                        Error::Runtime("Could not set up the timer queue".to_string())
+                    })?;
+                    let f = v8::Local::<v8::Function>::try_from(f)?;
+                    Ok(v8::Global::new(&mut scope, f))
+                };
+                (Some(get_fn(0)?), Some(get_fn(1)?), Some(get_fn(2)?))
+            } else {
+                (None, None, None)
+            };
+
+        let mut runtime = Self {
+            deno_runtime,
+            module_loader,
+
+            default_entrypoint: options.default_entrypoint,
+            capture_load_errors: options.capture_load_errors,
+            logger: options.logger,
+            type_registry: None,
+            manifest_preloads,
+            lazy_modules: HashMap::new(),
+            console_output: None,
+            console_locations: options.console_locations,
+            module_ids: HashMap::new(),
+            tick_fn,
+            advance_fn,
+            clear_timers_fn,
+            duplicate_registration: options.duplicate_registration,
+            callbacks: Rc::default(),
+
+            #[cfg(feature = "io")]
+            _io_threads: io_threads,
+        };
+
+        let callbacks = runtime.callbacks.clone();
+        runtime
+            .deno_runtime()
+            .op_state()
+            .borrow_mut()
+            .put(callbacks);
+
+        if let Some(sink) = options.console_sink {
+            runtime.set_console_hook(Some(Rc::new(move |event: ConsoleEvent| {
+                sink(event.level, &event.message);
+            })))?;
+        } else if options.capture_console {
+            runtime.capture_console()?;
+        }
+
+        Ok(runtime)
+    }
+
+    /// Keeps console output in a buffer, to be read with `take_console_output`
+    fn capture_console(&mut self) -> Result<(), Error> {
+        let output = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = output.clone();
+        self.set_console_hook(Some(Rc::new(move |event: ConsoleEvent| {
+            sink.borrow_mut().push(event);
+        })))?;
+
+        self.console_output = Some(output);
+        Ok(())
+    }
+
+    /// Sends console output to the given handler, instead of the `console.log` function registered by the host
+    /// Passing `None` sends it back to that function. Returns the handler that was replaced
+    fn set_console_hook(
+        &mut self,
+        handler: Option<ext::rustyscript::ConsoleHandler>,
+    ) -> Result<Option<ext::rustyscript::ConsoleHandler>, Error> {
+        let locations = self.console_locations;
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        let previous = state
+            .try_take::<ext::rustyscript::ConsoleHook>()
+            .map(|hook| hook.handler);
+        if let Some(handler) = handler {
+            state.put(ext::rustyscript::ConsoleHook { handler, locations });
+        }
+        Ok(previous)
+    }
+
+    /// Sends console output into a size-capped buffer, instead of where it currently goes
+    /// Returns the buffer, and the handler it replaced - pass it to `restore_console` when done
+    pub fn redirect_console(
+        &mut self,
+        limit: usize,
+    ) -> Result<
+        (
+            Rc<std::cell::RefCell<CapturedOutput>>,
+            Option<ext::rustyscript::ConsoleHandler>,
+        ),
+        Error,
+    > {
+        let output = Rc::new(std::cell::RefCell::new(CapturedOutput::default()));
+        let sink = output.clone();
+        let previous = self.set_console_hook(Some(Rc::new(move |event: ConsoleEvent| {
+            sink.borrow_mut().push(&event, limit);
+        })))?;
+        Ok((output, previous))
+    }
+
+    /// Puts back the console handler replaced by `redirect_console`
+    pub fn restore_console(
+        &mut self,
+        previous: Option<ext::rustyscript::ConsoleHandler>,
+    ) -> Result<(), Error> {
+        self.set_console_hook(previous)?;
+        Ok(())
+    }
+
+    /// Returns the current memory use of the isolate
+    pub fn heap_stats(&mut self) -> HeapStats {
+        HeapStats::from_isolate(self.deno_runtime.v8_isolate())
+    }
+
+    /// Returns the console output captured so far, and clears the buffer
+    pub fn take_console_output(&mut self) -> Vec<ConsoleEvent> {
+        self.console_output
+            .as_ref()
+            .map(|output| std::mem::take(&mut *output.borrow_mut()))
+            .unwrap_or_default()
+    }
+
+    /// Writes a value out with v8's structured clone serializer
+    /// Fails for values that cannot be cloned, such as functions or WebAssembly modules
+    pub fn serialize_value(&mut self, value: &v8::Global<v8::Value>) -> Result<Vec<u8>, Error> {
+        let mut scope = self.deno_runtime.handle_scope();
+        let mut scope = v8::TryCatch::new(&mut scope);
+        let value = v8::Local::new(&mut scope, value);
+        let context = scope.get_current_context();
+
+        let serializer = v8::ValueSerializer::new(&mut scope, Box::new(StructuredClone));
+        serializer.write_header();
+        if serializer.write_value(context, value) == Some(true) {
+            return Ok(serializer.release());
+        }
+
+        let reason = scope.exception().map_or_else(
+            || "unknown error".to_string(),
+            |e| e.to_rust_string_lossy(&mut scope),
+        );
+        Err(Error::Runtime(format!(
+            "Value could not be transferred: {reason}"
+        )))
+    }
+
+    /// Reads a value written by [`InnerRuntime::serialize_value`] into this runtime
+    pub fn deserialize_value(&mut self, data: &[u8]) -> Result<v8::Global<v8::Value>, Error> {
+        let mut scope = self.deno_runtime.handle_scope();
+        let mut scope = v8::TryCatch::new(&mut scope);
+        let context = scope.get_current_context();
+
+        let deserializer = v8::ValueDeserializer::new(&mut scope, Box::new(StructuredClone), data);
+        let value = match deserializer.read_header(context) {
+            Some(true) => deserializer.read_value(context),
+            _ => None,
+        };
+        let Some(value) = value else {
+            let reason = scope.exception().map_or_else(
+                || "invalid data".to_string(),
+                |e| e.to_rust_string_lossy(&mut scope),
+            );
+            return Err(Error::Runtime(format!(
+                "Value could not be transferred: {reason}"
+            )));
+        };
+
+        Ok(v8::Global::new(&mut scope, value))
+    }
+
+    /// Moves the virtual clock forward, running each timer that comes due in order,
+    /// and draining the microtask queue after each one
+    /// Returns the number of timers that ran
+    pub fn advance_clock(&mut self, duration: Duration) -> Result<usize, Error> {
+        let Some(advance_fn) = self.advance_fn.clone() else {
+            return Err(Error::Runtime(
+                "The virtual clock is not enabled, see `RuntimeOptions::virtual_clock`".to_string(),
+            ));
+        };
+
+        let mut remaining = duration.as_secs_f64() * 1000.0;
+        let mut ran = 0;
+        loop {
+            let left = self.call_function_by_ref(None, &advance_fn, &(remaining,))?;
+            self.deno_runtime
+                .v8_isolate()
+                .perform_microtask_checkpoint();
+
+            let left: f64 = self.decode_value(left)?;
+            if left < 0.0 {
+                return Ok(ran);
+            }
+
+            ran += 1;
+            remaining = left;
+        }
+    }
+
+    /// Runs the next queued timer, then drains the microtask queue
+    /// Returns the number of timers still queued
+    pub fn tick(&mut self) -> Result<usize, Error> {
+        let Some(tick_fn) = self.tick_fn.clone() else {
+            return Err(Error::Runtime(
+                "Manual ticks are not enabled, see `RuntimeOptions::manual_ticks`".to_string(),
+            ));
+        };
+
+        let remaining = self.call_function_by_ref(None, &tick_fn, &())?;
+        self.deno_runtime
+            .v8_isolate()
+            .perform_microtask_checkpoint();
+        self.decode_value(remaining)
+    }
 
-            extension_transpiler: Some(Rc::new(|specifier, code| {
-                transpile_extension(&specifier, &code)
-            })),
+    /// Returns the exit status set by the script, if any
+    pub fn exit_code(&mut self) -> Option<i32> {
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow().ok()?;
+        state
+            .try_borrow::<ext::rustyscript::ExitCode>()
+            .map(|code| code.0)
+    }
 
-            create_params: isolate_params,
-            shared_array_buffer_store: options.shared_array_buffer_store.clone(),
+    /// Clears the exit status left by the previous call
+    /// If that call ended with `rustyscript.exit`, also lifts the termination so the isolate can run again
+    pub fn reset_exit_code(&mut self) {
+        let exited = {
+            let state = self.deno_runtime().op_state();
+            let mut state = state.borrow_mut();
+            state.try_take::<ext::rustyscript::ExitCode>();
+            state.try_take::<ext::rustyscript::ScriptExited>().is_some()
+        };
 
-            startup_snapshot: options.startup_snapshot,
-            extensions,
+        if exited {
+            self.deno_runtime()
+                .v8_isolate()
+                .cancel_terminate_execution();
+        }
+    }
 
-            ..Default::default()
-        })?;
+    /// Lists the async ops, resources and timers that are currently outstanding
+    pub fn pending_ops(&mut self) -> Vec<OpInfo> {
+        use deno_core::stats::{RuntimeActivity, RuntimeActivityStatsFilter};
 
-        // Add a callback to terminate the runtime if the max_heap_size limit is approached
-        if options.max_heap_size.is_some() {
-            let isolate_handle = deno_runtime.v8_isolate().thread_safe_handle();
+        let filter = RuntimeActivityStatsFilter::default()
+            .with_ops()
+            .with_resources()
+            .with_timers();
+        let stats = self
+            .deno_runtime
+            .runtime_activity_stats_factory()
+            .capture(&filter);
+
+        stats
+            .dump()
+            .active
+            .into_iter()
+            .map(|activity| match activity {
+                RuntimeActivity::AsyncOp(.., name) => OpInfo {
+                    kind: OpKind::AsyncOp,
+                    name: name.to_string(),
+                },
+                RuntimeActivity::Resource(.., name) => OpInfo {
+                    kind: OpKind::Resource,
+                    name: name.to_string(),
+                },
+                RuntimeActivity::Timer(..) => OpInfo {
+                    kind: OpKind::Timer,
+                    name: "setTimeout".to_string(),
+                },
+                RuntimeActivity::Interval(..) => OpInfo {
+                    kind: OpKind::Interval,
+                    name: "setInterval".to_string(),
+                },
+            })
+            .collect()
+    }
 
-            deno_runtime.add_near_heap_limit_callback(move |current_value, _| {
-                isolate_handle.terminate_execution();
+    /// Returns the number of async ops, resources and timers that are currently outstanding
+    pub fn pending_ops_count(&mut self) -> usize {
+        self.pending_ops().len()
+    }
 
-                // Signal the outer runtime to cancel block_on future (avoid hanging) and return friendly error
-                heap_exhausted_token.cancel();
+    /// Cancels every pending `setTimeout` and `setInterval` timer, including those queued by `manual_ticks`
+    /// Returns the number of timers cancelled
+    pub fn clear_timers(&mut self) -> Result<usize, Error> {
+        use deno_core::stats::{RuntimeActivity, RuntimeActivityStatsFilter};
 
-                // Spike the heap limit while terminating to avoid segfaulting
-                // Callback may fire multiple times if memory usage increases quicker then termination finalizes
-                5 * current_value
-            });
+        let mut cleared = 0;
+        if let Some(clear_timers_fn) = self.clear_timers_fn.clone() {
+            let count = self.call_function_by_ref(None, &clear_timers_fn, &())?;
+            cleared += self.decode_value::<usize>(count)?;
         }
 
-        Ok(Self {
-            deno_runtime,
-            module_loader,
+        let filter = RuntimeActivityStatsFilter::default().with_timers();
+        let ids: Vec<_> = self
+            .deno_runtime
+            .runtime_activity_stats_factory()
+            .capture(&filter)
+            .dump()
+            .active
+            .into_iter()
+            .filter_map(|activity| match activity {
+                RuntimeActivity::Timer(id) | RuntimeActivity::Interval(id) => Some(id),
+                _ => None,
+            })
+            .collect();
+
+        if !ids.is_empty() {
+            self.deno_runtime.execute_script(
+                "",
+                format!("for (const id of {ids:?}) Deno.core.cancelTimer(id);"),
+            )?;
+            cleared += ids.len();
+        }
 
-            default_entrypoint: options.default_entrypoint,
-        })
+        Ok(cleared)
+    }
+
+    /// Evaluates the manifest modules marked with `preload`, in manifest order
+    /// Does nothing once they have been loaded
+    pub async fn preload_manifest(&mut self) -> Result<(), Error> {
+        for specifier in std::mem::take(&mut self.manifest_preloads) {
+            let module_id = self.deno_runtime.load_side_es_module(&specifier).await?;
+
+            let mut load_error = None;
+            self.evaluate_module(module_id, &mut load_error).await?;
+            if let Some(e) = load_error {
+                self.log(LogLevel::Warn, || {
+                    format!("Error preloading {specifier}: {e}")
+                });
+            } else {
+                self.log(LogLevel::Info, || format!("Loaded module {specifier}"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a message to the logger, if one was provided
+    pub fn log(&self, level: LogLevel, message: impl FnOnce() -> String) {
+        if let Some(logger) = &self.logger {
+            logger.log(level, &message());
+        }
     }
 
     /// Access the underlying deno runtime instance directly
@@ -235,6 +1773,25 @@ impl InnerRuntime {
         &mut self.deno_runtime
     }
 
+    /// Consumes the runtime, capturing its heap - modules, globals and extension state - as a snapshot
+    /// Handles held by the host are released first, since they cannot be carried over
+    #[cfg(feature = "snapshot_builder")]
+    pub fn into_snapshot(mut self) -> Result<Box<[u8]>, Error> {
+        self.lazy_modules.clear();
+        self.type_registry = None;
+        self.tick_fn = None;
+        self.advance_fn = None;
+        self.clear_timers_fn = None;
+
+        match self.deno_runtime {
+            DenoRuntime::Snapshottable(runtime) => Ok(runtime.snapshot()),
+            DenoRuntime::Standard(_) => Err(Error::Runtime(
+                "Runtime was not created with `snapshottable` set, and cannot be snapshotted"
+                    .to_string(),
+            )),
+        }
+    }
+
     /// Remove and return a value from the state
     pub fn take<T>(&mut self) -> Option<T>
     where
@@ -270,43 +1827,289 @@ impl InnerRuntime {
     where
         F: RsAsyncFunction,
     {
+        self.register_one(name, Box::new(callback) as Box<dyn RsAsyncFunction>)
+    }
+
+    /// Register a rust function
+    /// The function must return a `serde_json::Value`
+    /// and accept a slice of `serde_json::Value` as arguments
+    pub fn register_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsFunction,
+    {
+        self.register_one(name, Box::new(callback) as Box<dyn RsFunction>)
+    }
+
+    /// Checks if a rust function of the given kind is registered under `name`
+    pub fn is_function_registered(&self, kind: FunctionKind, name: &str) -> Result<bool, Error> {
+        fn has<F: ?Sized + 'static>(state: &deno_core::OpState, name: &str) -> bool {
+            state
+                .try_borrow::<HashMap<String, Box<F>>>()
+                .is_some_and(|functions| functions.contains_key(name))
+        }
+
+        let state = self.deno_runtime.op_state();
+        let state = state.try_borrow()?;
+        Ok(match kind {
+            FunctionKind::Sync => has::<dyn RsFunction>(&state, name),
+            FunctionKind::Async => has::<dyn RsAsyncFunction>(&state, name),
+            FunctionKind::Stream => has::<dyn RsStreamFunction>(&state, name),
+            FunctionKind::Iterator => has::<dyn RsIteratorFunction>(&state, name),
+        })
+    }
+
+    /// Register a set of rust functions at once
+    /// Fails without registering anything if any name is already registered
+    pub fn register_functions(
+        &mut self,
+        functions: HashMap<String, Box<dyn RsFunction>>,
+    ) -> Result<(), Error> {
+        self.register_all(functions)
+    }
+
+    /// Register a set of non-blocking rust functions at once
+    /// Fails without registering anything if any name is already registered
+    pub fn register_async_functions(
+        &mut self,
+        functions: HashMap<String, Box<dyn RsAsyncFunction>>,
+    ) -> Result<(), Error> {
+        self.register_all(functions)
+    }
+
+    /// Register a set of rust functions as the host imports of a WASM namespace
+    /// Each is registered as a sync function named `namespace.name`, and added to the imports
+    /// of every WASM module instantiated from JS
+    pub fn register_wasm_imports(
+        &mut self,
+        namespace: &str,
+        imports: HashMap<String, Box<dyn RsFunction>>,
+    ) -> Result<(), Error> {
+        let names: Vec<String> = imports.keys().cloned().collect();
+        self.register_all(
+            imports
+                .into_iter()
+                .map(|(name, callback)| (format!("{namespace}.{name}"), callback))
+                .collect(),
+        )?;
+
         let state = self.deno_runtime().op_state();
         let mut state = state.try_borrow_mut()?;
+        if !state.has::<ext::rustyscript::WasmImports>() {
+            state.put(ext::rustyscript::WasmImports::default());
+        }
 
-        if !state.has::<HashMap<String, Box<dyn RsAsyncFunction>>>() {
-            state.put(HashMap::<String, Box<dyn RsAsyncFunction>>::new());
+        let imports = state.borrow_mut::<ext::rustyscript::WasmImports>();
+        imports.generation += 1;
+        let registered = imports.namespaces.entry(namespace.to_string()).or_default();
+        for name in names {
+            if !registered.contains(&name) {
+                registered.push(name);
+            }
         }
 
-        // Insert the callback into the state
-        state
-            .borrow_mut::<HashMap<String, Box<dyn RsAsyncFunction>>>()
-            .insert(name.to_string(), Box::new(callback));
+        Ok(())
+    }
+
+    /// Wraps a closure in a JS function, without registering it under any name
+    /// The closure cannot be re-entered - a callback that ends up calling itself fails instead
+    pub fn create_callback<F>(&mut self, callback: F) -> Result<crate::js_value::Callback, Error>
+    where
+        F: FnMut(&[serde_json::Value]) -> Result<serde_json::Value, Error> + 'static,
+    {
+        let callbacks = self.callbacks.clone();
+        let mut scope = self.deno_runtime().handle_scope();
+        crate::js_value::CallbackPool::create(&callbacks, &mut scope, Box::new(callback))
+    }
 
+    /// Drops the closure behind a callback - calling it from JS afterwards throws
+    pub fn release_callback(&mut self, callback: &crate::js_value::Callback) -> Result<(), Error> {
+        self.callbacks.try_borrow_mut()?.release(callback.id());
         Ok(())
     }
 
-    /// Register a rust function
-    /// The function must return a `serde_json::Value`
-    /// and accept a slice of `serde_json::Value` as arguments
-    pub fn register_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    /// Set the permissions applied to web requests made by the given module
+    /// Other modules keep the permissions given in the runtime options
+    #[cfg(feature = "web")]
+    pub fn set_module_web_permissions(
+        &mut self,
+        module: &Module,
+        permissions: Rc<dyn ext::web::WebPermissions>,
+    ) -> Result<(), Error> {
+        let specifier = module.filename().to_module_specifier(None)?;
+        let state = self.deno_runtime().op_state();
+        let state = state.try_borrow()?;
+        if let Some(scopes) = state.try_borrow::<ext::web::ModuleWebPermissions>() {
+            scopes.set(specifier.as_str(), permissions);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the implementation of a registered rust function
+    /// Fails if no function is registered under `name`
+    pub fn replace_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
     where
         F: RsFunction,
     {
+        self.replace_one(name, Box::new(callback) as Box<dyn RsFunction>)
+    }
+
+    /// Replace the implementation of a registered non-blocking rust function
+    /// Fails if no function is registered under `name`
+    pub fn replace_async_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsAsyncFunction,
+    {
+        self.replace_one(name, Box::new(callback) as Box<dyn RsAsyncFunction>)
+    }
+
+    /// Swaps the callback registered under `name` in one of the function tables stored in the state
+    /// Ignores `duplicate_registration`, since replacing is the point
+    fn replace_one<F: ?Sized + 'static>(
+        &mut self,
+        name: &str,
+        callback: Box<F>,
+    ) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        match state
+            .try_borrow_mut::<HashMap<String, Box<F>>>()
+            .and_then(|registered| registered.get_mut(name))
+        {
+            Some(existing) => {
+                *existing = callback;
+                Ok(())
+            }
+            None => Err(Error::ValueNotFound(name.to_string())),
+        }
+    }
+
+    /// Inserts a callback into one of the function tables stored in the state,
+    /// following `duplicate_registration` if the name is already taken
+    fn register_one<F: ?Sized + 'static>(
+        &mut self,
+        name: &str,
+        callback: Box<F>,
+    ) -> Result<(), Error> {
+        let policy = self.duplicate_registration;
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<HashMap<String, Box<F>>>() {
+            state.put(HashMap::<String, Box<F>>::new());
+        }
+
+        let registered = state.borrow_mut::<HashMap<String, Box<F>>>();
+        if registered.contains_key(name) {
+            match policy {
+                DuplicateRegistration::Overwrite => {}
+                DuplicateRegistration::Reject => {
+                    return Err(Error::FunctionAlreadyRegistered(name.to_string()))
+                }
+                DuplicateRegistration::KeepFirst => return Ok(()),
+            }
+        }
+
+        registered.insert(name.to_string(), callback);
+        Ok(())
+    }
+
+    /// Inserts callbacks into one of the function tables stored in the state
+    /// Always rejects duplicates, whatever `duplicate_registration` says, so a batch is all-or-nothing
+    fn register_all<F: ?Sized + 'static>(
+        &mut self,
+        functions: HashMap<String, Box<F>>,
+    ) -> Result<(), Error> {
+        let state = self.deno_runtime().op_state();
+        let mut state = state.try_borrow_mut()?;
+
+        if !state.has::<HashMap<String, Box<F>>>() {
+            state.put(HashMap::<String, Box<F>>::new());
+        }
+
+        let registered = state.borrow_mut::<HashMap<String, Box<F>>>();
+        if let Some(name) = functions.keys().find(|name| registered.contains_key(*name)) {
+            return Err(Error::FunctionAlreadyRegistered(name.clone()));
+        }
+        registered.extend(functions);
+
+        Ok(())
+    }
+
+    /// Register a rust function returning a stream of values
+    /// The function must return a [`JsStreamSource`]
+    /// and accept a slice of `serde_json::Value` as arguments
+    pub fn register_stream_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsStreamFunction,
+    {
+        self.register_one(name, Box::new(callback) as Box<dyn RsStreamFunction>)
+    }
+
+    /// Register a rust function returning an iterator
+    /// The function must return a [`JsIteratorSource`]
+    /// and accept a slice of `serde_json::Value` as arguments
+    pub fn register_iterator_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
+    where
+        F: RsIteratorFunction,
+    {
+        self.register_one(name, Box::new(callback) as Box<dyn RsIteratorFunction>)
+    }
+
+    /// Register a custom JS representation for a rust type
+    /// Replaces any conversion previously registered for the same type
+    pub fn register_conversion<T: 'static>(
+        &mut self,
+        conversion: &JsTypeConversion,
+    ) -> Result<(), Error> {
+        if self.type_registry.is_none() {
+            self.type_registry = Some(TypeRegistry::new(&mut self.deno_runtime)?);
+        }
+
+        if let Some(registry) = &mut self.type_registry {
+            registry.register(
+                &mut self.deno_runtime,
+                std::any::type_name::<T>(),
+                conversion,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Register a named blob of bytes, to be read from JS as an `ArrayBuffer`
+    /// The bytes are held once by the runtime, and every access from JS gets its own copy
+    pub fn register_blob(&mut self, name: &str, data: Vec<u8>) -> Result<(), Error> {
         let state = self.deno_runtime().op_state();
         let mut state = state.try_borrow_mut()?;
 
-        if !state.has::<HashMap<String, Box<dyn RsFunction>>>() {
-            state.put(HashMap::<String, Box<dyn RsFunction>>::new());
+        if !state.has::<ext::rustyscript::BlobPool>() {
+            state.put(ext::rustyscript::BlobPool::new());
         }
 
-        // Insert the callback into the state
         state
-            .borrow_mut::<HashMap<String, Box<dyn RsFunction>>>()
-            .insert(name.to_string(), Box::new(callback));
+            .borrow_mut::<ext::rustyscript::BlobPool>()
+            .insert(name.to_string(), data);
 
         Ok(())
     }
 
+    /// Closes every open resource, cancelling pending fetches and streams,
+    /// then terminates JS execution so no queued timers or promise callbacks can run
+    pub fn abort_all(&mut self) {
+        let state = self.deno_runtime().op_state();
+        if let Ok(mut state) = state.try_borrow_mut() {
+            let rids: Vec<_> = state.resource_table.names().map(|(rid, _)| rid).collect();
+            for rid in rids {
+                // Resources may already be closed by the time we get to them
+                state.resource_table.close(rid).ok();
+            }
+        }
+
+        self.deno_runtime.v8_isolate().terminate_execution();
+    }
+
     /// Runs the JS event loop to completion
     pub async fn await_event_loop(
         &mut self,
@@ -316,13 +2119,57 @@ impl InnerRuntime {
         if let Some(timeout) = timeout {
             Ok(tokio::select! {
                 r = self.deno_runtime.run_event_loop(options) => r,
-                () = tokio::time::sleep(timeout) => Ok(()),
+                () = tokio::time::sleep(timeout) => {
+                    self.log(LogLevel::Warn, || {
+                        format!("Event loop still had pending work after {timeout:?}")
+                    });
+                    Ok(())
+                },
             }?)
         } else {
             Ok(self.deno_runtime.run_event_loop(options).await?)
         }
     }
 
+    /// Runs the JS event loop to completion, calling `callback` at most once per `interval`
+    /// with the progress so far. If the callback returns false, the loop stops early,
+    /// leaving any remaining work pending
+    pub async fn await_event_loop_with_progress(
+        &mut self,
+        options: PollEventLoopOptions,
+        interval: Duration,
+        mut callback: impl FnMut(&EventLoopProgress) -> bool,
+    ) -> Result<(), Error> {
+        // Reporting re-arms the poll, so a zero interval would spin while the loop waits
+        let interval = interval.max(Duration::from_millis(1));
+        let start = std::time::Instant::now();
+        let mut progress = EventLoopProgress::default();
+        let mut next_report = Box::pin(tokio::time::sleep(interval));
+
+        std::future::poll_fn(|cx| {
+            progress.ticks += 1;
+            if let Poll::Ready(result) = self.deno_runtime.poll_event_loop(cx, options) {
+                return Poll::Ready(result.map_err(Error::from));
+            }
+
+            if next_report.poll_unpin(cx).is_ready() {
+                progress.elapsed = start.elapsed();
+                if !callback(&progress) {
+                    return Poll::Ready(Ok(()));
+                }
+
+                // Schedule the next report, and make sure we are polled again to arm it
+                next_report
+                    .as_mut()
+                    .reset(tokio::time::Instant::now() + interval);
+                cx.waker().wake_by_ref();
+            }
+
+            Poll::Pending
+        })
+        .await
+    }
+
     /// Advances the JS event loop by one tick
     /// Return true if the event loop is pending
     pub async fn advance_event_loop(
@@ -355,10 +2202,7 @@ impl InnerRuntime {
         T: DeserializeOwned,
     {
         let result = self.deno_runtime().execute_script("", expr.to_string())?;
-
-        let mut scope = self.deno_runtime.handle_scope();
-        let result = v8::Local::new(&mut scope, result);
-        Ok(from_v8(&mut scope, result)?)
+        self.decode_value(result)
     }
 
     /// Attempt to get a value out of the global context (globalThis.name)
@@ -411,16 +2255,138 @@ impl InnerRuntime {
         }
     }
 
+    /// Returns the JS that was executed for a loaded module, after transpilation
+    /// The specifier can be a URL, or a path relative to the current directory
+    pub fn get_transpiled_source(&self, specifier: &str) -> Option<String> {
+        let specifier =
+            deno_core::resolve_url_or_path(specifier, &std::env::current_dir().ok()?).ok()?;
+        self.module_loader.transpiled_source(specifier.as_str())
+    }
+
+    /// Returns the name, kind and arity of every export of a module, sorted by name
+    pub fn get_export_signatures(
+        &mut self,
+        module_context: &ModuleHandle,
+    ) -> Result<Vec<ExportSignature>, Error> {
+        let module_namespace = self
+            .deno_runtime
+            .get_module_namespace(module_context.id())?;
+        let mut scope = self.deno_runtime.handle_scope();
+        let module_namespace = module_namespace.open(&mut scope);
+
+        let mut signatures = vec![];
+        let names = module_namespace
+            .get_own_property_names(&mut scope, v8::GetPropertyNamesArgs::default());
+        if let Some(names) = names {
+            let length_key = "length".to_v8_string(&mut scope)?;
+            for i in 0..names.length() {
+                let Some(key) = names.get_index(&mut scope, i) else {
+                    continue;
+                };
+                let name = key.to_rust_string_lossy(&mut scope);
+
+                let value = module_namespace.get(&mut scope, key);
+                let (kind, arity) = match value {
+                    Some(f) if f.is_function() => {
+                        // Classes are functions to v8 - only their source tells them apart
+                        let is_class = f.to_string(&mut scope).is_some_and(|s| {
+                            s.to_rust_string_lossy(&mut scope).starts_with("class")
+                        });
+                        if is_class {
+                            (ExportKind::Class, None)
+                        } else {
+                            let arity = f
+                                .to_object(&mut scope)
+                                .and_then(|f| f.get(&mut scope, length_key.into()))
+                                .and_then(|length| length.uint32_value(&mut scope));
+                            (ExportKind::Function, arity)
+                        }
+                    }
+                    _ => (ExportKind::Value, None),
+                };
+
+                signatures.push(ExportSignature { name, kind, arity });
+            }
+        }
+
+        signatures.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(signatures)
+    }
+
     pub async fn resolve_with_event_loop(
         &mut self,
         value: v8::Global<v8::Value>,
     ) -> Result<v8::Global<v8::Value>, Error> {
-        let future = self.deno_runtime.resolve(value);
+        let future = self.deno_runtime.resolve(value.clone());
         let result = self
             .deno_runtime
             .with_event_loop_future(future, PollEventLoopOptions::default())
-            .await?;
-        Ok(result)
+            .await;
+        result.map_err(|e| self.with_rejection_properties(&value, e.into()))
+    }
+
+    /// Resolves a set of values together, running the event loop until every promise among them has settled
+    /// Values that are not promises resolve immediately, and a failure only affects its own entry
+    pub async fn resolve_all_with_event_loop(
+        &mut self,
+        values: Vec<Result<v8::Global<v8::Value>, Error>>,
+    ) -> Vec<Result<v8::Global<v8::Value>, Error>> {
+        let futures: Vec<_> = values
+            .iter()
+            .filter_map(|value| value.as_ref().ok())
+            .map(|value| self.deno_runtime.resolve(value.clone()))
+            .collect();
+        let settled = self
+            .deno_runtime
+            .with_event_loop_future(
+                Box::pin(async move {
+                    Ok::<_, deno_core::anyhow::Error>(
+                        deno_core::futures::future::join_all(futures).await,
+                    )
+                }),
+                PollEventLoopOptions::default(),
+            )
+            .await;
+
+        // The event loop itself failed, so none of the values could be resolved
+        let mut settled = match settled {
+            Ok(settled) => settled.into_iter(),
+            Err(e) => {
+                let message = e.to_string();
+                return values
+                    .into_iter()
+                    .map(|value| value.and_then(|_| Err(Error::Runtime(message.clone()))))
+                    .collect();
+            }
+        };
+
+        values
+            .into_iter()
+            .map(|value| {
+                let value = value?;
+                let result = settled
+                    .next()
+                    .ok_or_else(|| Error::Runtime("Value was not resolved".to_string()))?;
+                result.map_err(|e| self.with_rejection_properties(&value, e.into()))
+            })
+            .collect()
+    }
+
+    /// Attaches the properties of a rejected promise's reason to the error it produced
+    fn with_rejection_properties(&mut self, value: &v8::Global<v8::Value>, error: Error) -> Error {
+        let Error::JsError(e) = error else {
+            return error;
+        };
+
+        let mut scope = self.deno_runtime.handle_scope();
+        let value = v8::Local::new(&mut scope, value);
+        match v8::Local::<v8::Promise>::try_from(value) {
+            Ok(promise) if matches!(promise.state(), v8::PromiseState::Rejected) => {
+                let reason = promise.result(&mut scope);
+                Error::JsError(e.with_properties(&mut scope, reason))
+            }
+            _ => Error::JsError(e),
+        }
     }
 
     pub fn decode_value<T>(&mut self, value: v8::Global<v8::Value>) -> Result<T, Error>
@@ -428,8 +2394,28 @@ impl InnerRuntime {
         T: DeserializeOwned,
     {
         let mut scope = self.deno_runtime.handle_scope();
-        let result = v8::Local::<v8::Value>::new(&mut scope, value);
-        Ok(from_v8(&mut scope, result)?)
+        let mut result = v8::Local::<v8::Value>::new(&mut scope, value);
+        let Some(registry) = &self.type_registry else {
+            return Ok(from_v8(&mut scope, result)?);
+        };
+
+        // Values are only converted for the `JsRepr` types that decoding reaches
+        // An early failure can hide further types, so decode again until none are new
+        let mut converted = Vec::new();
+        loop {
+            let (decoded, reached) =
+                type_registry::track(registry.names(), || from_v8::<T>(&mut scope, result));
+            let pending: Vec<_> = reached
+                .into_iter()
+                .filter(|name| !converted.contains(name))
+                .collect();
+            if pending.is_empty() {
+                return Ok(decoded?);
+            }
+
+            result = registry.from_js(&mut scope, result, &pending)?;
+            converted.extend(pending);
+        }
     }
 
     pub fn get_value_ref(
@@ -475,8 +2461,61 @@ impl InnerRuntime {
             .try_into()
             .or::<Error>(Err(Error::ValueNotCallable(name.to_string())))?;
 
-        // Return it as a global
-        Ok(v8::Global::<v8::Function>::new(&mut scope, f))
+        // Return it as a global
+        Ok(v8::Global::<v8::Function>::new(&mut scope, f))
+    }
+
+    /// Resolves an exported function once, so that it can be called repeatedly without a name lookup
+    /// The function is tied to the module's current load - see `check_function_origin`
+    pub fn get_function(
+        &mut self,
+        module_context: &ModuleHandle,
+        name: &str,
+    ) -> Result<crate::js_value::Function, Error> {
+        let function = self.get_function_by_name(Some(module_context), name)?;
+        let mut scope = self.deno_runtime.handle_scope();
+
+        // Recorded on the function itself, so nothing outlives it
+        let origin = (module_context.module().filename(), module_context.id());
+        let origin = deno_core::serde_v8::to_v8(&mut scope, origin)?;
+        let key = FUNCTION_ORIGIN_KEY.to_v8_string(&mut scope)?;
+        let key = v8::Private::for_api(&mut scope, Some(key));
+        v8::Local::new(&mut scope, &function).set_private(&mut scope, key, origin);
+
+        crate::js_value::Function::try_from_v8(&mut scope, function)
+    }
+
+    /// Fails if a function from `get_function` came from a module that has since been loaded again
+    pub fn check_function_origin(
+        &mut self,
+        function: &crate::js_value::Function,
+    ) -> Result<(), Error> {
+        let origin = {
+            let mut scope = self.deno_runtime.handle_scope();
+            let function = function.as_global(&mut scope);
+            let key = FUNCTION_ORIGIN_KEY.to_v8_string(&mut scope)?;
+            let key = v8::Private::for_api(&mut scope, Some(key));
+            match v8::Local::new(&mut scope, function).get_private(&mut scope, key) {
+                Some(origin) if !origin.is_undefined() => {
+                    Some(deno_core::serde_v8::from_v8::<(
+                        std::path::PathBuf,
+                        deno_core::ModuleId,
+                    )>(&mut scope, origin)?)
+                }
+                _ => None,
+            }
+        };
+        let Some((filename, module_id)) = origin else {
+            return Ok(());
+        };
+
+        match self.module_ids.get(&filename) {
+            Some(current) if *current != module_id => Err(Error::Runtime(format!(
+                "The function was resolved from {}, which has since been reloaded - resolve it again",
+                filename.display()
+            ))),
+            _ => Ok(()),
+        }
     }
 
     pub fn call_function_by_ref(
@@ -485,6 +2524,31 @@ impl InnerRuntime {
         function: &v8::Global<v8::Function>,
         args: &impl serde::ser::Serialize,
     ) -> Result<v8::Global<v8::Value>, Error> {
+        self.call_function_with_args(module_context, function, |scope| decode_args(args, scope))
+    }
+
+    /// Calls a function, reading its arguments as a JSON array from `reader`
+    /// See [`decode_streamed_args`]
+    pub fn call_function_by_ref_streamed(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &v8::Global<v8::Function>,
+        reader: impl std::io::Read,
+    ) -> Result<v8::Global<v8::Value>, Error> {
+        self.call_function_with_args(module_context, function, |scope| {
+            decode_streamed_args(reader, scope)
+        })
+    }
+
+    fn call_function_with_args<F>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &v8::Global<v8::Function>,
+        get_args: F,
+    ) -> Result<v8::Global<v8::Value>, Error>
+    where
+        F: for<'a> FnOnce(&mut v8::HandleScope<'a>) -> Result<Vec<v8::Local<'a, v8::Value>>, Error>,
+    {
         // Namespace, if provided
         let module_namespace = if let Some(module_context) = module_context {
             Some(
@@ -496,54 +2560,60 @@ impl InnerRuntime {
         };
 
         let mut scope = self.deno_runtime.handle_scope();
-        let mut scope = v8::TryCatch::new(&mut scope);
-
-        // Get the namespace
-        // Module-level if supplied, none otherwise
-        let namespace: v8::Local<v8::Value> = if let Some(namespace) = module_namespace {
-            v8::Local::<v8::Object>::new(&mut scope, namespace).into()
-        } else {
-            // Create a new object to use as the namespace if none is provided
-            //let obj: v8::Local<v8::Value> = v8::Object::new(&mut scope).into();
-            let obj: v8::Local<v8::Value> = v8::undefined(&mut scope).into();
-            obj
-        };
-
-        let function_instance = function.open(&mut scope);
-
-        // Prep arguments
-        let args = decode_args(args, &mut scope)?;
-
-        // Call the function
-        let result = function_instance.call(&mut scope, namespace, &args);
-        match result {
-            Some(value) => {
-                let value = v8::Global::new(&mut scope, value);
-                Ok(value)
+        let function_instance = v8::Local::new(&mut scope, function);
+
+        // Functions from sandboxed modules get their arguments built in their own context,
+        // since any object from the runtime's context would lead back to its globals
+        let context = scope.get_current_context();
+        let function_context = function_instance
+            .get_creation_context(&mut scope)
+            .unwrap_or(context);
+        let sandboxed = function_context != context;
+
+        let outcome = {
+            let mut scope = v8::ContextScope::new(&mut scope, function_context);
+            let mut scope = v8::TryCatch::new(&mut scope);
+
+            // Get the namespace
+            // Module-level if supplied, none otherwise
+            let namespace: v8::Local<v8::Value> = if let Some(namespace) = module_namespace {
+                v8::Local::<v8::Object>::new(&mut scope, namespace).into()
+            } else {
+                // Create a new object to use as the namespace if none is provided
+                //let obj: v8::Local<v8::Value> = v8::Object::new(&mut scope).into();
+                let obj: v8::Local<v8::Value> = v8::undefined(&mut scope).into();
+                obj
+            };
+
+            // Prep arguments
+            // Registered conversions belong to the runtime's context, so sandboxes do not use them
+            let registry = self.type_registry.as_ref().filter(|_| !sandboxed);
+            let names = registry.map_or(&[][..], TypeRegistry::names);
+            let (args, reached) = type_registry::track(names, || get_args(&mut scope));
+            let mut args = args?;
+            if let Some(registry) = registry.filter(|_| !reached.is_empty()) {
+                for arg in &mut args {
+                    *arg = registry.to_js(&mut scope, *arg)?;
+                }
             }
-            None if scope.has_caught() => {
-                let e = scope
-                    .message()
-                    .ok_or_else(|| Error::Runtime("Unknown error".to_string()))?;
-
-                let filename = e.get_script_resource_name(&mut scope);
-                let linenumber = e.get_line_number(&mut scope).unwrap_or_default();
-                let filename = if let Some(v) = filename {
-                    let filename = v.to_rust_string_lossy(&mut scope);
-                    format!("{filename}:{linenumber}: ")
-                } else if let Some(module_context) = module_context {
-                    let filename = module_context.module().filename().to_string_lossy();
-                    format!("{filename}:{linenumber}: ")
-                } else {
-                    String::new()
-                };
 
-                let msg = e.get(&mut scope).to_rust_string_lossy(&mut scope);
+            // Call the function
+            match function_instance.call(&mut scope, namespace, &args) {
+                Some(value) => Ok(v8::Global::new(&mut scope, value)),
+                None => Err(scope
+                    .exception()
+                    .map(|exception| v8::Global::new(&mut scope, exception))),
+            }
+        };
 
-                let s = format!("{filename}{msg}");
-                Err(Error::Runtime(s))
+        // Errors are converted in the runtime's context, which holds the source maps
+        match outcome {
+            Ok(value) => Ok(value),
+            Err(Some(exception)) => {
+                let exception = v8::Local::new(&mut scope, exception);
+                Err(crate::error::JsError::from_exception(&mut scope, exception).into())
             }
-            None => Err(Error::Runtime(
+            Err(None) => Err(Error::Runtime(
                 "Unknown error during function execution".to_string(),
             )),
         }
@@ -633,6 +2703,589 @@ impl InnerRuntime {
         Ok(None)
     }
 
+    /// Evaluates a loaded module, running the event loop until it completes
+    /// If `capture_load_errors` is set, exceptions thrown by the module are stored in
+    /// `load_error` (if it is empty) instead of being returned
+    async fn evaluate_module(
+        &mut self,
+        module_id: deno_core::ModuleId,
+        load_error: &mut Option<Error>,
+    ) -> Result<(), Error> {
+        let mod_load = self.deno_runtime.mod_evaluate(module_id);
+        let result = self
+            .with_event_loop_future(mod_load, PollEventLoopOptions::default())
+            .await;
+
+        match result {
+            Err(e @ (Error::JsError(_) | Error::Runtime(_) | Error::StackOverflow))
+                if self.capture_load_errors =>
+            {
+                load_error.get_or_insert(e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Compiles a module into the runtime without evaluating it, returning its ID and any transpiler warnings
+    /// Modules carrying bytecode are compiled through the loader, which hands the bytecode to v8
+    async fn compile_module(
+        &mut self,
+        module: &Module,
+        specifier: &deno_core::ModuleSpecifier,
+        is_main: bool,
+    ) -> Result<(deno_core::ModuleId, Vec<crate::Diagnostic>), Error> {
+        // Modules with bytecode or an explicit type are handed to the loader, which knows what to do with them
+        if module.code_cache().is_some() || module.module_type().is_some() {
+            self.module_loader
+                .insert_source(specifier.clone(), module.contents().to_string());
+            if let Some(code_cache) = module.code_cache() {
+                self.module_loader
+                    .insert_code_cache(specifier.clone(), code_cache.to_vec());
+            }
+            if let Some(module_type) = module.module_type() {
+                self.module_loader
+                    .insert_module_type(specifier.clone(), module_type.into());
+            }
+
+            let module_id = if is_main {
+                self.deno_runtime.load_main_es_module(specifier).await?
+            } else {
+                self.deno_runtime.load_side_es_module(specifier).await?
+            };
+            return Ok((module_id, Vec::new()));
+        }
+
+        let contents = self
+            .module_loader
+            .transform_source(specifier, module.contents())?;
+        let ((code, sourcemap), diagnostics) = transpile_with_diagnostics(specifier, &contents)?;
+
+        // Code given to v8 directly skips the loader, which is what attaches stored bytecode
+        let module_id = if self.module_loader.has_code_cache_dir() {
+            self.module_loader
+                .insert_prepared_source(specifier.clone(), code.clone());
+            if is_main {
+                self.deno_runtime.load_main_es_module(specifier).await?
+            } else {
+                self.deno_runtime.load_side_es_module(specifier).await?
+            }
+        } else {
+            let fast_code = deno_core::FastString::from(code.clone());
+            if is_main {
+                self.deno_runtime
+                    .load_main_es_module_from_code(specifier, fast_code)
+                    .await?
+            } else {
+                self.deno_runtime
+                    .load_side_es_module_from_code(specifier, fast_code)
+                    .await?
+            }
+        };
+
+        // Update source map cache
+        self.module_loader
+            .insert_transpiled_source(specifier.as_str(), code);
+        self.module_loader.insert_source_map(
+            specifier.as_str(),
+            contents,
+            sourcemap.map(|s| s.to_vec()),
+        );
+
+        Ok((module_id, diagnostics))
+    }
+
+    /// Compiles a module to v8 bytecode, which can be given back with [`Module::from_code_cache`]
+    /// The module is not added to the runtime
+    ///
+    /// The source goes through the same steps as when the module is loaded, and the bytes
+    /// record a hash of the result, so they are only used for the code they were compiled from
+    pub fn compile_module_to_bytes(&mut self, module: &Module) -> Result<Vec<u8>, Error> {
+        use crate::module_loader::code_cache::{add_header, CodeCacheDir};
+
+        let specifier = module.filename().to_module_specifier(None)?;
+        self.module_loader
+            .set_import_meta(&specifier, module.import_meta());
+        let contents = self
+            .module_loader
+            .transform_source(&specifier, module.contents())?;
+        let (code, _) = transpile(&specifier, &contents)?;
+        let hash = CodeCacheDir::hash(code.as_bytes());
+
+        let mut scope = self.deno_runtime.handle_scope();
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let name = specifier.as_str().to_v8_string(&mut scope)?;
+        let code = code.to_v8_string(&mut scope)?;
+        let origin = v8::ScriptOrigin::new(
+            &mut scope,
+            name.into(),
+            0,
+            0,
+            false,
+            0,
+            None,
+            false,
+            false,
+            true,
+            None,
+        );
+        let mut source = v8::script_compiler::Source::new(code, Some(&origin));
+
+        let Some(compiled) = v8::script_compiler::compile_module(&mut scope, &mut source) else {
+            let message = scope.message().map_or_else(
+                || "Unknown error".to_string(),
+                |m| m.get(&mut scope).to_rust_string_lossy(&mut scope),
+            );
+            return Err(Error::Runtime(format!("{specifier}: {message}")));
+        };
+
+        compiled
+            .get_unbound_module_script(&mut scope)
+            .create_code_cache()
+            .map(|code_cache| add_header(hash, &code_cache))
+            .ok_or_else(|| Error::Runtime(format!("{specifier}: Could not create a code cache")))
+    }
+
+    /// Compile a module without instantiating it, returning the specifiers it statically imports
+    fn get_module_requests(
+        &mut self,
+        specifier: &deno_core::ModuleSpecifier,
+        code: &str,
+    ) -> Result<Vec<String>, Error> {
+        let mut scope = self.deno_runtime.handle_scope();
+        let mut scope = v8::TryCatch::new(&mut scope);
+
+        let name = specifier.as_str().to_v8_string(&mut scope)?;
+        let code = code.to_v8_string(&mut scope)?;
+        let origin = v8::ScriptOrigin::new(
+            &mut scope,
+            name.into(),
+            0,
+            0,
+            false,
+            0,
+            None,
+            false,
+            false,
+            true,
+            None,
+        );
+        let mut source = v8::script_compiler::Source::new(code, Some(&origin));
+
+        let Some(module) = v8::script_compiler::compile_module(&mut scope, &mut source) else {
+            let message = scope.message().map_or_else(
+                || "Unknown error".to_string(),
+                |m| m.get(&mut scope).to_rust_string_lossy(&mut scope),
+            );
+            return Err(Error::Runtime(format!("{specifier}: {message}")));
+        };
+
+        let requests = module.get_module_requests();
+        let mut specifiers = Vec::with_capacity(requests.length());
+        for i in 0..requests.length() {
+            let Some(request) = requests.get(&mut scope, i) else {
+                continue;
+            };
+            if let Ok(request) = v8::Local::<v8::ModuleRequest>::try_from(request) {
+                specifiers.push(request.get_specifier().to_rust_string_lossy(&mut scope));
+            }
+        }
+
+        Ok(specifiers)
+    }
+
+    /// Loads a module in its own v8 context, which can only see the given globals
+    ///
+    /// Builtins in the list are the context's own copies, while other globals are copied over from
+    /// the runtime's context, and remain shared with it.
+    /// The module cannot import other modules or declare async functions, and code generation from
+    /// strings is disabled, since any of those would reach outside of its context.
+    /// Its exports are re-exported to the runtime by a module loaded under the same filename.
+    pub async fn load_sandboxed_module(
+        &mut self,
+        module: &Module,
+        allowed_globals: &[&str],
+    ) -> Result<ModuleHandle, Error> {
+        self.load_isolated_module(module, allowed_globals, false)
+            .await
+    }
+
+    /// Loads a module in pure function mode
+    /// It is sandboxed with only deterministic builtins visible, and may not declare generators
+    pub async fn load_pure_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.load_isolated_module(module, &PURE_GLOBALS, true).await
+    }
+
+    /// Loads a sandboxed or pure module - see [`InnerRuntime::load_sandboxed_module`]
+    async fn load_isolated_module(
+        &mut self,
+        module: &Module,
+        allowed_globals: &[&str],
+        pure: bool,
+    ) -> Result<ModuleHandle, Error> {
+        let specifier = module.filename().to_module_specifier(None)?;
+        let contents = self
+            .module_loader
+            .transform_source(&specifier, module.contents())?;
+        let (code, _) = transpile(&specifier, &contents)?;
+        if let Some(violation) = find_isolation_violation(&specifier, &code, pure)? {
+            let kind = if pure { "pure" } else { "sandboxed" };
+            return Err(Error::Runtime(format!(
+                "{specifier} cannot be loaded as a {kind} module: it {violation}"
+            )));
+        }
+
+        let namespace = self.evaluate_in_sandbox(&specifier, &code, allowed_globals)?;
+
+        // Hand the namespace to the module re-exporting it through a temporary global
+        let names = {
+            let mut scope = self.deno_runtime.handle_scope();
+            let global = scope.get_current_context().global(&mut scope);
+            let key = SANDBOX_NAMESPACE_GLOBAL.to_v8_string(&mut scope)?;
+            let namespace = v8::Local::new(&mut scope, namespace);
+            global.set(&mut scope, key.into(), namespace.into());
+
+            let mut names = Vec::new();
+            let keys =
+                namespace.get_own_property_names(&mut scope, v8::GetPropertyNamesArgs::default());
+            if let Some(keys) = keys {
+                for i in 0..keys.length() {
+                    if let Some(key) = keys.get_index(&mut scope, i) {
+                        names.push(key.to_rust_string_lossy(&mut scope));
+                    }
+                }
+            }
+            names
+        };
+
+        let mut bindings = Vec::with_capacity(names.len());
+        let mut exports = Vec::with_capacity(names.len());
+        for (i, name) in names.iter().enumerate() {
+            let name = serde_json::to_string(name)?;
+            bindings.push(format!("{name}: export{i}"));
+            exports.push(format!("export{i} as {name}"));
+        }
+        let facade = Module::new(
+            module.filename(),
+            &format!(
+                "const {{ {} }} = globalThis.{SANDBOX_NAMESPACE_GLOBAL};\nexport {{ {} }};",
+                bindings.join(", "),
+                exports.join(", ")
+            ),
+        );
+        let result = self.load_modules(None, vec![&facade]).await;
+
+        let mut scope = self.deno_runtime.handle_scope();
+        let global = scope.get_current_context().global(&mut scope);
+        let key = SANDBOX_NAMESPACE_GLOBAL.to_v8_string(&mut scope)?;
+        global.delete(&mut scope, key.into());
+
+        result
+    }
+
+    /// Evaluates a module in a new context holding only the allowed globals, returning its namespace
+    fn evaluate_in_sandbox(
+        &mut self,
+        specifier: &deno_core::ModuleSpecifier,
+        code: &str,
+        allowed_globals: &[&str],
+    ) -> Result<v8::Global<v8::Object>, Error> {
+        let mut scope = self.deno_runtime.handle_scope();
+
+        // Globals from the runtime's context must be read before leaving it
+        let runtime_global = scope.get_current_context().global(&mut scope);
+        let mut host_globals = Vec::with_capacity(allowed_globals.len());
+        for name in allowed_globals {
+            let key = name.to_v8_string(&mut scope)?;
+            if let Some(value) = runtime_global.get(&mut scope, key.into()) {
+                host_globals.push((key, value));
+            }
+        }
+        let json_key = "JSON".to_v8_string(&mut scope)?;
+        let host_json = runtime_global
+            .get(&mut scope, json_key.into())
+            .ok_or_else(|| Error::Runtime("Could not prepare the sandbox".to_string()))?;
+
+        let context = v8::Context::new(&mut scope);
+        let outcome = {
+            let mut scope = v8::ContextScope::new(&mut scope, context);
+            let mut scope = v8::TryCatch::new(&mut scope);
+
+            let host = v8::Object::new(&mut scope);
+            for (key, value) in host_globals {
+                host.set(&mut scope, key.into(), value);
+            }
+            let allowed = deno_core::serde_v8::to_v8(&mut scope, allowed_globals)
+                .map_err(|e| Error::Runtime(e.to_string()))?;
+
+            let setup = SANDBOX_SETUP_SOURCE.to_v8_string(&mut scope)?;
+            let setup = v8::Script::compile(&mut scope, setup, None)
+                .and_then(|script| script.run(&mut scope))
+                .and_then(|setup| v8::Local::<v8::Function>::try_from(setup).ok())
+                .ok_or_else(|| Error::Runtime("Could not prepare the sandbox".to_string()))?;
+            let undefined = v8::undefined(&mut scope).into();
+            let prepared = setup
+                .call(&mut scope, undefined, &[allowed, host.into(), host_json])
+                .is_some();
+
+            let name = specifier.as_str().to_v8_string(&mut scope)?;
+            let code = code.to_v8_string(&mut scope)?;
+            let origin = v8::ScriptOrigin::new(
+                &mut scope,
+                name.into(),
+                0,
+                0,
+                false,
+                0,
+                None,
+                false,
+                false,
+                true,
+                None,
+            );
+            let mut source = v8::script_compiler::Source::new(code, Some(&origin));
+
+            let module = if prepared {
+                v8::script_compiler::compile_module(&mut scope, &mut source)
+            } else {
+                None
+            };
+            let module = module.filter(|module| {
+                module
+                    .instantiate_module(&mut scope, reject_sandbox_import)
+                    .is_some()
+            });
+            let evaluated = module.and_then(|module| {
+                let result = module.evaluate(&mut scope);
+                scope.perform_microtask_checkpoint();
+                if module.get_status() == v8::ModuleStatus::Errored {
+                    let exception = module.get_exception();
+                    scope.throw_exception(exception);
+                    return None;
+                }
+                result.map(|_| module)
+            });
+
+            // Errors are converted in the runtime's context, which holds the source maps
+            match evaluated {
+                Some(module) => {
+                    let namespace =
+                        v8::Local::<v8::Object>::try_from(module.get_module_namespace())?;
+                    Ok(v8::Global::new(&mut scope, namespace))
+                }
+                None => Err(scope
+                    .exception()
+                    .map(|exception| v8::Global::new(&mut scope, exception))),
+            }
+        };
+
+        match outcome {
+            Ok(namespace) => Ok(namespace),
+            Err(Some(exception)) => {
+                let exception = v8::Local::new(&mut scope, exception);
+                Err(crate::error::JsError::from_exception(&mut scope, exception).into())
+            }
+            Err(None) => Err(Error::Runtime(format!(
+                "{specifier}: Unknown error while evaluating the sandboxed module"
+            ))),
+        }
+    }
+
+    /// Loads a set of modules that can import each other without filesystem access
+    /// All sources are registered first, then each module is evaluated in order
+    pub async fn load_module_set(
+        &mut self,
+        modules: &[Module],
+    ) -> Result<Vec<ModuleHandle>, Error> {
+        let specifiers = self.insert_module_sources(modules)?;
+
+        let mut handles = Vec::with_capacity(modules.len());
+        for (module, specifier) in modules.iter().zip(specifiers) {
+            // Already-imported modules are reused, not loaded twice
+            let module_id = self.deno_runtime.load_side_es_module(&specifier).await?;
+
+            let mut load_error = None;
+            self.evaluate_module(module_id, &mut load_error).await?;
+            self.log(LogLevel::Info, || format!("Loaded module {specifier}"));
+
+            self.module_ids
+                .insert(module.filename().to_path_buf(), module_id);
+
+            let mut handle = ModuleHandle::new(module, module_id, None);
+            let entrypoint = self.get_module_entrypoint(&mut handle)?;
+            handles
+                .push(ModuleHandle::new(module, module_id, entrypoint).with_load_error(load_error));
+        }
+
+        Ok(handles)
+    }
+
+    /// Makes the sources of a set of modules available to the loader, so they can import each other
+    fn insert_module_sources(
+        &mut self,
+        modules: &[Module],
+    ) -> Result<Vec<deno_core::ModuleSpecifier>, Error> {
+        let mut specifiers = Vec::with_capacity(modules.len());
+        for module in modules {
+            let specifier = module.filename().to_module_specifier(None)?;
+            if module.is_trusted() {
+                self.module_loader.trust(&specifier);
+            }
+            self.module_loader
+                .set_import_meta(&specifier, module.import_meta());
+            self.module_loader
+                .insert_source(specifier.clone(), module.contents().to_string());
+            specifiers.push(specifier);
+        }
+
+        Ok(specifiers)
+    }
+
+    /// Compiles and links a set of modules, without running their top-level code
+    /// A module is evaluated the first time it is requested with `get_lazy_module`,
+    /// or when another module imports it
+    pub async fn load_modules_lazy(&mut self, modules: &[Module]) -> Result<(), Error> {
+        let specifiers = self.insert_module_sources(modules)?;
+        for (module, specifier) in modules.iter().zip(specifiers) {
+            let module_id = self.deno_runtime.load_side_es_module(&specifier).await?;
+            self.lazy_modules
+                .insert(specifier, LazyModule::Compiled(module.clone(), module_id));
+        }
+
+        Ok(())
+    }
+
+    /// Loads a module by a specifier relative to an already-loaded module, as if the host had requested it
+    /// A module that was already imported is not run again
+    pub async fn import_relative(
+        &mut self,
+        base: &ModuleHandle,
+        specifier: &str,
+    ) -> Result<ModuleHandle, Error> {
+        let referrer = base.module().filename().to_module_specifier(None)?;
+        let url = deno_core::resolve_import(specifier, referrer.as_str())?;
+        let module_id = self.deno_runtime.load_side_es_module(&url).await?;
+
+        // Evaluated through a dynamic import, in case it has already been run
+        let import = format!("import({})", serde_json::Value::from(url.as_str()));
+        let promise = self.deno_runtime.execute_script("", import)?;
+        self.resolve_with_event_loop(promise).await?;
+        self.log(LogLevel::Info, || format!("Loaded module {url}"));
+
+        let filename = url
+            .to_file_path()
+            .unwrap_or_else(|()| std::path::PathBuf::from(url.as_str()));
+        let source = self
+            .module_loader
+            .transpiled_source(url.as_str())
+            .unwrap_or_default();
+        let module = Module::new(filename, &source);
+
+        let mut handle = ModuleHandle::new(&module, module_id, None);
+        let entrypoint = self.get_module_entrypoint(&mut handle)?;
+        self.module_ids
+            .insert(module.filename().to_path_buf(), module_id);
+        Ok(ModuleHandle::new(&module, module_id, entrypoint))
+    }
+
+    /// Returns a handle to a module loaded with `load_modules_lazy`,
+    /// evaluating it first if this is the first request for it
+    pub async fn get_lazy_module(&mut self, filename: &Path) -> Result<ModuleHandle, Error> {
+        let specifier = filename.to_module_specifier(None)?;
+        let (module, module_id) = match self.lazy_modules.get(&specifier) {
+            Some(LazyModule::Evaluated(handle)) => return Ok(handle.clone()),
+            Some(LazyModule::Compiled(module, module_id)) => (module.clone(), *module_id),
+            None => {
+                return Err(Error::ModuleNotFound(format!(
+                    "{specifier} was not loaded lazily"
+                )))
+            }
+        };
+
+        // Evaluated through a dynamic import, since the module may already
+        // have been run as a dependency of another - in which case it is not run again
+        let import = format!("import({})", serde_json::Value::from(specifier.as_str()));
+        let promise = self.deno_runtime.execute_script("", import)?;
+        self.resolve_with_event_loop(promise).await?;
+        self.log(LogLevel::Info, || format!("Loaded module {specifier}"));
+
+        let mut handle = ModuleHandle::new(&module, module_id, None);
+        let entrypoint = self.get_module_entrypoint(&mut handle)?;
+        let handle = ModuleHandle::new(&module, module_id, entrypoint);
+        self.module_ids
+            .insert(module.filename().to_path_buf(), module_id);
+        self.lazy_modules
+            .insert(specifier, LazyModule::Evaluated(handle.clone()));
+
+        Ok(handle)
+    }
+
+    /// Walk the static import graph of a module without evaluating anything
+    /// Imports are resolved and fetched with the runtime's module loader, so the
+    /// same permissions, import providers and caches apply
+    pub async fn measure_module_graph(
+        &mut self,
+        module: &Module,
+    ) -> Result<ModuleGraphSize, Error> {
+        use deno_core::{ModuleLoadResponse, ModuleLoader, ModuleSourceCode, ModuleType};
+
+        let root = module.filename().to_module_specifier(None)?;
+        let contents = self
+            .module_loader
+            .transform_source(&root, module.contents())?;
+        let (code, _) = transpile(&root, &contents)?;
+
+        let mut size = ModuleGraphSize {
+            module_count: 1,
+            total_bytes: code.len(),
+        };
+        let mut seen = HashSet::from([root.clone()]);
+        let mut queue = vec![(root, code)];
+
+        while let Some((referrer, code)) = queue.pop() {
+            for request in self.get_module_requests(&referrer, &code)? {
+                // Extension modules are part of the runtime, not the graph
+                if request.starts_with("ext:") {
+                    continue;
+                }
+
+                let specifier = self.module_loader.resolve(
+                    &request,
+                    referrer.as_str(),
+                    deno_core::ResolutionKind::Import,
+                )?;
+                if !seen.insert(specifier.clone()) {
+                    continue;
+                }
+
+                let source = match self.module_loader.load(
+                    &specifier,
+                    Some(&referrer),
+                    false,
+                    deno_core::RequestedModuleType::None,
+                ) {
+                    ModuleLoadResponse::Sync(source) => source?,
+                    ModuleLoadResponse::Async(source) => source.await?,
+                };
+
+                size.module_count += 1;
+                match source.code {
+                    ModuleSourceCode::String(code)
+                        if source.module_type == ModuleType::JavaScript =>
+                    {
+                        size.total_bytes += code.as_str().len();
+                        queue.push((specifier, code.as_str().to_string()));
+                    }
+                    ModuleSourceCode::String(code) => size.total_bytes += code.as_str().len(),
+                    ModuleSourceCode::Bytes(code) => size.total_bytes += code.as_bytes().len(),
+                }
+            }
+        }
+
+        Ok(size)
+    }
+
     /// Load one or more modules
     /// Returns a future that resolves to a handle to the main module, or the last
     /// side-module
@@ -651,54 +3304,64 @@ impl InnerRuntime {
         }
 
         let mut module_handle_stub = ModuleHandle::default();
+        let mut side_module_errors = Vec::new();
+        let mut warnings = Vec::new();
 
         // Get additional modules first
         for side_module in side_modules {
             let module_specifier = side_module.filename().to_module_specifier(None)?;
-            let (code, sourcemap) = transpile(&module_specifier, side_module.contents())?;
-            let fast_code = deno_core::FastString::from(code.clone());
+            if side_module.is_trusted() {
+                self.module_loader.trust(&module_specifier);
+            }
+            self.module_loader
+                .set_import_meta(&module_specifier, side_module.import_meta());
 
-            let s_modid = self
-                .deno_runtime
-                .load_side_es_module_from_code(&module_specifier, fast_code)
+            let (s_modid, diagnostics) = self
+                .compile_module(side_module, &module_specifier, false)
                 .await?;
+            warnings.extend(diagnostics);
 
-            // Update source map cache
-            self.module_loader.insert_source_map(
-                module_specifier.as_str(),
-                code,
-                sourcemap.map(|s| s.to_vec()),
-            );
-
-            let mod_load = self.deno_runtime.mod_evaluate(s_modid);
-            self.with_event_loop_future(mod_load, PollEventLoopOptions::default())
-                .await?;
+            let mut load_error = None;
+            self.evaluate_module(s_modid, &mut load_error).await?;
+            if let Some(e) = load_error {
+                side_module_errors.push((side_module.filename().to_path_buf(), e));
+            }
+            self.log(LogLevel::Info, || {
+                format!("Loaded module {module_specifier}")
+            });
+            self.module_ids
+                .insert(side_module.filename().to_path_buf(), s_modid);
             module_handle_stub = ModuleHandle::new(side_module, s_modid, None);
         }
 
         // Load main module
+        let mut load_error = None;
         if let Some(module) = main_module {
             let module_specifier = module.filename().to_module_specifier(None)?;
-            let (code, sourcemap) = transpile(&module_specifier, module.contents())?;
-            let fast_code = deno_core::FastString::from(code.clone());
-
-            let module_id = self
-                .deno_runtime
-                .load_main_es_module_from_code(&module_specifier, fast_code)
-                .await?;
+            if module.is_trusted() {
+                self.module_loader.trust(&module_specifier);
+            }
+            self.module_loader
+                .set_import_meta(&module_specifier, module.import_meta());
 
-            // Update source map cache
-            self.module_loader.insert_source_map(
-                module_specifier.as_str(),
-                code,
-                sourcemap.map(|s| s.to_vec()),
-            );
+            let (module_id, diagnostics) =
+                self.compile_module(module, &module_specifier, true).await?;
+            warnings.extend(diagnostics);
 
             // Finish execution
-            let mod_load = self.deno_runtime.mod_evaluate(module_id);
-            self.with_event_loop_future(mod_load, PollEventLoopOptions::default())
-                .await?;
+            self.evaluate_module(module_id, &mut load_error).await?;
+            self.log(LogLevel::Info, || {
+                format!("Loaded module {module_specifier}")
+            });
+            self.module_ids
+                .insert(module.filename().to_path_buf(), module_id);
             module_handle_stub = ModuleHandle::new(module, module_id, None);
+        } else if side_module_errors
+            .last()
+            .is_some_and(|(filename, _)| filename == module_handle_stub.module().filename())
+        {
+            // The returned handle is the last side module - its own error belongs on it
+            load_error = side_module_errors.pop().map(|(_, e)| e);
         }
 
         // Try to get the default entrypoint
@@ -708,7 +3371,10 @@ impl InnerRuntime {
             module_handle_stub.module(),
             module_handle_stub.id(),
             entrypoint,
-        ))
+        )
+        .with_load_error(load_error)
+        .with_side_module_errors(side_module_errors)
+        .with_warnings(warnings))
     }
 }
 
@@ -716,7 +3382,9 @@ impl InnerRuntime {
 mod test_inner_runtime {
     use serde::Deserialize;
 
-    use crate::{async_callback, big_json_args, js_value::Function, json_args, sync_callback};
+    use crate::{
+        async_callback, big_json_args, js_value::Function, json_args, sync_callback, Undefined,
+    };
 
     #[cfg(any(feature = "web", feature = "web_stub"))]
     use crate::js_value::Promise;
@@ -768,6 +3436,10 @@ mod test_inner_runtime {
         let args = decode_args(&2, &mut scope).expect("Could not decode args");
         assert_eq!(args.len(), 1);
 
+        // single array, wrapped in a tuple
+        let args = decode_args(&(vec![1, 2, 3],), &mut scope).expect("Could not decode args");
+        assert_eq!(args.len(), 1);
+
         // multiple heterogeneous
         let args = decode_args(&json_args!(2, "test"), &mut scope).expect("Could not decode args");
         assert_eq!(args.len(), 2);
@@ -850,6 +3522,114 @@ mod test_inner_runtime {
         assert_eq!(result, 5);
     }
 
+    #[test]
+    #[cfg(feature = "web")]
+    fn test_register_stream_function() {
+        let mut runtime = InnerRuntime::new(RuntimeOptions::default(), CancellationToken::new())
+            .expect("Could not load runtime");
+        runtime
+            .register_stream_function("count", |args| {
+                let n = args
+                    .first()
+                    .and_then(serde_json::Value::as_u64)
+                    .unwrap_or(0);
+                let values = (0..n).map(|i| Ok(serde_json::Value::from(i)));
+                Ok(Box::pin(deno_core::futures::stream::iter(values)))
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.v = [];
+            for await (const value of rustyscript.stream_functions.count(3)) {
+                globalThis.v.push(value);
+            }
+            ",
+        );
+
+        let rt = &mut runtime;
+        let module = run_async_task(|| async move { rt.load_modules(Some(&module), vec![]).await });
+
+        let result = runtime
+            .get_value_ref(Some(&module), "v")
+            .expect("Could not find global");
+        let result: Vec<usize> = runtime.decode_value(result).expect("Could not decode");
+        assert_eq!(vec![0, 1, 2], result);
+    }
+
+    #[test]
+    fn test_register_iterator_function() {
+        let mut runtime = InnerRuntime::new(RuntimeOptions::default(), CancellationToken::new())
+            .expect("Could not load runtime");
+        let pulled = Rc::new(std::cell::Cell::new(0));
+        let counter = pulled.clone();
+        runtime
+            .register_iterator_function("naturals", move |_| {
+                let counter = counter.clone();
+                Ok(Box::new((0..).map(move |i: u64| {
+                    counter.set(counter.get() + 1);
+                    Ok(serde_json::Value::from(i))
+                })))
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.v = [];
+            for (const value of rustyscript.iterator_functions.naturals()) {
+                if (value > 2) break;
+                globalThis.v.push(value);
+            }
+            ",
+        );
+
+        let rt = &mut runtime;
+        let module = run_async_task(|| async move { rt.load_modules(Some(&module), vec![]).await });
+
+        let result = runtime
+            .get_value_ref(Some(&module), "v")
+            .expect("Could not find global");
+        let result: Vec<usize> = runtime.decode_value(result).expect("Could not decode");
+        assert_eq!(vec![0, 1, 2], result);
+
+        // Only the values that were read were produced
+        assert_eq!(4, pulled.get());
+    }
+
+    #[test]
+    fn test_register_blob() {
+        let mut runtime = InnerRuntime::new(RuntimeOptions::default(), CancellationToken::new())
+            .expect("Could not load runtime");
+        runtime
+            .register_blob("data", vec![1, 2, 3])
+            .expect("Could not register blob");
+
+        let result: usize = runtime
+            .eval("new Uint8Array(rustyscript.blob('data'))[1]")
+            .expect("Could not eval");
+        assert_eq!(result, 2);
+
+        // Writes only reach the reader's own copy
+        let result: usize = runtime
+            .eval(
+                "new Uint8Array(rustyscript.blob('data'))[1] = 9;
+                new Uint8Array(rustyscript.blob('data'))[1]",
+            )
+            .expect("Could not eval");
+        assert_eq!(result, 2);
+
+        let result: bool = runtime
+            .eval("rustyscript.blob('data') === rustyscript.blob('data')")
+            .expect("Could not eval");
+        assert!(!result);
+
+        runtime
+            .eval::<Undefined>("rustyscript.blob('missing')")
+            .expect_err("Did not detect missing blob");
+    }
+
     #[cfg(any(feature = "web", feature = "web_stub"))]
     #[test]
     fn test_eval() {
@@ -1009,6 +3789,16 @@ mod test_inner_runtime {
                 .call_function_by_ref(Some(&handle), &f, json_args!())
                 .expect_err("Did not catch error");
 
+            let f = runtime.get_function_by_name(None, "fna").unwrap();
+            let result = runtime
+                .call_function_by_ref_streamed(None, &f, "[[1, 2, 3], 4]".as_bytes())
+                .expect("Could not call with streamed args");
+            assert_v8!(result, vec![1, 2, 3], Vec<usize>, runtime);
+
+            runtime
+                .call_function_by_ref_streamed(None, &f, "[1, 2".as_bytes())
+                .expect_err("Did not catch truncated args");
+
             Ok(())
         });
     }
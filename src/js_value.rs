@@ -221,6 +221,9 @@ impl Value {
     }
 }
 
+mod callback;
+pub use callback::*;
+
 mod function;
 pub use function::*;
 
@@ -0,0 +1,278 @@
+use super::Function;
+use crate::Error;
+use deno_core::{serde_json, serde_v8::GlobalValue, v8};
+use serde::Serialize;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// A rust closure that can be passed to JS as a function argument
+/// Created with [`crate::Runtime::create_callback`]
+///
+/// In JS it is a plain function - calling it runs the closure, with the arguments and return value
+/// converted through JSON like those of registered functions. The closure is not registered under
+/// any name, so scripts can only reach it through the function they were given
+///
+/// The closure is freed once this handle is dropped and JS no longer references the function,
+/// when it is garbage-collected. It can also be released early with [`crate::Runtime::release_callback`],
+/// after which calling it from JS throws
+///
+/// A callback can only be passed to the runtime that created it
+#[derive(Debug, Clone)]
+pub struct Callback {
+    function: Function,
+    id: u64,
+}
+
+impl Callback {
+    /// The key the closure is stored under
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the JS function that runs the closure
+    #[must_use]
+    pub fn as_function(&self) -> &Function {
+        &self.function
+    }
+}
+
+// Only meaningful when passed as an argument into v8 - other serializers cannot represent a function
+impl Serialize for Callback {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        GlobalValue {
+            v8_value: self.function.clone().into(),
+        }
+        .serialize(serializer)
+    }
+}
+
+type RsCallback = dyn FnMut(&[serde_json::Value]) -> Result<serde_json::Value, Error>;
+
+/// The closures behind a runtime's callbacks
+/// Each is keyed by an id carried in its JS function's internal data, which scripts cannot read
+#[derive(Default)]
+pub(crate) struct CallbackPool {
+    next_id: u64,
+    closures: HashMap<u64, Rc<RefCell<Box<RsCallback>>>>,
+
+    // Fire when a function is collected - kept until then, so the finalizers stay registered
+    handles: Vec<v8::Weak<v8::Function>>,
+}
+
+impl CallbackPool {
+    /// Wraps a closure in a new JS function
+    pub(crate) fn create(
+        pool: &Rc<RefCell<Self>>,
+        scope: &mut v8::HandleScope,
+        callback: Box<RsCallback>,
+    ) -> Result<Callback, Error> {
+        let id = {
+            let mut pool = pool.try_borrow_mut()?;
+            pool.handles.retain(|handle| !handle.is_empty());
+            pool.next_id += 1;
+            pool.closures
+                .insert(pool.next_id, Rc::new(RefCell::new(callback)));
+            pool.next_id
+        };
+
+        let data = v8::BigInt::new_from_u64(scope, id);
+        let Some(function) = v8::Function::builder(call_callback)
+            .data(data.into())
+            .build(scope)
+        else {
+            pool.try_borrow_mut()?.closures.remove(&id);
+            return Err(Error::Runtime(
+                "Could not create the callback function".to_string(),
+            ));
+        };
+
+        // Free the closure once the function is garbage-collected
+        let weak_pool = Rc::downgrade(pool);
+        let handle = v8::Weak::with_finalizer(
+            scope,
+            function,
+            Box::new(move |_| {
+                if let Some(pool) = weak_pool.upgrade() {
+                    if let Ok(mut pool) = pool.try_borrow_mut() {
+                        pool.closures.remove(&id);
+                    }
+                }
+            }),
+        );
+        pool.try_borrow_mut()?.handles.push(handle);
+
+        let function: v8::Local<v8::Value> = function.into();
+        let function = v8::Global::new(scope, function);
+        let function = Function::try_from_v8(scope, function)?;
+        Ok(Callback { function, id })
+    }
+
+    /// Drops the closure stored under the given id
+    pub(crate) fn release(&mut self, id: u64) {
+        self.closures.remove(&id);
+    }
+
+    /// Returns the closure stored under the given id, if it has not been released
+    fn get(&self, id: u64) -> Option<Rc<RefCell<Box<RsCallback>>>> {
+        self.closures.get(&id).cloned()
+    }
+}
+
+/// Entry point of every callback function - finds the closure from the function's data and runs it
+fn call_callback(
+    scope: &mut v8::HandleScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    match run_callback(scope, &args) {
+        Ok(value) => rv.set(value),
+        Err(e) => {
+            if let Some(message) = v8::String::new(scope, &e.to_string()) {
+                let exception = v8::Exception::error(scope, message);
+                scope.throw_exception(exception);
+            }
+        }
+    }
+}
+
+fn run_callback<'s>(
+    scope: &mut v8::HandleScope<'s>,
+    args: &v8::FunctionCallbackArguments<'s>,
+) -> Result<v8::Local<'s, v8::Value>, Error> {
+    let id = v8::Local::<v8::BigInt>::try_from(args.data())?
+        .u64_value()
+        .0;
+
+    let closure = {
+        let state = deno_core::JsRuntime::op_state_from(scope);
+        let state = state.try_borrow()?;
+        let pool = state.try_borrow::<Rc<RefCell<CallbackPool>>>().cloned();
+        pool.and_then(|pool| pool.try_borrow().ok()?.get(id))
+    };
+    let closure = closure
+        .ok_or_else(|| Error::Runtime("This rust callback has been released".to_string()))?;
+
+    let values = (0..args.length())
+        .map(|i| deno_core::serde_v8::from_v8(scope, args.get(i)))
+        .collect::<Result<Vec<serde_json::Value>, _>>()?;
+
+    let result = {
+        let mut closure = closure.try_borrow_mut().map_err(|_| {
+            Error::Runtime("A rust callback cannot be called from within itself".to_string())
+        })?;
+        closure(&values)?
+    };
+
+    Ok(deno_core::serde_v8::to_v8(scope, result)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json_args, Error, Module, Runtime, RuntimeOptions, Undefined};
+    use deno_core::serde_json::Value;
+
+    #[test]
+    fn test_callback() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const each = (values, f) => values.forEach((v) => f(v));
+            export const map = (values, f) => values.map((v) => f(v));
+            export const keep = (f) => { globalThis.kept = f; };
+        ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        // The closure keeps its own state between calls
+        let mut total = 0;
+        let callback = runtime
+            .create_callback(move |args| {
+                total += args[0].as_i64().unwrap_or_default();
+                Ok(Value::from(total))
+            })
+            .unwrap();
+
+        let totals: Vec<i64> = runtime
+            .call_function(Some(&handle), "map", json_args!(vec![1, 2, 3], &callback))
+            .unwrap();
+        assert_eq!(vec![1, 3, 6], totals);
+
+        // The closure has no name scripts could call it by
+        let result = runtime.eval::<Value>("rustyscript.functions['rustyscript.callback.1']()");
+        assert!(result.is_err());
+
+        // Released callbacks throw when called
+        runtime
+            .call_function::<Undefined>(Some(&handle), "keep", json_args!(&callback))
+            .unwrap();
+        runtime.release_callback(callback).unwrap();
+        let result = runtime.eval::<Undefined>("kept(1)");
+        assert!(matches!(result, Err(Error::JsError(_))), "{result:?}");
+
+        // Errors from the closure arrive in JS as exceptions
+        let failing = runtime
+            .create_callback(|_| Err(Error::Runtime("no thanks".to_string())))
+            .unwrap();
+        let result = runtime.call_function::<Undefined>(
+            Some(&handle),
+            "each",
+            json_args!(vec![1], &failing),
+        );
+        assert!(result.unwrap_err().to_string().contains("no thanks"));
+    }
+
+    #[test]
+    fn test_callback_collected() {
+        let module = Module::new(
+            "test.js",
+            "export const keep = (f) => { globalThis.kept = f; };",
+        );
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+        let handle = runtime.load_module(&module).unwrap();
+
+        // Observe the closure being dropped through something it owns
+        let owned = Rc::new(());
+        let observer = Rc::downgrade(&owned);
+        let callback = runtime
+            .create_callback(move |_| {
+                let _ = &owned;
+                Ok(Value::Null)
+            })
+            .unwrap();
+
+        runtime
+            .call_function::<Undefined>(Some(&handle), "keep", json_args!(&callback))
+            .unwrap();
+        drop(callback);
+
+        // Still referenced from JS
+        runtime
+            .deno_runtime()
+            .v8_isolate()
+            .low_memory_notification();
+        runtime
+            .block_on_event_loop(deno_core::PollEventLoopOptions::default(), None)
+            .unwrap();
+        assert!(observer.upgrade().is_some());
+
+        runtime.eval::<Undefined>("kept = null").unwrap();
+        for _ in 0..10 {
+            if observer.upgrade().is_none() {
+                break;
+            }
+            runtime
+                .deno_runtime()
+                .v8_isolate()
+                .low_memory_notification();
+            runtime
+                .block_on_event_loop(deno_core::PollEventLoopOptions::default(), None)
+                .unwrap();
+        }
+        assert!(observer.upgrade().is_none(), "The closure was not freed");
+    }
+}
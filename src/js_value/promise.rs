@@ -40,11 +40,77 @@ where
     ///
     /// # Errors
     /// Will return an error if the promise cannot be resolved into the given type,
-    /// or if a runtime error occurs
+    /// if a runtime error occurs, or if the runtime was aborted
     pub async fn into_future<'a>(self, runtime: &mut crate::Runtime) -> Result<T, crate::Error> {
+        runtime.ensure_usable()?;
         self.resolve(runtime.deno_runtime()).await
     }
 
+    /// Advances the event loop by a single tick, then checks whether the promise has settled, without blocking
+    /// Useful for integrating promises into a synchronous loop, such as a game's frame loop
+    ///
+    /// Returns `None` while the promise is pending, and once it has settled, `Some` with its value,
+    /// or the error it was rejected with. The promise is marked as handled, so a rejection is only
+    /// reported through this method, and not as an unhandled rejection
+    ///
+    /// # Errors
+    /// Will return an error if the promise was rejected, if its value cannot be deserialized into the given type,
+    /// or if a runtime error occurs while advancing the event loop
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ js_value::Promise, Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let promise: Promise<u32> = runtime.eval("new Promise((resolve) => setTimeout(() => resolve(2), 10))")?;
+    ///
+    /// let value = loop {
+    ///     if let Some(value) = promise.poll_once(&mut runtime) {
+    ///         break value?;
+    ///     }
+    ///     // ... the rest of the frame
+    /// };
+    /// assert_eq!(value, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn poll_once(&self, runtime: &mut crate::Runtime) -> Option<Result<T, crate::Error>> {
+        if let Err(e) = runtime.ensure_usable() {
+            return Some(Err(e));
+        }
+
+        {
+            let mut scope = runtime.deno_runtime().handle_scope();
+            let promise = v8::Local::new(&mut scope, &self.0 .0);
+            if let Ok(promise) = v8::Local::<v8::Promise>::try_from(promise) {
+                promise.mark_as_handled();
+            }
+        }
+
+        if let Err(e) = runtime.advance_event_loop(PollEventLoopOptions::default()) {
+            return Some(Err(e));
+        }
+
+        let mut scope = runtime.deno_runtime().handle_scope();
+        let promise = v8::Local::new(&mut scope, &self.0 .0);
+        let promise = v8::Local::<v8::Promise>::try_from(promise).ok()?;
+        match promise.state() {
+            v8::PromiseState::Pending => None,
+            v8::PromiseState::Fulfilled => {
+                let value = promise.result(&mut scope);
+                Some(deno_core::serde_v8::from_v8(&mut scope, value).map_err(Into::into))
+            }
+            v8::PromiseState::Rejected => {
+                let reason = promise.result(&mut scope);
+                Some(Err(crate::error::JsError::from_exception(
+                    &mut scope, reason,
+                )
+                .into()))
+            }
+        }
+    }
+
     /// Blocks until the promise is resolved
     ///
     /// # Errors
@@ -79,4 +145,36 @@ mod test {
         let value = value.into_value(&mut runtime).unwrap();
         assert_eq!(value, 42);
     }
+
+    #[test]
+    fn test_poll_once() {
+        let mut runtime = Runtime::new(RuntimeOptions::default()).unwrap();
+
+        let promise: Promise<usize> = runtime
+            .eval("new Promise((resolve) => setTimeout(() => resolve(42), 50))")
+            .unwrap();
+        assert!(promise.poll_once(&mut runtime).is_none());
+
+        let mut ticks = 0;
+        let value = loop {
+            ticks += 1;
+            if let Some(value) = promise.poll_once(&mut runtime) {
+                break value.unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert_eq!(value, 42);
+        assert!(ticks > 1);
+
+        let promise: Promise<usize> = runtime
+            .eval("new Promise((_, reject) => setTimeout(() => reject(new Error('nope')), 10))")
+            .unwrap();
+        let error = loop {
+            if let Some(result) = promise.poll_once(&mut runtime) {
+                break result.expect_err("Promise should have been rejected");
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert!(matches!(error, crate::Error::JsError(e) if e.message.contains("nope")));
+    }
 }
@@ -239,6 +239,7 @@
 //! |                  |                                                                                                           |                  |                                                                                               |
 //! |`fs_import`       |Enables importing arbitrary code from the filesystem through JS                                            |**NO**            |None                                                                                           |
 //! |`url_import`      |Enables importing arbitrary code from network locations through JS                                         |**NO**            |`reqwest`                                                                                      |
+//! |`npm_import`      |Enables importing `npm:` packages through JS, from a host-configured ESM registry                          |**NO**            |`reqwest`                                                                                      |
 //! |                  |                                                                                                           |                  |                                                                                               |
 //! |`worker`          |Enables access to the threaded worker API [`worker`]                                                       |yes               |None                                                                                           |
 //! |`snapshot_builder`|Enables access to [`SnapshotBuilder`], a runtime for creating snapshots that can improve start-times       |yes               |None                                                                                           |
@@ -268,14 +269,18 @@ pub mod error;
 pub mod js_value;
 pub mod module_loader;
 
+mod cancellation_handle;
+mod chunk_collector;
 mod ext;
 mod inner_runtime;
+mod logger;
 mod module;
 mod module_handle;
 mod module_wrapper;
 mod runtime;
 mod traits;
 mod transpiler;
+mod type_registry;
 mod utilities;
 
 #[cfg(feature = "worker")]
@@ -292,16 +297,38 @@ pub use deno_tls;
 #[cfg(feature = "web")]
 pub use ext::web::{AllowlistWebPermissions, DefaultWebPermissions, WebOptions, WebPermissions};
 
+#[cfg(feature = "io")]
+pub use ext::io::IoOptions;
+
+#[cfg(feature = "webstorage")]
+pub use ext::webstorage::{StorageQuotaPolicy, WebStorageQuota};
+
 pub use ext::ExtensionOptions;
 
 // Expose some important stuff from us
-pub use error::Error;
-pub use inner_runtime::{RsAsyncFunction, RsFunction};
-pub use module::{Module, StaticModule};
+pub use cancellation_handle::CancellationHandle;
+pub use chunk_collector::ChunkCollector;
+pub use error::{Error, JsError, OpError};
+pub use inner_runtime::{
+    JsIteratorSource, JsStreamSource, RsAsyncFunction, RsFunction, RsIteratorFunction,
+    RsStreamFunction,
+};
+pub use logger::{LogLevel, Logger};
+pub use module::{
+    BindingIssue, ExportComparison, ExportKind, ExportSignature, FunctionCall, FunctionKind,
+    Module, ModuleGraphSize, ModuleType, StaticModule, StaticModuleSet,
+};
 pub use module_handle::ModuleHandle;
 pub use module_wrapper::ModuleWrapper;
-pub use runtime::{Runtime, RuntimeOptions, Undefined};
-pub use utilities::{evaluate, import, init_platform, resolve_path, validate};
+pub use runtime::{
+    CallOutcome, CapturedOutput, ConsoleEvent, ConsoleLevel, DenoStub, DuplicateRegistration,
+    EventLoopProgress, HeapStats, OpInfo, OpKind, Runtime, RuntimeOptions, Undefined,
+};
+pub use transpiler::{transpile_with_sourcemap, Diagnostic, DiagnosticSeverity, SourceMap};
+pub use type_registry::{JsRepr, JsTypeConversion};
+pub use utilities::{
+    compare_exports, evaluate, import, init_platform, merge_config_modules, resolve_path, validate,
+};
 
 #[cfg(test)]
 mod test {
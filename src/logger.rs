@@ -0,0 +1,67 @@
+//! A simple logging interface used by the runtime for internal diagnostics
+//! (module loads, timeouts, permission denials), and available to registered functions and ops
+use std::fmt::Display;
+
+/// The severity of a message sent to a [`Logger`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// Verbose information, useful when debugging
+    Debug,
+
+    /// Routine events, such as a module being loaded
+    Info,
+
+    /// Something went wrong, but the runtime can continue; timeouts and permission denials
+    Warn,
+
+    /// A failure the host should know about
+    Error,
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Debug => write!(f, "DEBUG"),
+            Self::Info => write!(f, "INFO"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// A destination for diagnostic messages from the runtime
+/// Set with `RuntimeOptions::logger`
+///
+/// - Registered functions can capture a clone of [`crate::Runtime::logger`]
+/// - Ops can borrow it from the state, as `Rc<dyn Logger>`
+///
+/// Any `Fn(LogLevel, &str)` closure can be used as a logger
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ LogLevel, Module, Runtime, RuntimeOptions };
+/// use std::rc::Rc;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     logger: Some(Rc::new(|level: LogLevel, message: &str| eprintln!("[{level}] {message}"))),
+///     ..Default::default()
+/// })?;
+///
+/// runtime.load_module(&Module::new("test.js", "export const a = 1;"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Logger {
+    /// Record a message at the given level
+    fn log(&self, level: LogLevel, message: &str);
+}
+
+impl<F> Logger for F
+where
+    F: Fn(LogLevel, &str),
+{
+    fn log(&self, level: LogLevel, message: &str) {
+        self(level, message);
+    }
+}
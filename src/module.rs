@@ -1,4 +1,6 @@
+use deno_core::serde_json;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
 use std::fmt::Display;
 use std::fs::{read_dir, read_to_string};
@@ -47,11 +49,99 @@ macro_rules! module {
     };
 }
 
+/// A static representation of a tree of modules, embedded together
+/// Modules in the set can import each other by their filenames, without touching the filesystem
+///
+/// Load the whole set with [`crate::Runtime::load_module_set`]
+pub struct StaticModuleSet(&'static [StaticModule]);
+impl StaticModuleSet {
+    /// Create a new `StaticModuleSet`
+    /// use the `module_set!` macro instead!
+    #[must_use]
+    pub const fn new(modules: &'static [StaticModule]) -> Self {
+        Self(modules)
+    }
+
+    /// Get instances of the modules in this set that can be used with a runtime
+    #[must_use]
+    pub fn to_modules(&self) -> Vec<Module> {
+        self.0.iter().map(StaticModule::to_module).collect()
+    }
+
+    /// Returns the number of modules in the set
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the set contains no modules
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Creates a static set of modules
+/// Contents can be any constant `&'static str` expression, such as `include_str!`
+///
+/// # Example
+///
+/// ```rust
+/// use rustyscript::{ module_set, StaticModuleSet };
+///
+/// const MY_SCRIPTS: StaticModuleSet = module_set!(
+///     "lib/math.js" => "export const add = (a, b) => a + b;",
+///     "main.js" => "import { add } from './lib/math.js'; export default () => add(1, 2);",
+/// );
+///
+/// let modules = MY_SCRIPTS.to_modules();
+/// ```
+#[macro_export]
+macro_rules! module_set {
+    ($($filename:literal => $contents:expr),* $(,)?) => {
+        $crate::StaticModuleSet::new(&[$($crate::StaticModule::new($filename, $contents)),*])
+    };
+}
+
+/// How a module's contents are interpreted, overriding the guess made from its filename's extension
+/// See [`Module::with_type`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ModuleType {
+    /// An ES module - transpiled first if its extension is a typescript one
+    JavaScript,
+
+    /// A JSON document, exposed as the module's default export
+    /// Other modules must import it with `with { type: 'json' }`
+    Json,
+}
+
+impl From<ModuleType> for deno_core::ModuleType {
+    fn from(module_type: ModuleType) -> Self {
+        match module_type {
+            ModuleType::JavaScript => deno_core::ModuleType::JavaScript,
+            ModuleType::Json => deno_core::ModuleType::Json,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Default)]
 /// Represents a pice of javascript for execution.
 pub struct Module {
     filename: PathBuf,
     contents: String,
+
+    // Never read from serialized data - a stored or received module must not grant itself trust
+    #[serde(skip)]
+    trusted: bool,
+
+    #[serde(default)]
+    import_meta: BTreeMap<String, serde_json::Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    code_cache: Option<Vec<u8>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    module_type: Option<ModuleType>,
 }
 
 impl Display for Module {
@@ -83,9 +173,163 @@ impl Module {
         Self {
             filename: filename.as_ref().to_path_buf(),
             contents: contents.to_string(),
+            trusted: false,
+            import_meta: BTreeMap::new(),
+            code_cache: None,
+            module_type: None,
+        }
+    }
+
+    /// Creates a new `Module` whose contents are interpreted as the given type,
+    /// instead of the type suggested by the filename's extension
+    ///
+    /// Useful for extensionless names, such as those of modules served from URLs,
+    /// or for JSON stored under another extension
+    ///
+    /// # Arguments
+    /// * `filename` - A string representing the filename of the module.
+    /// * `contents` - A string containing the contents of the module.
+    /// * `module_type` - How the contents should be interpreted
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Module, ModuleType, Runtime, Error, serde_json::Value };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::with_type("settings.txt", r#"{ "debug": true }"#, ModuleType::Json);
+    ///
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let handle = runtime.load_module(&module)?;
+    /// let settings: Value = runtime.get_value(Some(&handle), "default")?;
+    /// assert_eq!(Value::Bool(true), settings["debug"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn with_type(filename: impl AsRef<Path>, contents: &str, module_type: ModuleType) -> Self {
+        Self {
+            module_type: Some(module_type),
+            ..Self::new(filename, contents)
         }
     }
 
+    /// Returns the type given with [`Module::with_type`], if any
+    /// If `None`, the type is inferred from the filename's extension
+    #[must_use]
+    pub fn module_type(&self) -> Option<ModuleType> {
+        self.module_type
+    }
+
+    /// Creates a new `Module` with v8 bytecode compiled from its source ahead of time,
+    /// so loading it skips most of the compilation work
+    ///
+    /// Get the bytecode from [`crate::Runtime::compile_module_to_bytes`]. It records a hash of the code it
+    /// was compiled from, and the version of rustyscript that compiled it - if either no longer matches,
+    /// the bytecode is ignored and the module compiled normally
+    ///
+    /// # Arguments
+    /// * `filename` - A string representing the filename of the module.
+    /// * `contents` - A string containing the contents of the module.
+    /// * `code_cache` - The bytecode compiled from `contents`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Module, Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("module.js", "export const value = 42;");
+    ///
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let bytes = runtime.compile_module_to_bytes(&module)?;
+    ///
+    /// // Later, possibly in another process running the same version of rustyscript
+    /// let module = Module::from_code_cache("module.js", module.contents(), bytes);
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let handle = runtime.load_module(&module)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn from_code_cache(
+        filename: impl AsRef<Path>,
+        contents: &str,
+        code_cache: Vec<u8>,
+    ) -> Self {
+        Self {
+            code_cache: Some(code_cache),
+            ..Self::new(filename, contents)
+        }
+    }
+
+    /// Returns the bytecode given with [`Module::from_code_cache`], if any
+    #[must_use]
+    pub fn code_cache(&self) -> Option<&[u8]> {
+        self.code_cache.as_deref()
+    }
+
+    /// Adds a property to the module's `import.meta` object
+    ///
+    /// `import.meta.url` is always set to the module's resolved specifier, and
+    /// `import.meta.main` to whether it was loaded as the main module -
+    /// use this to provide any other metadata a script expects
+    ///
+    /// # Arguments
+    /// * `key` - The name of the property
+    /// * `value` - The value of the property
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// let module = Module::new("module.js", "console.log(import.meta.env);")
+    ///     .with_import_meta("env", "production");
+    /// ```
+    #[must_use]
+    pub fn with_import_meta(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.import_meta.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Returns the extra `import.meta` properties set with [`Module::with_import_meta`]
+    #[must_use]
+    pub fn import_meta(&self) -> &BTreeMap<String, serde_json::Value> {
+        &self.import_meta
+    }
+
+    /// Marks the module as trusted, or untrusted
+    ///
+    /// Trusted modules are meant for host-authored code, such as bootstrap scripts.
+    /// They are exempt from the import restrictions placed on other modules:
+    /// - They may import files from the filesystem, even without the `fs_import` feature
+    /// - Files they import are trusted in turn
+    ///
+    /// Modules are untrusted by default. Never mark user-authored code as trusted.
+    /// Trust is not serialized - a deserialized module is always untrusted, and must be marked again
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// let module = Module::new("bootstrap.js", "import './lib/setup.js';").with_trusted(true);
+    /// assert!(module.is_trusted());
+    /// ```
+    #[must_use]
+    pub fn with_trusted(mut self, trusted: bool) -> Self {
+        self.trusted = trusted;
+        self
+    }
+
+    /// Returns true if the module has been marked as trusted
+    /// See [`Module::with_trusted`]
+    #[must_use]
+    pub fn is_trusted(&self) -> bool {
+        self.trusted
+    }
+
     /// Loads a `Module` instance from a file with the given filename.
     ///
     /// # Arguments
@@ -114,6 +358,7 @@ impl Module {
     }
 
     /// Attempt to load all `.js`/`.ts` files in a given directory
+    /// Modules are returned sorted by path, so the order does not depend on the filesystem
     /// Fails if any of the files cannot be loaded
     ///
     /// # Arguments
@@ -137,24 +382,56 @@ impl Module {
     /// # }
     /// ```
     pub fn load_dir(directory: impl AsRef<Path>) -> Result<Vec<Self>, std::io::Error> {
-        let mut files: Vec<Self> = Vec::new();
+        Self::load_dir_filtered(directory, |_| true)
+    }
+
+    /// Attempt to load the `.js`/`.ts` files in a given directory that match a filter
+    /// Useful to skip declaration files (`.d.ts`) or tests kept next to the modules
+    /// Modules are returned sorted by path, so the order does not depend on the filesystem
+    ///
+    /// # Arguments
+    /// * `directory` - A string representing the target directory
+    /// * `filter` - Called with the path of each `.js`/`.ts` file, returning `true` to load it
+    ///
+    /// # Returns
+    /// A `Result` containing a vec of loaded `Module` instances or an `std::io::Error` if there
+    /// are issues reading a file.
+    ///
+    /// # Errors
+    /// Will return an error if the directory cannot be read, or if any matching file cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::Module;
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let modules = Module::load_dir_filtered("src/ext/web_stub", |path| {
+    ///     let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    ///     !name.ends_with(".d.ts") && !name.contains(".test.")
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_dir_filtered(
+        directory: impl AsRef<Path>,
+        filter: impl Fn(&Path) -> bool,
+    ) -> Result<Vec<Self>, std::io::Error> {
+        let mut paths = Vec::new();
         for file in read_dir(directory)? {
-            let file = file?;
-            if let Some(filename) = file.path().to_str() {
-                // Skip non-js files
-                let extension = Path::new(&filename)
-                    .extension()
-                    .and_then(OsStr::to_str)
-                    .unwrap_or_default();
-                if !["js", "ts"].contains(&extension) {
-                    continue;
-                }
+            let path = file?.path();
 
-                files.push(Self::load(filename)?);
+            // Skip non-js files
+            let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+            if !["js", "ts"].contains(&extension) || path.to_str().is_none() || !filter(&path) {
+                continue;
             }
+
+            paths.push(path);
         }
 
-        Ok(files)
+        paths.sort();
+        paths.into_iter().map(Self::load).collect()
     }
 
     /// Returns the filename of the module.
@@ -192,12 +469,406 @@ impl Module {
     pub fn contents(&self) -> &str {
         &self.contents
     }
+
+    /// Finds every call to a registered rust function (`rustyscript.functions.name(...)` and the like)
+    /// in the module's source, without running it
+    ///
+    /// Only direct calls are found - a proxy stored in a variable, or a computed name, is not followed
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Module, FunctionKind };
+    ///
+    /// let module = Module::new("test.js", "rustyscript.functions.add(1, 2);");
+    /// let calls = module.function_calls();
+    /// assert_eq!(calls[0].kind, FunctionKind::Sync);
+    /// assert_eq!(calls[0].name, "add");
+    /// assert_eq!(calls[0].args, Some(2));
+    /// ```
+    #[must_use]
+    pub fn function_calls(&self) -> Vec<FunctionCall> {
+        crate::transpiler::find_function_calls(&self.filename, &self.contents)
+    }
+
+    /// Describes the module's exports from its source, without running any of it
+    /// Useful for tooling such as autocomplete, where executing a script's top-level code is not an option
+    ///
+    /// The result can be stored and reused as long as the module's source does not change
+    ///
+    /// Names re-exported with `export * from '...'` are not listed. Names re-exported from
+    /// another module, or destructured from a value, are listed with [`ExportKind::Unknown`]
+    ///
+    /// # Errors
+    /// Will return an error if the module's source cannot be parsed
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Module, ExportKind };
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new("test.js", "
+    ///     export const add = (a, b) => a + b;
+    ///     export class Counter {}
+    /// ");
+    /// let exports = module.export_signatures()?;
+    /// assert_eq!(exports[0].name, "Counter");
+    /// assert_eq!(exports[0].kind, ExportKind::Class);
+    /// assert_eq!(exports[1].name, "add");
+    /// assert_eq!(exports[1].arity, Some(2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_signatures(&self) -> Result<Vec<ExportSignature>, crate::Error> {
+        Ok(crate::transpiler::find_exports(
+            &self.filename,
+            &self.contents,
+        )?)
+    }
+}
+
+/// The size of a module and everything it statically imports
+/// See [`crate::Runtime::measure_module_graph`]
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+pub struct ModuleGraphSize {
+    /// Number of unique modules in the graph, including the root
+    pub module_count: usize,
+
+    /// Total size, in bytes, of the transpiled source of every module in the graph
+    pub total_bytes: usize,
+}
+
+/// The shape of a single export of a module
+/// See [`crate::Runtime::get_export_signatures`] for a loaded module, and [`Module::export_signatures`]
+/// to read it from the source without running the module
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ExportSignature {
+    /// Name of the export
+    pub name: String,
+
+    /// What kind of value the export is
+    pub kind: ExportKind,
+
+    /// Number of declared parameters if the export is a function, `None` otherwise
+    /// Counts parameters up to the first default or rest parameter, like a function's `length`
+    pub arity: Option<u32>,
+}
+
+/// What kind of value an export is
+/// See [`ExportSignature`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum ExportKind {
+    /// A function declaration, function expression or arrow function
+    Function,
+
+    /// A class
+    Class,
+
+    /// Any other value
+    Value,
+
+    /// Re-exported from another module, or destructured from a value, so its kind
+    /// cannot be known from the source without running it
+    Unknown,
+}
+
+/// The proxy a registered rust function is called through from JS
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum FunctionKind {
+    /// `rustyscript.functions`, see [`crate::Runtime::register_function`]
+    Sync,
+
+    /// `rustyscript.async_functions`, see [`crate::Runtime::register_async_function`]
+    Async,
+
+    /// `rustyscript.stream_functions`, see [`crate::Runtime::register_stream_function`]
+    Stream,
+
+    /// `rustyscript.iterator_functions`, see [`crate::Runtime::register_iterator_function`]
+    Iterator,
+}
+
+/// A call to a registered rust function, found in a module's source
+/// See [`Module::function_calls`]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct FunctionCall {
+    /// Which proxy the function is called through
+    pub kind: FunctionKind,
+
+    /// Name of the function
+    pub name: String,
+
+    /// Number of arguments passed, or `None` if the call spreads an array into its arguments
+    pub args: Option<usize>,
+
+    /// 1-based line of the call in the module's source
+    pub line: usize,
+}
+
+/// A problem with a call to a registered rust function
+/// See [`crate::Runtime::check_function_calls`]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum BindingIssue {
+    /// No function of that kind is registered under the name
+    Unregistered(FunctionCall),
+
+    /// The call passes a different number of arguments than the function expects
+    ArityMismatch {
+        /// The offending call
+        call: FunctionCall,
+
+        /// The number of arguments the function expects
+        expected: usize,
+    },
+}
+
+/// Differences between the exports of two versions of a module
+/// See [`crate::compare_exports`]
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+pub struct ExportComparison {
+    /// Exports only present in the new version
+    pub added: Vec<ExportSignature>,
+
+    /// Exports only present in the old version
+    pub removed: Vec<ExportSignature>,
+
+    /// Exports present in both versions, with a different shape - as `(old, new)`
+    pub changed: Vec<(ExportSignature, ExportSignature)>,
+}
+
+impl ExportComparison {
+    /// Compare two sets of export signatures
+    #[must_use]
+    pub fn new(old: &[ExportSignature], new: &[ExportSignature]) -> Self {
+        let mut comparison = Self::default();
+        for old_export in old {
+            match new.iter().find(|e| e.name == old_export.name) {
+                Some(new_export) if new_export != old_export => {
+                    comparison
+                        .changed
+                        .push((old_export.clone(), new_export.clone()));
+                }
+                Some(_) => {}
+                None => comparison.removed.push(old_export.clone()),
+            }
+        }
+
+        comparison.added = new
+            .iter()
+            .filter(|e| !old.iter().any(|o| o.name == e.name))
+            .cloned()
+            .collect();
+
+        comparison
+    }
+
+    /// True if both versions have exactly the same export signatures
+    #[must_use]
+    pub fn is_identical(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// True if the new version can replace the old one without breaking callers
+    /// New exports are allowed, but nothing may be removed or change shape
+    #[must_use]
+    pub fn is_backward_compatible(&self) -> bool {
+        self.removed.is_empty() && self.changed.is_empty()
+    }
 }
 
 #[cfg(test)]
 mod test_module {
     use super::*;
 
+    #[test]
+    fn test_trusted_not_serialized() {
+        let module = Module::new("bootstrap.js", "").with_trusted(true);
+        let json = serde_json::to_value(&module).expect("Could not serialize module");
+        assert!(json.get("trusted").is_none());
+
+        let json = serde_json::json!({
+            "filename": "evil.js",
+            "contents": "import '/etc/passwd';",
+            "trusted": true,
+        });
+        let module: Module = serde_json::from_value(json).expect("Could not deserialize module");
+        assert!(!module.is_trusted());
+    }
+
+    #[test]
+    fn test_export_comparison() {
+        let sig = |name: &str, arity| ExportSignature {
+            name: name.to_string(),
+            kind: if arity.is_some() {
+                ExportKind::Function
+            } else {
+                ExportKind::Value
+            },
+            arity,
+        };
+        let old = [sig("a", Some(1)), sig("b", None), sig("c", Some(0))];
+        let new = [sig("a", Some(2)), sig("b", None), sig("d", None)];
+
+        let comparison = ExportComparison::new(&old, &new);
+        assert_eq!(vec![sig("d", None)], comparison.added);
+        assert_eq!(vec![sig("c", Some(0))], comparison.removed);
+        assert_eq!(
+            vec![(sig("a", Some(1)), sig("a", Some(2)))],
+            comparison.changed
+        );
+        assert!(!comparison.is_backward_compatible());
+
+        let comparison = ExportComparison::new(&old, &old);
+        assert!(comparison.is_identical());
+    }
+
+    #[test]
+    fn test_check_literal_annotations() {
+        use crate::DiagnosticSeverity;
+
+        let module = Module::new(
+            "test.ts",
+            "
+            const name: string = `user`;
+            export let count: number = true;
+            const ok: boolean = false, bad: string = 1;
+            ",
+        );
+        let found: Vec<_> = module
+            .check_literal_annotations()
+            .into_iter()
+            .map(|d| (d.severity, d.line, d.message))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    DiagnosticSeverity::Error,
+                    3,
+                    "Type 'boolean' is not assignable to type 'number'".to_string()
+                ),
+                (
+                    DiagnosticSeverity::Error,
+                    4,
+                    "Type 'number' is not assignable to type 'string'".to_string()
+                ),
+            ]
+        );
+
+        let module = Module::new("test.ts", "const x: number = ;");
+        let problems = module.check_literal_annotations();
+        assert_eq!(problems[0].severity, DiagnosticSeverity::Error);
+
+        // Problems the parser recovers from are only warnings
+        let module = Module::new(
+            "test.ts",
+            "export class Shape {\n    constructor<T>() {}\n}",
+        );
+        let problems = module.check_literal_annotations();
+        assert!(!problems.is_empty());
+        assert!(problems
+            .iter()
+            .all(|d| d.severity == DiagnosticSeverity::Warning));
+
+        let module = Module::new("test.ts", "export const x: number = 1;");
+        assert!(module.check_literal_annotations().is_empty());
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let module = Module::new(
+            "test.ts",
+            "
+            // rustyscript.functions.commented(1)
+            const x: number = rustyscript.functions.add(1, [2, 3], { a: 4, b: 5 },);
+            await rustyscript.async_functions['fetch_data']();
+            rustyscript.functions.log(`${x}, ${(() => 1, 2)()}`, ...rest);
+            const f = rustyscript.functions.stored;
+            ",
+        );
+
+        let calls = module.function_calls();
+        let found: Vec<_> = calls
+            .iter()
+            .map(|call| (call.kind, call.name.as_str(), call.args, call.line))
+            .collect();
+        assert_eq!(
+            vec![
+                (FunctionKind::Sync, "add", Some(3), 3),
+                (FunctionKind::Async, "fetch_data", Some(0), 4),
+                (FunctionKind::Sync, "log", None, 5),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn test_export_signatures() {
+        let module = Module::new(
+            "test.ts",
+            "
+            import { helper } from './helper.ts';
+            function local(a: number, b = 2) {}
+            const hidden = 1;
+            export interface Options { verbose: boolean }
+            export type Id = string;
+            export async function run(this: Window, input: string, ...rest: string[]) {}
+            export const handler = ((event, context) => {}) as Handler;
+            export let counter = 0, [destructured] = [1];
+            export const { first, second: renamedSecond = (x) => x, ...others } = {};
+            export class Plugin {}
+            export enum Mode { A, B }
+            export { local, hidden as renamed };
+            export { helper } from './helper.ts';
+            export * as utils from './utils.ts';
+            export * from './everything.ts';
+            export default function (x) {}
+            ",
+        );
+
+        let shape = |name: &str, kind, arity| ExportSignature {
+            name: name.to_string(),
+            kind,
+            arity,
+        };
+        assert_eq!(
+            vec![
+                shape("Mode", ExportKind::Value, None),
+                shape("Plugin", ExportKind::Class, None),
+                shape("counter", ExportKind::Value, None),
+                shape("default", ExportKind::Function, Some(1)),
+                shape("destructured", ExportKind::Unknown, None),
+                shape("first", ExportKind::Unknown, None),
+                shape("handler", ExportKind::Function, Some(2)),
+                shape("helper", ExportKind::Unknown, None),
+                shape("local", ExportKind::Function, Some(1)),
+                shape("others", ExportKind::Unknown, None),
+                shape("renamed", ExportKind::Value, None),
+                shape("renamedSecond", ExportKind::Unknown, None),
+                shape("run", ExportKind::Function, Some(1)),
+                shape("utils", ExportKind::Value, None),
+            ],
+            module.export_signatures().expect("Could not read exports")
+        );
+
+        let module = Module::new("broken.js", "export const = ;");
+        module
+            .export_signatures()
+            .expect_err("Invalid source was parsed");
+    }
+
+    #[test]
+    fn test_module_set() {
+        const SET: StaticModuleSet = module_set!(
+            "a.js" => "export const a = 1;",
+            "b.js" => include_str!("../examples/javascript/example_module.js"),
+        );
+
+        let modules = SET.to_modules();
+        assert_eq!(2, SET.len());
+        assert_eq!("a.js", modules[0].filename().to_str().unwrap());
+        assert_eq!("export const a = 1;", modules[0].contents());
+    }
+
     #[test]
     fn test_new_module() {
         let module = Module::new("module.js", "console.log('Hello, World!');");
@@ -221,4 +892,35 @@ mod test_module {
             Module::load_dir("src/ext/rustyscript").expect("Failed to load modules from directory");
         assert!(!modules.is_empty());
     }
+
+    #[test]
+    fn test_load_dir_filtered() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustyscript_load_dir_filtered_{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).expect("Could not create directory");
+
+        // Written out of order, to check that loading sorts them
+        for name in ["c.ts", "init_b.js", "a.js", "notes.txt"] {
+            std::fs::write(dir.join(name), format!("// {name}")).expect("Could not write file");
+        }
+
+        let modules = Module::load_dir_filtered(&dir, |path| {
+            !path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| name.starts_with("init_"))
+        })
+        .expect("Failed to load modules from directory");
+        std::fs::remove_dir_all(&dir).ok();
+
+        let names: Vec<_> = modules
+            .iter()
+            .filter_map(|m| m.filename().file_name().and_then(OsStr::to_str))
+            .collect();
+        assert_eq!(vec!["a.js", "c.ts"], names);
+        assert_eq!("// a.js", modules[0].contents());
+    }
 }
@@ -1,16 +1,31 @@
+use std::path::{Path, PathBuf};
+
 use deno_core::v8;
 use deno_core::ModuleId;
 
-use crate::Module;
+use crate::{Diagnostic, Error, Module};
 
 /// Represents a loaded instance of a module within a runtime
-#[derive(Clone, Debug, Eq, PartialEq, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct ModuleHandle {
     entrypoint: Option<v8::Global<v8::Function>>,
     module_id: ModuleId,
     module: Module,
+    load_error: Option<Box<Error>>,
+    side_module_errors: Vec<(PathBuf, Error)>,
+    warnings: Vec<Diagnostic>,
 }
 
+// The load error and warnings are informational, and do not affect which module a handle refers to
+impl PartialEq for ModuleHandle {
+    fn eq(&self, other: &Self) -> bool {
+        self.entrypoint == other.entrypoint
+            && self.module_id == other.module_id
+            && self.module == other.module
+    }
+}
+impl Eq for ModuleHandle {}
+
 impl ModuleHandle {
     /// Create a new module instance
     pub(crate) fn new(
@@ -22,9 +37,30 @@ impl ModuleHandle {
             module_id,
             entrypoint,
             module: module.clone(),
+            load_error: None,
+            side_module_errors: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
+    /// Attach an error captured while evaluating the module
+    pub(crate) fn with_load_error(mut self, error: Option<Error>) -> Self {
+        self.load_error = error.map(Box::new);
+        self
+    }
+
+    /// Attach the errors captured while evaluating the side modules loaded alongside this one
+    pub(crate) fn with_side_module_errors(mut self, errors: Vec<(PathBuf, Error)>) -> Self {
+        self.side_module_errors = errors;
+        self
+    }
+
+    /// Attach the non-fatal diagnostics reported while compiling the module
+    pub(crate) fn with_warnings(mut self, warnings: Vec<Diagnostic>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
     /// Create a new module handle from raw parts
     /// # Safety
     /// This function is unsafe because it allows using potentially invalid `ModuleIds`.
@@ -55,4 +91,31 @@ impl ModuleHandle {
     pub fn entrypoint(&self) -> &Option<v8::Global<v8::Function>> {
         &self.entrypoint
     }
+
+    /// Return the exception thrown by this module's top-level code, if any
+    /// Only set when the runtime was created with `capture_load_errors` enabled,
+    /// otherwise such exceptions cause the load itself to fail
+    #[must_use]
+    pub fn load_error(&self) -> Option<&Error> {
+        self.load_error.as_deref()
+    }
+
+    /// Return the exceptions thrown by the top-level code of side modules loaded alongside this one,
+    /// along with the filename of the side module that threw each
+    /// Like [`ModuleHandle::load_error`], only set when `capture_load_errors` is enabled
+    pub fn side_module_errors(&self) -> impl Iterator<Item = (&Path, &Error)> {
+        self.side_module_errors
+            .iter()
+            .map(|(filename, e)| (filename.as_path(), e))
+    }
+
+    /// Return the warnings reported while compiling this module
+    /// These are problems the transpiler recovered from - the module loaded and ran regardless,
+    /// but a host running a linting pass may want to show them
+    ///
+    /// Only modules that need transpiling, such as TypeScript, are checked
+    #[must_use]
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
 }
@@ -1,19 +1,71 @@
 //! Module loader implementation for rustyscript
 //! This module provides tools for caching module data, resolving module specifiers, and loading modules
 #![allow(deprecated)]
-use deno_core::{anyhow::Error, ModuleLoader, ModuleSpecifier};
+use deno_core::{anyhow::Error, ModuleLoader, ModuleSpecifier, ModuleType};
 use std::{cell::RefCell, rc::Rc};
 
 mod cache_provider;
+mod code_cache;
+mod commonjs;
+mod fs_cache_provider;
+mod import_map;
 mod import_provider;
+mod import_timeouts;
 mod inner_loader;
+mod language_plugin;
+mod manifest;
+pub(crate) mod raw_imports;
+mod retry_policy;
+mod virtual_fs;
+
+#[cfg(feature = "npm_import")]
+mod npm;
 
 use inner_loader::InnerRustyLoader;
 pub(crate) use inner_loader::LoaderOptions;
 
 // Public exports
-pub use cache_provider::{ClonableSource, ModuleCacheProvider};
+pub use cache_provider::{content_hash, ClonableSource, ModuleCacheProvider};
+pub use commonjs::CommonJsMode;
+pub use fs_cache_provider::FileSystemModuleCacheProvider;
+pub use import_map::ImportMap;
 pub use import_provider::ImportProvider;
+pub use import_timeouts::ImportTimeouts;
+pub use language_plugin::LanguagePlugin;
+pub use manifest::{ManifestEntry, ModuleManifest};
+pub use retry_policy::ImportRetryPolicy;
+pub use virtual_fs::{VirtualFileSystem, VirtualFiles};
+
+#[cfg(feature = "npm_import")]
+pub use npm::NpmRegistry;
+
+/// A hook that turns an import specifier, and the URL of the module importing it, into a module URL
+/// Set with `RuntimeOptions::module_resolver`, it replaces the default URL resolution of every import,
+/// which can be restored for specifiers the hook does not handle with [`deno_core::resolve_import`].
+/// When set, `RuntimeOptions::import_map` is not applied
+///
+/// The URL returned is still subject to the loader's usual checks - a custom scheme must be
+/// whitelisted, and the module must be available from the manifest, the cache or an [`ImportProvider`]
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ Runtime, RuntimeOptions, module_loader::ModuleResolver, deno_core };
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// // Serve `plugin:name` imports from a directory of plugins
+/// let resolver: ModuleResolver = Box::new(|specifier, referrer| match specifier.strip_prefix("plugin:") {
+///     Some(name) => Ok(deno_core::resolve_path(&format!("plugins/{name}.js"), &std::env::current_dir()?)?),
+///     None => Ok(deno_core::resolve_import(specifier, referrer)?),
+/// });
+///
+/// let runtime = Runtime::new(RuntimeOptions {
+///     module_resolver: Some(resolver),
+///     ..Default::default()
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub type ModuleResolver = Box<dyn Fn(&str, &str) -> Result<ModuleSpecifier, Error>>;
 
 /// The primary module loader implementation for rustyscript
 /// This structure manages fetching module code, transpilation, and caching
@@ -36,6 +88,73 @@ impl RustyLoader {
             .borrow_mut()
             .add_source_map(file_name, code, source_map);
     }
+
+    /// Records the JS that will be executed for a module, after transpilation
+    pub fn insert_transpiled_source(&self, specifier: &str, code: String) {
+        self.inner
+            .borrow_mut()
+            .add_transpiled_source(specifier, code);
+    }
+
+    /// Runs the language plugin registered for the module's extension, if any, over its source
+    pub fn transform_source(
+        &self,
+        specifier: &ModuleSpecifier,
+        code: &str,
+    ) -> Result<String, Error> {
+        self.inner.borrow().transform_source(specifier, code)
+    }
+
+    /// Returns the JS that was executed for a module, if it has been loaded
+    pub fn transpiled_source(&self, specifier: &str) -> Option<String> {
+        self.inner
+            .borrow()
+            .get_transpiled_source(specifier)
+            .cloned()
+    }
+
+    /// Sets the extra `import.meta` properties for a module - see [`crate::Module::with_import_meta`]
+    pub fn set_import_meta(
+        &self,
+        specifier: &ModuleSpecifier,
+        meta: &std::collections::BTreeMap<String, deno_core::serde_json::Value>,
+    ) {
+        self.inner.borrow_mut().set_import_meta(specifier, meta);
+    }
+
+    /// Marks a module as trusted - see [`crate::Module::with_trusted`]
+    pub fn trust(&self, specifier: &ModuleSpecifier) {
+        self.inner.borrow_mut().trust(specifier.as_str());
+    }
+
+    /// Makes a module's source available to imports, without filesystem or network access
+    pub fn insert_source(&self, specifier: ModuleSpecifier, code: String) {
+        self.inner.borrow_mut().add_source(specifier, code);
+    }
+
+    /// Sets the type a module is loaded as - see [`crate::Module::with_type`]
+    pub fn insert_module_type(&self, specifier: ModuleSpecifier, module_type: ModuleType) {
+        self.inner
+            .borrow_mut()
+            .set_module_type(specifier, module_type);
+    }
+
+    /// Sets the bytecode handed to v8 when a module is loaded - see [`crate::Module::from_code_cache`]
+    pub fn insert_code_cache(&self, specifier: ModuleSpecifier, code_cache: Vec<u8>) {
+        self.inner
+            .borrow_mut()
+            .add_code_cache(specifier, code_cache);
+    }
+
+    /// True if bytecode is persisted to a directory - see [`crate::RuntimeOptions::code_cache_dir`]
+    pub fn has_code_cache_dir(&self) -> bool {
+        self.inner.borrow().has_code_cache_dir()
+    }
+
+    /// Sets the already-transpiled code of a module, loaded next through the loader
+    pub fn insert_prepared_source(&self, specifier: ModuleSpecifier, code: String) {
+        self.inner.borrow_mut().add_prepared_source(specifier, code);
+    }
 }
 
 //
@@ -74,6 +193,21 @@ impl ModuleLoader for RustyLoader {
         )
     }
 
+    /// Called by v8 once it has compiled a module that was given a code cache hash,
+    /// with bytecode that later runs can load instead of compiling again
+    fn code_cache_ready(
+        &self,
+        module_specifier: ModuleSpecifier,
+        hash: u64,
+        code_cache: &[u8],
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = ()>>> {
+        // Written right away, so the entry exists even if the event loop never runs again
+        self.inner
+            .borrow()
+            .store_code_cache(&module_specifier, hash, code_cache);
+        Box::pin(std::future::ready(()))
+    }
+
     fn get_source_map(&self, file_name: &str) -> Option<Vec<u8>> {
         self.inner.borrow().get_source_map(file_name)?.1.clone()
     }
@@ -154,6 +288,71 @@ mod test {
         }
     }
 
+    /// Test cache provider, keyed by content only
+    #[derive(Default)]
+    struct ContentCacheProvider {
+        entries: std::collections::HashMap<u64, (ModuleSource, Option<Vec<u8>>)>,
+        stores: Rc<std::cell::Cell<usize>>,
+    }
+    impl ModuleCacheProvider for ContentCacheProvider {
+        fn set(&mut self, _specifier: &ModuleSpecifier, _source: ModuleSource) {}
+
+        fn get(&self, _specifier: &ModuleSpecifier) -> Option<ModuleSource> {
+            None
+        }
+
+        fn set_by_content(&mut self, hash: u64, source: ModuleSource, source_map: Option<Vec<u8>>) {
+            self.stores.set(self.stores.get() + 1);
+            self.entries.insert(hash, (source, source_map));
+        }
+
+        fn get_by_content(&self, hash: u64) -> Option<(ModuleSource, Option<Vec<u8>>)> {
+            let specifier = ModuleSpecifier::parse("file:///cached.ts").unwrap();
+            self.entries
+                .get(&hash)
+                .map(|(s, map)| (s.clone(&specifier), map.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_content_cache() {
+        let stores = Rc::new(std::cell::Cell::new(0));
+        let a = ModuleSpecifier::parse("file:///a/util.ts").unwrap();
+        let b = ModuleSpecifier::parse("file:///b/util_copy.ts").unwrap();
+        let code = "export const x: number = 1;".to_string();
+
+        let loader = RustyLoader::new(LoaderOptions {
+            cache_provider: Some(Box::new(ContentCacheProvider {
+                stores: stores.clone(),
+                ..Default::default()
+            })),
+            manifest_sources: std::collections::HashMap::from([
+                (a.clone(), code.clone()),
+                (b.clone(), code),
+            ]),
+            ..LoaderOptions::default()
+        });
+
+        for specifier in [&a, &b] {
+            let response =
+                loader.load(specifier, None, false, deno_core::RequestedModuleType::None);
+            let ModuleLoadResponse::Async(future) = response else {
+                panic!("Unexpected response");
+            };
+            let source = future.await.expect("Could not load module");
+            let ModuleSourceCode::String(code) = source.code else {
+                panic!("Unexpected source code type");
+            };
+            assert!(!code.as_str().contains(": number"));
+
+            // Errors in either module still map back to the typescript source
+            assert!(loader.get_source_map(specifier.as_str()).is_some());
+        }
+
+        // The copy was served from the entry stored for the original
+        assert_eq!(1, stores.get());
+    }
+
     struct TestImportProvider {
         i: usize,
     }
@@ -44,4 +44,72 @@ pub trait ModuleCacheProvider {
 
     /// Get a module from the cache
     fn get(&self, specifier: &ModuleSpecifier) -> Option<ModuleSource>;
+
+    /// Apply a transpiled module to the cache, keyed by a hash of its original source
+    /// (see [`content_hash`]) instead of its specifier, along with its source map if it has one
+    ///
+    /// Identical modules loaded under different names then share a single entry, including
+    /// any compiled bytecode. The default implementation does not cache anything
+    #[allow(unused_variables)]
+    fn set_by_content(&mut self, hash: u64, source: ModuleSource, source_map: Option<Vec<u8>>) {}
+
+    /// Get a transpiled module and its source map from the cache by the hash of its original source
+    /// Consulted after a specifier miss, once the source has been read but before it is transpiled
+    #[allow(unused_variables)]
+    fn get_by_content(&self, hash: u64) -> Option<(ModuleSource, Option<Vec<u8>>)> {
+        None
+    }
+}
+
+/// Hashes the source of a module, as used by [`ModuleCacheProvider::get_by_content`]
+///
+/// The hash covers the file extension, which decides how the source is transpiled, and the source text.
+/// It is stable across runs and platforms (64-bit FNV-1a), so it can key a persistent cache
+#[must_use]
+pub fn content_hash(specifier: &ModuleSpecifier, source: &str) -> u64 {
+    let extension = std::path::Path::new(specifier.path())
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    fnv1a(
+        extension
+            .bytes()
+            .chain(std::iter::once(0))
+            .chain(source.bytes()),
+    )
+}
+
+/// 64-bit FNV-1a, stable across runs and platforms
+pub(crate) fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    bytes.fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_content_hash() {
+        let a = ModuleSpecifier::parse("file:///a/util.ts").unwrap();
+        let b = ModuleSpecifier::parse("file:///b/copy_of_util.ts").unwrap();
+        let c = ModuleSpecifier::parse("file:///b/util.js").unwrap();
+
+        assert_eq!(
+            content_hash(&a, "export const x = 1;"),
+            content_hash(&b, "export const x = 1;")
+        );
+        assert_ne!(
+            content_hash(&a, "export const x = 1;"),
+            content_hash(&a, "export const x = 2;")
+        );
+        assert_ne!(
+            content_hash(&a, "export const x = 1;"),
+            content_hash(&c, "export const x = 1;")
+        );
+    }
 }
@@ -0,0 +1,100 @@
+//! Persists the bytecode v8 compiles for modules, so that later runs can skip compiling them
+use deno_core::ModuleSpecifier;
+use std::path::{Path, PathBuf};
+
+/// Written at the top of each entry - bytecode is specific to the v8 version that produced it,
+/// which is pinned by the version of rustyscript
+const CACHE_VERSION: &str = concat!("rustyscript-code-cache ", env!("CARGO_PKG_VERSION"));
+
+/// Prefixes bytecode with the cache version, and the hash of the code it was compiled from
+pub(crate) fn add_header(hash: u64, code_cache: &[u8]) -> Vec<u8> {
+    let mut data = format!("{CACHE_VERSION}\n{hash:016x}\n").into_bytes();
+    data.extend_from_slice(code_cache);
+    data
+}
+
+/// Returns the bytecode from data written by [`add_header`], if it was compiled from code with the given hash
+pub(crate) fn strip_header(data: &[u8], hash: u64) -> Option<&[u8]> {
+    data.strip_prefix(format!("{CACHE_VERSION}\n{hash:016x}\n").as_bytes())
+}
+
+/// A directory of v8 code cache entries, one per module
+/// Each entry records a hash of the code it was compiled from, and is ignored once the code changes
+///
+/// Failures to read or write the directory are ignored - the module is simply compiled again
+#[derive(Debug, Clone)]
+pub(crate) struct CodeCacheDir {
+    dir: PathBuf,
+}
+
+impl CodeCacheDir {
+    /// Create a cache storing bytecode in `dir`, which is created on the first write if needed
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Hashes the code v8 will compile for a module
+    pub fn hash(code: &[u8]) -> u64 {
+        super::cache_provider::fnv1a(code.iter().copied())
+    }
+
+    /// Path of the file storing a module's bytecode
+    fn entry_path(&self, specifier: &ModuleSpecifier) -> PathBuf {
+        let hash = super::cache_provider::fnv1a(specifier.as_str().bytes());
+        self.dir.join(format!("{hash:016x}.codecache"))
+    }
+
+    /// Returns the bytecode stored for a module, if it was compiled from code with the given hash
+    pub fn get(&self, specifier: &ModuleSpecifier, hash: u64) -> Option<Vec<u8>> {
+        let data = std::fs::read(self.entry_path(specifier)).ok()?;
+        let header = format!("{CACHE_VERSION}\n{hash:016x}\n{specifier}\n");
+        data.strip_prefix(header.as_bytes()).map(<[u8]>::to_vec)
+    }
+
+    /// Stores the bytecode v8 produced for a module, replacing any previous entry
+    pub fn set(&self, specifier: &ModuleSpecifier, hash: u64, code_cache: &[u8]) {
+        if std::fs::create_dir_all(&self.dir).is_ok() {
+            let mut data = format!("{CACHE_VERSION}\n{hash:016x}\n{specifier}\n").into_bytes();
+            data.extend_from_slice(code_cache);
+            let _ = std::fs::write(self.entry_path(specifier), data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_code_cache_dir() {
+        let dir =
+            std::env::temp_dir().join(format!("rustyscript_code_cache_{}", std::process::id()));
+        let specifier = ModuleSpecifier::parse("file:///app/main.js").unwrap();
+        let cache = CodeCacheDir::new(&dir);
+
+        let hash = CodeCacheDir::hash(b"export const x = 1;");
+        assert!(cache.get(&specifier, hash).is_none());
+
+        cache.set(&specifier, hash, &[1, 2, 3]);
+        assert_eq!(Some(vec![1, 2, 3]), cache.get(&specifier, hash));
+
+        // Bytecode compiled from other code is not used
+        let changed = CodeCacheDir::hash(b"export const x = 2;");
+        assert!(cache.get(&specifier, changed).is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_code_cache_header() {
+        let hash = CodeCacheDir::hash(b"export const x = 1;");
+        let data = add_header(hash, &[1, 2, 3]);
+        assert_eq!(Some(&[1, 2, 3][..]), strip_header(&data, hash));
+
+        let changed = CodeCacheDir::hash(b"export const x = 2;");
+        assert!(strip_header(&data, changed).is_none());
+        assert!(strip_header(&[1, 2, 3], hash).is_none());
+    }
+}
@@ -0,0 +1,23 @@
+/// How modules written as CommonJS (`module.exports` and `require`) are handled when loaded
+///
+/// A CommonJS module is a `.cjs` or `.cts` file, or a module using `module.exports`,
+/// `exports.name` or `require(...)` without any `import` or `export` declarations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommonJsMode {
+    /// Load CommonJS modules as-is - they will fail on `module` or `require`, and export nothing
+    #[default]
+    Ignore,
+
+    /// Refuse to load CommonJS modules, with an error naming the module
+    Reject,
+
+    /// Convert CommonJS modules to ES modules when loaded
+    ///
+    /// - `module.exports` becomes the default export
+    /// - Names assigned with `exports.name = ...` or `module.exports.name = ...`, or listed in
+    ///   a `module.exports = { ... }` literal, become named exports
+    /// - `require('specifier')` calls with a string literal are turned into static imports, so every
+    ///   required module is loaded up front; `require` returns the module's default export if it has one,
+    ///   and its namespace otherwise
+    Convert,
+}
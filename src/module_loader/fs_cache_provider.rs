@@ -0,0 +1,271 @@
+//! A module cache that persists to disk, so that separate runs can share fetched and transpiled modules
+use super::ModuleCacheProvider;
+use deno_core::{ModuleCodeBytes, ModuleSource, ModuleSourceCode, ModuleSpecifier, ModuleType};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
+
+/// Written at the top of each entry - entries written by another version are discarded,
+/// since their code may have been transpiled differently
+const CACHE_VERSION: &str = concat!("rustyscript-module-cache ", env!("CARGO_PKG_VERSION"));
+
+/// A [`ModuleCacheProvider`] storing modules as files in a directory
+///
+/// Each module is stored in its own file, named after a hash of its specifier.
+/// Entries written by a different version of rustyscript, or older than the TTL set with
+/// [`FileSystemModuleCacheProvider::with_ttl`], are ignored and removed when read.
+///
+/// Entries are written to a temporary file, then renamed into place - so other runtimes and processes
+/// sharing the directory never read a partly-written entry
+///
+/// Failures to read or write the directory are ignored - the module is simply fetched again
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ Runtime, RuntimeOptions, module_loader::FileSystemModuleCacheProvider };
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), rustyscript::Error> {
+/// let cache_dir = std::env::temp_dir().join("rustyscript_doc_cache");
+/// let cache = FileSystemModuleCacheProvider::new(&cache_dir)
+///     .with_ttl(Duration::from_secs(60 * 60 * 24));
+///
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     module_cache: Some(Box::new(cache)),
+///     ..Default::default()
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileSystemModuleCacheProvider {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+}
+
+impl FileSystemModuleCacheProvider {
+    /// Create a cache storing modules in `dir`, which is created on the first write if needed
+    #[must_use]
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            ttl: None,
+        }
+    }
+
+    /// Ignore, and remove, entries older than `ttl`
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The directory the cache is stored in
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of the file storing a module
+    fn entry_path(&self, specifier: &ModuleSpecifier) -> PathBuf {
+        let hash = super::cache_provider::fnv1a(specifier.as_str().bytes());
+        self.dir.join(format!("{hash:016x}.module"))
+    }
+
+    /// Checks if an entry is older than the TTL
+    fn is_expired(&self, path: &Path) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+
+        let age = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        match age {
+            Some(age) => age > ttl,
+            None => true,
+        }
+    }
+
+    /// Encodes a module as a header of 4 lines, followed by its code
+    fn encode(specifier: &ModuleSpecifier, source: &ModuleSource) -> Vec<u8> {
+        let module_type = match &source.module_type {
+            ModuleType::JavaScript => "javascript".to_string(),
+            ModuleType::Json => "json".to_string(),
+            ModuleType::Wasm => "wasm".to_string(),
+            ModuleType::Other(name) => format!("other:{name}"),
+        };
+        let (kind, code) = match &source.code {
+            ModuleSourceCode::String(code) => ("string", code.as_bytes()),
+            ModuleSourceCode::Bytes(code) => ("bytes", code.as_bytes()),
+        };
+
+        let mut data =
+            format!("{CACHE_VERSION}\n{module_type}\n{kind}\n{specifier}\n").into_bytes();
+        data.extend_from_slice(code);
+        data
+    }
+
+    /// Decodes a module written by [`Self::encode`]
+    /// Returns `None` if the entry is for a different module or version, or is malformed
+    fn decode(specifier: &ModuleSpecifier, data: &[u8]) -> Option<ModuleSource> {
+        let mut header = Vec::with_capacity(4);
+        let mut rest = data;
+        for _ in 0..4 {
+            let end = rest.iter().position(|b| *b == b'\n')?;
+            header.push(std::str::from_utf8(&rest[..end]).ok()?);
+            rest = &rest[end + 1..];
+        }
+
+        if header[0] != CACHE_VERSION || header[3] != specifier.as_str() {
+            return None;
+        }
+
+        let module_type = match header[1] {
+            "javascript" => ModuleType::JavaScript,
+            "json" => ModuleType::Json,
+            "wasm" => ModuleType::Wasm,
+            other => ModuleType::Other(other.strip_prefix("other:")?.to_string().into()),
+        };
+        let code = match header[2] {
+            "string" => ModuleSourceCode::String(String::from_utf8(rest.to_vec()).ok()?.into()),
+            "bytes" => ModuleSourceCode::Bytes(ModuleCodeBytes::Boxed(rest.to_vec().into())),
+            _ => return None,
+        };
+
+        Some(ModuleSource::new(module_type, code, specifier, None))
+    }
+
+    /// Writes an entry to a temporary file in the same directory, then renames it into place
+    fn write_entry(&self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        std::fs::create_dir_all(&self.dir)?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let temp = path.with_extension(format!("tmp-{}-{id}", std::process::id()));
+        let result = std::fs::write(&temp, data).and_then(|()| std::fs::rename(&temp, path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+}
+
+impl ModuleCacheProvider for FileSystemModuleCacheProvider {
+    fn set(&mut self, specifier: &ModuleSpecifier, source: ModuleSource) {
+        let path = self.entry_path(specifier);
+        let _ = self.write_entry(&path, &Self::encode(specifier, &source));
+    }
+
+    fn get(&self, specifier: &ModuleSpecifier) -> Option<ModuleSource> {
+        let path = self.entry_path(specifier);
+        let data = std::fs::read(&path).ok()?;
+
+        let source = if self.is_expired(&path) {
+            None
+        } else {
+            Self::decode(specifier, &data)
+        };
+
+        // Stale or unreadable entries are removed, so they are not read again
+        if source.is_none() {
+            let _ = std::fs::remove_file(&path);
+        }
+        source
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::module_loader::{ImportProvider, LoaderOptions, RustyLoader};
+    use deno_core::{anyhow::Error, ModuleLoadResponse, ModuleLoader, RequestedModuleType};
+    use std::{cell::Cell, rc::Rc};
+
+    /// Serves every module with the same code, counting each fetch
+    struct CountingProvider(Rc<Cell<usize>>);
+    impl ImportProvider for CountingProvider {
+        fn import(
+            &mut self,
+            _: &ModuleSpecifier,
+            _: Option<&ModuleSpecifier>,
+            _: bool,
+            _: RequestedModuleType,
+        ) -> Option<Result<String, Error>> {
+            self.0.set(self.0.get() + 1);
+            Some(Ok("export const x: number = 1;".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fs_cache() {
+        let dir = std::env::temp_dir().join(format!("rustyscript_fs_cache_{}", std::process::id()));
+        let specifier = ModuleSpecifier::parse("https://example.com/util.ts").unwrap();
+        let fetches = Rc::new(Cell::new(0));
+        let loader = || {
+            RustyLoader::new(LoaderOptions {
+                cache_provider: Some(Box::new(FileSystemModuleCacheProvider::new(&dir))),
+                import_provider: Some(Box::new(CountingProvider(fetches.clone()))),
+                ..LoaderOptions::default()
+            })
+        };
+
+        // The first loader fetches the module, and stores it
+        let ModuleLoadResponse::Async(future) =
+            loader().load(&specifier, None, false, RequestedModuleType::None)
+        else {
+            panic!("Expected the module to be fetched");
+        };
+        future.await.expect("Could not load module");
+        assert_eq!(1, fetches.get());
+
+        // Only the finished entry is left in the directory
+        let entries: Vec<_> = std::fs::read_dir(&dir)
+            .expect("Cache was not written")
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(1, entries.len());
+        assert!(entries[0].to_string_lossy().ends_with(".module"));
+
+        // A fresh loader is served from the directory, without fetching again
+        let ModuleLoadResponse::Sync(source) =
+            loader().load(&specifier, None, false, RequestedModuleType::None)
+        else {
+            panic!("Expected the module to come from the cache");
+        };
+        assert_eq!(1, fetches.get());
+        let ModuleSourceCode::String(code) = source.expect("Could not load module").code else {
+            panic!("Unexpected source code type");
+        };
+        assert!(code.as_str().contains("export const x = 1"));
+
+        // Expired entries are ignored, and removed
+        let cache = FileSystemModuleCacheProvider::new(&dir).with_ttl(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(cache.get(&specifier).is_none());
+        assert!(FileSystemModuleCacheProvider::new(&dir)
+            .get(&specifier)
+            .is_none());
+
+        // Bytes survive the round trip
+        let mut cache = FileSystemModuleCacheProvider::new(&dir);
+        let bytes = ModuleSource::new(
+            ModuleType::Wasm,
+            ModuleSourceCode::Bytes(ModuleCodeBytes::Boxed(vec![0, 97, 115, 109].into())),
+            &specifier,
+            None,
+        );
+        cache.set(&specifier, bytes);
+        let source = cache.get(&specifier).expect("Could not read entry");
+        assert_eq!(ModuleType::Wasm, source.module_type);
+        let ModuleSourceCode::Bytes(code) = source.code else {
+            panic!("Unexpected source code type");
+        };
+        assert_eq!(&[0, 97, 115, 109], code.as_bytes());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
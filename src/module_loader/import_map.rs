@@ -0,0 +1,279 @@
+//! Import maps, which alias import specifiers to URLs, following the WICG import maps specification
+use crate::Error;
+use deno_core::{serde_json, ModuleSpecifier};
+use std::path::Path;
+
+/// Mappings from a specifier, or a specifier prefix ending in `/`, to a URL
+/// Sorted so that longer keys are tried first. A `None` address blocks the specifier
+type SpecifierMap = Vec<(String, Option<ModuleSpecifier>)>;
+
+/// An import map, aliasing bare specifiers such as `@app/utils` to module URLs
+/// Set with `RuntimeOptions::import_map`, and applied to every import before the loader's own checks
+///
+/// Follows the standard JSON format, used by browsers and Deno:
+/// - `imports` maps specifiers to addresses. Keys ending in `/` map every specifier starting with them
+/// - `scopes` holds additional `imports`, applied only to modules whose URL starts with the scope
+///
+/// Addresses may be absolute URLs, or paths starting with `/`, `./` or `../`
+///
+/// ```json
+/// {
+///     "imports": {
+///         "@app/utils": "./src/utils/mod.ts",
+///         "@app/": "./src/"
+///     },
+///     "scopes": {
+///         "./vendor/": { "@app/utils": "./vendor/utils.ts" }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportMap {
+    imports: SpecifierMap,
+    scopes: Vec<(String, SpecifierMap)>,
+}
+
+impl ImportMap {
+    /// Parse an import map from JSON
+    /// Relative addresses and scopes are resolved against the current working directory
+    ///
+    /// # Errors
+    /// Will return an error if the JSON is not a valid import map
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let base = ModuleSpecifier::from_directory_path(std::env::current_dir()?)
+            .map_err(|()| Error::Runtime("Invalid working directory".to_string()))?;
+        Self::parse(json, &base)
+    }
+
+    /// Load an import map from a JSON file
+    /// Relative addresses and scopes are resolved against the location of the file
+    ///
+    /// # Errors
+    /// Will return an error if the file cannot be read, or is not a valid import map
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = std::env::current_dir()?.join(path);
+        let base = ModuleSpecifier::from_file_path(&path)
+            .map_err(|()| Error::Runtime(format!("Invalid import map path: {}", path.display())))?;
+        Self::parse(&std::fs::read_to_string(&path)?, &base)
+    }
+
+    /// Parse an import map from JSON, resolving relative addresses and scopes against `base`
+    ///
+    /// # Errors
+    /// Will return an error if the JSON is not a valid import map
+    pub fn parse(json: &str, base: &ModuleSpecifier) -> Result<Self, Error> {
+        let json: serde_json::Value = serde_json::from_str(json)?;
+        let Some(json) = json.as_object() else {
+            return Err(Error::JsonDecode(
+                "An import map must be a JSON object".to_string(),
+            ));
+        };
+
+        let imports = match json.get("imports") {
+            Some(imports) => parse_specifier_map(imports, base)?,
+            None => SpecifierMap::new(),
+        };
+
+        let mut scopes = Vec::new();
+        match json.get("scopes") {
+            Some(serde_json::Value::Object(entries)) => {
+                for (prefix, imports) in entries {
+                    let prefix = base.join(prefix)?.to_string();
+                    scopes.push((prefix, parse_specifier_map(imports, base)?));
+                }
+            }
+            Some(_) => {
+                return Err(Error::JsonDecode(
+                    "Import map `scopes` must be an object".to_string(),
+                ))
+            }
+            None => {}
+        }
+        scopes.sort_by(|a, b| b.0.cmp(&a.0));
+
+        Ok(Self { imports, scopes })
+    }
+
+    /// Applies the import map to a specifier imported by `referrer`
+    ///
+    /// # Returns
+    /// `None` if no mapping applies, otherwise the URL the specifier maps to
+    ///
+    /// # Errors
+    /// Will return an error if the specifier is blocked by the map, or the mapped URL is invalid
+    pub fn resolve(
+        &self,
+        specifier: &str,
+        referrer: &str,
+    ) -> Option<Result<ModuleSpecifier, Error>> {
+        let referrer = ModuleSpecifier::parse(referrer).ok();
+        let as_url = match &referrer {
+            Some(referrer) => parse_url_like(specifier, referrer),
+            None => ModuleSpecifier::from_directory_path(std::env::current_dir().ok()?)
+                .ok()
+                .and_then(|base| parse_url_like(specifier, &base)),
+        };
+        let normalized = as_url.as_ref().map_or(specifier, ModuleSpecifier::as_str);
+
+        if let Some(referrer) = &referrer {
+            for (prefix, imports) in &self.scopes {
+                let in_scope = referrer.as_str() == prefix
+                    || (prefix.ends_with('/') && referrer.as_str().starts_with(prefix.as_str()));
+                if in_scope {
+                    if let Some(result) = resolve_match(normalized, as_url.as_ref(), imports) {
+                        return Some(result);
+                    }
+                }
+            }
+        }
+
+        resolve_match(normalized, as_url.as_ref(), &self.imports)
+    }
+}
+
+/// Parses an `imports` object, skipping entries that cannot be used
+fn parse_specifier_map(
+    map: &serde_json::Value,
+    base: &ModuleSpecifier,
+) -> Result<SpecifierMap, Error> {
+    let Some(map) = map.as_object() else {
+        return Err(Error::JsonDecode(
+            "Import map `imports` must be an object".to_string(),
+        ));
+    };
+
+    let mut entries = SpecifierMap::new();
+    for (key, address) in map {
+        if key.is_empty() {
+            continue;
+        }
+        let key = parse_url_like(key, base).map_or_else(|| key.clone(), |url| url.to_string());
+
+        // Addresses that are not valid URLs block the specifier, as do prefixes mapped to non-prefixes
+        let address = address
+            .as_str()
+            .and_then(|address| parse_url_like(address, base))
+            .filter(|address| !key.ends_with('/') || address.as_str().ends_with('/'));
+        entries.push((key, address));
+    }
+
+    entries.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(entries)
+}
+
+/// Parses a specifier that is a URL, or a path starting with `/`, `./` or `../`
+/// Bare specifiers return `None`
+fn parse_url_like(specifier: &str, base: &ModuleSpecifier) -> Option<ModuleSpecifier> {
+    if ["/", "./", "../"].iter().any(|p| specifier.starts_with(p)) {
+        base.join(specifier).ok()
+    } else {
+        ModuleSpecifier::parse(specifier).ok()
+    }
+}
+
+/// Finds the mapping for a specifier in one set of imports
+fn resolve_match(
+    normalized: &str,
+    as_url: Option<&ModuleSpecifier>,
+    imports: &SpecifierMap,
+) -> Option<Result<ModuleSpecifier, Error>> {
+    let blocked = || {
+        Error::Runtime(format!(
+            "Import of {normalized} is blocked by the import map"
+        ))
+    };
+
+    for (key, address) in imports {
+        if key == normalized {
+            return Some(address.clone().ok_or_else(blocked));
+        }
+
+        // Prefixes only apply to bare specifiers, and URLs with special schemes
+        let is_special = match as_url {
+            Some(url) => matches!(
+                url.scheme(),
+                "ftp" | "file" | "http" | "https" | "ws" | "wss"
+            ),
+            None => true,
+        };
+        if key.ends_with('/') && normalized.starts_with(key.as_str()) && is_special {
+            let Some(address) = address else {
+                return Some(Err(blocked()));
+            };
+
+            // The remainder cannot be used to escape the address, such as with `../`
+            let url = address
+                .join(&normalized[key.len()..])
+                .ok()
+                .filter(|url| url.as_str().starts_with(address.as_str()));
+            return Some(url.ok_or_else(blocked));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_import_map() {
+        let base = ModuleSpecifier::parse("file:///app/").unwrap();
+        let map = ImportMap::parse(
+            r#"{
+                "imports": {
+                    "@app/utils": "./src/utils/mod.ts",
+                    "@app/": "./src/",
+                    "lodash": "https://cdn.example.com/lodash.js",
+                    "blocked": null,
+                    "bad/": "./not-a-prefix.js"
+                },
+                "scopes": {
+                    "./vendor/": { "lodash": "./vendor/lodash.js" }
+                }
+            }"#,
+            &base,
+        )
+        .expect("Could not parse import map");
+
+        let resolve = |specifier: &str, referrer: &str| {
+            map.resolve(specifier, referrer)
+                .map(|result| result.map(|url| url.to_string()))
+        };
+        let main = "file:///app/main.js";
+
+        // Exact matches win over prefixes
+        assert_eq!(
+            "file:///app/src/utils/mod.ts",
+            resolve("@app/utils", main).unwrap().unwrap()
+        );
+        assert_eq!(
+            "file:///app/src/db/mod.ts",
+            resolve("@app/db/mod.ts", main).unwrap().unwrap()
+        );
+        assert_eq!(
+            "https://cdn.example.com/lodash.js",
+            resolve("lodash", main).unwrap().unwrap()
+        );
+
+        // Scopes apply to the modules within them
+        assert_eq!(
+            "file:///app/vendor/lodash.js",
+            resolve("lodash", "file:///app/vendor/lib.js")
+                .unwrap()
+                .unwrap()
+        );
+
+        // Blocked and invalid entries fail, and prefixes cannot be escaped
+        assert!(resolve("blocked", main).unwrap().is_err());
+        assert!(resolve("bad/x.js", main).unwrap().is_err());
+        assert!(resolve("@app/../../etc/passwd", main).unwrap().is_err());
+
+        // Unmapped specifiers are left for the loader
+        assert!(resolve("./local.js", main).is_none());
+        assert!(resolve("unmapped", main).is_none());
+
+        ImportMap::parse("{ \"imports\": [] }", &base).expect_err("Invalid map was accepted");
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+/// Separate time budgets for imports, by scheme
+/// An import that takes longer than its budget fails with a timeout error
+///
+/// Local files should load near-instantly, so a short `file` timeout fails fast on a
+/// missing or stuck file, while `remote` (`http` and `https`) imports can get a longer budget.
+/// The remote budget covers every retry of an import - see [`super::ImportRetryPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportTimeouts {
+    /// Time budget for `file` imports - `None` for no limit
+    pub file: Option<Duration>,
+
+    /// Time budget for `http` and `https` imports - `None` for no limit
+    pub remote: Option<Duration>,
+}
+
+impl ImportTimeouts {
+    /// Create a new set of import timeouts
+    #[must_use]
+    pub fn new(file: Option<Duration>, remote: Option<Duration>) -> Self {
+        Self { file, remote }
+    }
+
+    /// Returns the time budget for imports with the given scheme, if any
+    #[must_use]
+    pub fn for_scheme(&self, scheme: &str) -> Option<Duration> {
+        match scheme {
+            "file" => self.file,
+            "http" | "https" => self.remote,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_for_scheme() {
+        let timeouts = ImportTimeouts::new(
+            Some(Duration::from_millis(50)),
+            Some(Duration::from_secs(10)),
+        );
+        assert_eq!(Some(Duration::from_millis(50)), timeouts.for_scheme("file"));
+        assert_eq!(Some(Duration::from_secs(10)), timeouts.for_scheme("https"));
+        assert_eq!(Some(Duration::from_secs(10)), timeouts.for_scheme("http"));
+        assert_eq!(None, timeouts.for_scheme("data"));
+        assert_eq!(None, ImportTimeouts::default().for_scheme("file"));
+    }
+}
@@ -1,21 +1,39 @@
 #![allow(deprecated)]
 #![allow(dead_code)]
-use crate::module_loader::{ClonableSource, ModuleCacheProvider};
-use crate::transpiler::transpile;
+use crate::module_loader::{
+    content_hash, ClonableSource, CommonJsMode, ImportRetryPolicy, ImportTimeouts, LanguagePlugin,
+    ModuleCacheProvider, VirtualFileSystem,
+};
+use crate::transpiler::{is_commonjs, substitute_constants, transpile, wrap_commonjs};
+use crate::Logger;
 use deno_core::anyhow::{anyhow, Error};
 use deno_core::futures::FutureExt;
-use deno_core::{ModuleLoadResponse, ModuleSource, ModuleSourceCode, ModuleSpecifier, ModuleType};
+use deno_core::serde_json;
+use deno_core::{
+    ModuleCodeBytes, ModuleLoadResponse, ModuleSource, ModuleSourceCode, ModuleSpecifier,
+    ModuleType,
+};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     path::Path,
 };
 
-use super::ImportProvider;
+use super::code_cache::CodeCacheDir;
+use super::{ImportMap, ImportProvider, ModuleResolver};
 
 /// Stores the source code and source ma#![allow(deprecated)]p for loaded modules
-type SourceMapCache = HashMap<String, (String, Option<Vec<u8>>)>;
+/// along with the JS that was executed for them, after transpilation
+type SourceMapCache = HashMap<String, (String, Option<Vec<u8>>, Option<String>)>;
+
+/// Returns the text of a module's source code
+fn source_text(code: &ModuleSourceCode) -> String {
+    match code {
+        ModuleSourceCode::String(code) => code.as_str().to_string(),
+        ModuleSourceCode::Bytes(code) => String::from_utf8_lossy(code.as_bytes()).to_string(),
+    }
+}
 
 /// Options for the `RustyLoader` struct
 /// Not for public use
@@ -34,8 +52,51 @@ pub struct LoaderOptions {
     /// An optional import provider to manage module resolution
     pub import_provider: Option<Box<dyn ImportProvider>>,
 
+    /// A hook replacing the default resolution of import specifiers into URLs
+    pub module_resolver: Option<ModuleResolver>,
+
+    /// Aliases applied to import specifiers before they are resolved
+    pub import_map: Option<ImportMap>,
+
     /// A whitelist of custom schema prefixes that are allowed to be loaded
     pub schema_whlist: HashSet<String>,
+
+    /// Sources of modules preloaded from a manifest, by specifier
+    pub manifest_sources: HashMap<ModuleSpecifier, String>,
+
+    /// In-memory files served for `file:` imports before the disk is consulted
+    pub virtual_fs: Option<Rc<dyn VirtualFileSystem>>,
+
+    /// How failed remote imports are retried
+    pub import_retry: ImportRetryPolicy,
+
+    /// Time budgets for imports, by scheme
+    pub import_timeouts: ImportTimeouts,
+
+    /// Compilers for additional source languages, by file extension
+    pub language_plugins: HashMap<String, Rc<dyn LanguagePlugin>>,
+
+    /// Compile-time constants substituted into module sources, by name
+    pub compile_constants: HashMap<String, serde_json::Value>,
+
+    /// How CommonJS modules are handled
+    pub commonjs: CommonJsMode,
+
+    /// The deepest chain of imports allowed below a root module, if limited
+    pub max_import_depth: Option<usize>,
+
+    /// The largest `text` or `bytes` import allowed, in bytes, if limited
+    pub max_raw_import_size: Option<usize>,
+
+    /// Receives notices about retried imports
+    pub logger: Option<Rc<dyn Logger>>,
+
+    /// A directory to persist compiled bytecode in, across runs
+    pub code_cache_dir: Option<std::path::PathBuf>,
+
+    /// The registry used to resolve `npm:` specifiers
+    #[cfg(feature = "npm_import")]
+    pub npm_registry: super::NpmRegistry,
 }
 
 /// Internal implementation of the module loader
@@ -46,9 +107,31 @@ pub struct LoaderOptions {
 pub struct InnerRustyLoader {
     cache_provider: Option<Box<dyn ModuleCacheProvider>>,
     fs_whlist: HashSet<String>,
+    trusted: HashSet<String>,
+    import_meta: HashMap<String, String>,
     source_map_cache: SourceMapCache,
     import_provider: Option<Box<dyn ImportProvider>>,
+    module_resolver: Option<ModuleResolver>,
+    import_map: Option<ImportMap>,
     schema_whlist: HashSet<String>,
+    manifest_sources: HashMap<ModuleSpecifier, String>,
+    virtual_fs: Option<Rc<dyn VirtualFileSystem>>,
+    import_retry: ImportRetryPolicy,
+    import_timeouts: ImportTimeouts,
+    language_plugins: HashMap<String, Rc<dyn LanguagePlugin>>,
+    compile_constants: HashMap<String, String>,
+    commonjs: CommonJsMode,
+    max_import_depth: Option<usize>,
+    max_raw_import_size: Option<usize>,
+    import_depths: HashMap<String, usize>,
+    logger: Option<Rc<dyn Logger>>,
+    code_cache: Option<CodeCacheDir>,
+    supplied_code_caches: HashMap<ModuleSpecifier, Vec<u8>>,
+    module_types: HashMap<ModuleSpecifier, ModuleType>,
+    prepared_sources: HashMap<ModuleSpecifier, String>,
+
+    #[cfg(feature = "npm_import")]
+    npm_registry: super::NpmRegistry,
 }
 
 impl InnerRustyLoader {
@@ -58,9 +141,39 @@ impl InnerRustyLoader {
         Self {
             cache_provider: options.cache_provider,
             fs_whlist: options.fs_whitelist,
+            trusted: HashSet::new(),
+            import_meta: HashMap::new(),
             source_map_cache: options.source_map_cache,
             import_provider: options.import_provider,
+            module_resolver: options.module_resolver,
+            import_map: options.import_map,
             schema_whlist: options.schema_whlist,
+            manifest_sources: options.manifest_sources,
+            virtual_fs: options.virtual_fs,
+            import_retry: options.import_retry,
+            import_timeouts: options.import_timeouts,
+            language_plugins: options
+                .language_plugins
+                .into_iter()
+                .map(|(extension, plugin)| (extension.to_ascii_lowercase(), plugin))
+                .collect(),
+            compile_constants: options
+                .compile_constants
+                .into_iter()
+                .map(|(name, value)| (name, format!("({value})")))
+                .collect(),
+            commonjs: options.commonjs,
+            max_import_depth: options.max_import_depth,
+            max_raw_import_size: options.max_raw_import_size,
+            import_depths: HashMap::new(),
+            logger: options.logger,
+            code_cache: options.code_cache_dir.map(CodeCacheDir::new),
+            supplied_code_caches: HashMap::new(),
+            module_types: HashMap::new(),
+            prepared_sources: HashMap::new(),
+
+            #[cfg(feature = "npm_import")]
+            npm_registry: options.npm_registry,
         }
     }
 
@@ -78,14 +191,74 @@ impl InnerRustyLoader {
         self.fs_whlist.contains(specifier)
     }
 
+    /// Records how deep below a root module an import sits, failing if it is too deep
+    /// Modules loaded from rust sit at depth 0, and each import adds one level -
+    /// a module reachable along several chains keeps the shallowest depth
+    fn check_import_depth(
+        &mut self,
+        url: &ModuleSpecifier,
+        referrer: &str,
+        max_depth: usize,
+    ) -> Result<(), Error> {
+        let depth = if referrer == "." {
+            0
+        } else {
+            self.import_depths
+                .get(referrer)
+                .map_or(1, |depth| depth + 1)
+        };
+
+        if depth > max_depth {
+            return Err(anyhow!(
+                "import depth limit of {max_depth} exceeded importing {url} from {referrer}"
+            ));
+        }
+
+        let entry = self.import_depths.entry(url.to_string()).or_insert(depth);
+        *entry = (*entry).min(depth);
+        Ok(())
+    }
+
+    /// Reads a `file:` module from the virtual filesystem, if there is one and it has the file
+    fn read_virtual_file(&self, url: &ModuleSpecifier) -> Option<String> {
+        let virtual_fs = self.virtual_fs.as_ref()?;
+        if url.scheme() != "file" {
+            return None;
+        }
+
+        let path = url.to_file_path().ok()?;
+        virtual_fs.read(&path)
+    }
+
+    /// Marks a module as trusted, exempting its imports from the import restrictions
+    pub fn trust(&mut self, specifier: &str) {
+        self.trusted.insert(specifier.to_string());
+    }
+
     pub fn resolve(
         &mut self,
         specifier: &str,
         referrer: &str,
         kind: deno_core::ResolutionKind,
     ) -> Result<ModuleSpecifier, Error> {
-        // Resolve the module specifier to an absolute URL
-        let url = deno_core::resolve_import(specifier, referrer)?;
+        // Resolve the module specifier to an absolute URL, with the host's resolver if there is one
+        // Otherwise the import map is applied first, if there is one
+        let url = if let Some(resolver) = &self.module_resolver {
+            resolver(specifier, referrer)?
+        } else if let Some(mapped) = self
+            .import_map
+            .as_ref()
+            .and_then(|map| map.resolve(specifier, referrer))
+        {
+            mapped?
+        } else {
+            deno_core::resolve_import(specifier, referrer)?
+        };
+
+        // Enforce the depth limit before anything else can accept the import
+        if let Some(max_depth) = self.max_import_depth {
+            self.check_import_depth(&url, referrer, max_depth)?;
+        }
 
         // Check if the module is in the cache
         if self
@@ -96,6 +269,16 @@ impl InnerRustyLoader {
             return Ok(url);
         }
 
+        // Modules from the manifest are always allowed
+        if self.manifest_sources.contains_key(&url) {
+            return Ok(url);
+        }
+
+        // As are files in the virtual filesystem
+        if self.read_virtual_file(&url).is_some() {
+            return Ok(url);
+        }
+
         // Check if the import provider allows the import
         if let Some(import_provider) = &mut self.import_provider {
             let resolve_result = import_provider.resolve(&url, referrer, kind);
@@ -104,6 +287,13 @@ impl InnerRustyLoader {
             }
         }
 
+        // Trusted modules may import from the filesystem, and extend their trust to those files
+        if url.scheme() == "file" && self.trusted.contains(referrer) {
+            self.trust(url.as_str());
+            self.whitelist_add(url.as_str());
+            return Ok(url);
+        }
+
         if referrer == "." {
             // Added from rust, add to the whitelist
             // so we can load it from the filesystem
@@ -114,10 +304,25 @@ impl InnerRustyLoader {
         match url.scheme() {
             // Remote fetch imports
             "https" | "http" => {
-                #[cfg(not(feature = "url_import"))]
+                // Without url_import, only modules served by the npm registry are allowed
+                #[cfg(all(not(feature = "url_import"), feature = "npm_import"))]
+                if !self.npm_registry.contains(&url) {
+                    return Err(anyhow!("web imports are not allowed here: {specifier}"));
+                }
+
+                #[cfg(not(any(feature = "url_import", feature = "npm_import")))]
                 return Err(anyhow!("web imports are not allowed here: {specifier}"));
             }
 
+            // npm package imports
+            "npm" => {
+                #[cfg(feature = "npm_import")]
+                return self.npm_registry.resolve(&url);
+
+                #[cfg(not(feature = "npm_import"))]
+                return Err(anyhow!("npm imports are not allowed here: {specifier}"));
+            }
+
             // Dynamic FS imports
             "file" =>
             {
@@ -154,13 +359,71 @@ impl InnerRustyLoader {
         let module_specifier = module_specifier.clone();
         let maybe_referrer = maybe_referrer.cloned();
 
+        // Text and bytes imports are read as-is, and never cached
+        if let deno_core::RequestedModuleType::Other(module_type) = &requested_module_type {
+            if super::raw_imports::is_raw_type(module_type) {
+                let module_type = module_type.to_string();
+                return Self::load_raw(
+                    &inner,
+                    module_specifier,
+                    maybe_referrer.as_ref(),
+                    is_dyn_import,
+                    requested_module_type,
+                    module_type,
+                );
+            }
+        }
+
+        // Root modules already transpiled by the runtime are only missing their bytecode
+        let prepared = inner
+            .borrow_mut()
+            .prepared_sources
+            .remove(&module_specifier);
+        if let Some(code) = prepared {
+            let source = ModuleSource::new(
+                ModuleType::JavaScript,
+                ModuleSourceCode::String(code.into()),
+                &module_specifier,
+                None,
+            );
+            let source = inner.borrow().attach_code_cache(&module_specifier, source);
+            return deno_core::ModuleLoadResponse::Sync(Ok(source));
+        }
+
         // Check if the module is in the cache first
         if let Some(cache) = &inner.borrow().cache_provider {
             if let Some(source) = cache.get(&module_specifier) {
+                let source = inner.borrow().attach_code_cache(&module_specifier, source);
                 return deno_core::ModuleLoadResponse::Sync(Ok(source));
             }
         }
 
+        // Next check the modules provided by the manifest
+        let manifest_source = inner
+            .borrow()
+            .manifest_sources
+            .get(&module_specifier)
+            .cloned();
+        if let Some(source) = manifest_source {
+            return ModuleLoadResponse::Async(
+                async move {
+                    Self::handle_load(inner, module_specifier, |_| async move { Ok(source) }).await
+                }
+                .boxed_local(),
+            );
+        }
+
+        // Next check the virtual filesystem
+        let virtual_source = inner.borrow().read_virtual_file(&module_specifier);
+        if let Some(source) = virtual_source {
+            return ModuleLoadResponse::Async(
+                async move {
+                    Self::handle_load(inner, module_specifier, |_| async move { Ok(source) }).await
+                }
+                .boxed_local(),
+            );
+        }
+
         // Next check the import provider
         let provider_result = inner.borrow_mut().import_provider.as_mut().and_then(|p| {
             p.import(
@@ -182,17 +445,46 @@ impl InnerRustyLoader {
         // We check permissions next
         match module_specifier.scheme() {
             // Remote fetch imports
-            #[cfg(feature = "url_import")]
-            "https" | "http" => ModuleLoadResponse::Async(
-                async move { Self::handle_load(inner, module_specifier, Self::load_remote).await }
+            #[cfg(any(feature = "url_import", feature = "npm_import"))]
+            "https" | "http" => {
+                let retry = inner.borrow().import_retry;
+                let logger = inner.borrow().logger.clone();
+                let timeout = inner.borrow().import_timeouts.remote;
+                ModuleLoadResponse::Async(
+                    async move {
+                        Self::handle_load(inner, module_specifier, |specifier| {
+                            Self::with_timeout(
+                                timeout,
+                                specifier.clone(),
+                                logger.clone(),
+                                Self::load_remote(specifier, retry, logger),
+                            )
+                        })
+                        .await
+                    }
                     .boxed_local(),
-            ),
+                )
+            }
 
             // FS imports
-            "file" => ModuleLoadResponse::Async(
-                async move { Self::handle_load(inner, module_specifier, Self::load_file).await }
+            "file" => {
+                let logger = inner.borrow().logger.clone();
+                let timeout = inner.borrow().import_timeouts.file;
+                ModuleLoadResponse::Async(
+                    async move {
+                        Self::handle_load(inner, module_specifier, |specifier| {
+                            Self::with_timeout(
+                                timeout,
+                                specifier.clone(),
+                                logger,
+                                Self::load_file(specifier),
+                            )
+                        })
+                        .await
+                    }
                     .boxed_local(),
-            ),
+                )
+            }
 
             // Default deny-all
             _ => ModuleLoadResponse::Sync(Err(anyhow!(
@@ -203,6 +495,153 @@ impl InnerRustyLoader {
         }
     }
 
+    /// Loads a `text` or `bytes` import - the module's contents as-is, skipping transpilation
+    /// Contents come from the manifest, the virtual filesystem, the import provider, the filesystem,
+    /// or the network, in that order
+    fn load_raw(
+        inner: &Rc<RefCell<Self>>,
+        module_specifier: ModuleSpecifier,
+        maybe_referrer: Option<&ModuleSpecifier>,
+        is_dyn_import: bool,
+        requested_module_type: deno_core::RequestedModuleType,
+        module_type: String,
+    ) -> ModuleLoadResponse {
+        let supplied = {
+            let mut loader = inner.borrow_mut();
+            let source = loader
+                .manifest_sources
+                .get(&module_specifier)
+                .cloned()
+                .or_else(|| loader.read_virtual_file(&module_specifier));
+            match source {
+                Some(source) => Some(Ok(source)),
+                None => loader.import_provider.as_mut().and_then(|p| {
+                    p.import(
+                        &module_specifier,
+                        maybe_referrer,
+                        is_dyn_import,
+                        requested_module_type,
+                    )
+                }),
+            }
+        };
+
+        let max_size = inner.borrow().max_raw_import_size;
+        let retry = inner.borrow().import_retry;
+        let logger = inner.borrow().logger.clone();
+        let timeout = match module_specifier.scheme() {
+            "file" => inner.borrow().import_timeouts.file,
+            _ => inner.borrow().import_timeouts.remote,
+        };
+
+        ModuleLoadResponse::Async(
+            async move {
+                let contents = match supplied {
+                    Some(source) => source?.into_bytes(),
+                    None => {
+                        Self::with_timeout(
+                            timeout,
+                            module_specifier.clone(),
+                            logger.clone(),
+                            Self::read_raw(module_specifier.clone(), max_size, retry, logger),
+                        )
+                        .await?
+                    }
+                };
+                Self::check_raw_size(&module_specifier, contents.len() as u64, max_size)?;
+
+                Ok(ModuleSource::new(
+                    ModuleType::Other(module_type.into()),
+                    ModuleSourceCode::Bytes(ModuleCodeBytes::Boxed(contents.into())),
+                    &module_specifier,
+                    None,
+                ))
+            }
+            .boxed_local(),
+        )
+    }
+
+    /// Reads the raw contents of a file or remote import, stopping once it grows past `max_size`
+    #[cfg_attr(
+        not(any(feature = "url_import", feature = "npm_import")),
+        allow(unused_variables)
+    )]
+    async fn read_raw(
+        module_specifier: ModuleSpecifier,
+        max_size: Option<usize>,
+        retry: ImportRetryPolicy,
+        logger: Option<Rc<dyn Logger>>,
+    ) -> Result<Vec<u8>, Error> {
+        match module_specifier.scheme() {
+            "file" => {
+                let path = module_specifier
+                    .to_file_path()
+                    .map_err(|()| anyhow!("`{module_specifier}` is not a valid file URL."))?;
+                let size = tokio::fs::metadata(&path).await?.len();
+                Self::check_raw_size(&module_specifier, size, max_size)?;
+                Ok(tokio::fs::read(path).await?)
+            }
+
+            #[cfg(any(feature = "url_import", feature = "npm_import"))]
+            "https" | "http" => {
+                let mut response = Self::fetch_remote(&module_specifier, retry, logger).await?;
+                if let Some(size) = response.content_length() {
+                    Self::check_raw_size(&module_specifier, size, max_size)?;
+                }
+
+                // The advertised length is not trusted - the body is checked as it arrives
+                let mut contents = Vec::new();
+                while let Some(chunk) = response.chunk().await? {
+                    contents.extend_from_slice(&chunk);
+                    Self::check_raw_size(&module_specifier, contents.len() as u64, max_size)?;
+                }
+                Ok(contents)
+            }
+
+            _ => Err(anyhow!(
+                "{} imports are not allowed here: {}",
+                module_specifier.scheme(),
+                module_specifier.as_str()
+            )),
+        }
+    }
+
+    /// Fails if a raw import is larger than the configured limit
+    fn check_raw_size(
+        module_specifier: &ModuleSpecifier,
+        size: u64,
+        max_size: Option<usize>,
+    ) -> Result<(), Error> {
+        match max_size {
+            Some(max_size) if size > max_size as u64 => Err(anyhow!(
+                "Import of {module_specifier} is larger than the limit of {max_size} bytes"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Runs an import, failing if it takes longer than the given time budget
+    async fn with_timeout<T, Fut>(
+        timeout: Option<std::time::Duration>,
+        module_specifier: ModuleSpecifier,
+        logger: Option<Rc<dyn Logger>>,
+        future: Fut,
+    ) -> Result<T, Error>
+    where
+        Fut: std::future::Future<Output = Result<T, Error>>,
+    {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, future).await.map_err(|_| {
+                let message = format!("Import of {module_specifier} timed out after {timeout:?}");
+                if let Some(logger) = &logger {
+                    logger.log(crate::LogLevel::Warn, &message);
+                }
+                anyhow!(message)
+            })?,
+            None => future.await,
+        }
+    }
+
     async fn load_file(module_specifier: ModuleSpecifier) -> Result<String, Error> {
         let path = module_specifier
             .to_file_path()
@@ -210,12 +649,57 @@ impl InnerRustyLoader {
         Ok(tokio::fs::read_to_string(path).await?)
     }
 
-    #[cfg(feature = "url_import")]
-    async fn load_remote(module_specifier: ModuleSpecifier) -> Result<String, Error> {
-        let response = reqwest::get(module_specifier).await?;
+    #[cfg(any(feature = "url_import", feature = "npm_import"))]
+    async fn load_remote(
+        module_specifier: ModuleSpecifier,
+        retry: ImportRetryPolicy,
+        logger: Option<Rc<dyn Logger>>,
+    ) -> Result<String, Error> {
+        let response = Self::fetch_remote(&module_specifier, retry, logger).await?;
         Ok(response.text().await?)
     }
 
+    /// Requests a remote import, retrying the failures the policy allows
+    #[cfg(any(feature = "url_import", feature = "npm_import"))]
+    async fn fetch_remote(
+        module_specifier: &ModuleSpecifier,
+        retry: ImportRetryPolicy,
+        logger: Option<Rc<dyn Logger>>,
+    ) -> Result<reqwest::Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let error = match reqwest::get(module_specifier.clone()).await {
+                Ok(response)
+                    if ImportRetryPolicy::is_retryable_status(response.status().as_u16()) =>
+                {
+                    anyhow!("{module_specifier}: HTTP {}", response.status())
+                }
+                Ok(response) => return Ok(response),
+
+                Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => e.into(),
+                Err(e) => return Err(e.into()),
+            };
+
+            // Out of retries, report the last failure
+            if attempt >= retry.max_retries {
+                return Err(error);
+            }
+
+            let delay = retry.delay(attempt);
+            attempt += 1;
+            if let Some(logger) = &logger {
+                logger.log(
+                    crate::LogLevel::Warn,
+                    &format!(
+                        "Retrying import of {module_specifier} in {delay:?} ({attempt}/{}): {error}",
+                        retry.max_retries
+                    ),
+                );
+            }
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     /// Loads a module's source code from the cache or from the provided handler
     async fn handle_load<F, Fut>(
         inner: Rc<RefCell<Self>>,
@@ -233,6 +717,9 @@ impl InnerRustyLoader {
             .as_ref()
             .map(|p| p.get(&module_specifier))
         {
+            inner
+                .borrow_mut()
+                .add_transpiled_source(module_specifier.as_str(), source_text(&source.code));
             return Ok(source);
         }
 
@@ -240,34 +727,58 @@ impl InnerRustyLoader {
         // Not in the cache, load the module from the handler
         //
 
-        // Get the module type first
-        let extension = Path::new(module_specifier.path())
-            .extension()
-            .unwrap_or_default();
-        let module_type = if extension.eq_ignore_ascii_case("json") {
-            ModuleType::Json
+        // Get the module type first - set explicitly, or guessed from the extension
+        let explicit_type = inner.borrow().module_types.get(&module_specifier).cloned();
+        let module_type = inner.borrow().module_type(&module_specifier);
+
+        // Load the module code
+        let code = handler(module_specifier.clone()).await?;
+        let code = inner.borrow().transform_source(&module_specifier, &code)?;
+
+        // An identical module may have been transpiled already, under another name
+        // Skipped for explicit types, since the cached entry may have been built as another type
+        let hash = content_hash(&module_specifier, &code);
+        let cached = if explicit_type.is_some() {
+            None
         } else {
-            ModuleType::JavaScript
+            inner
+                .borrow()
+                .cache_provider
+                .as_ref()
+                .and_then(|p| p.get_by_content(hash))
         };
 
-        // Load the module code, and transpile it if necessary
-        let code = handler(module_specifier.clone()).await?;
-        let (tcode, source_map) = transpile(&module_specifier, &code)?;
-
-        // Create the module source
-        let mut source = ModuleSource::new(
-            module_type,
-            ModuleSourceCode::String(tcode.into()),
-            &module_specifier,
-            None,
-        );
-
-        // Add the source to our source cache
-        inner.borrow_mut().add_source_map(
-            module_specifier.as_str(),
-            code,
-            source_map.map(|s| s.to_vec()),
-        );
+        let mut source = if let Some((cached, source_map)) = cached {
+            inner
+                .borrow_mut()
+                .add_source_map(module_specifier.as_str(), code, source_map);
+            cached.clone(&module_specifier)
+        } else {
+            // Transpile it if necessary, and create the module source
+            let (tcode, source_map) = if module_type == ModuleType::Json {
+                (code.clone(), None)
+            } else {
+                transpile(&module_specifier, &code)?
+            };
+            let source = ModuleSource::new(
+                module_type,
+                ModuleSourceCode::String(tcode.into()),
+                &module_specifier,
+                None,
+            );
+
+            // Add the source to our source cache
+            let source_map = source_map.map(|s| s.to_vec());
+            if explicit_type.is_none() {
+                if let Some(p) = &mut inner.borrow_mut().cache_provider {
+                    p.set_by_content(hash, source.clone(&module_specifier), source_map.clone());
+                }
+            }
+            inner
+                .borrow_mut()
+                .add_source_map(module_specifier.as_str(), code, source_map);
+            source
+        };
 
         // Cache the source if a cache provider is available
         // Could speed up loads on some future runtime
@@ -280,17 +791,201 @@ impl InnerRustyLoader {
             source = import_provider.post_process(&module_specifier, source)?;
         }
 
-        Ok(source)
+        inner
+            .borrow_mut()
+            .add_transpiled_source(module_specifier.as_str(), source_text(&source.code));
+        Ok(inner.borrow().attach_code_cache(&module_specifier, source))
+    }
+
+    /// Hands v8 the bytecode supplied with a module, or stored for it if the code cache is enabled
+    /// With no usable entry, v8 is still given the hash, so that it produces bytecode to store
+    fn attach_code_cache(
+        &self,
+        specifier: &ModuleSpecifier,
+        mut source: ModuleSource,
+    ) -> ModuleSource {
+        let supplied = self.supplied_code_caches.get(specifier);
+        if supplied.is_none() && self.code_cache.is_none() {
+            return source;
+        }
+
+        // Bytecode supplied by a cache provider is left in place
+        if source.module_type != ModuleType::JavaScript || source.code_cache.is_some() {
+            return source;
+        }
+
+        let hash = match &source.code {
+            ModuleSourceCode::String(code) => CodeCacheDir::hash(code.as_bytes()),
+            ModuleSourceCode::Bytes(code) => CodeCacheDir::hash(code.as_bytes()),
+        };
+        // Supplied bytecode records the hash of the code it was compiled from, and is dropped if that changed
+        let data = match supplied {
+            Some(bytes) => super::code_cache::strip_header(bytes, hash).map(<[u8]>::to_vec),
+            None => self
+                .code_cache
+                .as_ref()
+                .and_then(|cache| cache.get(specifier, hash)),
+        };
+        if let (Some(logger), Some(_)) = (&self.logger, &data) {
+            logger.log(
+                crate::LogLevel::Debug,
+                &format!("Using cached bytecode for {specifier}"),
+            );
+        }
+        source.code_cache = Some(deno_core::SourceCodeCacheInfo {
+            hash,
+            data: data.map(Into::into),
+        });
+        source
+    }
+
+    /// Sets the bytecode handed to v8 when a module is loaded - see [`crate::Module::from_code_cache`]
+    pub fn add_code_cache(&mut self, specifier: ModuleSpecifier, code_cache: Vec<u8>) {
+        self.supplied_code_caches.insert(specifier, code_cache);
+    }
+
+    /// Persists the bytecode v8 produced for a module, if the code cache is enabled
+    pub fn store_code_cache(&self, specifier: &ModuleSpecifier, hash: u64, code_cache: &[u8]) {
+        if let Some(cache) = &self.code_cache {
+            cache.set(specifier, hash, code_cache);
+            if let Some(logger) = &self.logger {
+                logger.log(
+                    crate::LogLevel::Debug,
+                    &format!("Stored bytecode for {specifier}"),
+                );
+            }
+        }
+    }
+
+    /// True if bytecode is persisted to a code cache directory
+    pub fn has_code_cache_dir(&self) -> bool {
+        self.code_cache.is_some()
+    }
+
+    /// Sets the transpiled code of a module the runtime is about to load, so that it goes
+    /// through the loader - and the code cache - without being transformed again
+    pub fn add_prepared_source(&mut self, specifier: ModuleSpecifier, code: String) {
+        self.prepared_sources.insert(specifier, code);
+    }
+
+    /// Sets the type a module is loaded as, instead of guessing it from the extension
+    pub fn set_module_type(&mut self, specifier: ModuleSpecifier, module_type: ModuleType) {
+        self.module_types.insert(specifier, module_type);
+    }
+
+    /// Adds a module's source, as if it had been part of the manifest
+    pub fn add_source(&mut self, specifier: ModuleSpecifier, source: String) {
+        self.manifest_sources.insert(specifier, source);
     }
 
     /// Returns a reference to a file in the source map cache
-    pub fn get_source_map(&self, filename: &str) -> Option<&(String, Option<Vec<u8>>)> {
+    pub fn get_source_map(
+        &self,
+        filename: &str,
+    ) -> Option<&(String, Option<Vec<u8>>, Option<String>)> {
         self.source_map_cache.get(filename)
     }
 
+    /// The type of a module - set explicitly, or guessed from the extension
+    pub fn module_type(&self, specifier: &ModuleSpecifier) -> ModuleType {
+        if let Some(module_type) = self.module_types.get(specifier) {
+            return module_type.clone();
+        }
+
+        let extension = Path::new(specifier.path()).extension().unwrap_or_default();
+        if extension.eq_ignore_ascii_case("json") {
+            ModuleType::Json
+        } else {
+            ModuleType::JavaScript
+        }
+    }
+
+    /// Runs the language plugin registered for the module's extension, if any, over its source
+    pub fn transform_source(
+        &self,
+        specifier: &ModuleSpecifier,
+        code: &str,
+    ) -> Result<String, Error> {
+        let extension = Path::new(specifier.path())
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        let code = match extension.and_then(|e| self.language_plugins.get(&e)) {
+            Some(plugin) => plugin.transform(specifier, code)?,
+            None => code.to_string(),
+        };
+
+        let code = match self.commonjs {
+            CommonJsMode::Reject if is_commonjs(specifier, &code) => {
+                return Err(anyhow!(
+                    "{specifier} is a CommonJS module, which cannot be loaded - see `RuntimeOptions::commonjs`"
+                ));
+            }
+            CommonJsMode::Convert if is_commonjs(specifier, &code) => {
+                wrap_commonjs(specifier, &code)
+            }
+            _ => code,
+        };
+        let code = substitute_constants(specifier, &code, &self.compile_constants);
+
+        // JSON modules have no `import.meta`
+        let preamble = match self.import_meta.get(specifier.as_str()) {
+            Some(preamble) if self.module_type(specifier) != ModuleType::Json => preamble,
+            _ => return Ok(code),
+        };
+
+        // Placed at the start of the first line, so that line numbers are unaffected
+        // A hashbang must stay first, so it goes at the start of the second line instead
+        if code.starts_with("#!") {
+            let (hashbang, rest) = code.split_at(code.find('\n').map_or(code.len(), |i| i + 1));
+            let separator = if rest.is_empty() && !hashbang.ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            };
+            Ok(format!("{hashbang}{separator}{preamble}{rest}"))
+        } else {
+            Ok(format!("{preamble}{code}"))
+        }
+    }
+
+    /// Sets the extra `import.meta` properties for a module
+    pub fn set_import_meta(
+        &mut self,
+        specifier: &ModuleSpecifier,
+        meta: &BTreeMap<String, serde_json::Value>,
+    ) {
+        if meta.is_empty() {
+            self.import_meta.remove(specifier.as_str());
+        } else {
+            let properties = serde_json::Value::Object(meta.clone().into_iter().collect());
+            self.import_meta.insert(
+                specifier.to_string(),
+                format!("Object.assign(import.meta, {properties});"),
+            );
+        }
+    }
+
+    /// Returns the JS that was executed for a module, after transpilation
+    pub fn get_transpiled_source(&self, specifier: &str) -> Option<&String> {
+        self.source_map_cache.get(specifier)?.2.as_ref()
+    }
+
+    /// Records the JS that will be executed for a module
+    /// Without an entry in the source map cache, the module is taken to be its own source
+    pub fn add_transpiled_source(&mut self, specifier: &str, code: String) {
+        self.source_map_cache
+            .entry(specifier.to_string())
+            .or_insert_with(|| (code.clone(), None, None))
+            .2 = Some(code);
+    }
+
     /// Adds a source map to the cache
     pub fn add_source_map(&mut self, filename: &str, source: String, source_map: Option<Vec<u8>>) {
-        self.source_map_cache
-            .insert(filename.to_string(), (source, source_map));
+        let entry = self
+            .source_map_cache
+            .entry(filename.to_string())
+            .or_default();
+        entry.0 = source;
+        entry.1 = source_map;
     }
 }
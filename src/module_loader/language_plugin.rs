@@ -0,0 +1,36 @@
+use deno_core::{anyhow::Error, ModuleSpecifier};
+
+/// A trait that can be implemented to support additional source languages
+/// such as CoffeeScript, or a custom DSL
+///
+/// Plugins are registered by file extension, with [`crate::RuntimeBuilder::with_language_plugin`]
+/// or `RuntimeOptions::language_plugins`. The loader hands the source of every module with a
+/// matching extension to the plugin, which compiles it into JavaScript before the module is compiled
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ module_loader::LanguagePlugin, deno_core::{ anyhow::Error, ModuleSpecifier } };
+///
+/// /// A tiny DSL where each line is `name = expression`
+/// struct Assignments;
+/// impl LanguagePlugin for Assignments {
+///     fn transform(&self, _specifier: &ModuleSpecifier, source: &str) -> Result<String, Error> {
+///         Ok(source
+///             .lines()
+///             .filter_map(|line| line.split_once('='))
+///             .map(|(name, value)| format!("export const {} = {};\n", name.trim(), value.trim()))
+///             .collect())
+///     }
+/// }
+/// ```
+pub trait LanguagePlugin {
+    /// Compile the source of a module into JavaScript
+    ///
+    /// # Arguments
+    /// - `specifier`: The module specifier being loaded
+    /// - `source`: The original source code of the module
+    ///
+    /// # Errors
+    /// An error will be returned to the caller, failing the module load
+    fn transform(&self, specifier: &ModuleSpecifier, source: &str) -> Result<String, Error>;
+}
@@ -0,0 +1,114 @@
+//! Manifests describing a set of modules to be made available to a runtime up front
+use crate::{traits::ToModuleSpecifier, Error};
+use deno_core::ModuleSpecifier;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A single module in a [`ModuleManifest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The module's specifier - a URL, or a path relative to the manifest
+    pub specifier: String,
+
+    /// The module's source code (JS or TS, decided by the specifier's extension)
+    pub source: String,
+
+    /// If true, the module is instantiated and evaluated when the runtime is created,
+    /// instead of on first import
+    #[serde(default)]
+    pub preload: bool,
+}
+
+/// A bundle of modules, shipped as a single JSON file, that a runtime loads in bulk at creation
+/// Set with `RuntimeOptions::manifest`
+///
+/// Modules in the manifest can be imported by their specifier without touching the filesystem
+/// or network, and entries marked with `preload` are evaluated up front
+///
+/// ```json
+/// {
+///     "modules": [
+///         { "specifier": "lib/math.js", "source": "export const add = (a, b) => a + b;", "preload": true },
+///         { "specifier": "https://example.com/util.ts", "source": "export const x: number = 1;" }
+///     ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleManifest {
+    /// The modules in the manifest
+    pub modules: Vec<ManifestEntry>,
+}
+
+impl ModuleManifest {
+    /// Parse a manifest from JSON
+    /// Relative specifiers are resolved against the current working directory
+    ///
+    /// # Errors
+    /// Will return an error if the JSON is not a valid manifest
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(deno_core::serde_json::from_str(json)?)
+    }
+
+    /// Load a manifest from a JSON file
+    /// Relative specifiers are resolved against the directory containing the manifest
+    ///
+    /// # Errors
+    /// Will return an error if the file cannot be read, or is not a valid manifest
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut manifest = Self::from_json(&std::fs::read_to_string(path)?)?;
+
+        let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let base = std::env::current_dir()?.join(base);
+        for entry in &mut manifest.modules {
+            entry.specifier = resolve_specifier(&entry.specifier, Some(&base))?.to_string();
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns the resolved specifier of each module in the manifest, along with its entry
+    ///
+    /// # Errors
+    /// Will return an error if a specifier cannot be resolved
+    pub fn resolve(&self) -> Result<Vec<(ModuleSpecifier, &ManifestEntry)>, Error> {
+        self.modules
+            .iter()
+            .map(|entry| Ok((resolve_specifier(&entry.specifier, None)?, entry)))
+            .collect()
+    }
+}
+
+/// Specifiers are either URLs or paths
+fn resolve_specifier(specifier: &str, base: Option<&Path>) -> Result<ModuleSpecifier, Error> {
+    match ModuleSpecifier::parse(specifier) {
+        Ok(specifier) => Ok(specifier),
+        Err(_) => specifier.to_module_specifier(base),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let manifest = ModuleManifest::from_json(
+            r#"{ "modules": [
+                { "specifier": "test.js", "source": "", "preload": true },
+                { "specifier": "https://example.com/a.js", "source": "" }
+            ] }"#,
+        )
+        .expect("Could not parse manifest");
+
+        let entries = manifest.resolve().expect("Could not resolve manifest");
+        assert_eq!("file", entries[0].0.scheme());
+        assert!(entries[0].0.path().ends_with("/test.js"));
+        assert!(entries[0].1.preload);
+
+        assert_eq!("https://example.com/a.js", entries[1].0.as_str());
+        assert!(!entries[1].1.preload);
+
+        ModuleManifest::from_json("{ \"modules\": 5 }").expect_err("Invalid manifest was accepted");
+    }
+}
@@ -0,0 +1,134 @@
+//! Resolution of `npm:` specifiers to an ESM-serving registry
+use deno_core::{anyhow::anyhow, ModuleSpecifier};
+
+/// The registry used by default - serves npm packages as ES modules
+const DEFAULT_REGISTRY: &str = "https://esm.sh/";
+
+/// Maps `npm:package@version/subpath` specifiers to a registry that serves the package's ESM entry
+///
+/// The registry must serve ES modules at `<registry>/<package>@<version>/<subpath>`,
+/// as CDNs such as `esm.sh` do, or as a host-controlled mirror or cache might.
+///
+/// Modules loaded from the registry (including any they import from it) are fetched over http(s),
+/// and cached by the runtime's module cache if one is set
+///
+/// Only available with the `npm_import` feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpmRegistry {
+    base: ModuleSpecifier,
+}
+
+impl Default for NpmRegistry {
+    fn default() -> Self {
+        Self {
+            base: ModuleSpecifier::parse(DEFAULT_REGISTRY).expect("Invalid default registry"),
+        }
+    }
+}
+
+impl NpmRegistry {
+    /// Create a resolver for the registry at the given base URL
+    ///
+    /// # Errors
+    /// Will return an error if the URL is invalid, or is not an http(s) URL
+    pub fn new(base: &str) -> Result<Self, deno_core::anyhow::Error> {
+        let mut base = ModuleSpecifier::parse(base)?;
+        if !matches!(base.scheme(), "http" | "https") {
+            return Err(anyhow!("npm registry must be an http(s) URL: {base}"));
+        }
+
+        // Make sure packages are joined onto the base, rather than replacing its last segment
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+
+        Ok(Self { base })
+    }
+
+    /// The base URL of the registry
+    #[must_use]
+    pub fn base(&self) -> &ModuleSpecifier {
+        &self.base
+    }
+
+    /// Returns true if the given URL is served by this registry
+    #[must_use]
+    pub fn contains(&self, url: &ModuleSpecifier) -> bool {
+        url.as_str().starts_with(self.base.as_str())
+    }
+
+    /// Maps an `npm:` specifier to the registry URL serving it
+    ///
+    /// # Errors
+    /// Will return an error if the specifier is not a valid `npm:` specifier
+    pub fn resolve(
+        &self,
+        specifier: &ModuleSpecifier,
+    ) -> Result<ModuleSpecifier, deno_core::anyhow::Error> {
+        if specifier.scheme() != "npm" {
+            return Err(anyhow!("not an npm specifier: {specifier}"));
+        }
+
+        let path = specifier.path().trim_start_matches('/');
+        let mut segments = path.split('/');
+        let name = match segments.next() {
+            Some(scope) if scope.starts_with('@') => match segments.next() {
+                Some(name) if !name.is_empty() => format!("{scope}/{name}"),
+                _ => return Err(anyhow!("invalid scoped npm package: {specifier}")),
+            },
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => return Err(anyhow!("missing npm package name: {specifier}")),
+        };
+
+        if name.contains(['\\', ' ']) {
+            return Err(anyhow!("invalid npm package name: {specifier}"));
+        }
+
+        let subpath = segments.collect::<Vec<_>>().join("/");
+        let path = if subpath.is_empty() {
+            name
+        } else {
+            format!("{name}/{subpath}")
+        };
+
+        Ok(self.base.join(&path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let registry = NpmRegistry::new("https://registry.example/npm").unwrap();
+        let resolve = |s: &str| {
+            registry
+                .resolve(&ModuleSpecifier::parse(s).unwrap())
+                .map(|u| u.to_string())
+        };
+
+        assert_eq!(
+            "https://registry.example/npm/lodash@4.17.21",
+            resolve("npm:lodash@4.17.21").unwrap()
+        );
+        assert_eq!(
+            "https://registry.example/npm/@scope/pkg@1.0.0/sub/path.js",
+            resolve("npm:@scope/pkg@1.0.0/sub/path.js").unwrap()
+        );
+        assert_eq!(
+            "https://registry.example/npm/preact",
+            resolve("npm:/preact").unwrap()
+        );
+
+        resolve("npm:@scope").expect_err("Incomplete scoped package was accepted");
+        resolve("npm:").expect_err("Missing package was accepted");
+
+        let inside = ModuleSpecifier::parse("https://registry.example/npm/x").unwrap();
+        let outside = ModuleSpecifier::parse("https://evil.example/npm/x").unwrap();
+        assert!(registry.contains(&inside));
+        assert!(!registry.contains(&outside));
+
+        NpmRegistry::new("file:///registry").expect_err("Non-http registry was accepted");
+    }
+}
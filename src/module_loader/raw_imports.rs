@@ -0,0 +1,80 @@
+//! Support for `text` and `bytes` imports, which expose a file's contents as-is as the module's default export
+//!
+//! ```js
+//! import template from './page.html' with { type: 'text' };
+//! import logo from './logo.png' with { type: 'bytes' };
+//! ```
+use deno_core::{
+    anyhow::anyhow, error::AnyError, v8, CustomModuleEvaluationKind, FastString, ModuleSourceCode,
+};
+use std::{borrow::Cow, collections::HashMap};
+
+/// Module types accepted in an import's `type` attribute
+const SUPPORTED_TYPES: &[&str] = &["json", "text", "bytes"];
+
+/// Returns true for the module types loaded as raw contents
+pub(crate) fn is_raw_type(module_type: &str) -> bool {
+    matches!(module_type, "text" | "bytes")
+}
+
+/// Rejects import attributes other than a supported `type`, with a `TypeError`
+#[allow(clippy::implicit_hasher)]
+pub(crate) fn validate_import_attributes(
+    scope: &mut v8::HandleScope,
+    attributes: &HashMap<String, String>,
+) {
+    for (key, value) in attributes {
+        let message = if key != "type" {
+            format!("\"{key}\" attribute is not supported.")
+        } else if !SUPPORTED_TYPES.contains(&value.as_str()) {
+            format!("\"{value}\" is not a valid module type.")
+        } else {
+            continue;
+        };
+
+        let Some(message) = v8::String::new(scope, &message) else {
+            return;
+        };
+        let exception = v8::Exception::type_error(scope, message);
+        scope.throw_exception(exception);
+        return;
+    }
+}
+
+/// Builds the default export of a `text` or `bytes` module - a string, or a `Uint8Array`
+pub(crate) fn evaluate_raw_module(
+    scope: &mut v8::HandleScope,
+    module_type: Cow<'_, str>,
+    module_name: &FastString,
+    code: ModuleSourceCode,
+) -> Result<CustomModuleEvaluationKind, AnyError> {
+    let bytes = match &code {
+        ModuleSourceCode::String(code) => code.as_bytes(),
+        ModuleSourceCode::Bytes(code) => code.as_bytes(),
+    };
+
+    let value: v8::Local<v8::Value> = match module_type.as_ref() {
+        "text" => {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|e| anyhow!("{}: not valid UTF-8: {e}", module_name.as_str()))?;
+            v8::String::new(scope, text)
+                .ok_or_else(|| anyhow!("{}: too large for a string", module_name.as_str()))?
+                .into()
+        }
+
+        "bytes" => {
+            let len = bytes.len();
+            let store = v8::ArrayBuffer::new_backing_store_from_vec(bytes.to_vec()).make_shared();
+            let buffer = v8::ArrayBuffer::with_backing_store(scope, &store);
+            v8::Uint8Array::new(scope, buffer, 0, len)
+                .ok_or_else(|| anyhow!("{}: too large for a Uint8Array", module_name.as_str()))?
+                .into()
+        }
+
+        other => return Err(anyhow!("Unsupported module type: {other}")),
+    };
+
+    Ok(CustomModuleEvaluationKind::Synthetic(v8::Global::new(
+        scope, value,
+    )))
+}
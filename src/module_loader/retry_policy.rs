@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+/// Controls how remote imports are retried after a transient failure
+/// (connection errors, timeouts, and HTTP 408, 429, 500, 502, 503 or 504)
+///
+/// The delay before each retry doubles, starting from `backoff`
+/// Retries are reported to the runtime's logger, if there is one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportRetryPolicy {
+    /// Number of retries after the first attempt - 0 disables retrying
+    pub max_retries: u32,
+
+    /// Delay before the first retry
+    pub backoff: Duration,
+}
+
+impl Default for ImportRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl ImportRetryPolicy {
+    /// Create a new retry policy
+    #[must_use]
+    pub fn new(max_retries: u32, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+        }
+    }
+
+    /// Returns the delay before the given retry (starting at 0)
+    #[must_use]
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(retry))
+    }
+
+    /// Checks if a response with the given HTTP status should be retried
+    #[must_use]
+    pub fn is_retryable_status(status: u16) -> bool {
+        matches!(status, 408 | 429 | 500 | 502 | 503 | 504)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = ImportRetryPolicy::new(3, Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(100), policy.delay(0));
+        assert_eq!(Duration::from_millis(400), policy.delay(2));
+        assert!(policy.delay(u32::MAX) > policy.delay(2));
+
+        assert!(ImportRetryPolicy::is_retryable_status(503));
+        assert!(!ImportRetryPolicy::is_retryable_status(404));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// A trait that can be implemented to serve `file:` imports from memory, instead of the real filesystem
+/// Useful when a multi-file project is embedded in the binary, for example with `include_dir!`
+///
+/// Registered with [`crate::RuntimeBuilder::with_virtual_fs`] or [`crate::RuntimeOptions::virtual_fs`].
+/// The loader consults it before the disk - and files it serves may be imported even without
+/// the `fs_import` feature, so the sandbox stays closed to everything else
+///
+/// For a fixed set of files, use [`VirtualFiles`]
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ Runtime, RuntimeOptions, Module, Error, module_loader::VirtualFiles };
+/// use std::rc::Rc;
+///
+/// # fn main() -> Result<(), Error> {
+/// let files = VirtualFiles::new([
+///     ("app/utils.js", "export const twice = (x) => x * 2;"),
+/// ]);
+/// let mut runtime = Runtime::new(RuntimeOptions {
+///     virtual_fs: Some(Rc::new(files)),
+///     ..Default::default()
+/// })?;
+///
+/// let module = Module::new("app/main.js", "import { twice } from './utils.js'; export const x = twice(2);");
+/// let module = runtime.load_module(&module)?;
+/// let x: i32 = runtime.get_value(Some(&module), "x")?;
+/// assert_eq!(4, x);
+/// # Ok(())
+/// # }
+/// ```
+pub trait VirtualFileSystem {
+    /// Returns the source of the file at the given absolute path, or `None` if there is no such file
+    /// Files not found here are looked up on disk, subject to the usual import restrictions
+    fn read(&self, path: &Path) -> Option<String>;
+}
+
+/// A fixed set of in-memory files, mapping paths to sources
+/// See [`VirtualFileSystem`]
+///
+/// Paths are absolute, or relative to the current directory at the time the set is created.
+/// They are resolved once, up front, so each lookup is a single hash map access
+#[derive(Debug, Clone, Default)]
+pub struct VirtualFiles(HashMap<PathBuf, String>);
+
+impl VirtualFiles {
+    /// Create a set of files from `(path, source)` pairs
+    #[must_use]
+    pub fn new<P, S>(files: impl IntoIterator<Item = (P, S)>) -> Self
+    where
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        Self(
+            files
+                .into_iter()
+                .map(|(path, source)| (normalize(&cwd.join(path)), source.into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<HashMap<String, String>> for VirtualFiles {
+    fn from(files: HashMap<String, String>) -> Self {
+        Self::new(files)
+    }
+}
+
+impl VirtualFileSystem for VirtualFiles {
+    fn read(&self, path: &Path) -> Option<String> {
+        self.0.get(&normalize(path)).cloned()
+    }
+}
+
+/// Drops `.` components, so `./app/main.js` and `app/main.js` resolve to the same key
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
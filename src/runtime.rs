@@ -1,15 +1,28 @@
 use crate::{
-    inner_runtime::{InnerRuntime, RsAsyncFunction, RsFunction},
+    inner_runtime::{
+        InnerRuntime, RsAsyncFunction, RsFunction, RsIteratorFunction, RsStreamFunction,
+    },
     js_value::Function,
-    Error, Module, ModuleHandle,
+    Error, Module, ModuleGraphSize, ModuleHandle,
 };
 use deno_core::{serde_json, PollEventLoopOptions};
-use std::{rc::Rc, time::Duration};
+use std::{
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 use tokio_util::sync::CancellationToken;
 
 /// Represents the set of options accepted by the runtime constructor
 pub use crate::inner_runtime::RuntimeOptions;
 
+pub use crate::inner_runtime::EventLoopProgress;
+
+pub use crate::inner_runtime::{
+    CapturedOutput, ConsoleEvent, ConsoleLevel, DenoStub, DuplicateRegistration, HeapStats, OpInfo,
+    OpKind,
+};
+
 /// For functions returning nothing. Acts as a placeholder for the return type
 /// Should accept any type of value from javascript
 ///
@@ -17,6 +30,34 @@ pub use crate::inner_runtime::RuntimeOptions;
 /// Note: This used to be an alias for `serde_json::Value`, but was changed for performance reasons
 pub type Undefined = crate::js_value::Value;
 
+/// How a call made with [`Runtime::call_function_outcome`] ended
+#[derive(Debug, Clone)]
+pub enum CallOutcome<T> {
+    /// The function returned (or its promise resolved to) a value
+    Returned(T),
+
+    /// The function threw, or its promise rejected
+    Threw(Error),
+
+    /// The call did not finish within the runtime's timeout
+    TimedOut,
+}
+
+impl<T> CallOutcome<T> {
+    /// Sorts the result of a call into an outcome
+    /// Errors raised by the host rather than the script, such as [`Error::Runtime`], stay as `Err`
+    fn classify(result: Result<T, Error>) -> Result<Self, Error> {
+        match result {
+            Ok(value) => Ok(Self::Returned(value)),
+            Err(e @ (Error::JsError(_) | Error::OpError(_) | Error::StackOverflow)) => {
+                Ok(Self::Threw(e))
+            }
+            Err(Error::Timeout(_)) => Ok(Self::TimedOut),
+            Err(e) => Err(e),
+        }
+    }
+}
+
 /// A runtime instance that can be used to execute JavaScript code and interact with it
 /// Most runtime functions have 3 variants - blocking, async, and immediate
 /// For example:
@@ -32,6 +73,10 @@ pub struct Runtime {
     tokio: Rc<tokio::runtime::Runtime>,
     timeout: std::time::Duration,
     heap_exhausted_token: CancellationToken,
+    cancellation: CancellationHandle,
+    aborted: bool,
+    expires_at: Option<Instant>,
+    error_format: crate::error::ErrorFormat,
 }
 
 impl Runtime {
@@ -95,12 +140,31 @@ impl Runtime {
         tokio: Rc<tokio::runtime::Runtime>,
     ) -> Result<Self, Error> {
         let heap_exhausted_token = CancellationToken::new();
-        Ok(Self {
-            timeout: options.timeout,
-            inner: InnerRuntime::new(options, heap_exhausted_token.clone())?,
+        let expires_at = options
+            .max_lifetime
+            .map(|lifetime| Instant::now() + lifetime);
+        let timeout = options.timeout;
+        let error_format = options.error_format.clone();
+
+        let mut inner = InnerRuntime::new(options, heap_exhausted_token.clone())?;
+        let cancellation =
+            CancellationHandle::new(inner.deno_runtime().v8_isolate().thread_safe_handle());
+
+        let mut runtime = Self {
+            timeout,
+            expires_at,
+            inner,
             tokio,
             heap_exhausted_token,
-        })
+            cancellation,
+            aborted: false,
+            error_format,
+        };
+
+        // Evaluate any modules the manifest asks to have ready up front
+        runtime.run_async_task(|runtime| async move { runtime.inner.preload_manifest().await })?;
+
+        Ok(runtime)
     }
 
     /// Access the underlying deno runtime instance directly
@@ -108,6 +172,115 @@ impl Runtime {
         self.inner.deno_runtime()
     }
 
+    /// Run a closure with the raw v8 isolate backing this runtime
+    /// An escape hatch for isolate-level settings the rest of the API does not expose, such as
+    /// the microtasks policy, or callbacks for promise rejections and host-defined options
+    ///
+    /// # Safety
+    /// The runtime, and deno, rely on state they installed on the isolate. The closure must not:
+    /// - Replace callbacks or slots deno has set (such as the promise reject callback, the host
+    ///   import hooks, or the near-heap-limit callback) without restoring them before returning
+    /// - Enter, exit or dispose of the isolate, or leave a scope or context entered
+    /// - Switch the microtasks policy to `Auto` - deno drains the queue explicitly, and depends on doing so
+    /// - Hold on to the isolate, or to any handle created from it, past the end of the closure
+    ///
+    /// Breaking these leads to undefined behaviour, rather than an error
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    /// use rustyscript::deno_core::v8;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    ///
+    /// // Safety: the policy set is the one deno already uses
+    /// unsafe {
+    ///     runtime.with_isolate(|isolate| isolate.set_microtasks_policy(v8::MicrotasksPolicy::Explicit));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub unsafe fn with_isolate<R>(
+        &mut self,
+        f: impl FnOnce(&mut deno_core::v8::Isolate) -> R,
+    ) -> R {
+        f(self.inner.deno_runtime().v8_isolate())
+    }
+
+    /// Consumes the runtime, and captures its current state as a snapshot
+    /// Unlike [`crate::SnapshotBuilder`], this works on a runtime already set up through the usual API -
+    /// load a large module graph once, then seed any number of runtimes from the result through
+    /// [`RuntimeOptions::startup_snapshot`], with everything already loaded
+    ///
+    /// The runtime must have been created with [`RuntimeOptions::snapshottable`] set
+    /// Requires the `snapshot_builder` feature
+    ///
+    /// Caveats:
+    /// - Module handles, stored functions and other values taken from this runtime are invalid afterwards,
+    ///   and cannot be used with the seeded runtimes - reach loaded code through globals, or by importing it again
+    /// - Rust-side state, such as registered functions and values in the op state, is not captured,
+    ///   and must be registered again on each seeded runtime
+    /// - Seeded runtimes must use the same extensions and options, on the same system
+    ///
+    /// # Errors
+    /// Fails if the runtime was not created as snapshottable
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     snapshottable: true,
+    ///     ..Default::default()
+    /// })?;
+    /// runtime.load_module(&Module::new("setup.js", "globalThis.square = (x) => x * x;"))?;
+    ///
+    /// // Snapshots must outlive the runtimes using them
+    /// let snapshot: &'static [u8] = Box::leak(runtime.take_snapshot()?);
+    ///
+    /// for i in 0..3 {
+    ///     let mut runtime = Runtime::new(RuntimeOptions {
+    ///         startup_snapshot: Some(snapshot),
+    ///         ..Default::default()
+    ///     })?;
+    ///     let value: i64 = runtime.eval(&format!("square({i})"))?;
+    ///     assert_eq!(i * i, value);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "snapshot_builder")]
+    pub fn take_snapshot(self) -> Result<Box<[u8]>, Error> {
+        self.inner.into_snapshot()
+    }
+
+    /// Renders an error in the format set by [`RuntimeOptions::error_format`]
+    /// Lets a host pick the shape of its errors once - for a structured logger, say - instead of at every call site
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, Undefined, Error, error::ErrorFormat };
+    /// use std::rc::Rc;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     error_format: ErrorFormat::Custom(Rc::new(|e| format!("script failed: {}", e.format(&ErrorFormat::Message)))),
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// let e = runtime.eval::<Undefined>("throw new Error('oops')").unwrap_err();
+    /// assert_eq!("script failed: oops", runtime.format_error(&e));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn format_error(&self, error: &Error) -> String {
+        error.format(&self.error_format)
+    }
+
     /// Access the underlying tokio runtime used for blocking operations
     #[must_use]
     pub fn tokio_runtime(&self) -> std::rc::Rc<tokio::runtime::Runtime> {
@@ -120,12 +293,103 @@ impl Runtime {
         self.timeout
     }
 
+    /// Returns the logger provided in [`RuntimeOptions::logger`], if any
+    /// Clone it into registered functions to log through the same destination as the runtime
+    #[must_use]
+    pub fn logger(&self) -> Option<Rc<dyn crate::Logger>> {
+        self.inner.logger.clone()
+    }
+
     /// Returns the heap exhausted token for the runtime
     #[must_use]
     pub fn heap_exhausted_token(&self) -> CancellationToken {
         self.heap_exhausted_token.clone()
     }
 
+    /// Returns a handle that can cancel the runtime's in-flight call from another thread
+    ///
+    /// Unlike the runtime's timeout, cancellation is on demand - for example when a user clicks "stop".
+    /// The cancelled call fails with [`Error::Cancelled`], and the runtime remains usable
+    ///
+    /// Covers all blocking calls, and [`Runtime::call_function_async`]
+    /// See [`CancellationHandle`] for an example
+    #[must_use]
+    pub fn cancellation_handle(&self) -> CancellationHandle {
+        self.cancellation.clone()
+    }
+
+    /// Stop everything the runtime is doing, in one call
+    ///
+    /// - All open resources are closed, cancelling pending fetches, sockets and streams
+    /// - JS execution is terminated, so pending timers and promise callbacks will never run
+    /// - Outstanding promises can no longer resolve - resolving a [`crate::js_value::Promise`]
+    ///   afterwards fails with [`Error::Aborted`] instead of waiting forever
+    ///
+    /// This is intended for shutdown or emergency cancellation; the runtime cannot execute
+    /// any further JS afterwards, and every call, including the `_immediate` and `_async` variants,
+    /// will return [`Error::Aborted`]
+    ///
+    /// ```rust
+    /// use rustyscript::{Error, Runtime, RuntimeOptions};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions::default())?;
+    /// runtime.abort_all();
+    ///
+    /// let result = runtime.eval::<usize>("1 + 1");
+    /// assert!(matches!(result, Err(Error::Aborted)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn abort_all(&mut self) {
+        self.inner.abort_all();
+        self.aborted = true;
+    }
+
+    /// Returns true if [`Runtime::abort_all`] has been called on this runtime
+    #[must_use]
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Returns true if the runtime has outlived [`RuntimeOptions::max_lifetime`]
+    /// An expired runtime refuses every call that would run JS with [`Error::LifetimeExceeded`], and should be recreated
+    ///
+    /// ```rust
+    /// use rustyscript::{Error, Runtime, RuntimeOptions};
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     max_lifetime: Some(Duration::from_millis(50)),
+    ///     ..Default::default()
+    /// })?;
+    /// assert!(!runtime.is_expired());
+    ///
+    /// std::thread::sleep(Duration::from_millis(60));
+    /// assert!(runtime.is_expired());
+    /// assert!(matches!(runtime.eval::<usize>("1 + 1"), Err(Error::LifetimeExceeded)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+
+    /// Checks that the runtime can still accept calls, and clears the exit status of the previous one
+    pub(crate) fn ensure_usable(&mut self) -> Result<(), Error> {
+        if self.aborted {
+            Err(Error::Aborted)
+        } else if self.is_expired() {
+            Err(Error::LifetimeExceeded)
+        } else {
+            self.inner.reset_exit_code();
+            Ok(())
+        }
+    }
+
     /// Destroy the v8 runtime, releasing all resources
     /// Then the internal tokio runtime will be returned
     #[must_use]
@@ -133,6 +397,79 @@ impl Runtime {
         self.tokio
     }
 
+    /// Run the next queued timer, then drain the microtask queue
+    /// Requires [`RuntimeOptions::manual_ticks`], under which timers never fire on their own
+    ///
+    /// Timers run in order of their due time, then in the order they were scheduled - the delay
+    /// only decides ordering, so no real time passes. Intervals are re-queued after each run
+    ///
+    /// Returns the number of timers still queued
+    ///
+    /// # Errors
+    /// Fails if manual ticks are not enabled, or if the timer's callback throws
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     manual_ticks: true,
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// let module = Module::new("test.js", "
+    ///     globalThis.steps = [];
+    ///     setTimeout(() => steps.push('b'), 10);
+    ///     setTimeout(() => steps.push('a'), 5);
+    /// ");
+    /// runtime.load_module(&module)?;
+    ///
+    /// assert_eq!(1, runtime.tick()?);
+    /// assert_eq!(vec!["a"], runtime.eval::<Vec<String>>("steps")?);
+    ///
+    /// assert_eq!(0, runtime.tick()?);
+    /// assert_eq!(vec!["a", "b"], runtime.eval::<Vec<String>>("steps")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn tick(&mut self) -> Result<usize, Error> {
+        self.ensure_usable()?;
+        self.inner.tick()
+    }
+
+    /// Move the virtual clock forward, running every timer that comes due in order
+    /// The microtask queue is drained after each timer
+    /// Requires [`RuntimeOptions::virtual_clock`] or [`RuntimeOptions::manual_ticks`]
+    ///
+    /// Returns the number of timers that ran
+    ///
+    /// # Errors
+    /// Fails if the virtual clock is not enabled, or if a timer's callback throws
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, Error };
+    /// use std::time::{ Duration, UNIX_EPOCH };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     virtual_clock: Some(UNIX_EPOCH),
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// runtime.eval::<f64>("setTimeout(() => globalThis.fired = Date.now(), 1000)")?;
+    /// assert_eq!(0, runtime.advance_clock(Duration::from_millis(999))?);
+    /// assert_eq!(1, runtime.advance_clock(Duration::from_millis(1))?);
+    /// assert_eq!(1000, runtime.eval::<u64>("globalThis.fired")?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn advance_clock(&mut self, duration: Duration) -> Result<usize, Error> {
+        self.ensure_usable()?;
+        self.inner.advance_clock(duration)
+    }
+
     /// Advance the JS event loop by a single tick
     /// See [`Runtime::await_event_loop`] for fully running the event loop
     ///
@@ -163,9 +500,82 @@ impl Runtime {
         options: PollEventLoopOptions,
         timeout: Option<Duration>,
     ) -> Result<(), Error> {
+        self.ensure_usable()?;
         self.inner.await_event_loop(options, timeout).await
     }
 
+    /// Run the JS event loop to completion, reporting progress along the way
+    ///
+    /// `callback` is called at most once per `interval` while the loop has pending work,
+    /// so hosts can report progress, update a UI, or check for external cancellation.
+    /// Intervals below 1ms are raised to 1ms, so that reporting cannot keep the loop spinning while it waits
+    ///
+    /// If the callback returns false, the event loop stops early, leaving any remaining work pending
+    ///
+    /// # Arguments
+    /// * `options` - Options for the event loop polling, see [`deno_core::PollEventLoopOptions`]
+    /// * `interval` - Minimum time between calls to `callback`
+    /// * `callback` - Receives the progress so far, and returns false to stop the event loop
+    ///
+    /// # Errors
+    /// Can fail if a runtime error occurs during the event loop's execution
+    pub async fn await_event_loop_with_progress(
+        &mut self,
+        options: PollEventLoopOptions,
+        interval: Duration,
+        callback: impl FnMut(&EventLoopProgress) -> bool,
+    ) -> Result<(), Error> {
+        self.ensure_usable()?;
+        self.inner
+            .await_event_loop_with_progress(options, interval, callback)
+            .await
+    }
+
+    /// Run the JS event loop to completion, reporting progress along the way
+    ///
+    /// This is the blocking variant of [`Runtime::await_event_loop_with_progress`]
+    ///
+    /// # Arguments
+    /// * `options` - Options for the event loop polling, see [`deno_core::PollEventLoopOptions`]
+    /// * `interval` - Minimum time between calls to `callback`
+    /// * `callback` - Receives the progress so far, and returns false to stop the event loop
+    ///
+    /// # Errors
+    /// Can fail if a runtime error occurs during the event loop's execution
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.eval::<usize>("setTimeout(() => {}, 50)")?;
+    ///
+    /// runtime.block_on_event_loop_with_progress(
+    ///     Default::default(),
+    ///     Duration::from_millis(10),
+    ///     |progress| {
+    ///         println!("Still running after {:?}", progress.elapsed);
+    ///         true
+    ///     },
+    /// )?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_on_event_loop_with_progress(
+        &mut self,
+        options: PollEventLoopOptions,
+        interval: Duration,
+        callback: impl FnMut(&EventLoopProgress) -> bool,
+    ) -> Result<(), Error> {
+        self.run_async_task(|runtime| async move {
+            runtime
+                .await_event_loop_with_progress(options, interval, callback)
+                .await
+        })
+    }
+
     /// Run the JS event loop to completion, or until a timeout is reached
     /// Required when using the `_immediate` variants of functions
     ///
@@ -313,8 +723,12 @@ impl Runtime {
     /// Register a rust function to be callable from JS
     /// - The [`crate::sync_callback`] macro can be used to simplify this process
     ///
+    /// Registering a name already in use replaces the earlier function, unless
+    /// [`RuntimeOptions::duplicate_registration`] says otherwise
+    ///
     /// # Errors
     /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    /// Fails with [`Error::FunctionAlreadyRegistered`] if the name is in use and duplicates are rejected
     ///
     /// ```rust
     /// use rustyscript::{ Runtime, Module, serde_json::Value };
@@ -339,9 +753,67 @@ impl Runtime {
         self.inner.register_function(name, callback)
     }
 
+    /// Register a rust function to be callable from JS, with typed arguments and return value
+    ///
+    /// The arguments passed from JS are deserialized into `Args` - typically a tuple, with one
+    /// element per argument - and the result is serialized back to JS
+    /// Use `()` for a function taking no arguments
+    ///
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    ///
+    /// Calls from JS will throw a descriptive error if the wrong number or type of arguments is given
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_typed_function("repeat", |(text, count): (String, usize)| {
+    ///     Ok(text.repeat(count))
+    /// })?;
+    ///
+    /// let value: String = runtime.eval("rustyscript.functions.repeat('ab', 3)")?;
+    /// assert_eq!("ababab", value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_typed_function<Args, Ret, F>(
+        &mut self,
+        name: &str,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        Args: serde::de::DeserializeOwned,
+        Ret: serde::Serialize,
+        F: Fn(Args) -> Result<Ret, Error> + 'static,
+    {
+        let function_name = name.to_string();
+        self.inner.register_function(name, move |args| {
+            let array = serde_json::Value::Array(args.to_vec());
+            let parsed = match serde_json::from_value::<Args>(array) {
+                // `()` only deserializes from null
+                Err(_) if args.is_empty() => {
+                    serde_json::from_value::<Args>(serde_json::Value::Null)
+                }
+                result => result,
+            };
+            let args = parsed.map_err(|e| {
+                Error::Runtime(format!("Invalid arguments for `{function_name}`: {e}"))
+            })?;
+
+            let result = callback(args)?;
+            serde_json::to_value(result).map_err(|e| Error::Runtime(e.to_string()))
+        })
+    }
+
     /// Register a non-blocking rust function to be callable from JS
     /// - The [`crate::async_callback`] macro can be used to simplify this process
     ///
+    /// Calls always return a promise. If the function returns `Err`, the promise rejects with
+    /// an `Error` carrying the rust error's message - or, for an [`Error::OpError`], with a
+    /// `rustyscript.OpError` carrying its code and data - so `try`/`await`/`catch` works naturally
+    ///
     /// # Errors
     /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
     ///
@@ -367,769 +839,3026 @@ impl Runtime {
         self.inner.register_async_function(name, callback)
     }
 
-    /// Evaluate a piece of non-ECMAScript-module JavaScript code
-    /// The expression is evaluated in the global context, so changes persist
-    ///
-    /// Asynchronous code is supported, partially
-    /// - Top-level await is not supported
-    /// - The event loop will be run to completion after the expression is evaluated
-    /// - Eval must be run inside a tokio runtime for some async operations
-    ///
-    /// For proper async support, use one of:
-    /// - `call_function_async`
-    /// - `call_stored_function_async`
-    /// - `load_module_async`
-    /// - `load_modules_async`
-    ///
-    /// Or any of the `_immmediate` variants, paired with [`crate::js_value::Promise`]
-    ///
-    /// # Arguments
-    /// * `expr` - A string representing the JavaScript expression to evaluate
+    /// Replace the implementation of a rust function registered with [`Runtime::register_function`]
+    /// Later calls from JS use the new implementation, without recreating the runtime or reloading modules -
+    /// useful for host behaviour that changes while running, such as logging or feature flags
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the expression (`T`)
-    /// or an error (`Error`) if the expression cannot be evaluated or if the
-    /// result cannot be deserialized.
+    /// Unlike registering again, this ignores [`RuntimeOptions::duplicate_registration`]
     ///
     /// # Errors
-    /// Can fail if the expression cannot be evaluated, or if the result cannot be deserialized into the requested type
+    /// Fails with [`Error::ValueNotFound`] if no function is registered under `name`,
+    /// or if the state cannot be borrowed mutably
     ///
     /// # Example
     /// ```rust
-    /// use rustyscript::{ Runtime, Error };
+    /// use rustyscript::{ Runtime, serde_json::Value, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let value:
-    ///    usize = runtime.eval("2 + 2")?;
-    /// assert_eq!(4, value);
+    /// runtime.register_function("flag", |_| Ok(Value::Bool(false)))?;
+    /// let before: bool = runtime.eval("rustyscript.functions.flag()")?;
+    ///
+    /// runtime.replace_function("flag", |_| Ok(Value::Bool(true)))?;
+    /// let after: bool = runtime.eval("rustyscript.functions.flag()")?;
+    /// assert!(!before && after);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn eval<T>(&mut self, expr: &str) -> Result<T, Error>
+    pub fn replace_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
     where
-        T: serde::de::DeserializeOwned,
+        F: RsFunction,
     {
-        self.inner.eval(expr)
+        self.inner.replace_function(name, callback)
     }
 
-    /// Calls a stored javascript function and deserializes its return value.
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
-    ///
-    /// See [`Runtime::call_function`] for an example
-    ///
-    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
-    ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module providing global context for the function
-    /// * `function` - A The function object
-    /// * `args` - The arguments to pass to the function
-    ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if there are issues with calling the function,
-    /// or if the result cannot be deserialized.
+    /// Replace the implementation of a rust function registered with [`Runtime::register_async_function`]
+    /// Calls already in progress finish with the old implementation
+    /// See [`Runtime::replace_function`]
     ///
     /// # Errors
-    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
-    pub async fn call_stored_function_async<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        function: &Function,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
+    /// Fails with [`Error::ValueNotFound`] if no function is registered under `name`,
+    /// or if the state cannot be borrowed mutably
+    pub fn replace_async_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
     where
-        T: serde::de::DeserializeOwned,
+        F: RsAsyncFunction,
     {
-        let function = function.as_global(&mut self.deno_runtime().handle_scope());
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        self.inner.replace_async_function(name, callback)
     }
 
-    /// Calls a stored javascript function and deserializes its return value.
-    /// Blocks until:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Register a non-blocking rust function from a closure returning any future
+    /// Saves boxing the future by hand, so `|args| async move { ... }` can be passed directly
+    /// Behaves exactly like [`Runtime::register_async_function`] otherwise
     ///
-    /// See [`Runtime::call_function`] for an example
+    /// # Errors
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    /// Fails with [`Error::FunctionAlreadyRegistered`] if the name is in use and duplicates are rejected
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module providing global context for the function
-    /// * `function` - A The function object
-    /// * `args` - The arguments to pass to the function
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, serde_json::Value, Error };
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if there are issues with calling the function,
-    /// or if the result cannot be deserialized.
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_simple_async_function("lookup", |args| async move {
+    ///     let id = args.first().and_then(Value::as_i64).unwrap_or_default();
+    ///     Ok(Value::String(format!("user-{id}")))
+    /// })?;
     ///
-    /// # Errors
-    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
-    pub fn call_stored_function<T>(
+    /// let tokio_runtime = runtime.tokio_runtime();
+    /// let name: String = tokio_runtime.block_on(
+    ///     runtime.eval_async("await rustyscript.async_functions.lookup(7)")
+    /// )?;
+    /// assert_eq!("user-7", name);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_simple_async_function<F, Fut>(
         &mut self,
-        module_context: Option<&ModuleHandle>,
-        function: &Function,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
+        name: &str,
+        callback: F,
+    ) -> Result<(), Error>
     where
-        T: deno_core::serde::de::DeserializeOwned,
+        F: Fn(Vec<serde_json::Value>) -> Fut + 'static,
+        Fut: std::future::Future<Output = Result<serde_json::Value, Error>> + 'static,
     {
-        self.run_async_task(|runtime| async move {
-            runtime
-                .call_stored_function_async(module_context, function, args)
-                .await
-        })
+        type BoxedFuture =
+            std::pin::Pin<Box<dyn std::future::Future<Output = Result<serde_json::Value, Error>>>>;
+        self.inner
+            .register_async_function(name, move |args| -> BoxedFuture {
+                Box::pin(callback(args))
+            })
     }
 
-    /// Calls a stored javascript function and deserializes its return value.
-    /// Will not attempt to resolve promises, or run the event loop
-    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
-    /// The event loop should be run using [`Runtime::await_event_loop`]
+    /// Register a whole namespace of rust functions to be callable from JS in one call
+    /// See [`Runtime::register_function`]
     ///
-    /// See [`Runtime::call_function`] for an example
+    /// Nothing is registered if any of the names collides with an existing registration,
+    /// regardless of [`RuntimeOptions::duplicate_registration`]
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module providing global context for the function
-    /// * `function` - A The function object
-    /// * `args` - The arguments to pass to the function
+    /// # Errors
+    /// Can fail if a function with one of the names is already registered,
+    /// or if the state cannot be borrowed mutably
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if there are issues with calling the function,
-    /// or if the result cannot be deserialized.
+    /// ```rust
+    /// use rustyscript::{ Runtime, RsFunction, serde_json::Value, Error };
+    /// use std::collections::HashMap;
     ///
-    /// # Errors
-    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
-    pub fn call_stored_function_immediate<T>(
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    ///
+    /// let mut functions: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+    /// functions.insert("math.one".to_string(), Box::new(|_: &[Value]| Ok::<_, Error>(Value::from(1))));
+    /// functions.insert("math.two".to_string(), Box::new(|_: &[Value]| Ok::<_, Error>(Value::from(2))));
+    /// runtime.register_functions(functions)?;
+    ///
+    /// let sum: i64 = runtime.eval("rustyscript.functions['math.one']() + rustyscript.functions['math.two']()")?;
+    /// assert_eq!(sum, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_functions(
         &mut self,
-        module_context: Option<&ModuleHandle>,
-        function: &Function,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        let function = function.as_global(&mut self.deno_runtime().handle_scope());
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        self.inner.decode_value(result)
+        functions: HashMap<String, Box<dyn RsFunction>>,
+    ) -> Result<(), Error> {
+        self.inner.register_functions(functions)
     }
 
-    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
-    ///
-    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
-    ///
-    /// See [`Runtime::call_function`] for an example
-    ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the javascript function to call.
-    /// * `args` - The arguments to pass to the function
+    /// Register a whole namespace of non-blocking rust functions to be callable from JS in one call
+    /// See [`Runtime::register_async_function`]
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
+    /// Nothing is registered if any of the names collides with an existing registration,
+    /// regardless of [`RuntimeOptions::duplicate_registration`]
     ///
     /// # Errors
-    /// Fails if the function cannot be found, if there are issues with calling the function,
-    /// Or if the result cannot be deserialized into the requested type
-    pub async fn call_function_async<T>(
+    /// Can fail if a function with one of the names is already registered,
+    /// or if the state cannot be borrowed mutably
+    pub fn register_async_functions(
         &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        functions: HashMap<String, Box<dyn RsAsyncFunction>>,
+    ) -> Result<(), Error> {
+        self.inner.register_async_functions(functions)
     }
 
-    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
-    /// Blocks until:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Register a set of rust functions as host imports for WASM modules run from JS
+    /// They are added under `namespace` to the import object of every module instantiated through
+    /// `WebAssembly.instantiate` or `new WebAssembly.Instance`, taking precedence over imports of the same
+    /// name passed in by the script, and are also callable as `rustyscript.functions['namespace.name']`
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the javascript function to call.
-    /// * `args` - The arguments to pass to the function
+    /// See [`crate::RuntimeOptions::max_wasm_memory`] to bound the memory those modules can use
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
+    /// Nothing is registered if any of the names is already registered in the namespace,
+    /// regardless of [`RuntimeOptions::duplicate_registration`]
     ///
     /// # Errors
-    /// Fails if the function cannot be found, if there are issues with calling the function,
-    /// Or if the result cannot be deserialized into the requested type
-    ///
-    /// # Example
+    /// Can fail if a function with one of the names is already registered in the namespace,
+    /// or if the state cannot be borrowed mutably
     ///
     /// ```rust
-    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    /// use rustyscript::{ Runtime, RsFunction, serde_json::Value, Error };
+    /// use std::collections::HashMap;
     ///
-    /// # fn main() -> Result<(), Error> {
+    /// # fn main() -> Result<(), rustyscript::Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
-    /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.call_function(Some(&module), "f", json_args!())?;
+    ///
+    /// let mut imports: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+    /// imports.insert("log".to_string(), Box::new(|args: &[Value]| {
+    ///     println!("wasm says {}", args[0]);
+    ///     Ok::<_, Error>(Value::Null)
+    /// }));
+    /// runtime.register_wasm_imports("env", imports)?;
+    ///
+    /// // In JS: new WebAssembly.Instance(module, {}) - `env.log` is provided by the host
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_function<T>(
+    pub fn register_wasm_imports(
         &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        self.run_async_task(|runtime| async move {
-            runtime
-                .call_function_async(module_context, name, args)
-                .await
-        })
+        namespace: &str,
+        imports: HashMap<String, Box<dyn RsFunction>>,
+    ) -> Result<(), Error> {
+        self.inner.register_wasm_imports(namespace, imports)
     }
 
-    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
-    /// Will not attempt to resolve promises, or run the event loop
-    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
-    /// The event loop should be run using [`Runtime::await_event_loop`]
+    /// Set the permissions applied to `fetch` requests made by the given module
+    /// Other modules keep the permissions given in [`crate::WebOptions`]
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the javascript function to call.
-    /// * `args` - The arguments to pass to the function
+    /// The requesting module is the innermost module on the stack when `fetch` is called
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the function call (`T`)
-    /// or an error (`Error`) if the function cannot be found, if there are issues with
-    /// calling the function, or if the result cannot be deserialized.
+    /// The module's permissions cover the whole request, including any redirects followed. To keep
+    /// them apart, while one module's fetches are waiting for a response, fetches from other modules wait too
     ///
-    /// # Errors
-    /// Fails if the function cannot be found, if there are issues with calling the function,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Once any module has its own permissions, checks that cannot be traced back to a module are denied.
+    /// That includes APIs other than `fetch`, and `fetch` passed directly as a callback
+    /// (`setTimeout(fetch, 0, url)`, `promise.then(fetch)`)
     ///
-    /// # Example
+    /// Set the permissions before loading the module, to cover requests made while it is evaluated
+    ///
+    /// # Errors
+    /// Can fail if the module's filename cannot be resolved, or if the state cannot be borrowed
     ///
     /// ```rust
-    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    /// use rustyscript::{ AllowlistWebPermissions, Module, Runtime };
+    /// use std::rc::Rc;
     ///
-    /// # fn main() -> Result<(), Error> {
+    /// # fn main() -> Result<(), rustyscript::Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
-    /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
+    ///
+    /// // The plugin may only reach its own API, while other modules are unrestricted
+    /// let permissions = AllowlistWebPermissions::new();
+    /// permissions.allow_url("https://example.com/api");
+    ///
+    /// let plugin = Module::new("plugin.js", "export const name = 'plugin';");
+    /// runtime.set_module_web_permissions(&plugin, Rc::new(permissions))?;
+    /// runtime.load_module(&plugin)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_function_immediate<T>(
+    #[cfg(feature = "web")]
+    pub fn set_module_web_permissions(
         &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        let function = self.inner.get_function_by_name(module_context, name)?;
-        let result = self
-            .inner
-            .call_function_by_ref(module_context, &function, args)?;
-        self.inner.decode_value(result)
+        module: &Module,
+        permissions: Rc<dyn crate::WebPermissions>,
+    ) -> Result<(), Error> {
+        self.inner.set_module_web_permissions(module, permissions)
     }
 
-    /// Get a value from a runtime instance
-    /// Blocks until:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
-    ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// Wrap a rust closure as a JS function, to be passed as an argument to a JS call
+    /// Unlike [`Runtime::register_function`], the closure has no global name and may be `FnMut`
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
-    /// Or if the result cannot be deserialized into the requested type
+    /// The closure is freed once the returned handle is dropped and JS no longer references the
+    /// function, or earlier with [`Runtime::release_callback`]. See [`crate::js_value::Callback`] for details
     ///
     /// # Errors
-    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
+    /// Can fail if the state cannot be borrowed, or the JS function cannot be created
     ///
     /// # Example
-    ///
     /// ```rust
-    /// use rustyscript::{ Runtime, Module, Error };
+    /// use rustyscript::{ json_args, Runtime, Module, Undefined, Error, serde_json::Value };
+    /// use std::{ cell::RefCell, rc::Rc };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
+    /// let module = Module::new("test.js", "export const each = (values, f) => values.forEach(f);");
     /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.get_value(Some(&module), "my_value")?;
+    ///
+    /// let seen = Rc::new(RefCell::new(Vec::new()));
+    /// let sink = seen.clone();
+    /// let callback = runtime.create_callback(move |args| {
+    ///     sink.borrow_mut().push(args[0].clone());
+    ///     Ok(Value::Null)
+    /// })?;
+    ///
+    /// runtime.call_function::<Undefined>(Some(&module), "each", json_args!(vec![1, 2], &callback))?;
+    /// runtime.release_callback(callback)?;
+    /// assert_eq!(*seen.borrow(), vec![Value::from(1), Value::from(2)]);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_value<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-    ) -> Result<T, Error>
+    pub fn create_callback<F>(&mut self, callback: F) -> Result<crate::js_value::Callback, Error>
     where
-        T: serde::de::DeserializeOwned,
+        F: FnMut(&[serde_json::Value]) -> Result<serde_json::Value, Error> + 'static,
     {
-        self.run_async_task(
-            |runtime| async move { runtime.get_value_async(module_context, name).await },
-        )
+        self.ensure_usable()?;
+        self.inner.create_callback(callback)
     }
 
-    /// Get a value from a runtime instance
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Drop the closure behind a callback created with [`Runtime::create_callback`]
+    /// JS code still holding the function will get an error if it calls it
     ///
-    /// See [`Runtime::get_value`] for an example
+    /// # Errors
+    /// Can fail if the state cannot be borrowed
+    pub fn release_callback(&mut self, callback: crate::js_value::Callback) -> Result<(), Error> {
+        self.inner.release_callback(&callback)
+    }
+
+    /// Register a rust function returning a stream of values, to be consumed from JS
+    /// as a standard `ReadableStream` through `rustyscript.stream_functions`
     ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
-    ///
-    /// # Returns
-    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Values are pulled from the stream as JS reads them, and cancelling the `ReadableStream`
+    /// drops the rust stream. Requires the `web` feature, which provides `ReadableStream`
     ///
     /// # Errors
-    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
-    pub async fn get_value_async<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-    ) -> Result<T, Error>
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, serde_json::Value, Error };
+    /// use rustyscript::deno_core::futures::stream;
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_stream_function("lines", |_args| {
+    ///     let lines = ["first", "second"].map(|l| Ok(Value::from(l)));
+    ///     Ok(Box::pin(stream::iter(lines)))
+    /// })?;
+    ///
+    /// // In JS: for await (const line of rustyscript.stream_functions.lines()) { ... }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_stream_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
     where
-        T: serde::de::DeserializeOwned,
+        F: RsStreamFunction,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        let result = self.inner.resolve_with_event_loop(result).await?;
-        self.inner.decode_value(result)
+        self.inner.register_stream_function(name, callback)
     }
 
-    /// Get a value from a runtime instance
-    /// Will not attempt to resolve promises, or run the event loop
-    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
-    /// The event loop should be run using [`Runtime::await_event_loop`]
-    ///
-    /// # Arguments
-    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
-    /// * `name` - A string representing the name of the value to find
+    /// Register a rust function returning an iterator, to be consumed from JS
+    /// as a generator through `rustyscript.iterator_functions`
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Values are pulled from the iterator one at a time as JS iterates, so the host never has to
+    /// materialize the whole sequence. Leaving the loop early drops the rust iterator
     ///
     /// # Errors
-    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
-    ///
-    /// # Example
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
     ///
     /// ```rust
-    /// use rustyscript::{ Runtime, Module, Error };
+    /// use rustyscript::{ Runtime, serde_json::Value };
     ///
-    /// # fn main() -> Result<(), Error> {
+    /// # fn main() -> Result<(), rustyscript::Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
-    /// let module = runtime.load_module(&module)?;
-    /// let value: usize = runtime.get_value_immediate(Some(&module), "my_value")?;
+    /// runtime.register_iterator_function("squares", |_args| {
+    ///     Ok(Box::new((1..).map(|i: u64| Ok(Value::from(i * i)))))
+    /// })?;
+    ///
+    /// let first: Vec<u64> = runtime.eval("
+    ///     const values = [];
+    ///     for (const v of rustyscript.iterator_functions.squares()) {
+    ///         if (values.length === 3) break;
+    ///         values.push(v);
+    ///     }
+    ///     values
+    /// ")?;
+    /// assert_eq!(first, vec![1, 4, 9]);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_value_immediate<T>(
-        &mut self,
-        module_context: Option<&ModuleHandle>,
-        name: &str,
-    ) -> Result<T, Error>
+    pub fn register_iterator_function<F>(&mut self, name: &str, callback: F) -> Result<(), Error>
     where
-        T: serde::de::DeserializeOwned,
+        F: RsIteratorFunction,
     {
-        let result = self.inner.get_value_ref(module_context, name)?;
-        self.inner.decode_value(result)
+        self.inner.register_iterator_function(name, callback)
     }
 
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions
+    /// Register a custom JS representation for a rust type, such as a UUID or a timestamp
     ///
-    /// Blocks until the module has been executed AND the event loop has fully resolved
-    /// See [`Runtime::load_module_async`] for a non-blocking variant, or use with async
-    /// background tasks
+    /// Once registered, values of the type wrapped in a [`crate::JsRepr`] are converted automatically
+    /// when sent to JS (function arguments, including those built with `json_args!`), and when read back
+    /// from JS as a type containing a `JsRepr` of it, so they do not need to be converted by hand each time
     ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// Conversions are chosen by rust type - values that are not wrapped are never converted,
+    /// and nothing is inspected unless a `JsRepr` with a registered conversion is involved
     ///
-    /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading or executing the module
+    /// Registering a conversion for the same type again replaces it
+    ///
+    /// # Arguments
+    /// * `conversion` - JS functions describing the conversion, see [`crate::JsTypeConversion`]
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, or execution fails
+    /// Will return an error if the conversion functions are not valid JS
     ///
     /// # Example
+    /// See [`crate::JsRepr`]
+    pub fn register_conversion<T: 'static>(
+        &mut self,
+        conversion: crate::JsTypeConversion,
+    ) -> Result<(), Error> {
+        self.ensure_usable()?;
+        self.inner.register_conversion::<T>(&conversion)
+    }
+
+    /// Checks a module's calls to registered rust functions before it is run
+    /// Reports calls to functions that are not registered with this runtime, and calls
+    /// passing a different number of arguments than expected
+    ///
+    /// Calls are found statically, see [`Module::function_calls`]
+    ///
+    /// # Arguments
+    /// * `module` - The module to check
+    /// * `arities` - The number of arguments expected by some of the functions, by name
+    ///   Functions not listed here are not checked for arity
+    ///
+    /// # Errors
+    /// Can fail if the runtime's state is in use
     ///
+    /// # Example
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Module, BindingIssue, Error, serde_json::Value };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// runtime.load_module(&module);
+    /// runtime.register_function("add", |_| Ok(Value::Null))?;
+    ///
+    /// let module = Module::new("test.js", "
+    ///     rustyscript.functions.add(1);
+    ///     rustyscript.functions.sub(1, 2);
+    /// ");
+    /// let issues = runtime.check_function_calls(&module, &[("add", 2)])?;
+    /// assert!(matches!(&issues[0], BindingIssue::ArityMismatch { expected: 2, .. }));
+    /// assert!(matches!(&issues[1], BindingIssue::Unregistered(call) if call.name == "sub"));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn load_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
-        self.run_async_task(|runtime| async move {
-            let handle = runtime.load_module_async(module).await;
-            runtime
-                .await_event_loop(PollEventLoopOptions::default(), None)
-                .await?;
-            handle
-        })
+    pub fn check_function_calls(
+        &mut self,
+        module: &Module,
+        arities: &[(&str, usize)],
+    ) -> Result<Vec<crate::BindingIssue>, Error> {
+        let mut issues = Vec::new();
+        for call in module.function_calls() {
+            if !self.inner.is_function_registered(call.kind, &call.name)? {
+                issues.push(crate::BindingIssue::Unregistered(call));
+                continue;
+            }
+
+            let expected = arities
+                .iter()
+                .find(|(name, _)| *name == call.name)
+                .map(|(_, arity)| *arity);
+            if let (Some(expected), Some(args)) = (expected, call.args) {
+                if expected != args {
+                    issues.push(crate::BindingIssue::ArityMismatch { call, expected });
+                }
+            }
+        }
+
+        Ok(issues)
     }
 
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions
+    /// Register a named, read-only blob of bytes that scripts can access as an `ArrayBuffer`
+    /// using `rustyscript.blob(name)`
     ///
-    /// Returns a future that resolves to the handle for the loaded module
-    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
-    /// to resolve background tasks and async listeners
-    ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// The data is held once by the runtime and shared by every call, and each access
+    /// returns a fresh copy of it - writing to that buffer changes neither the stored
+    /// data nor what later readers see
     ///
-    /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading or executing the module
+    /// Registering a blob with an existing name replaces it
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, or execution fails
+    /// Since this function borrows the state, it can fail if the state cannot be borrowed mutably
     ///
-    /// See [`Runtime::load_module`] for an example
-    pub async fn load_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(None, vec![module]).await
+    /// ```rust
+    /// use rustyscript::Runtime;
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.register_blob("table", vec![1, 2, 3])?;
+    ///
+    /// let value: u8 = runtime.eval("new Uint8Array(rustyscript.blob('table'))[2]")?;
+    /// assert_eq!(value, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn register_blob(&mut self, name: &str, data: impl Into<Vec<u8>>) -> Result<(), Error> {
+        self.ensure_usable()?;
+        self.inner.register_blob(name, data.into())
     }
 
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions.
+    /// Evaluate a piece of non-ECMAScript-module JavaScript code
+    /// The expression is evaluated in the global context, so changes persist
     ///
-    /// Blocks until all modules have been executed AND the event loop has fully resolved
-    /// See [`Runtime::load_module_async`] for a non-blocking variant, or use with async
-    /// background tasks
+    /// Asynchronous code is supported, partially
+    /// - Top-level await is not supported
+    /// - The event loop will be run to completion after the expression is evaluated
+    /// - Eval must be run inside a tokio runtime for some async operations
     ///
-    /// This will load 'module' as the main module, and the others as side-modules.
-    /// Only one main module can be loaded per runtime
+    /// For proper async support, use one of:
+    /// - `eval_async`
+    /// - `call_function_async`
+    /// - `call_stored_function_async`
+    /// - `load_module_async`
+    /// - `load_modules_async`
+    ///
+    /// Or any of the `_immmediate` variants, paired with [`crate::js_value::Promise`]
     ///
     /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `expr` - A string representing the JavaScript expression to evaluate
     ///
     /// # Returns
-    /// A `Result` containing a handle for the loaded module
-    /// or an error (`Error`) if there are issues with loading or executing the module
+    /// A `Result` containing the deserialized result of the expression (`T`)
+    /// or an error (`Error`) if the expression cannot be evaluated or if the
+    /// result cannot be deserialized.
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, or execution fails
+    /// Can fail if the expression cannot be evaluated, or if the result cannot be deserialized into the requested type
     ///
     /// # Example
-    ///
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// runtime.load_modules(&module, vec![]);
+    /// let value:
+    ///    usize = runtime.eval("2 + 2")?;
+    /// assert_eq!(4, value);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn load_modules(
-        &mut self,
-        module: &Module,
-        side_modules: Vec<&Module>,
-    ) -> Result<ModuleHandle, Error> {
-        self.run_async_task(move |runtime| async move {
-            let handle = runtime.load_modules_async(module, side_modules).await;
-            runtime
-                .await_event_loop(PollEventLoopOptions::default(), None)
-                .await?;
-            handle
-        })
+    pub fn eval<T>(&mut self, expr: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        self.inner.eval(expr)
     }
 
-    /// Executes the given module, and returns a handle allowing you to extract values
-    /// And call functions.
+    /// Evaluate a JavaScript expression that may use `await`, and resolve its value
+    /// Returns a future that resolves when the expression, and any promise it results in, have settled
     ///
-    /// Returns a future that resolves to the handle for the loaded module
-    /// Makes no attempt to resolve the event loop - call [`Runtime::await_event_loop`] to
-    /// resolve background tasks and async listeners
-    ///
-    /// This will load 'module' as the main module, and the others as side-modules.
-    /// Only one main module can be loaded per runtime
-    ///
-    /// See [`Runtime::load_modules`] for an example
+    /// The expression is run as a script, like [`Runtime::eval`], inside an async function so that it can
+    /// use `await` - then the event loop is run until the value is ready. Changes to the global scope persist
     ///
     /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `expr` - A string representing the JavaScript expression to evaluate
     ///
     /// # Returns
-    /// A `Result` containing a handle for the loaded main module, or the last side-module
-    /// or an error (`Error`) if there are issues with loading or executing the modules
+    /// A `Result` containing the deserialized result of the expression (`T`)
     ///
     /// # Errors
-    /// Can fail if the modules cannot be loaded, or execution fails
-    pub async fn load_modules_async(
-        &mut self,
-        module: &Module,
-        side_modules: Vec<&Module>,
-    ) -> Result<ModuleHandle, Error> {
-        self.inner.load_modules(Some(module), side_modules).await
+    /// Can fail if the expression is invalid, throws or rejects, or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let tokio_runtime = runtime.tokio_runtime();
+    /// let value: i64 = tokio_runtime.block_on(async {
+    ///     runtime.eval_async("await new Promise(resolve => setTimeout(() => resolve(42), 10))").await
+    /// })?;
+    /// assert_eq!(42, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn eval_async<T>(&mut self, expr: &str) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let cancellation = self.cancellation.clone();
+        cancellation
+            .run(async move {
+                // The newline ends any trailing line comment
+                let script = format!("(async () => ({expr}\n))()");
+                let result = self.inner.deno_runtime().execute_script("", script)?;
+                let result = self.inner.resolve_with_event_loop(result).await?;
+                self.inner.decode_value(result)
+            })
+            .await
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
-    /// Blocks until:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
+    /// Evaluate an expression resulting in a function, and return a handle to it
+    /// The handle can be stored and called repeatedly, making it useful for user-supplied lambdas
+    /// as configuration. Equivalent to `eval::<js_value::Function>`
     ///
     /// # Arguments
-    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `expr` - A string representing a JavaScript expression that results in a function
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// A `Result` containing a handle to the resulting function, valid for the lifetime of this runtime
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Can fail if the expression cannot be evaluated, or if the result is not a function
     ///
     /// # Example
-    ///
     /// ```rust
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ json_args, Runtime, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// let module = runtime.load_module(&module)?;
+    /// let double = runtime.eval_function("(x) => x * 2")?;
     ///
-    /// // Run the entrypoint and handle the result
-    /// let value: String = runtime.call_entrypoint(&module, json_args!())?;
+    /// let value: i64 = double.call(&mut runtime, None, json_args!(21))?;
+    /// assert_eq!(42, value);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_entrypoint<T>(
-        &mut self,
-        module_context: &ModuleHandle,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        self.run_async_task(|runtime| async move {
-            runtime.call_entrypoint_async(module_context, args).await
-        })
+    pub fn eval_function(&mut self, expr: &str) -> Result<Function, Error> {
+        self.eval(expr)
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
-    /// Returns a future that resolves when:
-    /// - The event loop is resolved, and
-    /// - If the value is a promise, the promise is resolved
-    ///
-    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    /// Resolves a function exported by a module once, so that it can be called repeatedly
+    /// with [`Runtime::call_stored_function`] and its variants, without looking up its name each time
     ///
-    /// See [`Runtime::call_entrypoint`] for an example
+    /// The function is tied to the module's current load - if a module with the same filename
+    /// is loaded again, calling the stored function fails, and it must be resolved again
     ///
     /// # Arguments
     /// * `module_context` - A handle returned by loading a module into the runtime
-    ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// * `name` - The name of the exported function
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
-    /// Or if the result cannot be deserialized into the requested type
-    pub async fn call_entrypoint_async<T>(
+    /// Can fail if the export cannot be found, or is not a function
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "
+    ///     let count = 0;
+    ///     export const tick = (n) => count += n;
+    /// ");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let tick = runtime.get_function(&module, "tick")?;
+    /// for _ in 0..1000 {
+    ///     runtime.call_stored_function_immediate::<u32>(Some(&module), &tick, json_args!(1))?;
+    /// }
+    ///
+    /// let count: u32 = runtime.call_stored_function(Some(&module), &tick, json_args!(0))?;
+    /// assert_eq!(1000, count);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_function(
         &mut self,
         module_context: &ModuleHandle,
+        name: &str,
+    ) -> Result<Function, Error> {
+        self.ensure_usable()?;
+        self.inner.get_function(module_context, name)
+    }
+
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// See [`Runtime::call_function`] for an example
+    ///
+    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if there are issues with calling the function,
+    /// or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
+    pub async fn call_stored_function_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        self.inner.check_function_origin(function)?;
+        let function = function.as_global(&mut self.deno_runtime().handle_scope());
+        let result = self
+            .inner
+            .call_function_by_ref(module_context, &function, args)?;
+        let result = self.inner.resolve_with_event_loop(result).await?;
+        self.inner.decode_value(result)
+    }
+
+    /// Calls a stored javascript function and deserializes its return value.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// See [`Runtime::call_function`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if there are issues with calling the function,
+    /// or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
+    pub fn call_stored_function<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
         args: &impl serde::ser::Serialize,
     ) -> Result<T, Error>
     where
         T: deno_core::serde::de::DeserializeOwned,
     {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self
-                .inner
-                .call_function_by_ref(Some(module_context), entrypoint, args)?;
-            let result = self.inner.resolve_with_event_loop(result).await?;
-            self.inner.decode_value(result)
-        } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
-        }
+        self.run_async_task(|runtime| async move {
+            runtime
+                .call_stored_function_async(module_context, function, args)
+                .await
+        })
     }
 
-    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Calls a stored javascript function and deserializes its return value.
     /// Will not attempt to resolve promises, or run the event loop
     /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
     /// The event loop should be run using [`Runtime::await_event_loop`]
     ///
+    /// See [`Runtime::call_function`] for an example
+    ///
     /// # Arguments
-    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `module_context` - Optional handle to a module providing global context for the function
+    /// * `function` - A The function object
+    /// * `args` - The arguments to pass to the function
     ///
     /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if there are issues with calling the function,
+    /// or if the result cannot be deserialized.
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Can fail if there are issues with calling the function, or if the result cannot be deserialized into the requested type
+    pub fn call_stored_function_immediate<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        function: &Function,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        self.inner.check_function_origin(function)?;
+        let function = function.as_global(&mut self.deno_runtime().handle_scope());
+        let result = self
+            .inner
+            .call_function_by_ref(module_context, &function, args)?;
+        self.inner.decode_value(result)
+    }
+
+    /// Lists the operations that are keeping the event loop alive - pending async ops (such as fetches),
+    /// open resources, and timers
+    ///
+    /// Useful for debugging a script that never finishes. Note that a promise which is never
+    /// resolved, and is not waiting on an op, will not keep the event loop alive and is not listed
     ///
     /// # Example
     ///
     /// ```rust
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, OpKind, Undefined, Error };
     ///
     /// # fn main() -> Result<(), Error> {
     /// let mut runtime = Runtime::new(Default::default())?;
-    /// let module = Module::new("test.js", "export default () => 'test'");
-    /// let module = runtime.load_module(&module)?;
+    /// runtime.eval::<Undefined>("setTimeout(() => {}, 1000)")?;
     ///
-    /// // Run the entrypoint and handle the result
-    /// let value: String = runtime.call_entrypoint_immediate(&module, json_args!())?;
+    /// let pending = runtime.pending_ops();
+    /// assert!(pending.iter().any(|op| op.kind == OpKind::Timer));
     /// # Ok(())
     /// # }
     /// ```
-    pub fn call_entrypoint_immediate<T>(
-        &mut self,
-        module_context: &ModuleHandle,
-        args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        if let Some(entrypoint) = module_context.entrypoint() {
-            let result = self.run_async_task(|runtime| async move {
-                runtime
-                    .inner
-                    .call_function_by_ref(Some(module_context), entrypoint, args)
-            })?;
-            self.inner.decode_value(result)
-        } else {
-            Err(Error::MissingEntrypoint(module_context.module().clone()))
-        }
+    #[must_use]
+    pub fn pending_ops(&mut self) -> Vec<OpInfo> {
+        self.inner.pending_ops()
     }
 
-    /// Loads a module into a new runtime, executes the entry function and returns the
-    /// result of the module's execution, deserialized into the specified Rust type (`T`).
-    ///
-    /// # Arguments
-    /// * `module` - A `Module` object containing the module's filename and contents.
-    /// * `side_modules` - A set of additional modules to be loaded into memory for use
-    /// * `runtime_options` - Options for the creation of the runtime
-    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
+    /// Returns the number of operations keeping the event loop alive - see [`Runtime::pending_ops`]
+    /// If this is not 0, awaiting the event loop (or an async function call) may not return
+    /// until that work completes, or forever in the case of a `setInterval`
+    #[must_use]
+    pub fn pending_ops_count(&mut self) -> usize {
+        self.inner.pending_ops_count()
+    }
+
+    /// Cancels every pending `setTimeout` and `setInterval` timer, so that none of them fire
+    /// Useful before reusing a runtime, or to stop background work a script left behind
     ///
-    /// # Returns
-    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
-    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
-    /// or the result cannot be deserialized.
+    /// Returns the number of timers cancelled
     ///
     /// # Errors
-    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
-    /// Or if the result cannot be deserialized into the requested type
+    /// Fails if the timers could not be cancelled
     ///
     /// # Example
     ///
     /// ```rust
-    /// // Create a module with filename and contents
-    /// use rustyscript::{json_args, Runtime, Module, Error};
+    /// use rustyscript::{ Runtime, Undefined, Error };
     ///
     /// # fn main() -> Result<(), Error> {
-    /// let module = Module::new("test.js", "export default () => 2");
-    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.eval::<Undefined>("setInterval(() => {}, 1000)")?;
+    /// assert_eq!(1, runtime.pending_ops_count());
+    ///
+    /// runtime.clear_timers()?;
+    /// assert_eq!(0, runtime.pending_ops_count());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn execute_module<T>(
-        module: &Module,
-        side_modules: Vec<&Module>,
-        runtime_options: RuntimeOptions,
-        entrypoint_args: &impl serde::ser::Serialize,
-    ) -> Result<T, Error>
-    where
-        T: deno_core::serde::de::DeserializeOwned,
-    {
-        let mut runtime = Runtime::new(runtime_options)?;
-        let module = runtime.load_modules(module, side_modules)?;
-        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
-        Ok(value)
+    pub fn clear_timers(&mut self) -> Result<usize, Error> {
+        self.inner.clear_timers()
     }
 
-    /// Used for blocking functions
-    pub(crate) fn run_async_task<'a, T, F, U>(&'a mut self, f: F) -> Result<T, Error>
-    where
-        U: std::future::Future<Output = Result<T, Error>>,
-        F: FnOnce(&'a mut Runtime) -> U,
-    {
-        let timeout = self.timeout();
-        let rt = self.tokio_runtime();
-        let heap_exhausted_token = self.heap_exhausted_token();
-        rt.block_on(async move {
-            tokio::select! {
-                result = tokio::time::timeout(timeout, f(self)) => result?,
-                () = heap_exhausted_token.cancelled() => Err(Error::HeapExhausted),
-            }
-        })
+    /// Returns the console output captured since the last call, and clears the buffer in the same step
+    /// Requires [`RuntimeOptions::capture_console`] - otherwise nothing is captured, and this is always empty
+    ///
+    /// Taking the output after each request lets a reused runtime attribute logs to the request that wrote them
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, RuntimeOptions, ConsoleLevel, Undefined, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(RuntimeOptions {
+    ///     capture_console: true,
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// runtime.eval::<Undefined>("console.log('first request')")?;
+    /// let output = runtime.take_console_output();
+    /// assert_eq!("first request", output[0].message);
+    /// assert_eq!(ConsoleLevel::Info, output[0].level);
+    ///
+    /// runtime.eval::<Undefined>("console.error('second request')")?;
+    /// let output = runtime.take_console_output();
+    /// assert_eq!(1, output.len());
+    /// assert_eq!(ConsoleLevel::Error, output[0].level);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn take_console_output(&mut self) -> Vec<ConsoleEvent> {
+        self.inner.take_console_output()
     }
-}
 
-#[cfg(test)]
-mod test_runtime {
-    use crate::json_args;
-    use std::time::Duration;
+    /// Runs `f`, collecting everything it writes to the console into size-capped buffers
+    /// Output to stdout (`log`, `info`, `debug`) and stderr (`warn`, `error`) each keep at most `limit` bytes;
+    /// anything past that is dropped and the stream is marked as truncated, so a script
+    /// printing in a loop cannot exhaust the host's memory
+    ///
+    /// Only the console is captured, not the process's real stdout and stderr - anything written
+    /// to them directly, such as through `Deno.stdout` or by registered functions, still goes to the
+    /// process. Scripts' `Deno.stdout` and `Deno.stderr` can be redirected with `IoOptions` instead
+    ///
+    /// The `console.log` handler registered by the host, if any, is put back afterwards
+    /// Requires the `console` feature
+    ///
+    /// # Arguments
+    /// * `limit` - Maximum number of bytes kept for each stream
+    /// * `f` - The work to run, given this runtime
+    ///
+    /// # Returns
+    /// The result of `f`, and the output it produced - output is returned even if `f` fails
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Undefined, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let (result, output) = runtime.run_captured(16, |runtime| {
+    ///     runtime.eval::<Undefined>("console.error('oops'); for (let i = 0; i < 1000; i++) console.log('spam');")
+    /// });
+    /// result?;
+    ///
+    /// assert_eq!("oops\n", output.stderr);
+    /// assert_eq!("spam\nspam\nspam\ns", output.stdout);
+    /// assert!(output.stdout_truncated);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_captured<T>(
+        &mut self,
+        limit: usize,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> (Result<T, Error>, CapturedOutput) {
+        if let Err(e) = self.ensure_usable() {
+            return (Err(e), CapturedOutput::default());
+        }
 
-    use super::*;
-    use deno_core::extension;
+        let (output, previous) = match self.inner.redirect_console(limit) {
+            Ok(redirect) => redirect,
+            Err(e) => return (Err(e), CapturedOutput::default()),
+        };
 
-    #[test]
-    fn test_new() {
-        Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let mut result = f(self);
+        if let Err(e) = self.inner.restore_console(previous) {
+            result = result.and(Err(e));
+        }
 
-        extension!(test_extension);
-        Runtime::new(RuntimeOptions {
-            extensions: vec![test_extension::init_ops_and_esm()],
-            ..Default::default()
-        })
-        .expect("Could not create runtime with extensions");
+        let output = std::mem::take(&mut *output.borrow_mut());
+        (result, output)
     }
 
-    #[test]
-    #[allow(deprecated)]
+    /// Returns a snapshot of the runtime's memory use, mirroring v8's heap statistics
+    ///
+    /// Cheap enough to call between executions - useful to decide when to recycle a runtime
+    /// rather than letting it grow towards its heap limit
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Undefined, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.eval::<Undefined>("globalThis.data = new Array(100000).fill('x')")?;
+    ///
+    /// let stats = runtime.heap_stats();
+    /// assert!(stats.used_heap_size <= stats.total_heap_size);
+    /// println!("{} of {} bytes used", stats.used_heap_size, stats.heap_size_limit);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn heap_stats(&mut self) -> HeapStats {
+        self.inner.heap_stats()
+    }
+
+    /// Copies a value into another runtime using v8's structured clone algorithm
+    ///
+    /// Unlike a round-trip through JSON, this keeps the full shape of the value -
+    /// `Map`, `Set`, `Date`, `RegExp`, typed arrays and circular references all survive the copy
+    ///
+    /// # Arguments
+    /// * `value` - A value belonging to this runtime
+    /// * `dest` - The runtime to copy the value into
+    ///
+    /// # Returns
+    /// A `Value` belonging to `dest`
+    ///
+    /// # Errors
+    /// Will return an error if the value cannot be cloned, such as a function or a WebAssembly module
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, js_value::Value, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut source = Runtime::new(Default::default())?;
+    /// let mut dest = Runtime::new(Default::default())?;
+    ///
+    /// let value: Value = source.eval("[1, 2, 3]")?;
+    /// let copy = source.transfer_value(&value, &mut dest)?;
+    /// let copy: Vec<i32> = copy.try_into(&mut dest)?;
+    /// assert_eq!(vec![1, 2, 3], copy);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transfer_value(
+        &mut self,
+        value: &crate::js_value::Value,
+        dest: &mut Runtime,
+    ) -> Result<crate::js_value::Value, Error> {
+        self.ensure_usable()?;
+        dest.ensure_usable()?;
+        let data = self.inner.serialize_value(value.as_v8())?;
+        let value = dest.inner.deserialize_value(&data)?;
+        Ok(crate::js_value::Value::from_v8(value))
+    }
+
+    /// Returns the exit status set by the script, if any
+    ///
+    /// Scripts set it with `rustyscript.setExitCode(code)`, which lets execution continue,
+    /// or with `rustyscript.exit(code)`, which also terminates the script - `try`/`catch`
+    /// and `finally` blocks cannot intercept it
+    ///
+    /// If a call fails and this returns `Some`, the script ended itself on purpose
+    /// rather than failing with an error. The status is cleared when the next call starts,
+    /// and the runtime remains usable after an exit
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Undefined, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let result = runtime.eval::<Undefined>("rustyscript.exit(3)");
+    ///
+    /// assert!(result.is_err());
+    /// assert_eq!(runtime.exit_code(), Some(3));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn exit_code(&mut self) -> Option<i32> {
+        self.inner.exit_code()
+    }
+
+    /// Compiles a module to v8 bytecode, without loading it into the runtime
+    /// Persist the bytes, and pass them to [`Module::from_code_cache`] to skip most of the
+    /// compilation work the next time the module is loaded
+    ///
+    /// The bytecode is only valid for the same source, and the same version of v8
+    ///
+    /// # Errors
+    /// Fails if the module cannot be transpiled or compiled
+    ///
+    /// # Example
+    /// See [`Module::from_code_cache`]
+    pub fn compile_module_to_bytes(&mut self, module: &Module) -> Result<Vec<u8>, Error> {
+        self.ensure_usable()?;
+        self.inner.compile_module_to_bytes(module)
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    ///
+    /// See [`Runtime::call_function`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// Or if the result cannot be deserialized into the requested type
+    pub async fn call_function_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let cancellation = self.cancellation.clone();
+        cancellation
+            .run(async move {
+                let function = self.inner.get_function_by_name(module_context, name)?;
+                let result = self
+                    .inner
+                    .call_function_by_ref(module_context, &function, args)?;
+                let result = self.inner.resolve_with_event_loop(result).await?;
+                self.inner.decode_value(result)
+            })
+            .await
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name, reading its arguments
+    /// as a JSON array from a reader, and deserializes its return value.
+    ///
+    /// See [`Runtime::call_function_streamed`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `reader` - A source of JSON, containing an array of the arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if the arguments are not a valid JSON array,
+    /// if there are issues with calling the function, or if the result cannot be deserialized
+    pub async fn call_function_streamed_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        reader: impl std::io::Read,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let cancellation = self.cancellation.clone();
+        cancellation
+            .run(async move {
+                let function = self.inner.get_function_by_name(module_context, name)?;
+                let result =
+                    self.inner
+                        .call_function_by_ref_streamed(module_context, &function, reader)?;
+                let result = self.inner.resolve_with_event_loop(result).await?;
+                self.inner.decode_value(result)
+            })
+            .await
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name, reading its arguments
+    /// as a JSON array from a reader, and deserializes its return value.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// Arguments are converted to javascript one at a time as they are read,
+    /// so a large payload (a file, for example) is never fully built in memory
+    /// as a `Vec` of JSON values before the call
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `reader` - A source of JSON, containing an array of the arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if the arguments are not a valid JSON array,
+    /// if there are issues with calling the function, or if the result cannot be deserialized
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "export const sum = (a) => a.reduce((x, y) => x + y, 0);");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let payload = std::io::Cursor::new("[[1, 2, 3, 4]]");
+    /// let value: usize = runtime.call_function_streamed(Some(&module), "sum", payload)?;
+    /// assert_eq!(value, 10);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_function_streamed<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        reader: impl std::io::Read,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime
+                .call_function_streamed_async(module_context, name, reader)
+                .await
+        })
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.call_function(Some(&module), "f", json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_function<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime
+                .call_function_async(module_context, name, args)
+                .await
+        })
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name and deserializes its return value.
+    /// Will not attempt to resolve promises, or run the event loop
+    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
+    /// The event loop should be run using [`Runtime::await_event_loop`]
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the function call (`T`)
+    /// or an error (`Error`) if the function cannot be found, if there are issues with
+    /// calling the function, or if the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Fails if the function cannot be found, if there are issues with calling the function,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "export function f() { return 2; };");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.call_function_immediate(Some(&module), "f", json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_function_immediate<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let function = self.inner.get_function_by_name(module_context, name)?;
+        let result = self
+            .inner
+            .call_function_by_ref(module_context, &function, args)?;
+        self.inner.decode_value(result)
+    }
+
+    /// Calls a set of functions, then runs the event loop once for all of them
+    /// Blocks until every call has completed, and any promises they returned have settled
+    ///
+    /// This avoids running the event loop separately for each call, which can dominate the cost
+    /// of many small calls. Calls are made in order, and each succeeds or fails on its own -
+    /// one throwing or rejecting does not affect the others
+    ///
+    /// # Arguments
+    /// * `calls` - Each call's optional module context, function name, and arguments
+    ///
+    /// # Returns
+    /// The result of each call, in the same order as `calls`
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "
+    ///     export const double = (n) => n * 2;
+    ///     export const later = async (n) => n + 1;
+    /// ");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let results = runtime.call_batch(&[
+    ///     (Some(&module), "double", json_args!(2)),
+    ///     (Some(&module), "later", json_args!(2)),
+    ///     (Some(&module), "missing", json_args!(2)),
+    /// ]);
+    /// assert_eq!(results[0].as_ref().unwrap(), 4);
+    /// assert_eq!(results[1].as_ref().unwrap(), 3);
+    /// assert!(results[2].is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_batch<A>(
+        &mut self,
+        calls: &[(Option<&ModuleHandle>, &str, A)],
+    ) -> Vec<Result<serde_json::Value, Error>>
+    where
+        A: serde::ser::Serialize,
+    {
+        let results =
+            self.run_async_task(|runtime| async move { Ok(runtime.call_batch_async(calls).await) });
+
+        // Only fails if the calls could not be run at all
+        results.unwrap_or_else(|e| Self::fail_batch(calls.len(), &e))
+    }
+
+    /// Calls a set of functions, then runs the event loop once for all of them
+    /// Returns a future that resolves when every call has completed, and any promises they returned have settled
+    ///
+    /// See [`Runtime::call_batch`] for an example
+    ///
+    /// # Arguments
+    /// * `calls` - Each call's optional module context, function name, and arguments
+    ///
+    /// # Returns
+    /// The result of each call, in the same order as `calls`
+    pub async fn call_batch_async<A>(
+        &mut self,
+        calls: &[(Option<&ModuleHandle>, &str, A)],
+    ) -> Vec<Result<serde_json::Value, Error>>
+    where
+        A: serde::ser::Serialize,
+    {
+        if let Err(e) = self.ensure_usable() {
+            return Self::fail_batch(calls.len(), &e);
+        }
+
+        let cancellation = self.cancellation.clone();
+        let results = cancellation
+            .run(async {
+                // Every call is started before the event loop runs
+                let pending: Vec<_> = calls
+                    .iter()
+                    .map(|(module_context, name, args)| {
+                        let function = self.inner.get_function_by_name(*module_context, name)?;
+                        self.inner
+                            .call_function_by_ref(*module_context, &function, args)
+                    })
+                    .collect();
+
+                let results = self.inner.resolve_all_with_event_loop(pending).await;
+                Ok(results
+                    .into_iter()
+                    .map(|result| result.and_then(|value| self.inner.decode_value(value)))
+                    .collect())
+            })
+            .await;
+
+        results.unwrap_or_else(|e| Self::fail_batch(calls.len(), &e))
+    }
+
+    /// Fails every call in a batch that could not be run at all, each with the same error
+    fn fail_batch(len: usize, error: &Error) -> Vec<Result<serde_json::Value, Error>> {
+        (0..len).map(|_| Err(error.clone())).collect()
+    }
+
+    /// Calls a javascript function within the Deno runtime by its name, and classifies how the call ended
+    /// Blocks until the function is resolved, like [`Runtime::call_function`]
+    ///
+    /// Instead of folding every outcome into `Err`, a function that throws (or whose promise rejects)
+    /// yields [`CallOutcome::Threw`], and exceeding the runtime's timeout yields [`CallOutcome::TimedOut`]
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the javascript function to call.
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Returns
+    /// A `Result` containing the outcome of the call
+    ///
+    /// # Errors
+    /// Fails only for problems on the host side - if the function cannot be found,
+    /// if the arguments cannot be encoded, if the runtime itself fails ([`Error::Runtime`]),
+    /// or if the result cannot be deserialized into `T`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ json_args, CallOutcome, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "export function f(x) { if (x < 0) throw new Error('negative'); return x; };");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let outcome: CallOutcome<i64> = runtime.call_function_outcome(Some(&module), "f", json_args!(-1))?;
+    /// assert!(matches!(outcome, CallOutcome::Threw(_)));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_function_outcome<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<CallOutcome<T>, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let result = self.run_async_task(|runtime| async move {
+            let function = runtime.inner.get_function_by_name(module_context, name)?;
+            let result = runtime
+                .inner
+                .call_function_by_ref(module_context, &function, args)?;
+            let result = runtime.inner.resolve_with_event_loop(result).await?;
+            runtime.inner.decode_value(result)
+        });
+
+        CallOutcome::classify(result)
+    }
+
+    /// Returns the name, kind and arity (for functions) of every export of a loaded module, sorted by name
+    /// Compare the results for two versions of a module with [`crate::ExportComparison`]
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Errors
+    /// Can fail if the module's namespace cannot be accessed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export const add = (a, b) => a + b;");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let exports = runtime.get_export_signatures(&module)?;
+    /// assert_eq!(exports[0].name, "add");
+    /// assert_eq!(exports[0].arity, Some(2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_export_signatures(
+        &mut self,
+        module_context: &ModuleHandle,
+    ) -> Result<Vec<crate::ExportSignature>, Error> {
+        self.ensure_usable()?;
+        self.inner.get_export_signatures(module_context)
+    }
+
+    /// Returns the names of every export of a loaded module, sorted by name
+    /// Useful for discovering what a module provides without knowing its exports ahead of time
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Errors
+    /// Can fail if the module's namespace cannot be accessed
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export const name = 'plugin'; export default () => 1;");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// assert_eq!(runtime.get_module_exports(&module)?, vec!["default", "name"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_module_exports(
+        &mut self,
+        module_context: &ModuleHandle,
+    ) -> Result<Vec<String>, Error> {
+        self.ensure_usable()?;
+        Ok(self
+            .inner
+            .get_export_signatures(module_context)?
+            .into_iter()
+            .map(|signature| signature.name)
+            .collect())
+    }
+
+    /// Get the value of one of a loaded module's exports
+    /// Unlike [`Runtime::get_value`], only the module's own exports are searched, never the global context,
+    /// and promises are not awaited
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `name` - The name of the export
+    ///
+    /// # Errors
+    /// Will return [`Error::ValueNotFound`] if the module has no such export,
+    /// or an error if the value cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export const version = 3;");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let version: u32 = runtime.get_module_export_value(&module, "version")?;
+    /// assert_eq!(version, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_module_export_value<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        name: &str,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let value = self.inner.get_module_export_value(module_context, name)?;
+        self.inner.decode_value(value)
+    }
+
+    /// Runs a module's lifecycle hooks - such as `init`, `start` and `stop` - in the given order
+    ///
+    /// Each hook the module exports as a function is called with the same arguments, and awaited
+    /// if it returns a promise; hooks the module does not export are skipped
+    /// Stops at the first hook that fails
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `hooks` - The names of the hooks to run, in order
+    /// * `args` - The arguments to pass to each hook
+    ///
+    /// # Returns
+    /// The names of the hooks that were run
+    ///
+    /// # Errors
+    /// Fails with the error of the first hook that throws or rejects
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("plugin.js", "
+    ///     export function init(name) { console.log(`init ${name}`); }
+    ///     export async function start(name) { console.log(`start ${name}`); }
+    /// ");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let ran = runtime.run_hooks(&module, &["init", "start", "stop"], json_args!("plugin"))?;
+    /// assert_eq!(vec!["init", "start"], ran);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_hooks(
+        &mut self,
+        module_context: &ModuleHandle,
+        hooks: &[&str],
+        args: &impl serde::ser::Serialize,
+    ) -> Result<Vec<String>, Error> {
+        self.run_async_task(|runtime| async move {
+            runtime.run_hooks_async(module_context, hooks, args).await
+        })
+    }
+
+    /// Runs a module's lifecycle hooks in the given order
+    /// See [`Runtime::run_hooks`]
+    ///
+    /// # Errors
+    /// Fails with the error of the first hook that throws or rejects
+    pub async fn run_hooks_async(
+        &mut self,
+        module_context: &ModuleHandle,
+        hooks: &[&str],
+        args: &impl serde::ser::Serialize,
+    ) -> Result<Vec<String>, Error> {
+        self.ensure_usable()?;
+        let exports = self.inner.get_export_signatures(module_context)?;
+
+        let mut ran = Vec::new();
+        for hook in hooks {
+            // Only functions exported by the module count - never globals of the same name
+            if !exports
+                .iter()
+                .any(|export| export.name == *hook && export.arity.is_some())
+            {
+                continue;
+            }
+
+            let function = self
+                .inner
+                .get_function_by_name(Some(module_context), hook)?;
+            let result = self
+                .inner
+                .call_function_by_ref(Some(module_context), &function, args)?;
+            self.inner.resolve_with_event_loop(result).await?;
+            ran.push((*hook).to_string());
+        }
+
+        Ok(ran)
+    }
+
+    /// Returns the JS that was actually executed for a loaded module, after transpilation
+    /// Useful to persist compiled output, inspect what ran, or ship a precompiled bundle
+    ///
+    /// # Arguments
+    /// * `specifier` - The module's URL, or its path relative to the current directory
+    ///
+    /// # Returns
+    /// The transpiled source, or `None` if no such module has been loaded
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.ts", "export const x: number = 2;");
+    /// runtime.load_module(&module)?;
+    ///
+    /// let source = runtime.get_transpiled_source("test.ts").expect("Module was loaded");
+    /// assert!(!source.contains(": number"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn get_transpiled_source(&self, specifier: &str) -> Option<String> {
+        self.inner.get_transpiled_source(specifier)
+    }
+
+    /// Get a value from a runtime instance
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Errors
+    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.get_value(Some(&module), "my_value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_value<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.run_async_task(
+            |runtime| async move { runtime.get_value_async(module_context, name).await },
+        )
+    }
+
+    /// Get a value from a runtime instance
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// See [`Runtime::get_value`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Errors
+    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
+    pub async fn get_value_async<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let result = self.inner.get_value_ref(module_context, name)?;
+        let result = self.inner.resolve_with_event_loop(result).await?;
+        self.inner.decode_value(result)
+    }
+
+    /// Get a value from a runtime instance
+    /// Will not attempt to resolve promises, or run the event loop
+    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
+    /// The event loop should be run using [`Runtime::await_event_loop`]
+    ///
+    /// # Arguments
+    /// * `module_context` - Optional handle to a module to search - if None, or if the search fails, the global context is used
+    /// * `name` - A string representing the name of the value to find
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result or an error (`Error`) if the value cannot be found,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Errors
+    /// Can fail if the value cannot be found, or if the result cannot be deserialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("/path/to/module.js", "globalThis.my_value = 2;");
+    /// let module = runtime.load_module(&module)?;
+    /// let value: usize = runtime.get_value_immediate(Some(&module), "my_value")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_value_immediate<T>(
+        &mut self,
+        module_context: Option<&ModuleHandle>,
+        name: &str,
+    ) -> Result<T, Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let result = self.inner.get_value_ref(module_context, name)?;
+        self.inner.decode_value(result)
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions
+    ///
+    /// Blocks until the module has been executed AND the event loop has fully resolved
+    /// See [`Runtime::load_module_async`] for a non-blocking variant, or use with async
+    /// background tasks
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading or executing the module
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, or execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// runtime.load_module(&module);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move {
+            let handle = runtime.load_module_async(module).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions
+    ///
+    /// Returns a future that resolves to the handle for the loaded module
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading or executing the module
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, or execution fails
+    ///
+    /// See [`Runtime::load_module`] for an example
+    pub async fn load_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner.load_modules(None, vec![module]).await
+    }
+
+    /// Executes the given module with only a curated set of globals visible, and returns a handle for it
+    ///
+    /// The module is evaluated in its own v8 context, whose global object holds only the allowed globals;
+    /// any other global (`fetch`, `Deno`, `rustyscript`, ...) does not exist there.
+    /// Allowed builtins such as `Math` are the context's own copies. Allowed host globals, such as `console`,
+    /// are not shared with the module: their functions are replaced by wrappers that copy arguments, results
+    /// and errors across as JSON, and their objects by copies holding such wrappers.
+    /// Nothing from the runtime's context - including its global object - can be reached through them.
+    ///
+    /// Since the context has no module loader or event loop, the module cannot import other modules,
+    /// use `import.meta` or declare async functions, and `Promise` is never available.
+    /// `eval` and the `Function` constructors are disabled, since code generation from strings would bypass the
+    /// checks made on the module's source.
+    /// Arguments passed to the module's functions are created in its context for the same reason,
+    /// and do not go through conversions from [`Runtime::register_conversion`].
+    ///
+    /// Blocks until the module has been executed AND the event loop has fully resolved
+    /// See [`Runtime::load_sandboxed_module_async`] for a non-blocking variant
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `allowed_globals` - Names of the globals the module can see
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading or executing the module
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, or execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export const visible = typeof globalThis.rustyscript;");
+    /// let handle = runtime.load_sandboxed_module(&module, &["Math", "JSON"])?;
+    ///
+    /// let visible: String = runtime.get_value(Some(&handle), "visible")?;
+    /// assert_eq!(visible, "undefined");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_sandboxed_module(
+        &mut self,
+        module: &Module,
+        allowed_globals: &[&str],
+    ) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move {
+            let handle = runtime
+                .load_sandboxed_module_async(module, allowed_globals)
+                .await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Executes the given module with only a curated set of globals visible, and returns a handle for it
+    /// See [`Runtime::load_sandboxed_module`]
+    ///
+    /// Returns a future that resolves to the handle for the loaded module
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, or execution fails
+    pub async fn load_sandboxed_module_async(
+        &mut self,
+        module: &Module,
+        allowed_globals: &[&str],
+    ) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner
+            .load_sandboxed_module(module, allowed_globals)
+            .await
+    }
+
+    /// Executes the given module in pure function mode, and returns a handle for it
+    /// Intended for rule engines and other hosts evaluating many independent pure functions
+    ///
+    /// This is a stricter [`Runtime::load_sandboxed_module`], failing fast on anything stateful:
+    /// - The module runs in its own v8 context, and may not import anything, statically or with `import()`
+    /// - Only deterministic builtins (`Math`, `JSON`, `Object`, `Array`, ...) are visible,
+    ///   so timers, `Promise`, `Date`, `console` and all host APIs are `undefined`
+    /// - `eval` and the `Function` constructors are disabled
+    /// - Declaring an async function or a generator anywhere in the module is an error
+    ///
+    /// The event loop is not polled, since a pure module cannot schedule any work on it
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if the module breaks the rules above, or fails to execute
+    ///
+    /// # Errors
+    /// Can fail if the module imports anything, declares async functions or generators, or execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("rule.js", "export const check = (n) => Math.abs(n) < 10;");
+    /// let handle = runtime.load_pure_module(&module)?;
+    ///
+    /// let passed: bool = runtime.call_function(Some(&handle), "check", json_args!(-4))?;
+    /// assert!(passed);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_pure_module(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move { runtime.load_pure_module_async(module).await })
+    }
+
+    /// Executes the given module in pure function mode, and returns a handle for it
+    /// See [`Runtime::load_pure_module`]
+    ///
+    /// # Errors
+    /// Can fail if the module imports anything, declares async functions or generators, or execution fails
+    pub async fn load_pure_module_async(&mut self, module: &Module) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner.load_pure_module(module).await
+    }
+
+    /// Executes every module of an embedded set, and returns a handle for each, in order
+    /// Modules in the set can import each other by filename without filesystem access,
+    /// and a module already imported by an earlier one is not evaluated twice
+    ///
+    /// Blocks until all modules have been executed AND the event loop has fully resolved
+    /// See [`Runtime::load_module_set_async`] for a non-blocking variant
+    ///
+    /// # Arguments
+    /// * `modules` - A set of modules, created with [`crate::module_set!`]
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for each module in the set
+    /// or an error (`Error`) if there are issues with loading or executing the modules
+    ///
+    /// # Errors
+    /// Can fail if a module cannot be loaded, or execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Error, StaticModuleSet, module_set, json_args };
+    ///
+    /// const SCRIPTS: StaticModuleSet = module_set!(
+    ///     "lib.js" => "export const add = (a, b) => a + b;",
+    ///     "main.js" => "import { add } from './lib.js'; export default () => add(1, 2);",
+    /// );
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let handles = runtime.load_module_set(&SCRIPTS)?;
+    ///
+    /// let value: usize = runtime.call_entrypoint(&handles[1], json_args!())?;
+    /// assert_eq!(3, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_module_set(
+        &mut self,
+        modules: &crate::StaticModuleSet,
+    ) -> Result<Vec<ModuleHandle>, Error> {
+        self.run_async_task(|runtime| async move {
+            let handles = runtime.load_module_set_async(modules).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handles
+        })
+    }
+
+    /// Executes every module of an embedded set, and returns a handle for each, in order
+    /// See [`Runtime::load_module_set`]
+    ///
+    /// Returns a future that resolves to the handles for the loaded modules
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// # Errors
+    /// Can fail if a module cannot be loaded, or execution fails
+    pub async fn load_module_set_async(
+        &mut self,
+        modules: &crate::StaticModuleSet,
+    ) -> Result<Vec<ModuleHandle>, Error> {
+        self.ensure_usable()?;
+        self.inner.load_module_set(&modules.to_modules()).await
+    }
+
+    /// Compiles a set of modules without running them, deferring each module's top-level code
+    /// until it is first needed - when requested with [`Runtime::lazy_module`], or imported by
+    /// another module. Useful for large sets of modules where only a few are used per run
+    ///
+    /// Modules in the set can import each other by filename without filesystem access
+    ///
+    /// # Arguments
+    /// * `modules` - The modules to compile, such as those returned by [`Module::load_dir`]
+    ///
+    /// # Errors
+    /// Can fail if a module, or one of its imports, cannot be loaded or compiled
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error, json_args };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// runtime.load_modules_lazy(&[
+    ///     Module::new("used.js", "export const f = () => 2;"),
+    ///     Module::new("unused.js", "throw new Error('Never runs');"),
+    /// ])?;
+    ///
+    /// let handle = runtime.lazy_module("used.js")?;
+    /// let value: usize = runtime.call_function(Some(&handle), "f", json_args!())?;
+    /// assert_eq!(2, value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_modules_lazy(&mut self, modules: &[Module]) -> Result<(), Error> {
+        self.run_async_task(|runtime| async move { runtime.load_modules_lazy_async(modules).await })
+    }
+
+    /// Compiles a set of modules without running them
+    /// See [`Runtime::load_modules_lazy`]
+    ///
+    /// # Errors
+    /// Can fail if a module, or one of its imports, cannot be loaded or compiled
+    pub async fn load_modules_lazy_async(&mut self, modules: &[Module]) -> Result<(), Error> {
+        self.ensure_usable()?;
+        self.inner.load_modules_lazy(modules).await
+    }
+
+    /// Returns a handle to a module compiled with [`Runtime::load_modules_lazy`]
+    /// The module's top-level code is run on the first request, unless another module has already imported it
+    ///
+    /// Blocks until the module has been executed AND the event loop has fully resolved
+    ///
+    /// # Arguments
+    /// * `filename` - The filename the module was loaded with
+    ///
+    /// # Errors
+    /// Can fail if the module was not loaded lazily, or if its execution fails
+    pub fn lazy_module(
+        &mut self,
+        filename: impl AsRef<std::path::Path>,
+    ) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move {
+            let handle = runtime.lazy_module_async(filename).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Loads a module by a path relative to an already-loaded module, and returns a handle to it
+    /// Useful for discovering plugins that sit next to a known module
+    ///
+    /// The module is loaded as if the host had requested it directly, so the usual import rules apply.
+    /// If it was already imported, it is not run again
+    ///
+    /// Blocks until the module has been executed AND the event loop has fully resolved
+    ///
+    /// # Arguments
+    /// * `base` - A handle to the module the specifier is relative to
+    /// * `specifier` - A relative specifier, such as `./sibling.js`
+    ///
+    /// # Errors
+    /// Can fail if the specifier cannot be resolved, or if the module cannot be loaded or executed
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let base = Module::new("examples/javascript/plugin_host.js", "export const name = 'host';");
+    /// let base = runtime.load_module(&base)?;
+    ///
+    /// let sibling = runtime.import_relative(&base, "./example_module.js")?;
+    /// let food: String = runtime.get_value(Some(&sibling), "MY_FAVOURITE_FOOD")?;
+    /// assert_eq!("saskatoonberries", food);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_relative(
+        &mut self,
+        base: &ModuleHandle,
+        specifier: &str,
+    ) -> Result<ModuleHandle, Error> {
+        self.run_async_task(|runtime| async move {
+            let handle = runtime.import_relative_async(base, specifier).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Loads a module by a path relative to an already-loaded module
+    /// See [`Runtime::import_relative`]
+    ///
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// # Errors
+    /// Can fail if the specifier cannot be resolved, or if the module cannot be loaded or executed
+    pub async fn import_relative_async(
+        &mut self,
+        base: &ModuleHandle,
+        specifier: &str,
+    ) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner.import_relative(base, specifier).await
+    }
+
+    /// Returns a handle to a module compiled with [`Runtime::load_modules_lazy`], running it if needed
+    /// See [`Runtime::lazy_module`]
+    ///
+    /// Makes no attempt to fully resolve the event loop - call [`Runtime::await_event_loop`]
+    /// to resolve background tasks and async listeners
+    ///
+    /// # Errors
+    /// Can fail if the module was not loaded lazily, or if its execution fails
+    pub async fn lazy_module_async(
+        &mut self,
+        filename: impl AsRef<std::path::Path>,
+    ) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner.get_lazy_module(filename.as_ref()).await
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions.
+    ///
+    /// Blocks until all modules have been executed AND the event loop has fully resolved
+    /// See [`Runtime::load_module_async`] for a non-blocking variant, or use with async
+    /// background tasks
+    ///
+    /// This will load 'module' as the main module, and the others as side-modules.
+    /// Only one main module can be loaded per runtime
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded module
+    /// or an error (`Error`) if there are issues with loading or executing the module
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, or execution fails
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// runtime.load_modules(&module, vec![]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_modules(
+        &mut self,
+        module: &Module,
+        side_modules: Vec<&Module>,
+    ) -> Result<ModuleHandle, Error> {
+        self.run_async_task(move |runtime| async move {
+            let handle = runtime.load_modules_async(module, side_modules).await;
+            runtime
+                .await_event_loop(PollEventLoopOptions::default(), None)
+                .await?;
+            handle
+        })
+    }
+
+    /// Executes the given module, and returns a handle allowing you to extract values
+    /// And call functions.
+    ///
+    /// Returns a future that resolves to the handle for the loaded module
+    /// Makes no attempt to resolve the event loop - call [`Runtime::await_event_loop`] to
+    /// resolve background tasks and async listeners
+    ///
+    /// This will load 'module' as the main module, and the others as side-modules.
+    /// Only one main module can be loaded per runtime
+    ///
+    /// See [`Runtime::load_modules`] for an example
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    ///
+    /// # Returns
+    /// A `Result` containing a handle for the loaded main module, or the last side-module
+    /// or an error (`Error`) if there are issues with loading or executing the modules
+    ///
+    /// # Errors
+    /// Can fail if the modules cannot be loaded, or execution fails
+    pub async fn load_modules_async(
+        &mut self,
+        module: &Module,
+        side_modules: Vec<&Module>,
+    ) -> Result<ModuleHandle, Error> {
+        self.ensure_usable()?;
+        self.inner.load_modules(Some(module), side_modules).await
+    }
+
+    /// Measures the static import graph of a module, without evaluating any of it
+    /// Returns the number of unique modules, and the total size of their transpiled source
+    ///
+    /// Imports are resolved and fetched with the runtime's module loader, so the same permissions,
+    /// import providers and caches apply as for a real load.
+    /// Useful to reject oversized script bundles before loading them
+    ///
+    /// Dynamic imports are not followed, since they can only be discovered by running the code
+    ///
+    /// # Arguments
+    /// * `module` - The root module of the graph
+    ///
+    /// # Returns
+    /// A `Result` containing the size of the graph, or an error (`Error`) if a module in the graph
+    /// cannot be resolved, fetched, or parsed
+    ///
+    /// # Errors
+    /// Can fail if a module in the graph cannot be resolved, fetched, or parsed
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export const value = 2;");
+    ///
+    /// let size = runtime.measure_module_graph(&module)?;
+    /// if size.total_bytes > 1024 * 1024 {
+    ///     return Err(Error::Runtime("Script bundle is too large".to_string()));
+    /// }
+    ///
+    /// runtime.load_module(&module)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn measure_module_graph(&mut self, module: &Module) -> Result<ModuleGraphSize, Error> {
+        self.run_async_task(move |runtime| async move {
+            runtime.measure_module_graph_async(module).await
+        })
+    }
+
+    /// Measures the static import graph of a module, without evaluating any of it
+    /// Returns a future resolving to the number of unique modules, and the total size of their transpiled source
+    ///
+    /// See [`Runtime::measure_module_graph`] for more information
+    ///
+    /// # Arguments
+    /// * `module` - The root module of the graph
+    ///
+    /// # Errors
+    /// Can fail if a module in the graph cannot be resolved, fetched, or parsed
+    pub async fn measure_module_graph_async(
+        &mut self,
+        module: &Module,
+    ) -> Result<ModuleGraphSize, Error> {
+        self.ensure_usable()?;
+        self.inner.measure_module_graph(module).await
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Blocks until:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// // Run the entrypoint and handle the result
+    /// let value: String = runtime.call_entrypoint(&module, json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_entrypoint<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime.call_entrypoint_async(module_context, args).await
+        })
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Returns a future that resolves when:
+    /// - The event loop is resolved, and
+    /// - If the value is a promise, the promise is resolved
+    ///
+    /// Note that synchronous functions are run synchronously. Returned promises will be run asynchronously, however.
+    ///
+    /// See [`Runtime::call_entrypoint`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
+    /// Or if the result cannot be deserialized into the requested type
+    pub async fn call_entrypoint_async<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        if let Some(entrypoint) = module_context.entrypoint() {
+            let result = self
+                .inner
+                .call_function_by_ref(Some(module_context), entrypoint, args)?;
+            let result = self.inner.resolve_with_event_loop(result).await?;
+            self.inner.decode_value(result)
+        } else {
+            Err(Error::MissingEntrypoint(module_context.module().clone()))
+        }
+    }
+
+    /// Calls the default export of a module, and awaits the promise it returns, if any
+    /// Blocks until the result is ready
+    ///
+    /// Unlike [`Runtime::call_entrypoint`], this always uses the default export,
+    /// even if the module registered another entrypoint
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Errors
+    /// Can fail if the module has no default export, if it is not a function, if the call throws or rejects,
+    /// or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ json_args, Runtime, Module, Error };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "
+    ///     export default async function(name) {
+    ///         await new Promise((resolve) => setTimeout(resolve, 10));
+    ///         return `hello ${name}`;
+    ///     }
+    /// ");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let value: String = runtime.run_default(&module, json_args!("world"))?;
+    /// assert_eq!("hello world", value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_default<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.run_async_task(|runtime| async move {
+            runtime.run_default_async(module_context, args).await
+        })
+    }
+
+    /// Calls the default export of a module, and awaits the promise it returns, if any
+    /// Returns a future that resolves to the result
+    ///
+    /// See [`Runtime::run_default`] for an example
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    /// * `args` - The arguments to pass to the function
+    ///
+    /// # Errors
+    /// Can fail if the module has no default export, if it is not a function, if the call throws or rejects,
+    /// or if the result cannot be deserialized into the requested type
+    pub async fn run_default_async<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        let default = self
+            .inner
+            .get_module_export_value(module_context, "default")
+            .map_err(|_| Error::MissingEntrypoint(module_context.module().clone()))?;
+        let function = {
+            let mut scope = self.deno_runtime().handle_scope();
+            let default = deno_core::v8::Local::new(&mut scope, default);
+            let function = deno_core::v8::Local::<deno_core::v8::Function>::try_from(default)
+                .map_err(|_| Error::ValueNotCallable("default".to_string()))?;
+            deno_core::v8::Global::new(&mut scope, function)
+        };
+
+        let result = self
+            .inner
+            .call_function_by_ref(Some(module_context), &function, args)?;
+        let result = self.inner.resolve_with_event_loop(result).await?;
+        self.inner.decode_value(result)
+    }
+
+    /// Executes the entrypoint function of a module within the Deno runtime.
+    /// Will not attempt to resolve promises, or run the event loop
+    /// Promises can be returned by specifying the return type as [`crate::js_value::Promise`]
+    /// The event loop should be run using [`Runtime::await_event_loop`]
+    ///
+    /// # Arguments
+    /// * `module_context` - A handle returned by loading a module into the runtime
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = Module::new("test.js", "export default () => 'test'");
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// // Run the entrypoint and handle the result
+    /// let value: String = runtime.call_entrypoint_immediate(&module, json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn call_entrypoint_immediate<T>(
+        &mut self,
+        module_context: &ModuleHandle,
+        args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        self.ensure_usable()?;
+        if let Some(entrypoint) = module_context.entrypoint() {
+            let result = self.run_async_task(|runtime| async move {
+                runtime
+                    .inner
+                    .call_function_by_ref(Some(module_context), entrypoint, args)
+            })?;
+            self.inner.decode_value(result)
+        } else {
+            Err(Error::MissingEntrypoint(module_context.module().clone()))
+        }
+    }
+
+    /// Loads a module into a new runtime, executes the entry function and returns the
+    /// result of the module's execution, deserialized into the specified Rust type (`T`).
+    ///
+    /// # Arguments
+    /// * `module` - A `Module` object containing the module's filename and contents.
+    /// * `side_modules` - A set of additional modules to be loaded into memory for use
+    /// * `runtime_options` - Options for the creation of the runtime
+    /// * `entrypoint_args` - Arguments to pass to the entrypoint function
+    ///
+    /// # Returns
+    /// A `Result` containing the deserialized result of the entrypoint execution (`T`)
+    /// if successful, or an error (`Error`) if the entrypoint is missing, the execution fails,
+    /// or the result cannot be deserialized.
+    ///
+    /// # Errors
+    /// Can fail if the module cannot be loaded, if the entrypoint is missing, if the execution fails,
+    /// Or if the result cannot be deserialized into the requested type
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// // Create a module with filename and contents
+    /// use rustyscript::{json_args, Runtime, Module, Error};
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("test.js", "export default () => 2");
+    /// let value: usize = Runtime::execute_module(&module, vec![], Default::default(), json_args!())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute_module<T>(
+        module: &Module,
+        side_modules: Vec<&Module>,
+        runtime_options: RuntimeOptions,
+        entrypoint_args: &impl serde::ser::Serialize,
+    ) -> Result<T, Error>
+    where
+        T: deno_core::serde::de::DeserializeOwned,
+    {
+        let mut runtime = Runtime::new(runtime_options)?;
+        let module = runtime.load_modules(module, side_modules)?;
+        let value: T = runtime.call_entrypoint(&module, entrypoint_args)?;
+        Ok(value)
+    }
+
+    /// Used for blocking functions
+    pub(crate) fn run_async_task<'a, T, F, U>(&'a mut self, f: F) -> Result<T, Error>
+    where
+        U: std::future::Future<Output = Result<T, Error>>,
+        F: FnOnce(&'a mut Runtime) -> U,
+    {
+        self.ensure_usable()?;
+
+        let timeout = self.timeout();
+        let rt = self.tokio_runtime();
+        let heap_exhausted_token = self.heap_exhausted_token();
+        let cancellation = self.cancellation_handle();
+        let logger = self.logger();
+        let result = rt.block_on(async move {
+            let call = async move { tokio::time::timeout(timeout, f(self)).await? };
+            tokio::select! {
+                result = cancellation.run(call) => result,
+                () = heap_exhausted_token.cancelled() => Err(Error::HeapExhausted),
+            }
+        });
+
+        if let (Some(logger), Err(e @ (Error::Timeout(_) | Error::HeapExhausted))) =
+            (logger, &result)
+        {
+            logger.log(crate::LogLevel::Warn, &e.to_string());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test_runtime {
+    use crate::json_args;
+    use std::time::Duration;
+
+    use super::*;
+    use deno_core::extension;
+
+    #[test]
+    fn test_new() {
+        Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        extension!(test_extension);
+        Runtime::new(RuntimeOptions {
+            extensions: vec![test_extension::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .expect("Could not create runtime with extensions");
+    }
+
+    #[test]
+    fn test_runtime_extensions() {
+        #[deno_core::op2(fast)]
+        fn op_runtime_only() -> u32 {
+            42
+        }
+        extension!(runtime_only, ops = [op_runtime_only]);
+        extension!(needs_missing, deps = [missing_extension]);
+        extension!(rustyscript);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            runtime_extensions: vec![runtime_only::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .expect("Could not create runtime with runtime extensions");
+        let value: u32 = runtime
+            .eval("Deno.core.ops.op_runtime_only()")
+            .expect("Runtime extension op was not available");
+        assert_eq!(42, value);
+
+        let err = Runtime::new(RuntimeOptions {
+            runtime_extensions: vec![needs_missing::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .err()
+        .expect("Missing dependency was not detected");
+        assert!(err.to_string().contains("missing_extension"));
+
+        let err = Runtime::new(RuntimeOptions {
+            runtime_extensions: vec![rustyscript::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .err()
+        .expect("Duplicate extension was not detected");
+        assert!(err.to_string().contains("already loaded"));
+    }
+
+    #[test]
+    fn test_op_error() {
+        #[deno_core::op2(fast)]
+        fn op_reserve_quota() -> Result<(), crate::OpError> {
+            Err(crate::OpError::new("QUOTA_EXCEEDED", "Not enough quota")
+                .with_data(serde_json::json!({ "limit": 5 })))
+        }
+        extension!(op_error_test, ops = [op_reserve_quota]);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            runtime_extensions: vec![op_error_test::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .expect("Could not create runtime");
+
+        let (code, message, limit, is_op_error): (String, String, u32, bool) = runtime
+            .eval(
+                "(() => {
+                    try {
+                        Deno.core.ops.op_reserve_quota();
+                    } catch (e) {
+                        return [e.code, e.message, e.data.limit, e instanceof rustyscript.OpError];
+                    }
+                })()",
+            )
+            .expect("Could not catch the op error");
+        assert_eq!("QUOTA_EXCEEDED", code);
+        assert_eq!("Not enough quota", message);
+        assert_eq!(5, limit);
+        assert!(is_op_error);
+    }
+
+    #[test]
+    #[cfg(feature = "web")]
+    fn test_module_web_permissions() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        // Port 1 refuses connections, so allowed requests fail with a network error instead
+        let source = "
+            export async function request() {
+                try {
+                    await fetch('http://127.0.0.1:1/');
+                    return 'ok';
+                } catch (e) {
+                    return String(e);
+                }
+            }
+        ";
+        let scoped = Module::new("web_permissions/scoped.js", source);
+        let unscoped = Module::new("web_permissions/unscoped.js", source);
+
+        runtime
+            .set_module_web_permissions(&scoped, Rc::new(crate::AllowlistWebPermissions::new()))
+            .expect("Could not set the permissions");
+        let scoped = runtime.load_module(&scoped).expect("Could not load module");
+        let unscoped = runtime
+            .load_module(&unscoped)
+            .expect("Could not load module");
+
+        let result: String = runtime
+            .call_function(Some(&scoped), "request", json_args!())
+            .expect("Could not call function");
+        assert!(result.contains("is not allowed"), "{result}");
+
+        let result: String = runtime
+            .call_function(Some(&unscoped), "request", json_args!())
+            .expect("Could not call function");
+        assert!(!result.contains("is not allowed"), "{result}");
+
+        // Requests that cannot be traced to a module are denied
+        let untraced = Module::new(
+            "web_permissions/untraced.js",
+            "export const result = await Promise.resolve('http://127.0.0.1:1/')
+                .then(fetch)
+                .then(() => 'ok', String);",
+        );
+        let untraced = runtime
+            .load_module(&untraced)
+            .expect("Could not load module");
+        let result: String = runtime
+            .get_value(Some(&untraced), "result")
+            .expect("Could not get value");
+        assert!(result.contains("is not allowed"), "{result}");
+
+        // Scripts cannot claim another module's permissions
+        let spoof: String = runtime
+            .eval(
+                "try {
+                    Deno.core.ops.op_set_web_caller('file:///unscoped.js');
+                    'set';
+                } catch (e) {
+                    String(e);
+                }",
+            )
+            .expect("Could not eval");
+        assert!(spoof.contains("cannot be set"), "{spoof}");
+
+        // Redirects are checked against the module that made the request
+        let (port, server) = serve_responses(vec![
+            ("302 Found\r\nLocation: /end", ""),
+            ("200 OK", "done"),
+        ]);
+        let redirect = Module::new(
+            "web_permissions/redirect.js",
+            "export async function follow(url) {
+                const response = await fetch(url);
+                return `${response.status} ${response.redirected} ${await response.text()}`;
+            }",
+        );
+        let redirect = runtime
+            .load_module(&redirect)
+            .expect("Could not load module");
+        let result: String = runtime
+            .call_function(
+                Some(&redirect),
+                "follow",
+                json_args!(format!("http://127.0.0.1:{port}/start")),
+            )
+            .expect("Could not call function");
+        assert_eq!("200 true done", result);
+        assert_eq!(2, server.join().expect("Server panicked"));
+    }
+
+    #[test]
+    #[cfg(feature = "web")]
+    fn test_max_concurrent_fetches() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            extension_options: crate::ExtensionOptions {
+                web: crate::WebOptions {
+                    max_concurrent_fetches: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let limit: u32 = runtime
+            .eval("Deno.core.ops.op_fetch_concurrency_limit()")
+            .expect("Could not get the limit");
+        assert_eq!(1, limit);
+
+        // Queued fetches still all complete, in order
+        let module = Module::new(
+            "test.js",
+            "
+            const texts = await Promise.all(
+                [1, 2, 3].map((i) => fetch(`data:text/plain,${i}`).then((r) => r.text()))
+            );
+            export const joined = texts.join(',');
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let joined: String = runtime
+            .get_value(Some(&handle), "joined")
+            .expect("Could not get value");
+        assert_eq!("1,2,3", joined);
+
+        // A fetch keeps its slot until its body is read or cancelled
+        let module = Module::new(
+            "test2.js",
+            "
+            const log = [];
+            const first = await fetch('data:text/plain,a');
+            const second = fetch('data:text/plain,b').then((r) => {
+                log.push('b');
+                return r;
+            });
+
+            // The first body is unread, so the second fetch is still queued
+            await new Promise((r) => setTimeout(r, 10));
+            log.push('unread');
+            await first.text();
+
+            // Cancelling the second body frees the slot for a third
+            const third = fetch('data:text/plain,c').then((r) => {
+                log.push('c');
+                return r.text();
+            });
+            await new Promise((r) => setTimeout(r, 10));
+            log.push('uncancelled');
+            await (await second).body.cancel();
+            await third;
+            export const order = log.join(',');
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let order: String = runtime
+            .get_value(Some(&handle), "order")
+            .expect("Could not get value");
+        assert_eq!("unread,b,uncancelled,c", order);
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_insecure_crypto_seed() {
+        let random_values = || -> Vec<u8> {
+            let mut runtime = crate::RuntimeBuilder::new()
+                .with_insecure_crypto_seed(42)
+                .build()
+                .expect("Could not create the runtime");
+            runtime
+                .eval("Array.from(crypto.getRandomValues(new Uint8Array(8)))")
+                .expect("Could not get random values")
+        };
+        assert_eq!(random_values(), random_values());
+    }
+
+    #[test]
+    fn test_deterministic_seed() {
+        let random_values = |seed: u64| -> Vec<f64> {
+            let mut runtime = crate::RuntimeBuilder::new()
+                .with_deterministic_seed(seed)
+                .build()
+                .expect("Could not create the runtime");
+            runtime
+                .eval("Array.from({ length: 8 }, () => Math.random())")
+                .expect("Could not get random values")
+        };
+
+        let values = random_values(u64::MAX - 7);
+        assert_eq!(values, random_values(u64::MAX - 7));
+        assert_ne!(values, random_values(7));
+        assert!(values.iter().all(|v| (0.0..1.0).contains(v)));
+
+        #[cfg(feature = "crypto")]
+        {
+            let random_bytes = || -> Vec<u8> {
+                let mut runtime = crate::RuntimeBuilder::new()
+                    .with_deterministic_seed(1)
+                    .build()
+                    .expect("Could not create the runtime");
+                runtime
+                    .eval("Array.from(crypto.getRandomValues(new Uint8Array(8)))")
+                    .expect("Could not get random values")
+            };
+            assert_eq!(random_bytes(), random_bytes());
+        }
+    }
+
+    #[test]
+    fn test_max_concurrent_async_calls() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            max_concurrent_async_calls: Some(3),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        // Tracks the current and the highest number of calls in flight
+        let in_flight = Rc::new(std::cell::Cell::new((0, 0)));
+        let counter = in_flight.clone();
+        runtime
+            .register_async_function("query", move |args| {
+                let counter = counter.clone();
+                Box::pin(async move {
+                    let (current, peak) = counter.get();
+                    counter.set((current + 1, peak.max(current + 1)));
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    let (current, peak) = counter.get();
+                    counter.set((current - 1, peak));
+                    Ok(args[0].clone())
+                })
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const fanOut = () => Promise.all(
+                Array.from({ length: 20 }, (_, i) => rustyscript.async_functions.query(i))
+            );
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let results: Vec<usize> = runtime
+            .call_function(Some(&handle), "fanOut", json_args!())
+            .expect("Could not run queries");
+        assert_eq!((0..20).collect::<Vec<_>>(), results);
+        assert_eq!((0, 3), in_flight.get());
+
+        // No call could ever run under a limit of 0
+        let result = Runtime::new(RuntimeOptions {
+            max_concurrent_async_calls: Some(0),
+            ..Default::default()
+        });
+        let Err(e) = result else {
+            panic!("A limit of 0 was accepted");
+        };
+        assert!(e.to_string().contains("at least 1"), "{e}");
+    }
+
+    #[test]
+    fn test_async_function_rejects() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_async_function("fail", |args| {
+                Box::pin(async move {
+                    let error: Error = match args.first().and_then(serde_json::Value::as_str) {
+                        Some("structured") => crate::OpError::new("NOT_FOUND", "No such user")
+                            .with_data(serde_json::json!({ "id": 7 }))
+                            .into(),
+                        _ => Error::Runtime("plain failure".to_string()),
+                    };
+                    Err::<serde_json::Value, _>(error)
+                })
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            export async function attempt(kind) {
+                const pending = rustyscript.async_functions.fail(kind);
+                try {
+                    await pending;
+                    return null;
+                } catch (e) {
+                    return [e instanceof Error, e.message, e.code ?? null, e.data?.id ?? null];
+                }
+            }
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let (is_error, message, code, id): (bool, String, Option<String>, Option<u32>) = runtime
+            .call_function(Some(&handle), "attempt", json_args!("plain"))
+            .expect("Promise did not reject");
+        assert!(is_error);
+        assert!(message.contains("plain failure"));
+        assert_eq!(None, code);
+        assert_eq!(None, id);
+
+        let (is_error, message, code, id): (bool, String, Option<String>, Option<u32>) = runtime
+            .call_function(Some(&handle), "attempt", json_args!("structured"))
+            .expect("Promise did not reject");
+        assert!(is_error);
+        assert_eq!("No such user", message);
+        assert_eq!(Some("NOT_FOUND".to_string()), code);
+        assert_eq!(Some(7), id);
+    }
+
+    #[test]
+    fn test_register_functions() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_function("existing", |_| Ok(serde_json::Value::Null))
+            .expect("Could not register function");
+
+        let mut functions: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+        functions.insert(
+            "ns.double".to_string(),
+            Box::new(|args: &[serde_json::Value]| {
+                let value = args
+                    .first()
+                    .and_then(serde_json::Value::as_i64)
+                    .unwrap_or(0);
+                Ok::<_, Error>(serde_json::Value::from(value * 2))
+            }),
+        );
+        runtime
+            .register_functions(functions)
+            .expect("Could not register functions");
+        let value: i64 = runtime
+            .eval("rustyscript.functions['ns.double'](4)")
+            .expect("Could not call function");
+        assert_eq!(8, value);
+
+        let mut functions: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+        functions.insert(
+            "ns.fresh".to_string(),
+            Box::new(|_: &[serde_json::Value]| Ok::<_, Error>(serde_json::Value::Null)),
+        );
+        functions.insert(
+            "existing".to_string(),
+            Box::new(|_: &[serde_json::Value]| Ok::<_, Error>(serde_json::Value::Null)),
+        );
+        let err = runtime
+            .register_functions(functions)
+            .expect_err("Collision was not detected");
+        assert!(err.to_string().contains("existing"));
+
+        // Nothing from the failed batch was registered
+        let fresh_registered: bool = runtime
+            .eval("(() => { try { rustyscript.functions['ns.fresh'](); return true; } catch { return false; } })()")
+            .expect("Could not eval");
+        assert!(!fresh_registered);
+    }
+
+    #[test]
+    #[allow(deprecated)]
     fn test_into_arg() {
         assert_eq!(2, Runtime::into_arg(2));
         assert_eq!("test", Runtime::into_arg("test"));
@@ -1137,312 +3866,3338 @@ mod test_runtime {
     }
 
     #[test]
-    fn test_get_value() {
+    fn test_get_value() {
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.a = 2;
+            export const b = 'test';
+            export const fnc = null;
+        ",
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+
+        assert_eq!(
+            2,
+            runtime
+                .get_value::<usize>(Some(&module), "a")
+                .expect("Could not find global")
+        );
+        assert_eq!(
+            "test",
+            runtime
+                .get_value::<String>(Some(&module), "b")
+                .expect("Could not find export")
+        );
+        runtime
+            .get_value::<Undefined>(Some(&module), "c")
+            .expect_err("Could not detect null");
+        runtime
+            .get_value::<Undefined>(Some(&module), "d")
+            .expect_err("Could not detect undeclared");
+    }
+
+    #[test]
+    #[cfg(not(feature = "fs_import"))]
+    fn test_trusted_module() {
+        let source = "
+            import { MY_FAVOURITE_FOOD } from './example_module.js';
+            export const food = MY_FAVOURITE_FOOD;
+        ";
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new("examples/javascript/untrusted.js", source);
+        runtime
+            .load_module(&module)
+            .expect_err("Untrusted module imported from the filesystem");
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new("examples/javascript/trusted.js", source).with_trusted(true);
+        let handle = runtime
+            .load_module(&module)
+            .expect("Trusted module could not import from the filesystem");
+        let food: String = runtime
+            .get_value(Some(&handle), "food")
+            .expect("Could not get value");
+        assert_eq!(food, "saskatoonberries");
+    }
+
+    #[test]
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    fn test_call_function_outcome() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const ok = (x) => x * 2;
+            export const fail = () => { throw new Error('msg'); };
+            export const reject = async () => { throw new Error('msg'); };
+            export const slow = () => new Promise((r) => setTimeout(r, 5000));
+            ",
+        );
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let outcome: CallOutcome<i64> = runtime
+            .call_function_outcome(Some(&module), "ok", json_args!(2))
+            .expect("Could not call function");
+        assert!(matches!(outcome, CallOutcome::Returned(4)));
+
+        for name in ["fail", "reject"] {
+            let outcome: CallOutcome<i64> = runtime
+                .call_function_outcome(Some(&module), name, json_args!())
+                .expect("Could not call function");
+            assert!(matches!(outcome, CallOutcome::Threw(_)), "{name}");
+        }
+
+        let outcome: CallOutcome<Undefined> = runtime
+            .call_function_outcome(Some(&module), "slow", json_args!())
+            .expect("Could not call function");
+        assert!(matches!(outcome, CallOutcome::TimedOut));
+
+        runtime
+            .call_function_outcome::<i64>(Some(&module), "missing", json_args!())
+            .expect_err("Missing function was not an error");
+
+        // Failures of the runtime itself are not blamed on the script
+        let outcome = CallOutcome::<i64>::classify(Err(Error::Runtime("host".to_string())));
+        assert!(matches!(outcome, Err(Error::Runtime(_))));
+    }
+
+    #[test]
+    fn test_import_meta() {
+        use crate::traits::ToModuleSpecifier;
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const url = import.meta.url;
+            export const main = import.meta.main;
+            export const env = import.meta.env;
+            ",
+        )
+        .with_import_meta("env", "production");
+        let specifier = module.filename().to_module_specifier(None).unwrap();
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let handle = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+
+        let url: String = runtime.get_value(Some(&handle), "url").unwrap();
+        assert_eq!(url, specifier.as_str());
+
+        let main: bool = runtime.get_value(Some(&handle), "main").unwrap();
+        assert!(main);
+
+        let env: String = runtime.get_value(Some(&handle), "env").unwrap();
+        assert_eq!(env, "production");
+
+        // A hashbang stays on the first line, and line numbers are unchanged
+        let module = Module::new(
+            "hashbang.js",
+            "#!/usr/bin/env node\nexport const env = import.meta.env;\nexport const line = new Error().stack.split('\\n')[1];",
+        )
+        .with_import_meta("env", "staging");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let env: String = runtime.get_value(Some(&handle), "env").unwrap();
+        assert_eq!(env, "staging");
+        let line: String = runtime.get_value(Some(&handle), "line").unwrap();
+        assert!(line.contains("hashbang.js:3"), "{line}");
+
+        // JSON modules are left untouched
+        let module = Module::new("data.json", r#"{ "a": 1 }"#).with_import_meta("env", "staging");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let data: serde_json::Value = runtime.get_value(Some(&handle), "default").unwrap();
+        assert_eq!(data, serde_json::json!({ "a": 1 }));
+    }
+
+    #[test]
+    fn test_load_modules_lazy() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .load_modules_lazy(&[
+                Module::new(
+                    "lazy/counter.js",
+                    "globalThis.runs = (globalThis.runs ?? 0) + 1; export const n = 1;",
+                ),
+                Module::new(
+                    "lazy/main.js",
+                    "import { n } from './counter.js'; export default () => n + 1;",
+                ),
+                Module::new("lazy/broken.js", "throw new Error('Should not run');"),
+            ])
+            .expect("Could not load modules");
+
+        // Nothing has run yet
+        let runs: Option<usize> = runtime.eval("globalThis.runs").unwrap();
+        assert_eq!(runs, None);
+
+        let main = runtime
+            .lazy_module("lazy/main.js")
+            .expect("Could not run main");
+        let value: usize = runtime.call_entrypoint(&main, json_args!()).unwrap();
+        assert_eq!(value, 2);
+
+        // Already run as an import, and not run again
+        runtime
+            .lazy_module("lazy/counter.js")
+            .expect("Could not get counter");
+        runtime
+            .lazy_module("lazy/counter.js")
+            .expect("Could not get counter twice");
+        let runs: usize = runtime.eval("globalThis.runs").unwrap();
+        assert_eq!(runs, 1);
+
+        runtime
+            .lazy_module("lazy/missing.js")
+            .expect_err("Found a module that was never loaded");
+    }
+
+    #[test]
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    fn test_pending_ops() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        assert!(runtime.pending_ops().is_empty());
+
+        runtime
+            .eval::<Undefined>("globalThis.timer = setInterval(() => {}, 1000)")
+            .expect("Could not start timer");
+        let pending = runtime.pending_ops();
+        assert_eq!(1, pending.len());
+        assert_eq!(OpKind::Interval, pending[0].kind);
+
+        runtime
+            .eval::<Undefined>("clearInterval(globalThis.timer)")
+            .expect("Could not stop timer");
+        assert!(runtime.pending_ops().is_empty());
+    }
+
+    #[test]
+    fn test_with_isolate() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        // Safety: only reads from the isolate
+        let used = unsafe {
+            runtime.with_isolate(|isolate| {
+                let mut stats = deno_core::v8::HeapStatistics::default();
+                isolate.get_heap_statistics(&mut stats);
+                stats.used_heap_size()
+            })
+        };
+        assert!(used > 0);
+
+        let value: i64 = runtime.eval("1 + 1").expect("Could not eval");
+        assert_eq!(2, value);
+    }
+
+    #[test]
+    fn test_clear_timers() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        assert_eq!(0, runtime.clear_timers().expect("Could not clear timers"));
+
+        runtime
+            .eval::<Undefined>(
+                "
+                globalThis.fired = false;
+                setInterval(() => {}, 1000);
+                setTimeout(() => { globalThis.fired = true; }, 1);
+            ",
+            )
+            .expect("Could not start timers");
+        assert_eq!(2, runtime.pending_ops_count());
+
+        assert_eq!(2, runtime.clear_timers().expect("Could not clear timers"));
+        assert_eq!(0, runtime.pending_ops_count());
+
+        // Nothing is left to keep the event loop alive, or to fire later
+        runtime
+            .block_on_event_loop(
+                deno_core::PollEventLoopOptions::default(),
+                Some(Duration::from_secs(1)),
+            )
+            .expect("Event loop did not complete");
+        let fired: bool = runtime.eval("globalThis.fired").expect("Could not eval");
+        assert!(!fired);
+
+        // Timers queued for manual ticks are cleared as well
+        let mut runtime = Runtime::new(RuntimeOptions {
+            manual_ticks: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .eval::<Undefined>("setTimeout(() => {}, 1); setInterval(() => {}, 1);")
+            .expect("Could not start timers");
+        assert_eq!(2, runtime.clear_timers().expect("Could not clear timers"));
+        assert_eq!(0, runtime.tick().expect("Could not tick"));
+    }
+
+    #[test]
+    #[cfg(feature = "webstorage")]
+    fn test_webstorage_quota() {
+        use crate::{StorageQuotaPolicy, WebStorageQuota};
+
+        let mut options = RuntimeOptions::default();
+        options.extension_options.webstorage_quota = Some(WebStorageQuota {
+            max_bytes: 8,
+            policy: StorageQuotaPolicy::Reject,
+        });
+        let mut runtime = Runtime::new(options).expect("Could not create the runtime");
+        let error: String = runtime
+            .eval(
+                "
+                sessionStorage.setItem('a', '123');
+                try { sessionStorage.setItem('b', '12345'); 'none' } catch (e) { e.name }
+                ",
+            )
+            .expect("Could not eval");
+        assert_eq!(error, "QuotaExceededError");
+
+        let mut options = RuntimeOptions::default();
+        options.extension_options.webstorage_quota = Some(WebStorageQuota {
+            max_bytes: 8,
+            policy: StorageQuotaPolicy::EvictOldest,
+        });
+        let mut runtime = Runtime::new(options).expect("Could not create the runtime");
+        let keys: Vec<String> = runtime
+            .eval(
+                "
+                sessionStorage.setItem('a', '123');
+                sessionStorage.setItem('b', '12345');
+                [...Array(sessionStorage.length).keys()].map((i) => sessionStorage.key(i))
+                ",
+            )
+            .expect("Could not eval");
+        assert_eq!(keys, vec!["b"]);
+
+        // Rewriting an entry makes it the newest, and removals free their space
+        let keys: Vec<String> = runtime
+            .eval(
+                "
+                sessionStorage.clear();
+                sessionStorage.setItem('a', '1');
+                sessionStorage.setItem('b', '1');
+                sessionStorage.setItem('c', '1');
+                sessionStorage.setItem('a', '2');
+                sessionStorage.setItem('d', '12');
+                sessionStorage.removeItem('c');
+                sessionStorage.setItem('e', '1');
+                [...Array(sessionStorage.length).keys()].map((i) => sessionStorage.key(i)).sort()
+                ",
+            )
+            .expect("Could not eval");
+        assert_eq!(keys, vec!["a", "d", "e"]);
+    }
+
+    #[test]
+    fn test_compile_constants() {
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_compile_constant("FLAG_X", false)
+            .with_compile_constant("TARGET", "production")
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "test.ts",
+            "
+            declare const FLAG_X: boolean;
+            export const branch = FLAG_X ? 'enabled' : 'disabled';
+            export const target = `${TARGET}`;
+            export const untouched = { FLAG_X: 1 }.FLAG_X + 'FLAG_X'; // FLAG_X
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let branch: String = runtime.get_value(Some(&handle), "branch").unwrap();
+        assert_eq!(branch, "disabled");
+
+        let target: String = runtime.get_value(Some(&handle), "target").unwrap();
+        assert_eq!(target, "production");
+
+        let untouched: String = runtime.get_value(Some(&handle), "untouched").unwrap();
+        assert_eq!(untouched, "1FLAG_X");
+
+        // Bindings that shadow a constant keep their own values
+        let module = Module::new(
+            "shadowing.js",
+            "
+            import { TARGET as imported } from './shadowing_lib.js';
+            const param = (FLAG_X) => FLAG_X;
+            const { FLAG_X: renamed, TARGET } = { FLAG_X: 'destructured', TARGET: 'local' };
+            const maybe = { FLAG_X: 'optional' };
+            export const values = [param('argument'), renamed, TARGET, imported, maybe?.FLAG_X];
+            export const shorthand = { FLAG_X };
+            ",
+        );
+        let lib = Module::new("shadowing_lib.js", "export const TARGET = 'imported';");
+        let handle = runtime
+            .load_modules(&module, vec![&lib])
+            .expect("Could not load module");
+
+        let values: Vec<String> = runtime.get_value(Some(&handle), "values").unwrap();
+        assert_eq!(
+            values,
+            vec!["argument", "destructured", "local", "imported", "optional"]
+        );
+
+        let shorthand: serde_json::Value = runtime.get_value(Some(&handle), "shorthand").unwrap();
+        assert_eq!(shorthand, serde_json::json!({ "FLAG_X": false }));
+    }
+
+    #[test]
+    fn test_commonjs() {
+        use crate::module_loader::CommonJsMode;
+
+        let module = Module::new(
+            "legacy.js",
+            "
+            function add(a, b) { return a + b; }
+            exports.add = add;
+            module.exports.version = 2;
+            exports.load = (name) => require(name);
+            ",
+        );
+
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_commonjs(CommonJsMode::Convert)
+            .build()
+            .expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let sum: i64 = runtime
+            .call_function(Some(&handle), "add", json_args!(1, 2))
+            .expect("Could not call named export");
+        assert_eq!(3, sum);
+
+        let version: i64 = runtime
+            .get_value(Some(&handle), "version")
+            .expect("Could not get named export");
+        assert_eq!(2, version);
+
+        let exports: serde_json::Value = runtime
+            .get_value(Some(&handle), "default")
+            .expect("Could not get default export");
+        assert_eq!(serde_json::json!(2), exports["version"]);
+
+        let e = runtime
+            .call_function::<Undefined>(Some(&handle), "load", json_args!("fs"))
+            .unwrap_err();
+        assert!(e.to_string().contains("Cannot require 'fs'"));
+
+        // Rejected outright, naming the module
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_commonjs(CommonJsMode::Reject)
+            .build()
+            .expect("Could not create the runtime");
+        let e = runtime.load_module(&module).unwrap_err();
+        assert!(e.to_string().contains("CommonJS"));
+
+        // ES modules are never affected
+        let module = Module::new("esm.js", "export const exports = { a: 1 }; exports.a = 2;");
+        runtime
+            .load_module(&module)
+            .expect("Could not load ES module");
+    }
+
+    #[test]
+    fn test_deno_stub() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        let has_env: bool = runtime.eval("'env' in Deno").expect("Could not eval");
+        assert!(!has_env);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            deno_stub: Some(
+                DenoStub::new()
+                    .with_env("MODE", "test")
+                    .with_cwd("/app")
+                    .with_args(&["--fast"]),
+            ),
+            ..Default::default()
+        })
+        .expect("Could not create runtime");
+
+        let values: Vec<serde_json::Value> = runtime
+            .eval("[Deno.env.get('MODE'), Deno.env.get('HOME'), Deno.cwd(), Deno.args, typeof Deno.build.os]")
+            .expect("Could not eval");
+        assert_eq!(
+            vec![
+                serde_json::json!("test"),
+                serde_json::Value::Null,
+                serde_json::json!("/app"),
+                serde_json::json!(["--fast"]),
+                serde_json::json!("string"),
+            ],
+            values
+        );
+
+        let e = runtime
+            .eval::<Undefined>("Deno.env.set('MODE', 'prod')")
+            .unwrap_err();
+        assert!(e.to_string().contains("PermissionDenied"));
+
+        // The namespace itself cannot be replaced
+        let mode: String = runtime
+            .eval("Deno.env = null; Deno.env.get('MODE')")
+            .expect("Could not eval");
+        assert_eq!("test", mode);
+    }
+
+    #[test]
+    fn test_duplicate_registration() {
+        let constant = |value: i64| move |_: &[serde_json::Value]| Ok(serde_json::json!(value));
+
+        for (policy, expected) in [
+            (DuplicateRegistration::Overwrite, 2),
+            (DuplicateRegistration::KeepFirst, 1),
+            (DuplicateRegistration::Reject, 1),
+        ] {
+            let mut runtime = Runtime::new(RuntimeOptions {
+                duplicate_registration: policy,
+                ..Default::default()
+            })
+            .expect("Could not create the runtime");
+            runtime
+                .register_function("value", constant(1))
+                .expect("Could not register function");
+
+            let result = runtime.register_function("value", constant(2));
+            if policy == DuplicateRegistration::Reject {
+                assert!(
+                    matches!(result, Err(Error::FunctionAlreadyRegistered(name)) if name == "value")
+                );
+            } else {
+                result.expect("Could not register function");
+            }
+
+            let value: i64 = runtime
+                .eval("rustyscript.functions.value()")
+                .expect("Could not call function");
+            assert_eq!(expected, value, "{policy:?}");
+
+            // Batches reject names in use whatever the policy
+            let mut functions: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+            functions.insert("value".to_string(), Box::new(constant(3)));
+            assert!(matches!(
+                runtime.register_functions(functions),
+                Err(Error::FunctionAlreadyRegistered(name)) if name == "value"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_register_typed_function() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_typed_function("add", |(a, b): (i64, i64)| Ok(a + b))
+            .expect("Could not register function");
+        runtime
+            .register_typed_function("answer", |(): ()| Ok(42))
+            .expect("Could not register function");
+
+        let value: i64 = runtime
+            .eval("rustyscript.functions.add(1, 2)")
+            .expect("Could not call function");
+        assert_eq!(3, value);
+
+        let value: i64 = runtime
+            .eval("rustyscript.functions.answer()")
+            .expect("Could not call function");
+        assert_eq!(42, value);
+
+        let e = runtime
+            .eval::<i64>("rustyscript.functions.add(1)")
+            .expect_err("Wrong arity was accepted");
+        assert!(e.to_string().contains("Invalid arguments for `add`"));
+
+        runtime
+            .eval::<i64>("rustyscript.functions.add(1, 2, 3)")
+            .expect_err("Wrong arity was accepted");
+        runtime
+            .eval::<i64>("rustyscript.functions.add('1', 2)")
+            .expect_err("Wrong type was accepted");
+    }
+
+    #[test]
+    fn test_run_hooks() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "plugin.js",
+            "
+            export const log = [];
+            export const stop = 'not a hook';
+            export function init(n) { log.push(`init ${n}`); }
+            export async function start(n) {
+                await new Promise(r => setTimeout(r, 10));
+                log.push(`start ${n}`);
+            }
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let ran = runtime
+            .run_hooks(
+                &module,
+                &["init", "start", "stop", "missing"],
+                json_args!(1),
+            )
+            .expect("Could not run hooks");
+        assert_eq!(vec!["init", "start"], ran);
+
+        let log: Vec<String> = runtime
+            .get_value(Some(&module), "log")
+            .expect("Could not get log");
+        assert_eq!(vec!["init 1", "start 1"], log);
+
+        let module = Module::new(
+            "failing_plugin.js",
+            "
+            export let started = false;
+            export async function init() { throw new Error('init failed'); }
+            export function start() { started = true; }
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let e = runtime
+            .run_hooks(&module, &["init", "start"], json_args!())
+            .expect_err("Failing hook did not stop the lifecycle");
+        assert!(e.to_string().contains("init failed"));
+
+        let started: bool = runtime
+            .get_value(Some(&module), "started")
+            .expect("Could not get value");
+        assert!(!started);
+    }
+
+    #[test]
+    fn test_virtual_fs() {
+        let files = std::collections::HashMap::from([
+            (
+                "vfs/utils.js".to_string(),
+                "export const x = 5;".to_string(),
+            ),
+            (
+                "./vfs/nested/more.js".to_string(),
+                "export const z = 2;".to_string(),
+            ),
+        ]);
+        let mut runtime = Runtime::new(RuntimeOptions {
+            virtual_fs: Some(Rc::new(crate::module_loader::VirtualFiles::from(files))),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "vfs/main.js",
+            "import { x } from './utils.js'; import { z } from './nested/more.js'; export const y = x * z;",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let y: i32 = runtime
+            .get_value(Some(&module), "y")
+            .expect("Could not get value");
+        assert_eq!(10, y);
+
+        // Files missing from the virtual filesystem fall through to the usual rules
+        let module = Module::new("vfs/other.js", "import { q } from './missing.js';");
+        runtime
+            .load_module(&module)
+            .expect_err("Missing file should not load");
+    }
+
+    #[test]
+    fn test_run_captured() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            capture_console: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let (result, output) = runtime.run_captured(8, |runtime| {
+            runtime.eval::<Undefined>(
+                "console.log('é'.repeat(10)); console.warn('careful'); throw new Error('failed');",
+            )
+        });
+        result.expect_err("Error should be returned");
+        assert_eq!("éééé", output.stdout);
+        assert!(output.stdout_truncated);
+        assert_eq!("careful\n", output.stderr);
+        assert!(!output.stderr_truncated);
+
+        // The host's handler is restored
+        runtime
+            .eval::<Undefined>("console.log('after')")
+            .expect("Could not log");
+        let events = runtime.take_console_output();
+        assert_eq!(1, events.len());
+        assert_eq!("after", events[0].message);
+    }
+
+    #[test]
+    fn test_eval_async() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let tokio_runtime = runtime.tokio_runtime();
+
+        let value: i64 = tokio_runtime
+            .block_on(runtime.eval_async("await Promise.resolve(2) * 21"))
+            .expect("Could not evaluate");
+        assert_eq!(42, value);
+
+        // Global changes persist between evaluations
+        let _: Undefined = tokio_runtime
+            .block_on(runtime.eval_async("globalThis.counter = await Promise.resolve(1)"))
+            .expect("Could not evaluate");
+        let value: i64 = tokio_runtime
+            .block_on(runtime.eval_async("counter + 1 // trailing comment"))
+            .expect("Could not evaluate");
+        assert_eq!(2, value);
+
+        // Scripts are sloppy-mode, unlike modules, so `this` is the global object in plain functions
+        let sloppy: bool = tokio_runtime
+            .block_on(runtime.eval_async("(function () { return this === globalThis; })()"))
+            .expect("Could not evaluate");
+        assert!(sloppy);
+
+        tokio_runtime
+            .block_on(runtime.eval_async::<Undefined>("await Promise.reject(new Error('nope'))"))
+            .expect_err("Rejection should be an error");
+    }
+
+    #[test]
+    fn test_replace_function() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            duplicate_registration: DuplicateRegistration::Reject,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .replace_function("level", |_| Ok(serde_json::Value::from(1)))
+            .expect_err("Nothing to replace");
+
+        runtime
+            .register_function("level", |_| Ok(serde_json::Value::from(1)))
+            .expect("Could not register function");
+        let module = Module::new(
+            "test.js",
+            "export const level = () => rustyscript.functions.level();",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .call_function(Some(&module), "level", json_args!())
+            .expect("Could not call function");
+        assert_eq!(1, value);
+
+        runtime
+            .replace_function("level", |_| Ok(serde_json::Value::from(2)))
+            .expect("Could not replace function");
+        let value: i64 = runtime
+            .call_function(Some(&module), "level", json_args!())
+            .expect("Could not call function");
+        assert_eq!(2, value);
+
+        runtime
+            .register_async_function("fetch_level", |_| {
+                Box::pin(async { Ok(serde_json::Value::from(3)) })
+            })
+            .expect("Could not register function");
+        runtime
+            .replace_async_function("fetch_level", |_| {
+                Box::pin(async { Ok(serde_json::Value::from(4)) })
+            })
+            .expect("Could not replace function");
+        let tokio_runtime = runtime.tokio_runtime();
+        let value: i64 = tokio_runtime
+            .block_on(runtime.eval_async("await rustyscript.async_functions.fetch_level()"))
+            .expect("Could not call function");
+        assert_eq!(4, value);
+    }
+
+    #[test]
+    fn test_register_simple_async_function() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_simple_async_function("sum", |args| async move {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                let sum: i64 = args.iter().filter_map(serde_json::Value::as_i64).sum();
+                Ok(serde_json::Value::from(sum))
+            })
+            .expect("Could not register function");
+        runtime
+            .register_simple_async_function("fail", |_| async move {
+                Err(Error::Runtime("lookup failed".to_string()))
+            })
+            .expect("Could not register function");
+
+        let tokio_runtime = runtime.tokio_runtime();
+        let value: i64 = tokio_runtime
+            .block_on(runtime.eval_async("await rustyscript.async_functions.sum(1, 2, 3)"))
+            .expect("Could not call function");
+        assert_eq!(6, value);
+
+        let message: String = tokio_runtime
+            .block_on(
+                runtime
+                    .eval_async("await rustyscript.async_functions.fail().catch((e) => e.message)"),
+            )
+            .expect("Could not call function");
+        assert!(message.contains("lookup failed"));
+    }
+
+    #[cfg(feature = "snapshot_builder")]
+    #[test]
+    fn test_take_snapshot() {
+        let runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .take_snapshot()
+            .expect_err("Standard runtimes cannot be snapshotted");
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            snapshottable: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "snapshot_setup.js",
+            "const table = new Map([[1, 'one'], [2, 'two']]); globalThis.lookup = (n) => table.get(n);",
+        );
+        runtime.load_module(&module).expect("Could not load module");
+        let snapshot: &'static [u8] =
+            Box::leak(runtime.take_snapshot().expect("Could not take snapshot"));
+
+        for (n, expected) in [(1, "one"), (2, "two")] {
+            let mut runtime = Runtime::new(RuntimeOptions {
+                startup_snapshot: Some(snapshot),
+                ..Default::default()
+            })
+            .expect("Could not create the runtime");
+            let value: String = runtime
+                .eval(&format!("lookup({n})"))
+                .expect("Could not evaluate");
+            assert_eq!(expected, value);
+        }
+    }
+
+    #[cfg(feature = "snapshot_builder")]
+    #[test]
+    fn test_runtime_extensions_on_snapshot() {
+        #[deno_core::op2(fast)]
+        fn op_host_capability() -> u32 {
+            7
+        }
+        extension!(host_capability, ops = [op_host_capability]);
+        extension!(rustyscript);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            snapshottable: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new("snapshot_base.js", "globalThis.base = (n) => n * 10;");
+        runtime.load_module(&module).expect("Could not load module");
+        let snapshot: &'static [u8] =
+            Box::leak(runtime.take_snapshot().expect("Could not take snapshot"));
+
+        // The frozen base and the runtime-only op are both available
+        let mut runtime = Runtime::new(RuntimeOptions {
+            startup_snapshot: Some(snapshot),
+            runtime_extensions: vec![host_capability::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .expect("Could not create runtime with runtime extensions");
+        let value: u32 = runtime
+            .eval("base(Deno.core.ops.op_host_capability())")
+            .expect("Runtime extension op was not available");
+        assert_eq!(70, value);
+
+        // Extensions already in the snapshot cannot be added again
+        let err = Runtime::new(RuntimeOptions {
+            startup_snapshot: Some(snapshot),
+            runtime_extensions: vec![rustyscript::init_ops_and_esm()],
+            ..Default::default()
+        })
+        .err()
+        .expect("Duplicate extension was not detected");
+        assert!(err.to_string().contains("already loaded"));
+    }
+
+    #[test]
+    fn test_run_default() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 'registered');
+            export default async (a, b) => {
+                await new Promise((resolve) => setTimeout(resolve, 5));
+                return a + b;
+            };
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .run_default(&module, json_args!(2, 3))
+            .expect("Could not run default export");
+        assert_eq!(5, value);
+
+        let module = Module::new("no_default.js", "export const x = 1;");
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let e = runtime
+            .run_default::<Undefined>(&module, json_args!())
+            .expect_err("Module has no default export");
+        assert!(matches!(e, Error::MissingEntrypoint(_)));
+
+        let module = Module::new("value_default.js", "export default 5;");
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let e = runtime
+            .run_default::<Undefined>(&module, json_args!())
+            .expect_err("Default export is not a function");
+        assert!(matches!(e, Error::ValueNotCallable(_)));
+    }
+
+    #[test]
+    fn test_call_batch() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_module(&Module::new(
+                "test_call_batch.js",
+                "
+                globalThis.order = [];
+                export const add = (a, b) => { order.push('add'); return a + b; };
+                export const fail = () => { order.push('fail'); throw new Error('sync failure'); };
+                export const reject = async () => { order.push('reject'); throw new Error('async failure'); };
+                export const wait = async (ms) => {
+                    order.push('wait');
+                    await new Promise((resolve) => setTimeout(resolve, ms));
+                    return ms;
+                };
+                ",
+            ))
+            .expect("Could not load module");
+
+        let results = runtime.call_batch(&[
+            (Some(&module), "wait", serde_json::json!([20])),
+            (Some(&module), "fail", serde_json::json!([])),
+            (Some(&module), "add", serde_json::json!([1, 2])),
+            (Some(&module), "reject", serde_json::json!([])),
+            (Some(&module), "wait", serde_json::json!([10])),
+        ]);
+
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap(), 20);
+        assert!(
+            matches!(&results[1], Err(Error::JsError(e)) if e.message.contains("sync failure"))
+        );
+        assert_eq!(results[2].as_ref().unwrap(), 3);
+        assert!(
+            matches!(&results[3], Err(Error::JsError(e)) if e.message.contains("async failure"))
+        );
+        assert_eq!(results[4].as_ref().unwrap(), 10);
+
+        // Calls are made in order
+        let order: Vec<String> = runtime.eval("order").unwrap();
+        assert_eq!(order, vec!["wait", "fail", "add", "reject", "wait"]);
+
+        // A failure of the whole batch keeps its type in every result
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = runtime
+            .load_module(&Module::new(
+                "test_call_batch_timeout.js",
+                "
+                export const add = (a, b) => a + b;
+                export const wait = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+                ",
+            ))
+            .expect("Could not load module");
+        let results = runtime.call_batch(&[
+            (Some(&module), "add", serde_json::json!([1, 2])),
+            (Some(&module), "wait", serde_json::json!([60 * 1000])),
+        ]);
+        assert!(results.iter().all(|r| matches!(r, Err(Error::Timeout(_)))));
+    }
+
+    #[test]
+    fn test_module_with_type() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        let data = Module::with_type(
+            "module_type/data.txt",
+            r#"{ "name": "app", "port": 8080 }"#,
+            crate::ModuleType::Json,
+        );
+        let handle = runtime.load_module(&data).expect("Could not load module");
+        let value: serde_json::Value = runtime
+            .get_value(Some(&handle), "default")
+            .expect("Could not get value");
+        assert_eq!(serde_json::json!({ "name": "app", "port": 8080 }), value);
+
+        let main = Module::new(
+            "module_type/main.js",
+            "import data from './data.txt' with { type: 'json' }; export const port = data.port;",
+        );
+        let handle = runtime.load_module(&main).expect("Could not load module");
+        let port: u16 = runtime
+            .get_value(Some(&handle), "port")
+            .expect("Could not get value");
+        assert_eq!(8080, port);
+
+        let script = Module::with_type(
+            "module_type/script",
+            "export const value = 1;",
+            crate::ModuleType::JavaScript,
+        );
+        let handle = runtime.load_module(&script).expect("Could not load module");
+        let value: i64 = runtime
+            .get_value(Some(&handle), "value")
+            .expect("Could not get value");
+        assert_eq!(1, value);
+
+        let invalid =
+            Module::with_type("module_type/invalid.js", "{ nope", crate::ModuleType::Json);
+        assert!(runtime.load_module(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_compile_module_to_bytes() {
+        let source = "export const double = (x: number): number => x * 2;";
+        let module = Module::new("code_cache/typed.ts", source);
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let bytes = runtime
+            .compile_module_to_bytes(&module)
+            .expect("Could not compile module");
+        assert!(!bytes.is_empty());
+
+        // v8 only produces new bytecode when it could not use what it was given,
+        // so an empty cache directory shows that the bytes were accepted
+        let dir = std::env::temp_dir().join(format!(
+            "rustyscript_compile_to_bytes_{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        let stored = || std::fs::read_dir(&dir).map_or(0, Iterator::count);
+
+        let module = Module::from_code_cache("code_cache/typed.ts", source, bytes.clone());
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_code_cache_dir(&dir)
+            .build()
+            .expect("Could not create the runtime");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .call_function(Some(&handle), "double", json_args!(21))
+            .expect("Could not call function");
+        assert_eq!(42, value);
+        assert_eq!(0, stored());
+
+        // Bytes compiled from other code are ignored, and the module compiled normally
+        let module =
+            Module::from_code_cache("code_cache/stale.js", "export const value = 1;", bytes);
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .get_value(Some(&handle), "value")
+            .expect("Could not get value");
+        assert_eq!(1, value);
+        assert_eq!(1, stored());
+        std::fs::remove_dir_all(&dir).ok();
+
+        let invalid = Module::new("code_cache/invalid.js", "export const = ;");
+        assert!(runtime.compile_module_to_bytes(&invalid).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn test_io_options() {
+        // Collects everything written to stdout
+        #[derive(Clone, Default)]
+        struct SharedWriter(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for SharedWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = SharedWriter::default();
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_io(crate::IoOptions {
+                stdin: Some(Box::new(std::io::Cursor::new(b"hello".to_vec()))),
+                stdout: Some(Box::new(output.clone())),
+                ..Default::default()
+            })
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "io_options.js",
+            "
+            const buffer = new Uint8Array(16);
+            const n = await Deno.stdin.read(buffer);
+            const text = new TextDecoder().decode(buffer.subarray(0, n));
+            await Deno.stdout.write(new TextEncoder().encode(text.toUpperCase()));
+            ",
+        );
+        runtime.load_module(&module).expect("Could not load module");
+
+        // Dropping the runtime waits for its output to be delivered
+        drop(runtime);
+        assert_eq!(b"HELLO", output.0.lock().unwrap().as_slice());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        assert_eq!(runtime.exit_code(), None);
+
+        runtime
+            .eval::<Undefined>("rustyscript.setExitCode(2)")
+            .expect("setExitCode should not stop the script");
+        assert_eq!(runtime.exit_code(), Some(2));
+
+        let e = runtime
+            .eval::<Undefined>(
+                "try { rustyscript.exit(5) } catch { } finally { throw new Error('unreachable') }",
+            )
+            .unwrap_err();
+        assert!(!e.to_string().contains("unreachable"));
+        assert_eq!(runtime.exit_code(), Some(5));
+
+        // The next call starts without an exit status, on a usable isolate
+        let value: usize = runtime
+            .eval("1 + 1")
+            .expect("Runtime should be usable after exit");
+        assert_eq!(value, 2);
+        assert_eq!(runtime.exit_code(), None);
+    }
+
+    #[test]
+    fn test_check_function_calls() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        runtime
+            .register_function("add", |_| Ok(serde_json::Value::Null))
+            .expect("Could not register function");
+        runtime
+            .register_async_function("fetch", |_| {
+                Box::pin(async { Ok::<_, Error>(serde_json::Value::Null) })
+            })
+            .expect("Could not register function");
+
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.functions.add(1, 2);
+            rustyscript.functions.add(1);
+            rustyscript.functions.add(...[1, 2, 3]);
+            rustyscript.functions.fetch();
+            await rustyscript.async_functions.fetch('a');
+            ",
+        );
+        let issues = runtime
+            .check_function_calls(&module, &[("add", 2)])
+            .expect("Could not check calls");
+
+        assert_eq!(2, issues.len());
+        assert!(matches!(
+            &issues[0],
+            crate::BindingIssue::ArityMismatch { call, expected: 2 } if call.line == 3
+        ));
+        assert!(matches!(
+            &issues[1],
+            crate::BindingIssue::Unregistered(call) if call.name == "fetch" && call.line == 5
+        ));
+    }
+
+    #[test]
+    fn test_eval_function() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        let scale = runtime
+            .eval_function("(x, factor = 3) => x * factor")
+            .expect("Could not evaluate lambda");
+        for (x, expected) in [(1, 3), (2, 6), (5, 15)] {
+            let value: i64 = scale
+                .call(&mut runtime, None, json_args!(x))
+                .expect("Could not call lambda");
+            assert_eq!(expected, value);
+        }
+
+        runtime
+            .eval_function("2 + 2")
+            .expect_err("Non-function was accepted");
+    }
+
+    #[test]
+    fn test_language_plugin() {
+        struct Assignments;
+        impl crate::module_loader::LanguagePlugin for Assignments {
+            fn transform(
+                &self,
+                _specifier: &deno_core::ModuleSpecifier,
+                source: &str,
+            ) -> Result<String, deno_core::anyhow::Error> {
+                Ok(source
+                    .lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(name, value)| {
+                        format!("export const {} = {};\n", name.trim(), value.trim())
+                    })
+                    .collect())
+            }
+        }
+
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_language_plugin("vars", Assignments)
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new("config.VARS", "width = 4\nheight = width * 2");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let height: i64 = runtime
+            .get_value(Some(&handle), "height")
+            .expect("Could not get value");
+        assert_eq!(8, height);
+
+        // Plain javascript is untouched
+        let module = Module::new("plain.js", "export const width = 4;");
+        runtime.load_module(&module).expect("Could not load module");
+    }
+
+    #[test]
+    fn test_load_module() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export default () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        assert_ne!(0, module.id());
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module1 = Module::new(
+            "importme.js",
+            "
+            export const value = 2;
+        ",
+        );
+        let module2 = Module::new(
+            "test.js",
+            "
+            import { value } from './importme.js';
+            rustyscript.register_entrypoint(() => value);
+        ",
+        );
+        runtime
+            .load_module(&module1)
+            .expect("Could not load modules");
+        let module = runtime
+            .load_module(&module2)
+            .expect("Could not load modules");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            await new Promise(r => setTimeout(r, 2000));
+        ",
+        );
+        runtime
+            .load_modules(&module, vec![])
+            .expect_err("Did not interupt after timeout");
+    }
+
+    #[test]
+    fn test_load_modules() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        assert_ne!(0, module.id());
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module1 = Module::new(
+            "importme.js",
+            "
+            export const value = 2;
+        ",
+        );
+        let module2 = Module::new(
+            "test.js",
+            "
+            import { value } from './importme.js';
+            rustyscript.register_entrypoint(() => value);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module2, vec![&module1])
+            .expect("Could not load modules");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            await new Promise(r => setTimeout(r, 5000));
+        ",
+        );
+        runtime
+            .load_modules(&module, vec![])
+            .expect_err("Did not interupt after timeout");
+    }
+
+    #[test]
+    fn test_call_entrypoint() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call registered fn");
+        assert_eq!(2, value);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            default_entrypoint: Some("load".to_string()),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export const load = () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        let value: usize = runtime
+            .call_entrypoint(&module, json_args!())
+            .expect("Could not call exported fn");
+        assert_eq!(2, value);
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            "test.js",
+            "
+            export const load = () => 2;
+        ",
+        );
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+        runtime
+            .call_entrypoint::<Undefined>(&module, json_args!())
+            .expect_err("Did not detect no entrypoint");
+    }
+
+    #[test]
+    fn test_execute_module() {
+        let module = Module::new(
+            "test.js",
+            "
+            rustyscript.register_entrypoint(() => 2);
+        ",
+        );
+        let value: usize =
+            Runtime::execute_module(&module, vec![], RuntimeOptions::default(), json_args!())
+                .expect("Could not exec module");
+        assert_eq!(2, value);
+
+        let module = Module::new(
+            "test.js",
+            "
+            function load() { return 2; }
+        ",
+        );
+        Runtime::execute_module::<Undefined>(
+            &module,
+            vec![],
+            RuntimeOptions::default(),
+            json_args!(),
+        )
+        .expect_err("Could not detect no entrypoint");
+    }
+
+    #[test]
+    fn call_function() {
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.fna = (i) => i;
+            export function fnb() { return 'test'; }
+            export const fnc = 2;
+            export const fne = () => {};
+        ",
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_modules(&module, vec![])
+            .expect("Could not load module");
+
+        let result: usize = runtime
+            .call_function(Some(&module), "fna", json_args!(2))
+            .expect("Could not call global");
+        assert_eq!(2, result);
+
+        let result: String = runtime
+            .call_function(Some(&module), "fnb", json_args!())
+            .expect("Could not call export");
+        assert_eq!("test", result);
+
+        runtime
+            .call_function::<Undefined>(Some(&module), "fnc", json_args!())
+            .expect_err("Did not detect non-function");
+        runtime
+            .call_function::<Undefined>(Some(&module), "fnd", json_args!())
+            .expect_err("Did not detect undefined");
+        runtime
+            .call_function::<Undefined>(Some(&module), "fne", json_args!())
+            .expect("Did not allow undefined return");
+    }
+
+    ///
+    /// This function exists to check the safety of builtin core-ops
+    /// This is to confirm that updates to `deno_core` have not introduced any sandbox-breaking changes
+    /// in the form of a new op.
+    fn get_unrecognized_ops() -> Result<Vec<String>, Error> {
+        let mut runtime = Runtime::new(RuntimeOptions::default())?;
+        runtime.load_module(&Module::load("src/ext/op_whitelist.js")?)?;
+        let hnd = runtime.load_module(&Module::new(
+            "test_whitelist.js",
+            "
+            import { whitelist } from './src/ext/op_whitelist.js';
+            let ops = Deno.core.ops.op_op_names();
+            export const unsafe_ops = ops.filter(op => !whitelist.hasOwnProperty(op));
+        ",
+        ))?;
+
+        let unsafe_ops: Vec<String> = runtime.get_value(Some(&hnd), "unsafe_ops")?;
+        Ok(unsafe_ops)
+    }
+
+    #[test]
+    fn confirm_op_whitelist() {
+        let unsafe_ops = get_unrecognized_ops().expect("Could not get unsafe ops");
+        assert_eq!(0, unsafe_ops.len(), "Found unsafe ops: {unsafe_ops:?}.\nOnce confirmed safe, add them to `src/ext/op_whitelist.js`");
+    }
+
+    #[test]
+    fn test_capture_load_errors() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const value = 2;
+            throw new Error('top-level failure');
+            ",
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .load_module(&module)
+            .expect_err("Top-level exception did not fail the load");
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            capture_load_errors: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let handle = runtime
+            .load_module(&module)
+            .expect("Top-level exception was not captured");
+        let error = handle.load_error().expect("No load error recorded");
+        assert!(error.to_string().contains("top-level failure"));
+
+        let module = Module::new("test2.js", "export const value = 2;");
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        assert!(handle.load_error().is_none());
+
+        // A side module's exception is reported against the side module, not the main one
+        let side = Module::new("side.js", "throw new Error('side failure');");
+        let main = Module::new("main.js", "export const value = 2;");
+        let handle = runtime
+            .load_modules(&main, vec![&side])
+            .expect("Side module exception was not captured");
+        assert!(handle.load_error().is_none());
+        let errors: Vec<_> = handle.side_module_errors().collect();
+        assert_eq!(1, errors.len());
+        assert_eq!(side.filename(), errors[0].0);
+        assert!(errors[0].1.to_string().contains("side failure"));
+    }
+
+    #[test]
+    #[cfg(feature = "console")]
+    fn test_console_specifier() {
+        // A registered `console.log` gets the message alone
+        let output = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        let sink = output.clone();
+        runtime
+            .register_function("console.log", move |args| {
+                sink.borrow_mut().push(args.to_vec());
+                Ok(serde_json::Value::Null)
+            })
+            .expect("Could not register function");
+
+        let module = Module::new("console_test.js", "console.log('hello');");
+        runtime.load_module(&module).expect("Could not load module");
+        assert_eq!(vec![vec![serde_json::json!("hello\n")]], *output.borrow());
+
+        // Captured output records where it came from once the host asks for it
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_console_capture()
+            .with_console_locations()
+            .build()
+            .expect("Could not create the runtime");
+        runtime.load_module(&module).expect("Could not load module");
+
+        let output = runtime.take_console_output();
+        assert_eq!(1, output.len());
+        assert_eq!("hello", output[0].message);
+        assert!(output[0]
+            .specifier
+            .as_deref()
+            .is_some_and(|s| s.ends_with("/console_test.js")));
+    }
+
+    #[test]
+    #[cfg(feature = "console")]
+    fn test_console_sink() {
+        let output = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = output.clone();
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_console_sink(move |level, message| {
+                sink.borrow_mut().push((level, message.to_string()));
+            })
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "console_sink.js",
+            "
+            console.log('hello', 1);
+            console.warn('careful');
+            console.error({ code: 2 });
+            console.debug('details');
+            ",
+        );
+        runtime.load_module(&module).expect("Could not load module");
+
+        let output = output.borrow();
+        assert_eq!(
+            vec![
+                (crate::ConsoleLevel::Info, "hello 1".to_string()),
+                (crate::ConsoleLevel::Warn, "careful".to_string()),
+                (crate::ConsoleLevel::Error, "{ code: 2 }".to_string()),
+                (crate::ConsoleLevel::Debug, "details".to_string()),
+            ],
+            *output
+        );
+    }
+
+    #[test]
+    fn test_unhandled_rejection() {
+        let rejections = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = rejections.clone();
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_unhandled_rejection_handler(move |e| {
+                sink.borrow_mut().push((e.name.clone(), e.message.clone()));
+            })
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "rejections.js",
+            "
+            Promise.reject(new TypeError('forgotten'));
+            Promise.reject(new Error('caught')).catch(() => {});
+            export const later = () => { Promise.reject(new RangeError('later')); };
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        runtime
+            .call_function::<Undefined>(Some(&handle), "later", json_args!())
+            .expect("Could not call function");
+        runtime
+            .block_on_event_loop(deno_core::PollEventLoopOptions::default(), None)
+            .expect("Event loop failed");
+
+        assert_eq!(
+            vec![
+                ("TypeError".to_string(), "forgotten".to_string()),
+                ("RangeError".to_string(), "later".to_string()),
+            ],
+            *rejections.borrow()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "console")]
+    fn test_take_console_output() {
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_console_capture()
+            .build()
+            .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "console_capture.js",
+            "
+            export const handle = (id) => {
+                console.debug(`debug ${id}`);
+                console.warn(`warn ${id}`);
+            };
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        assert!(runtime.take_console_output().is_empty());
+
+        for id in 0..2 {
+            runtime
+                .call_function::<Undefined>(Some(&handle), "handle", json_args!(id))
+                .expect("Could not call function");
+
+            let output = runtime.take_console_output();
+            let levels: Vec<_> = output
+                .iter()
+                .map(|e| (e.level, e.message.clone()))
+                .collect();
+            assert_eq!(
+                vec![
+                    (ConsoleLevel::Debug, format!("debug {id}")),
+                    (ConsoleLevel::Warn, format!("warn {id}")),
+                ],
+                levels
+            );
+            assert!(output.iter().all(|e| e.specifier.is_none()));
+        }
+    }
+
+    #[test]
+    fn test_import_relative() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let base = Module::new("examples/javascript/base.js", "export const name = 'base';");
+        let base = runtime.load_module(&base).expect("Could not load module");
+
+        let sibling = runtime
+            .import_relative(&base, "./example_module.js")
+            .expect("Could not import sibling");
+        assert!(sibling
+            .module()
+            .filename()
+            .ends_with("examples/javascript/example_module.js"));
+
+        let food: String = runtime
+            .get_value(Some(&sibling), "MY_FAVOURITE_FOOD")
+            .expect("Could not get export");
+        assert_eq!("saskatoonberries", food);
+
+        runtime
+            .import_relative(&base, "./missing.js")
+            .expect_err("Missing module was imported");
+    }
+
+    #[test]
+    fn test_cancellation_handle() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let handle = runtime.cancellation_handle();
+
+        // Nothing is running, so this must not affect the next call
+        handle.cancel();
+        assert!(!handle.is_running());
+        let value: usize = runtime.eval("1 + 1").expect("Idle cancellation leaked");
+        assert_eq!(2, value);
+
+        let module = Module::new(
+            "test.js",
+            "
+            export function spin() { while (true) {} }
+            export const wait = () => new Promise(r => setTimeout(r, 60 * 1000));
+            ",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        for name in ["spin", "wait"] {
+            let canceller = handle.clone();
+            let thread = std::thread::spawn(move || {
+                while !canceller.is_running() {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                std::thread::sleep(Duration::from_millis(20));
+                canceller.cancel();
+            });
+
+            let result = runtime.call_function::<Undefined>(Some(&module), name, json_args!());
+            assert!(matches!(result, Err(Error::Cancelled)));
+            thread.join().expect("Cancelling thread panicked");
+        }
+
+        assert!(!handle.is_running());
+        let value: usize = runtime
+            .eval("2 + 2")
+            .expect("Runtime unusable after cancellation");
+        assert_eq!(4, value);
+    }
+
+    #[test]
+    fn test_heap_stats() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let before = runtime.heap_stats();
+        assert!(before.used_heap_size > 0);
+        assert!(before.number_of_native_contexts > 0);
+
+        runtime
+            .eval::<Undefined>("globalThis.data = new Array(1024 * 1024).fill(0)")
+            .expect("Could not allocate");
+        let after = runtime.heap_stats();
+        assert!(after.used_heap_size > before.used_heap_size);
+        assert!(after.used_heap_size <= after.total_heap_size);
+    }
+
+    #[test]
+    fn test_transfer_value() {
+        let mut source =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let mut dest =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+
+        let value: crate::js_value::Value = source
+            .eval(
+                "
+                const value = new Map([['when', new Date(1000)], ['bytes', new Uint8Array([1, 2, 3])]]);
+                value.set('self', value);
+                value.set('tags', new Set(['a', 'b']));
+                value
+                ",
+            )
+            .expect("Could not create value");
+        let copy = source
+            .transfer_value(&value, &mut dest)
+            .expect("Could not transfer value");
+
+        {
+            let mut scope = dest.deno_runtime().handle_scope();
+            let context = scope.get_current_context();
+            let global = context.global(&mut scope);
+            let key = deno_core::v8::String::new(&mut scope, "copy").unwrap();
+            let copy = deno_core::v8::Local::new(&mut scope, copy.as_v8());
+            global.set(&mut scope, key.into(), copy);
+        }
+
+        let intact: bool = dest
+            .eval(
+                "
+                copy instanceof Map
+                    && copy.get('self') === copy
+                    && copy.get('when') instanceof Date && copy.get('when').getTime() === 1000
+                    && copy.get('bytes') instanceof Uint8Array && copy.get('bytes')[2] === 3
+                    && copy.get('tags') instanceof Set && copy.get('tags').has('b')
+                ",
+            )
+            .expect("Could not inspect copy");
+        assert!(intact);
+
+        let function: crate::js_value::Value =
+            source.eval("() => 42").expect("Could not create function");
+        let e = source
+            .transfer_value(&function, &mut dest)
+            .expect_err("Function was transferred");
+        assert!(e.to_string().contains("could not be transferred"));
+    }
+
+    #[test]
+    fn test_get_transpiled_source() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        assert!(runtime.get_transpiled_source("transpiled.ts").is_none());
+
+        let module = Module::new(
+            "transpiled.ts",
+            "
+            import { y } from './transpiled_dep.ts';
+            export const x: number = y;
+            ",
+        );
+        let dep = Module::new("transpiled_dep.ts", "export const y: number = 2;");
+        runtime
+            .load_modules(&module, vec![&dep])
+            .expect("Could not load modules");
+
+        for specifier in ["transpiled.ts", "transpiled_dep.ts"] {
+            let source = runtime
+                .get_transpiled_source(specifier)
+                .expect("Transpiled source was not recorded");
+            assert!(source.contains("export const"));
+            assert!(!source.contains(": number"));
+        }
+    }
+
+    #[test]
+    fn test_source_mapped_errors() {
+        let source = "
+interface Shape {
+    width: number;
+    height: number;
+}
+
+export function fail(shape: Shape): number {
+    throw new Error(`bad shape ${shape.width}`);
+}
+";
+        let (js, map) =
+            crate::transpile_with_sourcemap("mapped.ts", source).expect("Could not transpile");
+        assert!(!js.contains("interface"));
+        assert!(map.is_some());
+
+        let (_, map) = crate::transpile_with_sourcemap("mapped.js", "export const x = 1;")
+            .expect("Could not transpile");
+        assert!(map.is_none());
+
+        // Errors thrown from TS report positions in the original source
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        let module = runtime
+            .load_module(&Module::new("mapped.ts", source))
+            .expect("Could not load module");
+        let e = runtime
+            .call_function::<Undefined>(
+                Some(&module),
+                "fail",
+                json_args!({"width": 1, "height": 2}),
+            )
+            .expect_err("Function should throw");
+        let Error::JsError(e) = e else {
+            panic!("Expected a JS error, got {e:?}");
+        };
+        assert_eq!(Some(8), e.frames.first().and_then(|f| f.line_number));
+        assert!(e
+            .source_line
+            .as_deref()
+            .is_some_and(|line| line.contains("bad shape")));
+    }
+
+    #[test]
+    fn test_module_warnings() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+
+        let module = Module::new("clean.ts", "export const x: number = 2;");
+        let module = runtime.load_module(&module).expect("Could not load module");
+        assert!(module.warnings().is_empty());
+
+        // Type parameters on a constructor are reported, but still transpile
+        let module = Module::new(
+            "warned.ts",
+            "export class Shape {\n    constructor<T>() {}\n}\nexport const x: number = 3;",
+        );
+        let module = runtime.load_module(&module).expect("Could not load module");
+        let value: i64 = runtime
+            .get_value(Some(&module), "x")
+            .expect("Could not get value");
+        assert_eq!(3, value);
+
+        let warning = module.warnings().first().expect("Expected a warning");
+        assert_eq!(crate::DiagnosticSeverity::Warning, warning.severity);
+        assert_eq!(2, warning.line);
+
+        // Warnings from every module loaded together are kept
+        let side_a = Module::new("side_a.ts", "export class A {\n    constructor<T>() {}\n}");
+        let side_b = Module::new(
+            "side_b.ts",
+            "export class B {\n\n    constructor<T>() {}\n}",
+        );
+        let main = Module::new("main.ts", "export const y: number = 4;");
+        let module = runtime
+            .load_modules(&main, vec![&side_a, &side_b])
+            .expect("Could not load modules");
+        let lines: Vec<_> = module.warnings().iter().map(|w| w.line).collect();
+        assert!(lines.contains(&2) && lines.contains(&3), "{lines:?}");
+    }
+
+    #[test]
+    fn test_get_module_exports() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = runtime
+            .load_module(&Module::new(
+                "test_module_exports.js",
+                "
+                globalThis.hidden = 1;
+                export const capabilities = ['search', 'index'];
+                export function run() {}
+                export { run as start };
+                ",
+            ))
+            .expect("Could not load module");
+
+        let exports = runtime
+            .get_module_exports(&module)
+            .expect("Could not list exports");
+        assert_eq!(exports, vec!["capabilities", "run", "start"]);
+
+        let capabilities: Vec<String> = runtime
+            .get_module_export_value(&module, "capabilities")
+            .expect("Could not get export");
+        assert_eq!(capabilities, vec!["search", "index"]);
+
+        // Globals are not exports
+        assert!(matches!(
+            runtime.get_module_export_value::<usize>(&module, "hidden"),
+            Err(Error::ValueNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_map() {
+        let import_map = crate::module_loader::ImportMap::from_json(
+            r#"{ "imports": { "@app/": "./import_map/src/" } }"#,
+        )
+        .expect("Could not parse import map");
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_map: Some(import_map),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        runtime
+            .load_modules_lazy(&[
+                Module::new(
+                    "import_map/src/utils.ts",
+                    "export const double = (n: number) => n * 2;",
+                ),
+                Module::new(
+                    "import_map/main.js",
+                    "import { double } from '@app/utils.ts'; export default () => double(21);",
+                ),
+            ])
+            .expect("Could not load modules");
+
+        let main = runtime
+            .lazy_module("import_map/main.js")
+            .expect("Could not run main");
+        let value: usize = runtime.call_entrypoint(&main, json_args!()).unwrap();
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_module_resolver() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            module_resolver: Some(Box::new(|specifier, referrer| {
+                match specifier.strip_prefix("plugin:") {
+                    Some("greet") => Ok(deno_core::resolve_path(
+                        "resolver/greet.js",
+                        &std::env::current_dir()?,
+                    )?),
+                    Some(name) => Err(deno_core::anyhow::anyhow!("Unknown plugin: {name}")),
+                    None => Ok(deno_core::resolve_import(specifier, referrer)?),
+                }
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        runtime
+            .load_modules_lazy(&[
+                Module::new("resolver/greet.js", "export const greeting = 'hi';"),
+                Module::new(
+                    "resolver/main.js",
+                    "import { greeting } from 'plugin:greet'; export default () => greeting;",
+                ),
+            ])
+            .expect("Could not load modules");
+
+        let main = runtime
+            .lazy_module("resolver/main.js")
+            .expect("Could not run main");
+        let value: String = runtime.call_entrypoint(&main, json_args!()).unwrap();
+        assert_eq!(value, "hi");
+
+        // Failures from the hook fail the import
+        let module = Module::new("resolver/other.js", "import 'plugin:missing';");
+        runtime
+            .load_module(&module)
+            .expect_err("Unresolvable import was loaded");
+    }
+
+    #[test]
+    fn test_raw_imports() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustyscript_runtime_raw_imports_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).expect("Could not create directory");
+        std::fs::write(dir.join("page.html"), "<p>hello</p>").expect("Could not write file");
+        std::fs::write(dir.join("logo.bin"), [0u8, 159, 146, 150]).expect("Could not write file");
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let module = Module::new(
+            dir.join("main.js"),
+            "
+            import page from './page.html' with { type: 'text' };
+            import logo from './logo.bin' with { type: 'bytes' };
+            export const text = page;
+            export const bytes = Array.from(logo);
+            export const isBytes = logo instanceof Uint8Array;
+            ",
+        );
+        let handle = runtime.load_module(&module).expect("Could not load module");
+
+        let text: String = runtime
+            .get_value(Some(&handle), "text")
+            .expect("Could not get value");
+        assert_eq!("<p>hello</p>", text);
+        let bytes: Vec<u8> = runtime
+            .get_value(Some(&handle), "bytes")
+            .expect("Could not get value");
+        assert_eq!(vec![0, 159, 146, 150], bytes);
+        let is_bytes: bool = runtime
+            .get_value(Some(&handle), "isBytes")
+            .expect("Could not get value");
+        assert!(is_bytes);
+
+        // Binary contents are not valid text
+        let module = Module::new(
+            dir.join("invalid.js"),
+            "import logo from './logo.bin' with { type: 'text' };",
+        );
+        runtime
+            .load_module(&module)
+            .expect_err("Invalid UTF-8 was accepted");
+
+        let module = Module::new(
+            dir.join("unknown.js"),
+            "import page from './page.html' with { type: 'css' };",
+        );
+        runtime
+            .load_module(&module)
+            .expect_err("Unknown module type was accepted");
+
+        // Imports past the size limit fail to load
+        let mut runtime = Runtime::new(RuntimeOptions {
+            max_raw_import_size: Some(4),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let module = Module::new(
+            dir.join("small.js"),
+            "import logo from './logo.bin' with { type: 'bytes' };",
+        );
+        runtime
+            .load_module(&module)
+            .expect("Import within the limit was rejected");
+        let module = Module::new(
+            dir.join("large.js"),
+            "import page from './page.html' with { type: 'text' };",
+        );
+        let e = runtime
+            .load_module(&module)
+            .expect_err("Import past the limit was accepted");
+        assert!(e.to_string().contains("larger than the limit"), "{e}");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_code_cache_dir() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustyscript_runtime_code_cache_{}",
+            std::process::id()
+        ));
+        std::fs::remove_dir_all(&dir).ok();
+        let modules = [
+            Module::new("code_cache/dep.js", "export const n = 41;"),
+            Module::new(
+                "code_cache/main.js",
+                "import { n } from './dep.js'; export default () => n + 1;",
+            ),
+        ];
+        let root = Module::new("code_cache/root.js", "export default () => 7;");
+
+        // The first runtime compiles every module, and stores its bytecode
+        // The second is handed that bytecode, which v8 accepts - otherwise it would be stored again
+        for run in 0..2 {
+            let messages = Rc::new(std::cell::RefCell::new(Vec::new()));
+            let sink = messages.clone();
+            let mut runtime = Runtime::new(RuntimeOptions {
+                code_cache_dir: Some(dir.clone()),
+                logger: Some(Rc::new(move |_, message: &str| {
+                    sink.borrow_mut().push(message.to_string());
+                })),
+                ..Default::default()
+            })
+            .expect("Could not create the runtime");
+            runtime
+                .load_modules_lazy(&modules)
+                .expect("Could not load modules");
+            let root = runtime.load_module(&root).expect("Could not load root");
+
+            let entries = std::fs::read_dir(&dir)
+                .expect("Code cache was not written")
+                .count();
+            assert_eq!(entries, 3);
+
+            let count = |prefix: &str| {
+                messages
+                    .borrow()
+                    .iter()
+                    .filter(|message| message.starts_with(prefix))
+                    .count()
+            };
+            let (stored, used) = if run == 0 { (3, 0) } else { (0, 3) };
+            assert_eq!(stored, count("Stored bytecode"));
+            assert_eq!(used, count("Using cached bytecode"));
+
+            let main = runtime
+                .lazy_module("code_cache/main.js")
+                .expect("Could not run main");
+            let value: usize = runtime.call_entrypoint(&main, json_args!()).unwrap();
+            assert_eq!(value, 42);
+            let value: usize = runtime.call_entrypoint(&root, json_args!()).unwrap();
+            assert_eq!(value, 7);
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_wasm_imports() {
+        // (import "host" "add" (func (param i32 i32) (result i32)))
+        // (func (export "run") (param i32) (result i32) local.get 0 i32.const 1 call 0)
+        const CALLS_HOST: &str = "new Uint8Array([
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            0x01, 0x0c, 0x02, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, 0x60, 0x01, 0x7f, 0x01, 0x7f,
+            0x02, 0x0c, 0x01, 0x04, 0x68, 0x6f, 0x73, 0x74, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00,
+            0x03, 0x02, 0x01, 0x01,
+            0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x01,
+            0x0a, 0x0a, 0x01, 0x08, 0x00, 0x20, 0x00, 0x41, 0x01, 0x10, 0x00, 0x0b,
+        ])";
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            max_wasm_memory: Some(2 * 64 * 1024),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let mut imports: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+        imports.insert(
+            "add".to_string(),
+            Box::new(|args: &[serde_json::Value]| {
+                let sum = args
+                    .iter()
+                    .filter_map(serde_json::Value::as_i64)
+                    .sum::<i64>();
+                Ok::<_, Error>(serde_json::Value::from(sum))
+            }),
+        );
+        runtime
+            .register_wasm_imports("host", imports)
+            .expect("Could not register the imports");
+
+        let value: i64 = runtime
+            .eval(&format!(
+                "new WebAssembly.Instance(new WebAssembly.Module({CALLS_HOST}), {{}}).exports.run(41)"
+            ))
+            .expect("Could not run the wasm module");
+        assert_eq!(value, 42);
+
+        let tokio_runtime = runtime.tokio_runtime();
+        let value: i64 = tokio_runtime
+            .block_on(runtime.eval_async(&format!(
+                "(await WebAssembly.instantiate({CALLS_HOST})).instance.exports.run(1)"
+            )))
+            .expect("Could not run the wasm module");
+        assert_eq!(value, 2);
+
+        // Memories with no maximum, or a maximum past the limit, are refused
+        for memory in ["0x00, 0x01", "0x01, 0x01, 0x03"] {
+            let module = format!(
+                "new WebAssembly.Module(new Uint8Array([
+                    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x05, {}, 0x01, {memory}
+                ]))",
+                memory.split(',').count() + 1
+            );
+            runtime
+                .eval::<Undefined>(&module)
+                .expect_err("Memory limit was not enforced");
+        }
+
+        runtime
+            .eval::<Undefined>("new WebAssembly.Memory({ initial: 1, maximum: 4 })")
+            .expect_err("Memory limit was not enforced");
+        let grown: bool = runtime
+            .eval("try { new WebAssembly.Memory({ initial: 2 }).grow(1); true } catch { false }")
+            .expect("Could not create the memory");
+        assert!(!grown);
+
+        // The original constructors cannot be reached or restored
+        runtime
+            .eval::<Undefined>(
+                "const memory = new WebAssembly.Memory({ initial: 1 });
+                new (Object.getPrototypeOf(memory).constructor)({ initial: 1, maximum: 4 })",
+            )
+            .expect_err("Memory limit was not enforced");
+        runtime
+            .eval::<Undefined>(
+                "'use strict';
+                WebAssembly.Memory.prototype.constructor = function () {};",
+            )
+            .expect_err("The constructor was replaced");
+
+        // Imports registered later are picked up by later instances
+        let mut imports: HashMap<String, Box<dyn RsFunction>> = HashMap::new();
+        imports.insert(
+            "add".to_string(),
+            Box::new(|args: &[serde_json::Value]| {
+                let sum = args
+                    .iter()
+                    .filter_map(serde_json::Value::as_i64)
+                    .sum::<i64>();
+                Ok::<_, Error>(serde_json::Value::from(sum + 100))
+            }),
+        );
+        runtime
+            .register_wasm_imports("next", imports)
+            .expect("Could not register the imports");
+        let value: i64 = runtime
+            .eval(&format!(
+                "(() => {{
+                    const bytes = {CALLS_HOST};
+                    const host = bytes.findIndex((b, i) => String.fromCharCode(...bytes.slice(i, i + 4)) === 'host');
+                    bytes.set([...'next'].map((c) => c.charCodeAt(0)), host);
+                    return new WebAssembly.Instance(new WebAssembly.Module(bytes), {{}}).exports.run(41);
+                }})()"
+            ))
+            .expect("Could not run the wasm module");
+        assert_eq!(value, 142);
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut runtime = Runtime::new(RuntimeOptions {
+            manual_ticks: true,
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
         let module = Module::new(
             "test.js",
             "
-            globalThis.a = 2;
-            export const b = 'test';
-            export const fnc = null;
-        ",
+            globalThis.steps = [];
+            setTimeout(() => {
+                steps.push('timeout');
+                Promise.resolve().then(() => steps.push('microtask'));
+            }, 10);
+            const id = setInterval(() => {
+                steps.push('interval');
+                if (steps.length > 2) clearInterval(id);
+            }, 4);
+            ",
         );
+        runtime.load_module(&module).expect("Could not load module");
+
+        let steps = |runtime: &mut Runtime| -> Vec<String> {
+            runtime
+                .eval("globalThis.steps")
+                .expect("Could not get steps")
+        };
+        assert!(steps(&mut runtime).is_empty());
+
+        assert_eq!(2, runtime.tick().expect("Could not tick"));
+        assert_eq!(vec!["interval"], steps(&mut runtime));
+
+        assert_eq!(2, runtime.tick().expect("Could not tick"));
+        assert_eq!(vec!["interval", "interval"], steps(&mut runtime));
+
+        // The timeout's microtask runs before the next task
+        assert_eq!(1, runtime.tick().expect("Could not tick"));
+        assert_eq!(
+            vec!["interval", "interval", "timeout", "microtask"],
+            steps(&mut runtime)
+        );
+
+        assert_eq!(0, runtime.tick().expect("Could not tick"));
+        assert_eq!(0, runtime.tick().expect("Could not tick"));
 
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+        runtime.tick().expect_err("Manual ticks were not enabled");
+    }
+
+    #[test]
+    fn test_advance_clock() {
+        let start = std::time::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut runtime = Runtime::new(RuntimeOptions {
+            virtual_clock: Some(start),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
 
+        let module = Module::new(
+            "test.js",
+            "
+            globalThis.steps = [];
+            setTimeout(() => steps.push(['timeout', Date.now()]), 250);
+            const id = setInterval(() => {
+                steps.push(['interval', Date.now()]);
+                Promise.resolve().then(() => steps.push(['microtask', Date.now()]));
+            }, 100);
+            globalThis.stop = () => clearInterval(id);
+            ",
+        );
+        runtime.load_module(&module).expect("Could not load module");
         assert_eq!(
-            2,
+            1_000_000,
             runtime
-                .get_value::<usize>(Some(&module), "a")
-                .expect("Could not find global")
+                .eval::<u64>("Date.now()")
+                .expect("Could not get time")
         );
+
+        let ran = runtime
+            .advance_clock(Duration::from_millis(250))
+            .expect("Could not advance clock");
+        assert_eq!(3, ran);
+
+        let steps: Vec<(String, u64)> = runtime.eval("steps").expect("Could not get steps");
         assert_eq!(
-            "test",
+            vec![
+                ("interval".to_string(), 1_000_100),
+                ("microtask".to_string(), 1_000_100),
+                ("interval".to_string(), 1_000_200),
+                ("microtask".to_string(), 1_000_200),
+                ("timeout".to_string(), 1_000_250),
+            ],
+            steps
+        );
+
+        // Time still moves when nothing is due
+        runtime
+            .eval::<Undefined>("stop()")
+            .expect("Could not stop interval");
+        let ran = runtime
+            .advance_clock(Duration::from_secs(5))
+            .expect("Could not advance clock");
+        assert_eq!(0, ran);
+        assert_eq!(
+            1_005_250,
             runtime
-                .get_value::<String>(Some(&module), "b")
-                .expect("Could not find export")
+                .eval::<u64>("Date.now()")
+                .expect("Could not get time")
         );
+
+        // `new Date()` and `Date()` read the virtual clock as well
+        let dates: (u64, bool, bool) = runtime
+            .eval("[new Date().getTime(), Date().startsWith(new Date().toString()), new Date(5) instanceof Date]")
+            .expect("Could not get dates");
+        assert_eq!((1_005_250, true, true), dates);
+
+        // Zero-delay timers are raised to 1ms, so they cannot stall the clock
         runtime
-            .get_value::<Undefined>(Some(&module), "c")
-            .expect_err("Could not detect null");
+            .eval::<Undefined>(
+                "
+                globalThis.count = 0;
+                setInterval(() => count++, 0);
+                const again = () => setTimeout(again, 0);
+                again();
+                ",
+            )
+            .expect("Could not start timers");
         runtime
-            .get_value::<Undefined>(Some(&module), "d")
-            .expect_err("Could not detect undeclared");
+            .advance_clock(Duration::from_millis(10))
+            .expect("Could not advance clock");
+        assert_eq!(
+            10,
+            runtime.eval::<u64>("count").expect("Could not get count")
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .advance_clock(Duration::from_secs(1))
+            .expect_err("The virtual clock was not enabled");
     }
 
     #[test]
-    fn test_load_module() {
+    fn test_load_sandboxed_module() {
+        let module = Module::new(
+            "test.js",
+            "
+            const rustyscript = 'local';
+            export const local = rustyscript;
+            export const math = typeof Math;
+            export const console_type = typeof console;
+            export const global_console = typeof globalThis.console;
+            export const global_math = typeof globalThis.Math;
+            ",
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let handle = runtime
+            .load_sandboxed_module(&module, &["Math"])
+            .expect("Could not load module");
+
+        let get = |runtime: &mut Runtime, name| -> String {
+            runtime
+                .get_value(Some(&handle), name)
+                .expect("Could not get value")
+        };
+        assert_eq!("local", get(&mut runtime, "local"));
+        assert_eq!("object", get(&mut runtime, "math"));
+        assert_eq!("undefined", get(&mut runtime, "console_type"));
+        assert_eq!("undefined", get(&mut runtime, "global_console"));
+        assert_eq!("object", get(&mut runtime, "global_math"));
+
+        // Globals are untouched outside the module
+        let rustyscript_type: String = runtime
+            .eval("typeof globalThis.rustyscript")
+            .expect("Could not eval");
+        assert_eq!("object", rustyscript_type);
+
+        // Imports would leave the sandbox's context
+        let module = Module::new(
+            "import.js",
+            "import { a } from './a.js'; export const b = a;",
+        );
+        runtime
+            .load_sandboxed_module(&module, &[])
+            .expect_err("Sandboxed modules cannot import");
+        let module = Module::new("dynamic.js", "export const load = () => import('./a.js');");
+        runtime
+            .load_sandboxed_module(&module, &[])
+            .expect_err("Sandboxed modules cannot import");
+    }
+
+    #[test]
+    fn test_sandboxed_module_escapes() {
+        let module = Module::new(
+            "escape.js",
+            "
+            const attempt = (f) => {
+                try {
+                    return typeof f().rustyscript;
+                } catch (e) {
+                    return e.name;
+                }
+            };
+            export const indirect_eval = attempt(() => (0, eval)('globalThis'));
+            export const function_constructor = attempt(() => Function('return this')());
+            export const object_constructor = attempt(() => ({}).constructor.constructor('return globalThis')());
+            export const generator_constructor = attempt(() => (function* () {}).constructor('return globalThis')().next());
+            export const from_argument = (arg) => attempt(() => arg.constructor.constructor('return globalThis')());
+            ",
+        );
+
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let handle = runtime
+            .load_sandboxed_module(&module, &["Math", "eval", "Function"])
+            .expect("Could not load module");
+
+        let get = |runtime: &mut Runtime, name| -> String {
+            runtime
+                .get_value(Some(&handle), name)
+                .expect("Could not get value")
+        };
+        assert_eq!("ReferenceError", get(&mut runtime, "indirect_eval"));
+        assert_eq!("ReferenceError", get(&mut runtime, "function_constructor"));
+        assert_eq!("EvalError", get(&mut runtime, "object_constructor"));
+        assert_eq!("EvalError", get(&mut runtime, "generator_constructor"));
+
+        // Arguments are created in the sandbox's context
+        let result: String = runtime
+            .call_function(Some(&handle), "from_argument", &(vec![1, 2, 3],))
+            .expect("Could not call function");
+        assert_eq!("EvalError", result);
+    }
+
+    #[test]
+    #[cfg(feature = "console")]
+    fn test_sandboxed_host_globals() {
+        // Allowed host globals are wrapped, so they do not lead back to the runtime's context
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
         let module = Module::new(
-            "test.js",
+            "console_escape.js",
             "
-            export default () => 2;
-        ",
+            const attempt = (f) => {
+                try {
+                    return typeof f().rustyscript;
+                } catch (e) {
+                    return e.name;
+                }
+            };
+            export const method = attempt(() => console.log.constructor('return globalThis')());
+            export const object = attempt(() => console.constructor.constructor('return globalThis')());
+            export const error = attempt(() => {
+                try {
+                    console.log(1n);
+                } catch (e) {
+                    return e.constructor.constructor('return globalThis')();
+                }
+            });
+            export const logged = console.log('still usable') === undefined;
+            ",
         );
-        let module = runtime
-            .load_modules(&module, vec![])
+        let handle = runtime
+            .load_sandboxed_module(&module, &["console"])
             .expect("Could not load module");
-        assert_ne!(0, module.id());
+        let get = |runtime: &mut Runtime, name| -> String {
+            runtime
+                .get_value(Some(&handle), name)
+                .expect("Could not get value")
+        };
+        assert_eq!("EvalError", get(&mut runtime, "method"));
+        assert_eq!("EvalError", get(&mut runtime, "object"));
+        assert_eq!("EvalError", get(&mut runtime, "error"));
+        let logged: bool = runtime
+            .get_value(Some(&handle), "logged")
+            .expect("Could not get value");
+        assert!(logged);
+    }
 
+    #[test]
+    fn test_load_pure_module() {
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module1 = Module::new(
-            "importme.js",
+
+        let module = Module::new(
+            "pure.js",
             "
-            export const value = 2;
-        ",
+            export const timers = typeof setTimeout;
+            export const promise = typeof Promise;
+            export const score = (values) => values.reduce((a, b) => a + b, 0) / values.length;
+            ",
         );
-        let module2 = Module::new(
+        let handle = runtime
+            .load_pure_module(&module)
+            .expect("Could not load pure module");
+        let timers: String = runtime
+            .get_value(Some(&handle), "timers")
+            .expect("Could not get value");
+        assert_eq!("undefined", timers);
+        let promise: String = runtime
+            .get_value(Some(&handle), "promise")
+            .expect("Could not get value");
+        assert_eq!("undefined", promise);
+        let score: f64 = runtime
+            .call_function(Some(&handle), "score", &(vec![1, 2, 3],))
+            .expect("Could not call function");
+        assert!((score - 2.0).abs() < f64::EPSILON);
+
+        let rejected = [
+            (
+                "static_import.js",
+                "import './other.js'; export const x = 1;",
+                "imports",
+            ),
+            (
+                "dynamic_import.js",
+                "export const f = () => import('./other.js');",
+                "import()",
+            ),
+            ("async_export.js", "export async function f() {}", "async"),
+            (
+                "async_local.js",
+                "const f = async () => 1; export const g = () => f;",
+                "async",
+            ),
+            (
+                "generator.js",
+                "function* g() {} export const f = () => g;",
+                "generator",
+            ),
+        ];
+        for (filename, source, reason) in rejected {
+            let err = runtime
+                .load_pure_module(&Module::new(filename, source))
+                .expect_err("Module was not rejected");
+            assert!(err.to_string().contains(reason), "{err}");
+        }
+
+        // Code cannot be generated from strings
+        let module = Module::new(
+            "eval.js",
+            "export const f = () => {
+                try {
+                    return (() => {}).constructor('return 1')();
+                } catch (e) {
+                    return e.name;
+                }
+            };",
+        );
+        let handle = runtime
+            .load_pure_module(&module)
+            .expect("Could not load pure module");
+        let result: String = runtime
+            .call_function(Some(&handle), "f", json_args!())
+            .expect("Could not call function");
+        assert_eq!("EvalError", result);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let manifest = crate::module_loader::ModuleManifest::from_json(
+            r#"{ "modules": [
+                { "specifier": "https://example.com/lib.ts", "source": "export const add = (a: number, b: number) => a + b;" },
+                { "specifier": "setup.js", "source": "globalThis.preloaded = 5;", "preload": true }
+            ] }"#,
+        )
+        .expect("Could not parse manifest");
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            manifest: Some(manifest),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let value: usize = runtime
+            .eval("globalThis.preloaded")
+            .expect("Manifest module was not preloaded");
+        assert_eq!(5, value);
+
+        let module = Module::new(
             "test.js",
             "
-            import { value } from './importme.js';
-            rustyscript.register_entrypoint(() => value);
-        ",
+            import { add } from 'https://example.com/lib.ts';
+            export default () => add(globalThis.preloaded, 2);
+            ",
         );
-        runtime
-            .load_module(&module1)
-            .expect("Could not load modules");
-        let module = runtime
-            .load_module(&module2)
-            .expect("Could not load modules");
+        let handle = runtime.load_module(&module).expect("Could not load module");
         let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
-        assert_eq!(2, value);
+            .call_entrypoint(&handle, json_args!())
+            .expect("Could not call entrypoint");
+        assert_eq!(7, value);
+    }
+
+    /// Serves one canned response per connection, in order, on a local port
+    /// Extra headers can follow the status, each after a `\r\n`
+    /// Returns the port, and a handle that yields the number of requests served
+    #[cfg(feature = "url_import")]
+    fn serve_responses(
+        responses: Vec<(&'static str, &'static str)>,
+    ) -> (u16, std::thread::JoinHandle<usize>) {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let port = listener.local_addr().expect("Could not get address").port();
+        let server = std::thread::spawn(move || {
+            let mut served = 0;
+            for (status, body) in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    break;
+                };
+
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Type: application/javascript\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .expect("Could not write response");
+                served += 1;
+            }
+            served
+        });
+        (port, server)
+    }
+
+    #[test]
+    #[cfg(feature = "url_import")]
+    fn test_import_retry() {
+        let module = |port: u16| {
+            Module::new(
+                "test.js",
+                &format!(
+                    "import {{ value }} from 'http://127.0.0.1:{port}/dep.js'; export const x = value;"
+                ),
+            )
+        };
+
+        // A transient failure is retried, and the retry logged
+        let (port, server) = serve_responses(vec![
+            ("503 Service Unavailable", ""),
+            ("200 OK", "export const value = 2;"),
+        ]);
+        let messages = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = messages.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_retry: crate::module_loader::ImportRetryPolicy::new(
+                2,
+                Duration::from_millis(10),
+            ),
+            logger: Some(Rc::new(move |level: crate::LogLevel, message: &str| {
+                sink.borrow_mut().push((level, message.to_string()));
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let handle = runtime
+            .load_module(&module(port))
+            .expect("Import was not retried");
+        let x: i64 = runtime
+            .get_value(Some(&handle), "x")
+            .expect("Could not get value");
+        assert_eq!(2, x);
+        assert_eq!(2, server.join().expect("Server panicked"));
+        assert!(messages
+            .borrow()
+            .iter()
+            .any(|(level, message)| *level == crate::LogLevel::Warn
+                && message.contains("Retrying import")));
+
+        // Without retries, the first failure fails the import
+        let (port, server) = serve_responses(vec![("503 Service Unavailable", "")]);
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let result = runtime.load_module(&module(port));
+        assert!(result.is_err());
+        assert_eq!(1, server.join().expect("Server panicked"));
+    }
+
+    #[test]
+    #[cfg(feature = "url_import")]
+    fn test_import_timeout() {
+        // Connections are queued by the OS, but never answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind");
+        let port = listener.local_addr().expect("Could not get address").port();
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_timeouts: crate::module_loader::ImportTimeouts::new(
+                None,
+                Some(Duration::from_millis(100)),
+            ),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let module = Module::new(
+            "test.js",
+            &format!("import 'http://127.0.0.1:{port}/dep.js';"),
+        );
+        let start = Instant::now();
+        let Err(e) = runtime.load_module(&module) else {
+            panic!("Import did not time out");
+        };
+        assert!(e.to_string().contains("timed out after 100ms"), "{e}");
+        assert!(start.elapsed() < Duration::from_secs(10));
+        drop(listener);
+    }
+
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    #[test]
+    fn test_logger() {
+        let messages = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = messages.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            timeout: Duration::from_millis(50),
+            logger: Some(Rc::new(move |level: crate::LogLevel, message: &str| {
+                sink.borrow_mut().push((level, message.to_string()));
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        assert!(runtime.logger().is_some());
+
+        let module = Module::new("test.js", "export const value = 2;");
+        runtime.load_module(&module).expect("Could not load module");
+        assert!(
+            messages
+                .borrow()
+                .iter()
+                .any(|(level, message)| *level == crate::LogLevel::Info
+                    && message.contains("test.js"))
+        );
+
+        let module = Module::new(
+            "test2.js",
+            "await new Promise(r => setTimeout(r, 60 * 1000));",
+        );
+        runtime
+            .load_module(&module)
+            .expect_err("Module did not time out");
+        assert!(messages
+            .borrow()
+            .iter()
+            .any(|(level, _)| *level == crate::LogLevel::Warn));
+
+        // So is an event loop cut short by its own timeout
+        messages.borrow_mut().clear();
+        let sink = messages.clone();
+        let mut runtime = Runtime::new(RuntimeOptions {
+            logger: Some(Rc::new(move |level: crate::LogLevel, message: &str| {
+                sink.borrow_mut().push((level, message.to_string()));
+            })),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        runtime
+            .eval::<Undefined>("setTimeout(() => {}, 60 * 1000)")
+            .expect("Could not schedule the timer");
+        runtime
+            .block_on_event_loop(
+                deno_core::PollEventLoopOptions::default(),
+                Some(Duration::from_millis(10)),
+            )
+            .expect("Event loop failed");
+        assert!(messages
+            .borrow()
+            .iter()
+            .any(|(level, message)| *level == crate::LogLevel::Warn
+                && message.contains("pending work")));
+    }
+
+    #[test]
+    fn test_max_import_depth() {
+        // mem:N imports mem:N+1, down to mem:5
+        struct ChainImports;
+        impl crate::module_loader::ImportProvider for ChainImports {
+            fn resolve(
+                &mut self,
+                specifier: &deno_core::ModuleSpecifier,
+                _referrer: &str,
+                _kind: deno_core::ResolutionKind,
+            ) -> Option<Result<deno_core::ModuleSpecifier, deno_core::anyhow::Error>> {
+                (specifier.scheme() == "mem").then(|| Ok(specifier.clone()))
+            }
+
+            fn import(
+                &mut self,
+                specifier: &deno_core::ModuleSpecifier,
+                _referrer: Option<&deno_core::ModuleSpecifier>,
+                _is_dyn_import: bool,
+                _requested_module_type: deno_core::RequestedModuleType,
+            ) -> Option<Result<String, deno_core::anyhow::Error>> {
+                let n: usize = specifier.path().parse().ok()?;
+                Some(Ok(if n < 5 {
+                    format!("export * from 'mem:{}';", n + 1)
+                } else {
+                    "export const bottom = 5;".to_string()
+                }))
+            }
+        }
+
+        let module = Module::new("chain.js", "export * from 'mem:1';");
 
         let mut runtime = Runtime::new(RuntimeOptions {
-            timeout: Duration::from_millis(50),
+            import_provider: Some(Box::new(ChainImports)),
+            max_import_depth: Some(5),
             ..Default::default()
         })
         .expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            await new Promise(r => setTimeout(r, 2000));
-        ",
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let bottom: usize = runtime
+            .get_value(Some(&handle), "bottom")
+            .expect("Could not get value");
+        assert_eq!(5, bottom);
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_provider: Some(Box::new(ChainImports)),
+            max_import_depth: Some(3),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+        let e = runtime
+            .load_module(&module)
+            .expect_err("Import depth limit was not enforced");
+        assert!(e.to_string().contains("import depth limit of 3"));
+    }
+
+    #[test]
+    fn test_measure_module_graph() {
+        const MODULE_A: &str = "export const a = 1;";
+        const MODULE_B: &str = "import { a } from 'mem:a'; export const b = a;";
+
+        struct MemoryImports;
+        impl crate::module_loader::ImportProvider for MemoryImports {
+            fn resolve(
+                &mut self,
+                specifier: &deno_core::ModuleSpecifier,
+                _referrer: &str,
+                _kind: deno_core::ResolutionKind,
+            ) -> Option<Result<deno_core::ModuleSpecifier, deno_core::anyhow::Error>> {
+                (specifier.scheme() == "mem").then(|| Ok(specifier.clone()))
+            }
+
+            fn import(
+                &mut self,
+                specifier: &deno_core::ModuleSpecifier,
+                _referrer: Option<&deno_core::ModuleSpecifier>,
+                _is_dyn_import: bool,
+                _requested_module_type: deno_core::RequestedModuleType,
+            ) -> Option<Result<String, deno_core::anyhow::Error>> {
+                match specifier.as_str() {
+                    "mem:a" => Some(Ok(MODULE_A.to_string())),
+                    "mem:b" => Some(Ok(MODULE_B.to_string())),
+                    _ => None,
+                }
+            }
+        }
+
+        let mut runtime = Runtime::new(RuntimeOptions {
+            import_provider: Some(Box::new(MemoryImports)),
+            ..Default::default()
+        })
+        .expect("Could not create the runtime");
+
+        let code = "import { a } from 'mem:a'; import { b } from 'mem:b'; export const c = a + b;";
+        let module = Module::new("test.js", code);
+        let size = runtime
+            .measure_module_graph(&module)
+            .expect("Could not measure module graph");
+        assert_eq!(3, size.module_count);
+        assert_eq!(
+            code.len() + MODULE_A.len() + MODULE_B.len(),
+            size.total_bytes
         );
+
+        // Nothing was evaluated, so the module can still be loaded
+        runtime.load_module(&module).expect("Could not load module");
+
+        let module = Module::new("test2.js", "import 'unknown:module';");
         runtime
-            .load_modules(&module, vec![])
-            .expect_err("Did not interupt after timeout");
+            .measure_module_graph(&module)
+            .expect_err("Disallowed import was not detected");
     }
 
     #[test]
-    fn test_load_modules() {
+    fn test_register_conversion() {
+        use crate::{JsRepr, JsTypeConversion};
+
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Timestamp(String);
+
+        #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Label(String);
+
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        runtime
+            .register_conversion::<Timestamp>(JsTypeConversion {
+                to_js: "(v) => new Date(v)".to_string(),
+                is_js: "(v) => v instanceof Date".to_string(),
+                from_js: "(v) => v.toISOString()".to_string(),
+            })
+            .expect("Could not register conversion");
+
         let module = Module::new(
             "test.js",
             "
-            rustyscript.register_entrypoint(() => 2);
-        ",
+            export const year = (d) => d.getUTCFullYear();
+            export const kind = (v) => typeof v;
+            export const next = ({ start }) => ({ day: new Date(start.getTime() + 86400000), label: 'next' });
+            export const dates = () => [new Date(0), new Date(0)];
+            ",
         );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
-        assert_ne!(0, module.id());
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let start = Timestamp("2024-01-01T00:00:00.000Z".to_string());
+        let year: i32 = runtime
+            .call_function(Some(&module), "year", json_args!(JsRepr(start.clone())))
+            .expect("Could not call function");
+        assert_eq!(2024, year);
+
+        #[derive(serde::Serialize)]
+        struct Range {
+            start: JsRepr<Timestamp>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct Next {
+            day: JsRepr<Timestamp>,
+            label: String,
+        }
 
+        let range = Range {
+            start: JsRepr(start.clone()),
+        };
+        let next: Next = runtime
+            .call_function(Some(&module), "next", json_args!(range))
+            .expect("Could not call function");
+        assert_eq!(
+            Timestamp("2024-01-02T00:00:00.000Z".to_string()),
+            next.day.0
+        );
+        assert_eq!("next", next.label);
+
+        let dates: Vec<JsRepr<Timestamp>> = runtime
+            .call_function(Some(&module), "dates", json_args!())
+            .expect("Could not call function");
+        assert_eq!(2, dates.len());
+        assert_eq!("1970-01-01T00:00:00.000Z", dates[1].0 .0);
+
+        // Values are converted by type, not by shape - unwrapped values and other types are left alone
+        let kind: String = runtime
+            .call_function(Some(&module), "kind", json_args!(start))
+            .expect("Could not call function");
+        assert_eq!("string", kind);
+
+        let label = JsRepr(Label("2024-01-01T00:00:00.000Z".to_string()));
+        let kind: String = runtime
+            .call_function(Some(&module), "kind", json_args!(label))
+            .expect("Could not call function");
+        assert_eq!("string", kind);
+
+        runtime
+            .register_conversion::<Timestamp>(JsTypeConversion {
+                to_js: "(v) => ".to_string(),
+                is_js: String::new(),
+                from_js: String::new(),
+            })
+            .expect_err("Invalid conversion was accepted");
+    }
+
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    #[test]
+    fn test_event_loop_progress() {
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module1 = Module::new(
-            "importme.js",
-            "
-            export const value = 2;
-        ",
-        );
-        let module2 = Module::new(
-            "test.js",
-            "
-            import { value } from './importme.js';
-            rustyscript.register_entrypoint(() => value);
-        ",
-        );
-        let module = runtime
-            .load_modules(&module2, vec![&module1])
-            .expect("Could not load modules");
-        let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
-        assert_eq!(2, value);
 
+        runtime
+            .eval::<usize>("setTimeout(() => {}, 100)")
+            .expect("Could not queue timer");
+        let mut reports = 0;
+        runtime
+            .block_on_event_loop_with_progress(
+                PollEventLoopOptions::default(),
+                Duration::from_millis(10),
+                |progress| {
+                    assert!(progress.ticks > 0);
+                    reports += 1;
+                    true
+                },
+            )
+            .expect("Event loop failed");
+        assert!(reports > 1);
+
+        // A zero interval is raised to 1ms, rather than reporting on every wakeup
+        runtime
+            .eval::<usize>("setTimeout(() => {}, 50)")
+            .expect("Could not queue timer");
+        let mut reports = 0;
+        runtime
+            .block_on_event_loop_with_progress(
+                PollEventLoopOptions::default(),
+                Duration::ZERO,
+                |_| {
+                    reports += 1;
+                    true
+                },
+            )
+            .expect("Event loop failed");
+        assert!((1..=60).contains(&reports), "{reports} reports");
+
+        // Stopping early leaves the work pending
+        runtime
+            .eval::<usize>("setTimeout(() => {}, 60 * 1000)")
+            .expect("Could not queue timer");
+        runtime
+            .block_on_event_loop_with_progress(
+                PollEventLoopOptions::default(),
+                Duration::from_millis(10),
+                |_| false,
+            )
+            .expect("Event loop failed");
+        assert!(runtime
+            .advance_event_loop(PollEventLoopOptions::default())
+            .expect("Event loop failed"));
+    }
+
+    #[test]
+    fn test_heap_exhaustion_handled() {
         let mut runtime = Runtime::new(RuntimeOptions {
-            timeout: Duration::from_millis(50),
+            max_heap_size: Some(10 * 1024 * 1024),
             ..Default::default()
         })
         .expect("Could not create the runtime");
         let module = Module::new(
             "test.js",
-            "
-            await new Promise(r => setTimeout(r, 5000));
-        ",
+            "const largeArray = new Array(40 * 1024 * 1024).fill('a');",
         );
         runtime
             .load_modules(&module, vec![])
-            .expect_err("Did not interupt after timeout");
+            .expect_err("Did not detect heap exhaustion");
     }
 
     #[test]
-    fn test_call_entrypoint() {
-        let mut runtime =
-            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            rustyscript.register_entrypoint(() => 2);
-        ",
-        );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
-        let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call registered fn");
-        assert_eq!(2, value);
-
+    fn test_heap_limit_callback() {
+        let reported = Rc::new(std::cell::Cell::new(0));
+        let reports = reported.clone();
         let mut runtime = Runtime::new(RuntimeOptions {
-            default_entrypoint: Some("load".to_string()),
+            max_heap_size: Some(10 * 1024 * 1024),
+            on_heap_limit: Some(Rc::new(move |current, _| {
+                assert!(current > 0);
+                reports.set(reports.get() + 1);
+            })),
             ..Default::default()
         })
         .expect("Could not create the runtime");
         let module = Module::new(
             "test.js",
-            "
-            export const load = () => 2;
-        ",
+            "const largeArray = new Array(40 * 1024 * 1024).fill('a');",
         );
-        let module = runtime
+        runtime
             .load_modules(&module, vec![])
-            .expect("Could not load module");
-        let value: usize = runtime
-            .call_entrypoint(&module, json_args!())
-            .expect("Could not call exported fn");
-        assert_eq!(2, value);
+            .expect_err("Did not detect heap exhaustion");
+        assert_eq!(1, reported.get());
+    }
 
+    #[cfg(any(feature = "web", feature = "web_stub"))]
+    #[test]
+    fn test_abort_all() {
         let mut runtime =
             Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "
-            export const load = () => 2;
-        ",
-        );
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
         runtime
-            .call_entrypoint::<Undefined>(&module, json_args!())
-            .expect_err("Did not detect no entrypoint");
-    }
+            .eval::<usize>("setTimeout(() => {}, 60 * 60 * 1000)")
+            .expect("Could not queue timer");
 
-    #[test]
-    fn test_execute_module() {
         let module = Module::new(
             "test.js",
             "
-            rustyscript.register_entrypoint(() => 2);
-        ",
+            export const value = 2;
+            export const f = () => 2;
+            ",
         );
-        let value: usize =
-            Runtime::execute_module(&module, vec![], RuntimeOptions::default(), json_args!())
-                .expect("Could not exec module");
-        assert_eq!(2, value);
+        let handle = runtime.load_module(&module).expect("Could not load module");
+        let pending: crate::js_value::Promise<usize> = runtime
+            .eval("new Promise(() => {})")
+            .expect("Could not create promise");
 
-        let module = Module::new(
-            "test.js",
-            "
-            function load() { return 2; }
-        ",
-        );
-        Runtime::execute_module::<Undefined>(
-            &module,
-            vec![],
-            RuntimeOptions::default(),
-            json_args!(),
-        )
-        .expect_err("Could not detect no entrypoint");
-    }
+        runtime.abort_all();
+        assert!(runtime.is_aborted());
 
-    #[test]
-    fn call_function() {
-        let module = Module::new(
-            "test.js",
-            "
-            globalThis.fna = (i) => i;
-            export function fnb() { return 'test'; }
-            export const fnc = 2;
-            export const fne = () => {};
-        ",
-        );
+        let err = runtime
+            .block_on_event_loop(PollEventLoopOptions::default(), None)
+            .expect_err("Event loop should not run after abort");
+        assert!(matches!(err, Error::Aborted));
 
-        let mut runtime =
-            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
-        let module = runtime
-            .load_modules(&module, vec![])
-            .expect("Could not load module");
+        let err = runtime
+            .eval::<usize>("1 + 1")
+            .expect_err("Runtime should not execute JS after abort");
+        assert!(matches!(err, Error::Aborted));
 
-        let result: usize = runtime
-            .call_function(Some(&module), "fna", json_args!(2))
-            .expect("Could not call global");
-        assert_eq!(2, result);
+        let err = runtime
+            .call_function_immediate::<usize>(Some(&handle), "f", json_args!())
+            .expect_err("Immediate call should not run after abort");
+        assert!(matches!(err, Error::Aborted));
 
-        let result: String = runtime
-            .call_function(Some(&module), "fnb", json_args!())
-            .expect("Could not call export");
-        assert_eq!("test", result);
+        let err = runtime
+            .get_value_immediate::<usize>(Some(&handle), "value")
+            .expect_err("Immediate read should not run after abort");
+        assert!(matches!(err, Error::Aborted));
 
-        runtime
-            .call_function::<Undefined>(Some(&module), "fnc", json_args!())
-            .expect_err("Did not detect non-function");
-        runtime
-            .call_function::<Undefined>(Some(&module), "fnd", json_args!())
-            .expect_err("Did not detect undefined");
-        runtime
-            .call_function::<Undefined>(Some(&module), "fne", json_args!())
-            .expect("Did not allow undefined return");
-    }
+        let tokio = runtime.tokio_runtime();
+        let err = tokio
+            .block_on(runtime.call_function_async::<usize>(Some(&handle), "f", json_args!()))
+            .expect_err("Async call should not run after abort");
+        assert!(matches!(err, Error::Aborted));
 
-    ///
-    /// This function exists to check the safety of builtin core-ops
-    /// This is to confirm that updates to `deno_core` have not introduced any sandbox-breaking changes
-    /// in the form of a new op.
-    fn get_unrecognized_ops() -> Result<Vec<String>, Error> {
-        let mut runtime = Runtime::new(RuntimeOptions::default())?;
-        runtime.load_module(&Module::load("src/ext/op_whitelist.js")?)?;
-        let hnd = runtime.load_module(&Module::new(
-            "test_whitelist.js",
-            "
-            import { whitelist } from './src/ext/op_whitelist.js';
-            let ops = Deno.core.ops.op_op_names();
-            export const unsafe_ops = ops.filter(op => !whitelist.hasOwnProperty(op));
-        ",
-        ))?;
+        let err = runtime
+            .call_function_streamed::<usize>(Some(&handle), "f", std::io::Cursor::new("[]"))
+            .expect_err("Streamed call should not run after abort");
+        assert!(matches!(err, Error::Aborted));
 
-        let unsafe_ops: Vec<String> = runtime.get_value(Some(&hnd), "unsafe_ops")?;
-        Ok(unsafe_ops)
+        let results =
+            tokio.block_on(runtime.call_batch_async(&[(Some(&handle), "f", json_args!())]));
+        assert!(matches!(results[0], Err(Error::Aborted)));
+
+        let err = tokio
+            .block_on(runtime.load_module_async(&Module::new("other.js", "export const x = 1;")))
+            .expect_err("Async load should not run after abort");
+        assert!(matches!(err, Error::Aborted));
+
+        let err = tokio
+            .block_on(runtime.eval_async::<usize>("1 + 1"))
+            .expect_err("Async eval should not run after abort");
+        assert!(matches!(err, Error::Aborted));
+
+        // Promises created before the abort fail instead of waiting forever
+        let err = pending
+            .poll_once(&mut runtime)
+            .expect("Pending promise was not rejected")
+            .expect_err("Pending promise resolved after abort");
+        assert!(matches!(err, Error::Aborted));
+        let err = tokio
+            .block_on(pending.into_future(&mut runtime))
+            .expect_err("Pending promise resolved after abort");
+        assert!(matches!(err, Error::Aborted));
     }
 
     #[test]
-    fn confirm_op_whitelist() {
-        let unsafe_ops = get_unrecognized_ops().expect("Could not get unsafe ops");
-        assert_eq!(0, unsafe_ops.len(), "Found unsafe ops: {unsafe_ops:?}.\nOnce confirmed safe, add them to `src/ext/op_whitelist.js`");
+    fn test_max_lifetime() {
+        let mut runtime = crate::RuntimeBuilder::new()
+            .with_max_lifetime(Duration::from_millis(100))
+            .build()
+            .expect("Could not create the runtime");
+        let value: usize = runtime.eval("1 + 1").expect("Could not eval");
+        assert_eq!(2, value);
+        let handle = runtime
+            .load_module(&Module::new("lib.js", "export const f = () => 2;"))
+            .expect("Could not load module");
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(runtime.is_expired());
+
+        let err = runtime
+            .eval::<usize>("1 + 1")
+            .expect_err("Expired runtime accepted a call");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let module = Module::new("test.js", "export const x = 1;");
+        let err = runtime
+            .load_module(&module)
+            .expect_err("Expired runtime accepted a call");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        // Every entry point is refused, not just the blocking ones
+        let err = runtime
+            .call_function_immediate::<usize>(Some(&handle), "f", json_args!())
+            .expect_err("Expired runtime accepted an immediate call");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let tokio = runtime.tokio_runtime();
+        let err = tokio
+            .block_on(runtime.call_function_async::<usize>(Some(&handle), "f", json_args!()))
+            .expect_err("Expired runtime accepted an async call");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let err = runtime
+            .call_function_streamed::<usize>(Some(&handle), "f", std::io::Cursor::new("[]"))
+            .expect_err("Expired runtime accepted a streamed call");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let results = runtime.call_batch(&[(Some(&handle), "f", json_args!())]);
+        assert!(matches!(results[0], Err(Error::LifetimeExceeded)));
+
+        let err = tokio
+            .block_on(runtime.load_module_async(&module))
+            .expect_err("Expired runtime accepted an async load");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let err = runtime
+            .get_function(&handle, "f")
+            .expect_err("Expired runtime returned a function");
+        assert!(matches!(err, Error::LifetimeExceeded));
+
+        let err = runtime.tick().expect_err("Expired runtime ran a timer");
+        assert!(matches!(err, Error::LifetimeExceeded));
     }
 
     #[test]
-    fn test_heap_exhaustion_handled() {
-        let mut runtime = Runtime::new(RuntimeOptions {
-            max_heap_size: Some(10 * 1024 * 1024),
-            ..Default::default()
-        })
-        .expect("Could not create the runtime");
-        let module = Module::new(
-            "test.js",
-            "const largeArray = new Array(40 * 1024 * 1024).fill('a');",
-        );
-        runtime
-            .load_modules(&module, vec![])
-            .expect_err("Did not detect heap exhaustion");
+    fn test_get_function_reload() {
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create the runtime");
+        let handle = runtime
+            .load_module(&Module::new("lib.js", "export const f = () => 1;"))
+            .expect("Could not load module");
+        let f = runtime
+            .get_function(&handle, "f")
+            .expect("Could not get function");
+        let value: usize = runtime
+            .call_stored_function(Some(&handle), &f, json_args!())
+            .expect("Could not call function");
+        assert_eq!(1, value);
+
+        // Loading the module again leaves the stored function stale
+        let handle = runtime
+            .load_module(&Module::new("lib.js", "export const f = () => 2;"))
+            .expect("Could not reload module");
+        let err = runtime
+            .call_stored_function::<usize>(Some(&handle), &f, json_args!())
+            .expect_err("Stale function was called");
+        assert!(err.to_string().contains("reloaded"), "{err}");
+
+        let f = runtime
+            .get_function(&handle, "f")
+            .expect("Could not get function");
+        let value: usize = runtime
+            .call_stored_function_immediate(Some(&handle), &f, json_args!())
+            .expect("Could not call function");
+        assert_eq!(2, value);
     }
 }
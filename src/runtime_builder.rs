@@ -12,7 +12,7 @@ use crate::module_loader::ImportProvider;
 /// let runtime = RuntimeBuilder::new()
 ///     .with_timeout(std::time::Duration::from_secs(5))
 ///     .with_default_entrypoint("main".to_string())
-///     .with_cryto_seed(42)
+///     .with_insecure_crypto_seed(42)
 ///     .build()
 ///     .expect("Failed to create runtime");
 /// ```
@@ -42,6 +42,15 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Add an extension that is not part of the startup snapshot
+    /// Unlike `with_extension`, create it with `init_ops_and_esm`, even when using a snapshot
+    /// See [`RuntimeOptions::runtime_extensions`]
+    #[must_use]
+    pub fn with_runtime_extension(mut self, extension: deno_core::Extension) -> Self {
+        self.0.runtime_extensions.push(extension);
+        self
+    }
+
     /// Set the default entrypoint for the runtime
     /// This is the function to use as entrypoint if a module does not provide one
     #[must_use]
@@ -58,6 +67,39 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set the maximum heap size for the runtime, in bytes
+    /// Scripts that approach it are terminated, and the call fails with [`Error::HeapExhausted`]
+    #[must_use]
+    pub fn with_max_heap_size(mut self, max_heap_size: usize) -> Self {
+        self.0.max_heap_size = Some(max_heap_size);
+        self
+    }
+
+    /// Set a callback to run when the heap approaches the maximum heap size, before the script is terminated
+    /// See [`crate::RuntimeOptions::on_heap_limit`]
+    #[must_use]
+    pub fn with_heap_limit_callback(mut self, callback: impl Fn(usize, usize) + 'static) -> Self {
+        self.0.on_heap_limit = Some(std::rc::Rc::new(callback));
+        self
+    }
+
+    /// Set the stack size for JS, in bytes
+    /// Scripts recursing past it fail with [`Error::StackOverflow`]
+    /// This is process-wide, so it can only be set once - see [`crate::RuntimeOptions::max_stack_size`]
+    #[must_use]
+    pub fn with_max_stack_size(mut self, max_stack_size: usize) -> Self {
+        self.0.max_stack_size = Some(max_stack_size);
+        self
+    }
+
+    /// Set the wall-clock lifetime of the runtime
+    /// After it, the runtime refuses new calls with [`Error::LifetimeExceeded`], and should be recreated
+    #[must_use]
+    pub fn with_max_lifetime(mut self, lifetime: std::time::Duration) -> Self {
+        self.0.max_lifetime = Some(lifetime);
+        self
+    }
+
     /// Add an import provider for the module loader
     /// This can be used to load modules from custom sources
     /// Or provide custom resolution logic or caching
@@ -67,6 +109,242 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Replace the default resolution of import specifiers into URLs
+    /// See [`crate::module_loader::ModuleResolver`]
+    #[must_use]
+    pub fn with_module_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&str, &str) -> Result<deno_core::ModuleSpecifier, deno_core::anyhow::Error> + 'static,
+    {
+        self.0.module_resolver = Some(Box::new(resolver));
+        self
+    }
+
+    /// Alias import specifiers with an import map
+    /// See [`crate::module_loader::ImportMap`]
+    #[must_use]
+    pub fn with_import_map(mut self, import_map: crate::module_loader::ImportMap) -> Self {
+        self.0.import_map = Some(import_map);
+        self
+    }
+
+    /// Retry remote imports that fail with a transient error
+    /// See [`crate::module_loader::ImportRetryPolicy`]
+    #[must_use]
+    pub fn with_import_retry(mut self, max_retries: u32, backoff: std::time::Duration) -> Self {
+        self.0.import_retry = crate::module_loader::ImportRetryPolicy::new(max_retries, backoff);
+        self
+    }
+
+    /// Compile modules with the given file extension (without the dot) using a language plugin
+    /// See [`crate::module_loader::LanguagePlugin`]
+    #[must_use]
+    pub fn with_language_plugin(
+        mut self,
+        extension: &str,
+        plugin: impl crate::module_loader::LanguagePlugin + 'static,
+    ) -> Self {
+        self.0
+            .language_plugins
+            .insert(extension.to_string(), std::rc::Rc::new(plugin));
+        self
+    }
+
+    /// Define a compile-time constant, substituted into module sources before they are compiled
+    /// See [`crate::RuntimeOptions::compile_constants`]
+    #[must_use]
+    pub fn with_compile_constant(
+        mut self,
+        name: &str,
+        value: impl Into<deno_core::serde_json::Value>,
+    ) -> Self {
+        self.0
+            .compile_constants
+            .insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Set how modules written as CommonJS are handled
+    /// See [`crate::module_loader::CommonJsMode`]
+    #[must_use]
+    pub fn with_commonjs(mut self, mode: crate::module_loader::CommonJsMode) -> Self {
+        self.0.commonjs = mode;
+        self
+    }
+
+    /// Set what happens when a rust function is registered under a name already in use
+    /// See [`crate::DuplicateRegistration`]
+    #[must_use]
+    pub fn with_duplicate_registration(mut self, policy: crate::DuplicateRegistration) -> Self {
+        self.0.duplicate_registration = policy;
+        self
+    }
+
+    /// Set how [`crate::Runtime::format_error`] renders errors
+    /// See [`crate::error::ErrorFormat`]
+    #[must_use]
+    pub fn with_error_format(mut self, format: crate::error::ErrorFormat) -> Self {
+        self.0.error_format = format;
+        self
+    }
+
+    /// Limit how many calls to registered async functions may be in flight at once
+    /// The limit must be at least 1 - see [`crate::RuntimeOptions::max_concurrent_async_calls`]
+    #[must_use]
+    pub fn with_max_concurrent_async_calls(mut self, limit: usize) -> Self {
+        self.0.max_concurrent_async_calls = Some(limit);
+        self
+    }
+
+    /// Persist the bytecode compiled for modules in `dir`, so later runs can skip compiling them
+    /// See [`crate::RuntimeOptions::code_cache_dir`]
+    #[must_use]
+    pub fn with_code_cache_dir(mut self, dir: impl AsRef<std::path::Path>) -> Self {
+        self.0.code_cache_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Limit the memory, in bytes, that a single WASM memory may grow to
+    /// See [`crate::RuntimeOptions::max_wasm_memory`]
+    #[must_use]
+    pub fn with_max_wasm_memory(mut self, bytes: usize) -> Self {
+        self.0.max_wasm_memory = Some(bytes);
+        self
+    }
+
+    /// Limit how deeply imports may be nested below a module loaded from rust
+    /// See [`crate::RuntimeOptions::max_import_depth`]
+    #[must_use]
+    pub fn with_max_import_depth(mut self, depth: usize) -> Self {
+        self.0.max_import_depth = Some(depth);
+        self
+    }
+
+    /// Limit the size, in bytes, of `text` and `bytes` imports
+    /// See [`crate::RuntimeOptions::max_raw_import_size`]
+    #[must_use]
+    pub fn with_max_raw_import_size(mut self, bytes: usize) -> Self {
+        self.0.max_raw_import_size = Some(bytes);
+        self
+    }
+
+    /// Set separate time budgets for `file` and `http(s)` imports - `None` for no limit
+    /// See [`crate::module_loader::ImportTimeouts`]
+    #[must_use]
+    pub fn with_import_timeouts(
+        mut self,
+        file: Option<std::time::Duration>,
+        remote: Option<std::time::Duration>,
+    ) -> Self {
+        self.0.import_timeouts = crate::module_loader::ImportTimeouts::new(file, remote);
+        self
+    }
+
+    /// Only run timers when [`crate::Runtime::tick`] is called, one at a time
+    /// See [`RuntimeOptions::manual_ticks`]
+    #[must_use]
+    pub fn with_manual_ticks(mut self) -> Self {
+        self.0.manual_ticks = true;
+        self
+    }
+
+    /// Keep console output in a buffer, read with [`crate::Runtime::take_console_output`]
+    /// See [`RuntimeOptions::capture_console`]
+    #[must_use]
+    pub fn with_console_capture(mut self) -> Self {
+        self.0.capture_console = true;
+        self
+    }
+
+    /// Pass each console message to a callback, with its level and formatted text
+    /// See [`RuntimeOptions::console_sink`]
+    #[must_use]
+    pub fn with_console_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(crate::ConsoleLevel, &str) + 'static,
+    {
+        self.0.console_sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Record the module that wrote each message in console output kept by the runtime
+    /// See [`RuntimeOptions::console_locations`]
+    #[must_use]
+    pub fn with_console_locations(mut self) -> Self {
+        self.0.console_locations = true;
+        self
+    }
+
+    /// Pass each promise rejection that goes unhandled to a callback, instead of failing the event loop
+    /// See [`RuntimeOptions::on_unhandled_rejection`]
+    #[must_use]
+    pub fn with_unhandled_rejection_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(crate::JsError) + 'static,
+    {
+        self.0.on_unhandled_rejection = Some(Box::new(handler));
+        self
+    }
+
+    /// Add a read-only stand-in for common `Deno` APIs, backed by the given values
+    /// See [`RuntimeOptions::deno_stub`]
+    #[must_use]
+    pub fn with_deno_stub(mut self, stub: crate::DenoStub) -> Self {
+        self.0.deno_stub = Some(stub);
+        self
+    }
+
+    /// Drive timers and `Date.now` from a virtual clock starting at `start`
+    /// See [`RuntimeOptions::virtual_clock`]
+    #[must_use]
+    pub fn with_virtual_clock(mut self, start: std::time::SystemTime) -> Self {
+        self.0.virtual_clock = Some(start);
+        self
+    }
+
+    /// Set a manifest of modules to make available to the runtime at creation
+    /// See [`crate::module_loader::ModuleManifest`]
+    #[must_use]
+    pub fn with_manifest(mut self, manifest: crate::module_loader::ModuleManifest) -> Self {
+        self.0.manifest = Some(manifest);
+        self
+    }
+
+    /// Serve `file:` imports from an in-memory filesystem before falling back to disk
+    /// See [`crate::module_loader::VirtualFileSystem`]
+    #[must_use]
+    pub fn with_virtual_fs(
+        mut self,
+        virtual_fs: impl crate::module_loader::VirtualFileSystem + 'static,
+    ) -> Self {
+        self.0.virtual_fs = Some(std::rc::Rc::new(virtual_fs));
+        self
+    }
+
+    /// Record exceptions thrown by a module's top-level code on its handle, instead of failing the load
+    /// See [`crate::ModuleHandle::load_error`] and [`crate::ModuleHandle::side_module_errors`]
+    #[must_use]
+    pub fn with_captured_load_errors(mut self) -> Self {
+        self.0.capture_load_errors = true;
+        self
+    }
+
+    /// Set a logger for the runtime's internal diagnostics
+    /// See [`crate::Logger`]
+    #[must_use]
+    pub fn with_logger(mut self, logger: std::rc::Rc<dyn crate::Logger>) -> Self {
+        self.0.logger = Some(logger);
+        self
+    }
+
+    /// Set the registry used to resolve `npm:` specifiers
+    #[cfg(feature = "npm_import")]
+    #[must_use]
+    pub fn with_npm_registry(mut self, registry: crate::module_loader::NpmRegistry) -> Self {
+        self.0.npm_registry = registry;
+        self
+    }
+
     /// Set the startup snapshot for the runtime
     /// This will reduce load times, but requires the same extensions to be loaded
     /// as when the snapshot was created
@@ -79,6 +357,15 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Create the runtime so that its state can later be captured with [`crate::Runtime::take_snapshot`]
+    /// See [`RuntimeOptions::snapshottable`]
+    #[cfg(feature = "snapshot_builder")]
+    #[must_use]
+    pub fn with_snapshottable(mut self) -> Self {
+        self.0.snapshottable = true;
+        self
+    }
+
     /// Set the params used to create the underlying V8 isolate
     /// This can be used to alter the behavior of the runtime.
     /// See the `rusty_v8` documentation for more information
@@ -114,11 +401,33 @@ impl RuntimeBuilder {
     /// Set the initial seed for the crypto extension
     #[cfg(feature = "crypto")]
     #[must_use]
-    pub fn with_cryto_seed(mut self, seed: u64) -> Self {
+    #[deprecated(since = "0.9.1", note = "Use `with_insecure_crypto_seed` instead")]
+    pub fn with_cryto_seed(self, seed: u64) -> Self {
+        self.with_insecure_crypto_seed(seed)
+    }
+
+    /// Seed the random source of the crypto extension, making it deterministic
+    ///
+    /// INSECURE: `crypto.getRandomValues`, `crypto.randomUUID` and generated keys become
+    /// predictable. This exists for reproducible tests of crypto-using scripts - never use it in production.
+    /// A warning is sent to the runtime's logger, if one is set
+    #[cfg(feature = "crypto")]
+    #[must_use]
+    pub fn with_insecure_crypto_seed(mut self, seed: u64) -> Self {
         self.0.extension_options.crypto_seed = Some(seed);
         self
     }
 
+    /// Make `Math.random`, and `crypto.getRandomValues` if enabled, produce the same values on every run
+    /// See [`RuntimeOptions::deterministic_seed`]
+    ///
+    /// INSECURE: random values become predictable - only use this for tests and fuzzing
+    #[must_use]
+    pub fn with_deterministic_seed(mut self, seed: u64) -> Self {
+        self.0.deterministic_seed = Some(seed);
+        self
+    }
+
     /// Set the options for the io extension
     #[cfg(feature = "io")]
     #[must_use]
@@ -127,6 +436,15 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Replace the stdio streams seen by scripts with host-controlled readers and writers
+    /// See [`crate::IoOptions`]
+    #[cfg(feature = "io")]
+    #[must_use]
+    pub fn with_io(mut self, io: crate::IoOptions) -> Self {
+        self.0.extension_options.io = io;
+        self
+    }
+
     /// Set the options for the webstorage extension
     #[cfg(feature = "webstorage")]
     #[must_use]
@@ -135,6 +453,14 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Limit the size of each webstorage area
+    #[cfg(feature = "webstorage")]
+    #[must_use]
+    pub fn with_webstorage_quota(mut self, quota: crate::WebStorageQuota) -> Self {
+        self.0.extension_options.webstorage_quota = Some(quota);
+        self
+    }
+
     /// Set the options for the cache extension
     #[cfg(feature = "cache")]
     #[must_use]
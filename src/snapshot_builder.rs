@@ -51,10 +51,21 @@ pub struct SnapshotBuilder {
     deno_runtime: JsRuntimeForSnapshot,
     tokio_runtime: Rc<tokio::runtime::Runtime>,
     options: RuntimeOptions,
+
+    // Waited for on drop, after `deno_runtime`
+    #[cfg(feature = "io")]
+    _io_threads: crate::ext::io::IoThreads,
 }
 impl SnapshotBuilder {
     /// Creates a new snapshot builder with the given options
     pub fn new(options: RuntimeOptions) -> Result<Self, Error> {
+        #[cfg(feature = "io")]
+        let (options, io_threads) = {
+            let mut options = options;
+            let io_threads = options.extension_options.connect_io()?;
+            (options, io_threads)
+        };
+
         let module_loader = Rc::new(RustyLoader::new(LoaderOptions {
             cache_provider: options.module_cache,
             import_provider: options.import_provider,
@@ -74,6 +85,14 @@ impl SnapshotBuilder {
                 transpile_extension(&specifier, &code)
             })),
 
+            // Allow `text` and `bytes` imports
+            validate_import_attributes_cb: Some(Box::new(
+                crate::module_loader::raw_imports::validate_import_attributes,
+            )),
+            custom_module_evaluation_cb: Some(Box::new(
+                crate::module_loader::raw_imports::evaluate_raw_module,
+            )),
+
             create_params: options.isolate_params,
             shared_array_buffer_store: options.shared_array_buffer_store,
 
@@ -96,8 +115,12 @@ impl SnapshotBuilder {
             options: RuntimeOptions {
                 timeout: options.timeout,
                 default_entrypoint: options.default_entrypoint,
+                logger: options.logger,
                 ..Default::default()
             },
+
+            #[cfg(feature = "io")]
+            _io_threads: io_threads,
         })
     }
 
@@ -140,10 +163,11 @@ impl SnapshotBuilder {
     /// Possibly resulting in a runtime panic if used incorrectly
     pub fn load_module(&mut self, module: &Module) -> Result<ModuleId, Error> {
         let timeout = self.options.timeout;
+        let logger = self.options.logger.clone();
         let deno_runtime = &mut self.deno_runtime;
         let tokio_runtime = self.tokio_runtime.clone();
 
-        tokio_runtime.block_on(async move {
+        let result = tokio_runtime.block_on(async move {
             tokio::time::timeout(timeout, async move {
                 let module_specifier = module.filename().to_module_specifier(None)?;
                 let (code, _) = transpiler::transpile(&module_specifier, module.contents())?;
@@ -160,6 +184,11 @@ impl SnapshotBuilder {
                 Ok::<ModuleId, Error>(modid)
             })
             .await
-        })?
+        });
+
+        if let (Some(logger), Err(e)) = (logger, &result) {
+            logger.log(crate::LogLevel::Warn, &Error::from(*e).to_string());
+        }
+        result?
     }
 }
@@ -5,20 +5,42 @@
 //! It will only transpile, not typecheck (like Deno's `--no-check` flag).
 
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
 
 use deno_ast::MediaType;
 use deno_ast::ParseParams;
 use deno_ast::SourceTextInfo;
+use deno_ast::TokenOrComment;
 use deno_core::anyhow::Error;
 use deno_core::error::AnyError;
 use deno_core::FastString;
 use deno_core::ModuleSpecifier;
 use deno_core::SourceMapData;
 
+use crate::module::{ExportKind, ExportSignature, FunctionCall, FunctionKind};
 use crate::traits::ToModuleSpecifier;
 
 pub type ModuleContents = (String, Option<SourceMapData>);
 
+/// A source map produced by [`transpile_with_sourcemap`]
+/// Maps positions in the transpiled JS back to the original source, in the standard (v3) JSON format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap(String);
+impl SourceMap {
+    /// Returns the source map as JSON text
+    #[must_use]
+    pub fn as_json(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the source map as raw bytes
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
 fn should_transpile(media_type: MediaType) -> bool {
     matches!(
         media_type,
@@ -33,12 +55,55 @@ fn should_transpile(media_type: MediaType) -> bool {
     )
 }
 
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    /// The module can still be used
+    Warning,
+
+    /// The module is invalid
+    Error,
+}
+
+/// A problem found in a module's source, and where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// A description of the problem
+    pub message: String,
+
+    /// How serious the problem is
+    pub severity: DiagnosticSeverity,
+
+    /// The line the problem starts on, starting at 1
+    pub line: usize,
+
+    /// The column the problem starts at, starting at 1
+    pub column: usize,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
 ///
 /// Transpiles source code from TS to JS without typechecking
 pub fn transpile(module_specifier: &ModuleSpecifier, code: &str) -> Result<ModuleContents, Error> {
+    transpile_with_diagnostics(module_specifier, code).map(|(contents, _)| contents)
+}
+
+///
+/// Transpiles source code from TS to JS without typechecking
+/// Also returns the non-fatal problems the parser recovered from, as warnings
+pub fn transpile_with_diagnostics(
+    module_specifier: &ModuleSpecifier,
+    code: &str,
+) -> Result<(ModuleContents, Vec<Diagnostic>), Error> {
     let media_type = MediaType::from_specifier(module_specifier);
     let should_transpile = should_transpile(media_type);
 
+    let mut diagnostics = Vec::new();
     let code = if should_transpile {
         let sti = SourceTextInfo::from_string(code.to_string());
         let text = sti.text();
@@ -51,6 +116,17 @@ pub fn transpile(module_specifier: &ModuleSpecifier, code: &str) -> Result<Modul
             maybe_syntax: None,
         })?;
 
+        // Fatal problems fail the transpile below, so only recoverable ones remain
+        diagnostics.extend(parsed.diagnostics().iter().map(|d| {
+            let position = d.display_position();
+            Diagnostic {
+                message: d.message().to_string(),
+                severity: DiagnosticSeverity::Warning,
+                line: position.line_number,
+                column: position.column_number,
+            }
+        }));
+
         let transpile_options = deno_ast::TranspileOptions {
             ..Default::default()
         };
@@ -77,7 +153,728 @@ pub fn transpile(module_specifier: &ModuleSpecifier, code: &str) -> Result<Modul
         (code.to_string(), None)
     };
 
-    Ok(code)
+    Ok((code, diagnostics))
+}
+
+/// Transpiles source code from TS to JS without typechecking, returning the source map alongside the JS
+/// The map is `None` for modules that need no transpiling, such as plain JS, since their positions are unchanged
+///
+/// Modules loaded by the runtime are mapped automatically, so that error positions point at the original source.
+/// This is for hosts that transpile code themselves, or need the map for their own tooling
+///
+/// # Arguments
+/// * `specifier` - The filename of the module, used to pick the language. Relative paths resolve against the current directory
+/// * `code` - The source code to transpile
+///
+/// # Errors
+/// Will return an error if the specifier is invalid, or the code cannot be parsed
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ transpile_with_sourcemap, Error };
+///
+/// # fn main() -> Result<(), Error> {
+/// let (js, map) = transpile_with_sourcemap("example.ts", "export const x: number = 2;")?;
+/// assert!(!js.contains(": number"));
+/// assert!(map.expect("TS is transpiled").as_json().contains("\"mappings\""));
+/// # Ok(())
+/// # }
+/// ```
+pub fn transpile_with_sourcemap(
+    specifier: &str,
+    code: &str,
+) -> Result<(String, Option<SourceMap>), crate::Error> {
+    let module_specifier = specifier.to_module_specifier(None)?;
+    let (code, source_map) = transpile(&module_specifier, code)?;
+    let source_map = source_map.map(|map| SourceMap(String::from_utf8_lossy(&map).into_owned()));
+    Ok((code, source_map))
+}
+
+///
+/// Replaces each use of a compile-time constant with its replacement text
+/// Only references that resolve to no declaration are replaced - a constant's name used as a property
+/// (`a.FLAG`, `a?.FLAG`), an object key, or a binding shadowing it (parameters, destructuring, imports)
+/// is left untouched. Shorthand properties (`{ FLAG }`) are expanded to `{ FLAG: value }`
+///
+/// Ambient declarations (`declare const FLAG: boolean`) only describe the constant, so they do not shadow it
+/// Modules that do not parse are returned unchanged, for the compiler to report
+pub fn substitute_constants(
+    module_specifier: &ModuleSpecifier,
+    code: &str,
+    constants: &HashMap<String, String>,
+) -> String {
+    use deno_ast::swc::ast::{Decl, Expr, Ident, ModuleDecl, ModuleItem, Pat, Prop, Stmt};
+    use deno_ast::swc::common::SyntaxContext;
+    use deno_ast::swc::visit::{Visit, VisitWith};
+    use deno_ast::{SourceRangedForSpanned, StartSourcePos};
+
+    struct Finder<'a> {
+        constants: &'a HashMap<String, String>,
+        unresolved: SyntaxContext,
+        top_level: SyntaxContext,
+        ambient: Vec<String>,
+        start: StartSourcePos,
+        found: Vec<(std::ops::Range<usize>, String)>,
+    }
+    impl Finder<'_> {
+        fn value(&self, ident: &Ident) -> Option<&String> {
+            let name = ident.sym.as_ref();
+            let is_global = ident.ctxt == self.unresolved
+                || (ident.ctxt == self.top_level && self.ambient.iter().any(|a| a == name));
+            is_global.then(|| self.constants.get(name)).flatten()
+        }
+    }
+    impl Visit for Finder<'_> {
+        fn visit_expr(&mut self, n: &Expr) {
+            match n {
+                Expr::Ident(ident) => {
+                    if let Some(value) = self.value(ident).cloned() {
+                        let range = ident.range().as_byte_range(self.start);
+                        self.found.push((range, value));
+                    }
+                }
+                _ => n.visit_children_with(self),
+            }
+        }
+
+        fn visit_prop(&mut self, n: &Prop) {
+            match n {
+                Prop::Shorthand(ident) => {
+                    if let Some(value) = self.value(ident) {
+                        let value = format!("{}: {value}", ident.sym);
+                        let range = ident.range().as_byte_range(self.start);
+                        self.found.push((range, value));
+                    }
+                }
+                _ => n.visit_children_with(self),
+            }
+        }
+    }
+
+    if constants.is_empty() {
+        return code.to_string();
+    }
+
+    let media_type = match MediaType::from_specifier(module_specifier) {
+        MediaType::Json => return code.to_string(),
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+    let Ok(parsed) = deno_ast::parse_module(ParseParams {
+        specifier: module_specifier.clone(),
+        text: code.into(),
+        media_type,
+        capture_tokens: false,
+        scope_analysis: true,
+        maybe_syntax: None,
+    }) else {
+        return code.to_string();
+    };
+
+    // Names only declared with `declare`, at the top level
+    let mut ambient = Vec::new();
+    let mut declared = Vec::new();
+    for item in &parsed.module().body {
+        let decl = match item {
+            ModuleItem::Stmt(Stmt::Decl(decl)) => decl,
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => &export.decl,
+            _ => continue,
+        };
+        let Decl::Var(var) = decl else {
+            continue;
+        };
+        for declarator in &var.decls {
+            if let Pat::Ident(ident) = &declarator.name {
+                let names = if var.declare {
+                    &mut ambient
+                } else {
+                    &mut declared
+                };
+                names.push(ident.sym.to_string());
+            }
+        }
+    }
+    ambient.retain(|name| !declared.contains(name));
+
+    let mut finder = Finder {
+        constants,
+        unresolved: parsed.unresolved_context(),
+        top_level: parsed.top_level_context(),
+        ambient,
+        start: parsed.text_info_lazy().range().start,
+        found: Vec::new(),
+    };
+    parsed.module().visit_with(&mut finder);
+    finder.found.sort_by_key(|(range, _)| range.start);
+
+    let mut result = String::with_capacity(code.len());
+    let mut last = 0;
+    for (range, value) in finder.found {
+        result.push_str(&code[last..range.start]);
+        result.push_str(&value);
+        last = range.end;
+    }
+    result.push_str(&code[last..]);
+    result
+}
+
+///
+/// Finds direct calls to registered rust functions in a module's source
+/// Matches `rustyscript.<proxy>.name(...)` and `rustyscript.<proxy>['name'](...)`
+pub fn find_function_calls(path: &Path, code: &str) -> Vec<FunctionCall> {
+    let media_type = match MediaType::from_path(path) {
+        MediaType::Json => return vec![],
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+
+    let tokens: Vec<_> = deno_ast::lex(code, media_type)
+        .into_iter()
+        .filter(|item| matches!(item.inner, TokenOrComment::Token(_)))
+        .collect();
+    let text = |i: usize| tokens.get(i).map(|token| &code[token.range.clone()]);
+
+    let mut calls = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if text(i) != Some("rustyscript") || text(i + 1) != Some(".") {
+            continue;
+        }
+        let kind = match text(i + 2) {
+            Some("functions") => FunctionKind::Sync,
+            Some("async_functions") => FunctionKind::Async,
+            Some("stream_functions") => FunctionKind::Stream,
+            Some("iterator_functions") => FunctionKind::Iterator,
+            _ => continue,
+        };
+
+        // The name, and the position of the opening parenthesis
+        let (name, open) = match (text(i + 3), text(i + 4), text(i + 5)) {
+            (Some("."), Some(name), _) => (name.to_string(), i + 5),
+            (Some("["), Some(name), Some("]"))
+                if name.len() >= 2 && (name.starts_with('\'') || name.starts_with('"')) =>
+            {
+                (name[1..name.len() - 1].to_string(), i + 6)
+            }
+            _ => continue,
+        };
+        if text(open) != Some("(") {
+            continue;
+        }
+
+        // Count the top-level commas up to the closing parenthesis
+        let mut depth = 0usize;
+        let mut commas = 0;
+        let mut spread = false;
+        let mut close = None;
+        for j in open + 1..tokens.len() {
+            match text(j) {
+                Some("(" | "[" | "{" | "${") => depth += 1,
+                Some(")") if depth == 0 => {
+                    close = Some(j);
+                    break;
+                }
+                Some(")" | "]" | "}") => depth = depth.saturating_sub(1),
+                Some(",") if depth == 0 => commas += 1,
+                Some("...") if depth == 0 => spread = true,
+                _ => {}
+            }
+        }
+        let Some(close) = close else {
+            continue;
+        };
+
+        let args = if close == open + 1 {
+            0
+        } else if text(close - 1) == Some(",") {
+            commas
+        } else {
+            commas + 1
+        };
+
+        calls.push(FunctionCall {
+            kind,
+            name,
+            args: (!spread).then_some(args),
+            line: code[..token.range.start].matches('\n').count() + 1,
+        });
+    }
+
+    calls
+}
+
+///
+/// Finds the first construct an isolated module is not allowed to use, and describes it
+/// Isolated modules run in their own context, without a module loader or an event loop,
+/// so they cannot import other modules, use `import.meta`, or declare async functions.
+/// Pure modules cannot declare generators either, since they hold state between calls
+pub fn find_isolation_violation(
+    module_specifier: &ModuleSpecifier,
+    code: &str,
+    pure: bool,
+) -> Result<Option<String>, Error> {
+    use deno_ast::swc::ast::{
+        ArrowExpr, AwaitExpr, CallExpr, Callee, ExportAll, Function, ImportDecl, MetaPropExpr,
+        MetaPropKind, NamedExport,
+    };
+    use deno_ast::swc::visit::{Visit, VisitWith};
+
+    struct Finder {
+        pure: bool,
+        found: Option<String>,
+    }
+    impl Finder {
+        fn report(&mut self, message: impl Into<String>) {
+            self.found.get_or_insert_with(|| message.into());
+        }
+    }
+    impl Visit for Finder {
+        fn visit_import_decl(&mut self, n: &ImportDecl) {
+            self.report(format!("imports {}", n.src.value));
+        }
+
+        fn visit_named_export(&mut self, n: &NamedExport) {
+            match &n.src {
+                Some(src) => self.report(format!("re-exports {}", src.value)),
+                None => n.visit_children_with(self),
+            }
+        }
+
+        fn visit_export_all(&mut self, n: &ExportAll) {
+            self.report(format!("re-exports {}", n.src.value));
+        }
+
+        fn visit_call_expr(&mut self, n: &CallExpr) {
+            if matches!(n.callee, Callee::Import(_)) {
+                self.report("uses a dynamic import()");
+            }
+            n.visit_children_with(self);
+        }
+
+        fn visit_meta_prop_expr(&mut self, n: &MetaPropExpr) {
+            if n.kind == MetaPropKind::ImportMeta {
+                self.report("uses import.meta");
+            }
+        }
+
+        fn visit_function(&mut self, n: &Function) {
+            if n.is_async {
+                self.report("declares an async function");
+            } else if self.pure && n.is_generator {
+                self.report("declares a generator");
+            }
+            n.visit_children_with(self);
+        }
+
+        fn visit_arrow_expr(&mut self, n: &ArrowExpr) {
+            if n.is_async {
+                self.report("declares an async function");
+            }
+            n.visit_children_with(self);
+        }
+
+        fn visit_await_expr(&mut self, n: &AwaitExpr) {
+            self.report("uses await");
+            n.visit_children_with(self);
+        }
+    }
+
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: module_specifier.clone(),
+        text: code.into(),
+        media_type: MediaType::JavaScript,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })?;
+
+    let mut finder = Finder { pure, found: None };
+    parsed.module().visit_with(&mut finder);
+    Ok(finder.found)
+}
+
+///
+/// Checks if a module is written as CommonJS
+/// `.cjs` and `.cts` files always are - other modules are if they use `module.exports`,
+/// `exports.name` or `require(...)`, and contain no `import` or `export` declarations
+pub fn is_commonjs(module_specifier: &ModuleSpecifier, code: &str) -> bool {
+    let media_type = match MediaType::from_specifier(module_specifier) {
+        MediaType::Cjs | MediaType::Cts => return true,
+        MediaType::Json => return false,
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+
+    let tokens = word_tokens(code, media_type);
+    let text = |i: usize| tokens.get(i).map(|(text, _)| *text);
+    let word = |i: usize| {
+        tokens
+            .get(i)
+            .and_then(|(text, is_word)| is_word.then_some(*text))
+    };
+
+    let mut uses_commonjs = false;
+    for i in 0..tokens.len() {
+        // Properties sharing a name (`a.module`, `a.require()`) do not count
+        if i > 0 && text(i - 1) == Some(".") {
+            continue;
+        }
+
+        match word(i) {
+            Some("export") => return false,
+            Some("import") if !matches!(text(i + 1), Some("(" | ".")) => return false,
+            Some("module") if text(i + 1) == Some(".") && text(i + 2) == Some("exports") => {
+                uses_commonjs = true;
+            }
+            Some("exports") if text(i + 1) == Some(".") => uses_commonjs = true,
+            Some("require") if text(i + 1) == Some("(") => uses_commonjs = true,
+            _ => {}
+        }
+    }
+
+    uses_commonjs
+}
+
+///
+/// Wraps a CommonJS module so that it can be loaded as an ES module
+/// `module.exports` becomes the default export, and names assigned to `exports.name`,
+/// `module.exports.name` or listed in a `module.exports = { ... }` literal become named exports
+///
+/// `require` calls with a string literal are hoisted into static imports, so every required
+/// module is loaded up front, and returns the default export of the required module if it has one
+///
+/// The wrapper starts on the first line, so line numbers are unaffected
+pub fn wrap_commonjs(module_specifier: &ModuleSpecifier, code: &str) -> String {
+    let media_type = match MediaType::from_specifier(module_specifier) {
+        MediaType::Unknown | MediaType::Cjs => MediaType::JavaScript,
+        MediaType::Cts => MediaType::TypeScript,
+        media_type => media_type,
+    };
+
+    let tokens = word_tokens(code, media_type);
+    let (required, names) = commonjs_bindings(&tokens);
+
+    let mut prefix = String::new();
+    let mut require_map = String::new();
+    for (i, specifier) in required.iter().enumerate() {
+        prefix.push_str(&format!("import * as __cjs_require_{i} from {specifier};"));
+        require_map.push_str(&format!("{specifier}: __cjs_require_{i},"));
+    }
+    prefix.push_str(&format!(
+        "const __cjs_required = {{{require_map}}};\
+        const __cjs_require = (specifier) => {{\
+            if (!Object.hasOwn(__cjs_required, specifier)) {{\
+                throw new Error(`Cannot require '${{specifier}}' - only string literals can be required`);\
+            }}\
+            const namespace = __cjs_required[specifier];\
+            return 'default' in namespace ? namespace.default : namespace;\
+        }};\
+        const __cjs_module = {{ exports: {{}} }};\
+        (function (exports, require, module) {{"
+    ));
+
+    let mut suffix = String::from(
+        "\n}).call(__cjs_module.exports, __cjs_module.exports, __cjs_require, __cjs_module);\n\
+        export default __cjs_module.exports;\n",
+    );
+    if !names.is_empty() {
+        let bindings: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("{name}: __cjs_export_{i}"))
+            .collect();
+        let exports: Vec<_> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("__cjs_export_{i} as {name}"))
+            .collect();
+        suffix.push_str(&format!(
+            "const {{ {} }} = __cjs_module.exports;\nexport {{ {} }};\n",
+            bindings.join(", "),
+            exports.join(", ")
+        ));
+    }
+
+    format!("{prefix}{code}{suffix}")
+}
+
+/// Finds the specifiers passed to `require`, and the names exported by a CommonJS module
+fn commonjs_bindings<'a>(tokens: &[(&'a str, bool)]) -> (Vec<&'a str>, Vec<&'a str>) {
+    let text = |i: usize| tokens.get(i).map(|(text, _)| *text);
+    let word = |i: usize| {
+        tokens
+            .get(i)
+            .and_then(|(text, is_word)| is_word.then_some(*text))
+    };
+    let is_string = |i: usize| text(i).is_some_and(|t| t.starts_with('\'') || t.starts_with('"'));
+
+    let mut required = Vec::new();
+    let mut names = Vec::new();
+    for i in 0..tokens.len() {
+        if i > 0 && text(i - 1) == Some(".") {
+            continue;
+        }
+
+        match word(i) {
+            Some("require")
+                if text(i + 1) == Some("(") && is_string(i + 2) && text(i + 3) == Some(")") =>
+            {
+                if let Some(specifier) = text(i + 2).filter(|s| !required.contains(s)) {
+                    required.push(specifier);
+                }
+            }
+
+            // exports.name = ...
+            Some("exports") if text(i + 1) == Some(".") && text(i + 3) == Some("=") => {
+                names.extend(word(i + 2));
+            }
+
+            // module.exports.name = ...
+            Some("module")
+                if text(i + 1) == Some(".")
+                    && text(i + 2) == Some("exports")
+                    && text(i + 3) == Some(".")
+                    && text(i + 5) == Some("=") =>
+            {
+                names.extend(word(i + 4));
+            }
+
+            // module.exports = { name, name: ..., name() {} }
+            Some("module")
+                if text(i + 1) == Some(".")
+                    && text(i + 2) == Some("exports")
+                    && text(i + 3) == Some("=")
+                    && text(i + 4) == Some("{") =>
+            {
+                let mut depth = 0usize;
+                for j in i + 5..tokens.len() {
+                    match text(j) {
+                        Some("}") if depth == 0 => break,
+                        Some("(" | "[" | "{" | "${") => depth += 1,
+                        Some(")" | "]" | "}") => depth = depth.saturating_sub(1),
+                        _ if depth == 0
+                            && matches!(text(j - 1), Some("{" | ","))
+                            && matches!(text(j + 1), Some("," | ":" | "}" | "(")) =>
+                        {
+                            names.extend(word(j));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+    names.retain(|name| *name != "default");
+    names.sort_unstable();
+    names.dedup();
+
+    (required, names)
+}
+
+/// Lexes a module's source, skipping comments
+/// Returns the text of each token, and whether it is a word (an identifier or keyword)
+fn word_tokens(code: &str, media_type: MediaType) -> Vec<(&str, bool)> {
+    deno_ast::lex(code, media_type)
+        .into_iter()
+        .filter_map(|item| match item.inner {
+            TokenOrComment::Token(token) => Some((
+                &code[item.range],
+                matches!(token, deno_ast::swc::parser::token::Token::Word(_)),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+///
+/// Lists the exports declared in a module's source, without running it
+/// Names re-exported with `export * from '...'` cannot be known without loading the other module, and are skipped
+pub fn find_exports(path: &Path, code: &str) -> Result<Vec<ExportSignature>, Error> {
+    use deno_ast::swc::ast::{
+        DefaultDecl, ExportSpecifier, ModuleDecl, ModuleExportName, ModuleItem, Stmt,
+    };
+
+    let media_type = match MediaType::from_path(path) {
+        MediaType::Unknown => MediaType::JavaScript,
+        media_type => media_type,
+    };
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: path.to_module_specifier(None)?,
+        text: code.into(),
+        media_type,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })?;
+    let module = parsed.module();
+
+    // Every top-level declaration, so `export { name }` can be described
+    let mut locals = HashMap::new();
+    for item in &module.body {
+        let decl = match item {
+            ModuleItem::Stmt(Stmt::Decl(decl)) => decl,
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export)) => &export.decl,
+            _ => continue,
+        };
+        for (name, kind, arity) in declared_bindings(decl) {
+            locals.insert(name, (kind, arity));
+        }
+    }
+
+    let export_name = |name: &ModuleExportName| match name {
+        ModuleExportName::Ident(ident) => ident.sym.to_string(),
+        ModuleExportName::Str(s) => s.value.to_string(),
+    };
+
+    let mut exports = Vec::new();
+    let mut push = |name: String, (kind, arity): (ExportKind, Option<u32>)| {
+        exports.push(ExportSignature { name, kind, arity });
+    };
+    for item in &module.body {
+        let ModuleItem::ModuleDecl(decl) = item else {
+            continue;
+        };
+        match decl {
+            ModuleDecl::ExportDecl(export) => {
+                for (name, kind, arity) in declared_bindings(&export.decl) {
+                    push(name, (kind, arity));
+                }
+            }
+            ModuleDecl::ExportDefaultDecl(export) => match &export.decl {
+                DefaultDecl::Fn(f) => push(
+                    "default".to_string(),
+                    (
+                        ExportKind::Function,
+                        Some(js_arity(f.function.params.iter().map(|p| &p.pat))),
+                    ),
+                ),
+                DefaultDecl::Class(_) => push("default".to_string(), (ExportKind::Class, None)),
+                DefaultDecl::TsInterfaceDecl(_) => {}
+            },
+            ModuleDecl::ExportDefaultExpr(export) => {
+                push("default".to_string(), expr_shape(&export.expr));
+            }
+            ModuleDecl::ExportNamed(export) if !export.type_only => {
+                for specifier in &export.specifiers {
+                    match specifier {
+                        ExportSpecifier::Named(named) if !named.is_type_only => {
+                            let local = export_name(&named.orig);
+                            let name = named
+                                .exported
+                                .as_ref()
+                                .map_or_else(|| local.clone(), export_name);
+
+                            // Re-exports from another module cannot be described without loading it
+                            let shape = match export.src {
+                                None => locals.get(&local).copied(),
+                                Some(_) => None,
+                            };
+                            push(name, shape.unwrap_or((ExportKind::Unknown, None)));
+                        }
+                        ExportSpecifier::Namespace(namespace) => {
+                            push(export_name(&namespace.name), (ExportKind::Value, None));
+                        }
+                        ExportSpecifier::Default(default) => {
+                            push(
+                                default.exported.sym.to_string(),
+                                (ExportKind::Unknown, None),
+                            );
+                        }
+                        ExportSpecifier::Named(_) => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(exports)
+}
+
+/// The runtime bindings introduced by a declaration - type-only declarations introduce none
+fn declared_bindings(decl: &deno_ast::swc::ast::Decl) -> Vec<(String, ExportKind, Option<u32>)> {
+    use deno_ast::swc::ast::{Decl, Pat};
+
+    match decl {
+        Decl::Fn(f) => vec![(
+            f.ident.sym.to_string(),
+            ExportKind::Function,
+            Some(js_arity(f.function.params.iter().map(|p| &p.pat))),
+        )],
+        Decl::Class(c) => vec![(c.ident.sym.to_string(), ExportKind::Class, None)],
+        Decl::Var(var) => var
+            .decls
+            .iter()
+            .flat_map(|declarator| {
+                // Destructured names take their values from the initializer at runtime
+                let Pat::Ident(ident) = &declarator.name else {
+                    return pattern_names(&declarator.name)
+                        .into_iter()
+                        .map(|name| (name, ExportKind::Unknown, None))
+                        .collect();
+                };
+                let (kind, arity) = declarator
+                    .init
+                    .as_deref()
+                    .map_or((ExportKind::Value, None), expr_shape);
+                vec![(ident.id.sym.to_string(), kind, arity)]
+            })
+            .collect(),
+        Decl::TsEnum(e) => vec![(e.id.sym.to_string(), ExportKind::Value, None)],
+        _ => vec![],
+    }
+}
+
+/// The names bound by a binding pattern, such as `{ a, b: [c, ...d] = [] }`
+fn pattern_names(pat: &deno_ast::swc::ast::Pat) -> Vec<String> {
+    use deno_ast::swc::ast::{BindingIdent, Expr};
+    use deno_ast::swc::visit::{Visit, VisitWith};
+
+    struct Finder(Vec<String>);
+    impl Visit for Finder {
+        fn visit_binding_ident(&mut self, n: &BindingIdent) {
+            self.0.push(n.id.sym.to_string());
+        }
+
+        // Defaults and computed keys are expressions, and bind nothing
+        fn visit_expr(&mut self, _: &Expr) {}
+    }
+
+    let mut finder = Finder(Vec::new());
+    pat.visit_with(&mut finder);
+    finder.0
+}
+
+/// Describes the value an expression evaluates to, as far as can be told without running it
+fn expr_shape(expr: &deno_ast::swc::ast::Expr) -> (ExportKind, Option<u32>) {
+    use deno_ast::swc::ast::Expr;
+
+    match expr {
+        Expr::Arrow(arrow) => (ExportKind::Function, Some(js_arity(arrow.params.iter()))),
+        Expr::Fn(f) => (
+            ExportKind::Function,
+            Some(js_arity(f.function.params.iter().map(|p| &p.pat))),
+        ),
+        Expr::Class(_) => (ExportKind::Class, None),
+        Expr::Paren(paren) => expr_shape(&paren.expr),
+        Expr::TsAs(e) => expr_shape(&e.expr),
+        Expr::TsSatisfies(e) => expr_shape(&e.expr),
+        Expr::TsConstAssertion(e) => expr_shape(&e.expr),
+        _ => (ExportKind::Value, None),
+    }
+}
+
+/// Mirrors a function's `length` - parameters before the first default or rest parameter
+fn js_arity<'a>(params: impl Iterator<Item = &'a deno_ast::swc::ast::Pat>) -> u32 {
+    use deno_ast::swc::ast::Pat;
+
+    let count = params
+        .filter(|pat| !matches!(pat, Pat::Ident(ident) if &*ident.id.sym == "this"))
+        .take_while(|pat| !matches!(pat, Pat::Assign(_) | Pat::Rest(_)))
+        .count();
+    u32::try_from(count).unwrap_or(u32::MAX)
 }
 
 ///
@@ -0,0 +1,286 @@
+//! Custom JS representations for rust types that cross the runtime boundary
+use std::cell::RefCell;
+
+use crate::{traits::ToV8String, Error};
+use deno_core::{v8, JsRuntime};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Marks a [`JsRepr`] value in the arguments sent to JS, naming the type it came from
+const REPR_TAG: &str = "$rustyscript_repr";
+
+/// Replaces marked values sent to JS, and values read back as a given type, using the registered conversions
+/// Only arrays and plain objects are descended into
+const REGISTRY_SOURCE: &str = "(() => {
+    const conversions = new Map();
+    const isPlain = (v) => v !== null && typeof v === 'object' && (
+        Object.getPrototypeOf(v) === Object.prototype || Object.getPrototypeOf(v) === null
+    );
+
+    // Arguments come from serde, so cannot contain cycles
+    const toJs = (value) => {
+        if (Array.isArray(value)) return value.map(toJs);
+        if (!isPlain(value)) return value;
+        if (Object.hasOwn(value, '$rustyscript_repr')) {
+            return conversions.get(value.$rustyscript_repr).toJs(value.value);
+        }
+        return Object.fromEntries(Object.entries(value).map(([k, v]) => [k, toJs(v)]));
+    };
+
+    const fromJs = (value, names) => {
+        const active = names.map((name) => conversions.get(name));
+        const seen = new Map();
+        const walk = (value) => {
+            for (const conversion of active) {
+                if (conversion.isJs(value)) return conversion.fromJs(value);
+            }
+            if (!Array.isArray(value) && !isPlain(value)) return value;
+            if (seen.has(value)) return seen.get(value);
+
+            const copy = Array.isArray(value) ? [] : {};
+            seen.set(value, copy);
+            for (const [k, v] of Object.entries(value)) copy[k] = walk(v);
+            return copy;
+        };
+        return walk(value);
+    };
+
+    return [(name, conversion) => conversions.set(name, conversion), toJs, fromJs];
+})()";
+
+thread_local! {
+    // The conversions available to the value being sent to or read from JS on this thread
+    static TRACKING: RefCell<Option<Tracking>> = const { RefCell::new(None) };
+}
+
+struct Tracking {
+    registered: Vec<&'static str>,
+    reached: Vec<&'static str>,
+}
+
+/// Runs `f` with `registered` as the types [`JsRepr`] values can be converted for
+/// Returns its result, and the registered types it reached
+pub(crate) fn track<R>(
+    registered: &[&'static str],
+    f: impl FnOnce() -> R,
+) -> (R, Vec<&'static str>) {
+    let previous = TRACKING.replace(Some(Tracking {
+        registered: registered.to_vec(),
+        reached: Vec::new(),
+    }));
+    let result = f();
+    let tracking = TRACKING.replace(previous);
+    (result, tracking.map(|t| t.reached).unwrap_or_default())
+}
+
+/// Records that a value of the named type was reached, returning true if it has a conversion
+fn reach(name: &'static str) -> bool {
+    TRACKING.with_borrow_mut(|tracking| match tracking {
+        Some(tracking) if tracking.registered.contains(&name) => {
+            if !tracking.reached.contains(&name) {
+                tracking.reached.push(name);
+            }
+            true
+        }
+        _ => false,
+    })
+}
+
+/// Sends a value to JS, and reads it back, using the conversion registered for its type
+/// with [`crate::Runtime::register_conversion`]
+///
+/// Values are only converted when wrapped - use this in arguments (such as in `json_args!`),
+/// in return types, or as the type of a field. Without a registered conversion,
+/// the value is serialized and deserialized as usual
+///
+/// # Example
+/// ```rust
+/// use rustyscript::{ json_args, JsRepr, JsTypeConversion, Module, Runtime, Error };
+/// use serde::{ Deserialize, Serialize };
+///
+/// /// A timestamp, serialized as milliseconds since the epoch
+/// #[derive(Serialize, Deserialize)]
+/// struct Timestamp { ms: f64 }
+///
+/// # fn main() -> Result<(), Error> {
+/// let mut runtime = Runtime::new(Default::default())?;
+/// runtime.register_conversion::<Timestamp>(JsTypeConversion {
+///     to_js: "(v) => new Date(v.ms)".to_string(),
+///     is_js: "(v) => v instanceof Date".to_string(),
+///     from_js: "(v) => ({ ms: v.getTime() })".to_string(),
+/// })?;
+///
+/// let module = Module::new("test.js", "export const tomorrow = (d) => new Date(d.getTime() + 86400000);");
+/// let module = runtime.load_module(&module)?;
+///
+/// let start = JsRepr(Timestamp { ms: 0.0 });
+/// let next: JsRepr<Timestamp> = runtime.call_function(Some(&module), "tomorrow", json_args!(start))?;
+/// assert_eq!(86_400_000.0, next.0.ms);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JsRepr<T>(pub T);
+
+impl<T: Serialize + 'static> Serialize for JsRepr<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = std::any::type_name::<T>();
+        if reach(name) {
+            let mut map = serializer.serialize_map(Some(2))?;
+            map.serialize_entry(REPR_TAG, name)?;
+            map.serialize_entry("value", &self.0)?;
+            map.end()
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de> + 'static> Deserialize<'de> for JsRepr<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        reach(std::any::type_name::<T>());
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Describes a custom JS representation for a rust type
+/// Register it with [`crate::Runtime::register_conversion`], and wrap values of the type in a [`JsRepr`]
+///
+/// Each field is the source of a JS function:
+/// - A [`JsRepr`] value sent to JS is replaced with `to_js(value)`, given the value as it would be serialized
+/// - When reading back a value whose rust type contains a [`JsRepr`] of the type,
+///   values in it matching `is_js` are replaced with `from_js(value)` before being deserialized
+///
+/// # Example
+/// ```rust
+/// use rustyscript::JsTypeConversion;
+///
+/// // Timestamps serialize as RFC 3339 strings, but should be `Date` objects in JS
+/// let conversion = JsTypeConversion {
+///     to_js: "(v) => new Date(v)".to_string(),
+///     is_js: "(v) => v instanceof Date".to_string(),
+///     from_js: "(v) => v.toISOString()".to_string(),
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsTypeConversion {
+    /// Converts the serialized form of the type into its JS representation
+    pub to_js: String,
+
+    /// Predicate recognising the JS representation, when read back as the type
+    pub is_js: String,
+
+    /// Converts the JS representation back into a form the type can be deserialized from
+    pub from_js: String,
+}
+
+/// Handles to the JS side of the type registry
+pub(crate) struct TypeRegistry {
+    names: Vec<&'static str>,
+    register: v8::Global<v8::Function>,
+    to_js: v8::Global<v8::Function>,
+    from_js: v8::Global<v8::Function>,
+}
+
+impl TypeRegistry {
+    /// Create an empty registry within the runtime
+    pub fn new(runtime: &mut JsRuntime) -> Result<Self, Error> {
+        let functions = runtime.execute_script("", REGISTRY_SOURCE)?;
+
+        let mut scope = runtime.handle_scope();
+        let functions = v8::Local::new(&mut scope, functions);
+        let functions = v8::Local::<v8::Array>::try_from(functions)?;
+
+        let mut get = |i: u32| -> Result<v8::Global<v8::Function>, Error> {
+            let f = functions
+                .get_index(&mut scope, i)
+                .ok_or_else(|| Error::Runtime("Could not create type registry".to_string()))?;
+            let f = v8::Local::<v8::Function>::try_from(f)?;
+            Ok(v8::Global::new(&mut scope, f))
+        };
+
+        Ok(Self {
+            names: Vec::new(),
+            register: get(0)?,
+            to_js: get(1)?,
+            from_js: get(2)?,
+        })
+    }
+
+    /// The names of the types with a registered conversion
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    /// Add or replace the conversion for the named type
+    pub fn register(
+        &mut self,
+        runtime: &mut JsRuntime,
+        name: &'static str,
+        conversion: &JsTypeConversion,
+    ) -> Result<(), Error> {
+        let conversion = runtime.execute_script(
+            "",
+            format!(
+                "({{ toJs: ({}), isJs: ({}), fromJs: ({}) }})",
+                conversion.to_js, conversion.is_js, conversion.from_js
+            ),
+        )?;
+
+        {
+            let mut scope = runtime.handle_scope();
+            let name = name.to_v8_string(&mut scope)?;
+            let conversion = v8::Local::new(&mut scope, conversion);
+            Self::call(&mut scope, &self.register, &[name.into(), conversion])?;
+        }
+
+        if !self.names.contains(&name) {
+            self.names.push(name);
+        }
+        Ok(())
+    }
+
+    /// Convert the [`JsRepr`] values within a value being sent to JS
+    pub fn to_js<'s>(
+        &self,
+        scope: &mut v8::HandleScope<'s>,
+        value: v8::Local<'s, v8::Value>,
+    ) -> Result<v8::Local<'s, v8::Value>, Error> {
+        Self::call(scope, &self.to_js, &[value])
+    }
+
+    /// Convert a value being read back as a type containing [`JsRepr`]s of the named types
+    pub fn from_js<'s>(
+        &self,
+        scope: &mut v8::HandleScope<'s>,
+        value: v8::Local<'s, v8::Value>,
+        names: &[&'static str],
+    ) -> Result<v8::Local<'s, v8::Value>, Error> {
+        let names = names
+            .iter()
+            .map(|name| name.to_v8_string(scope).map(Into::into))
+            .collect::<Result<Vec<v8::Local<v8::Value>>, _>>()?;
+        let names = v8::Array::new_with_elements(scope, &names);
+        Self::call(scope, &self.from_js, &[value, names.into()])
+    }
+
+    fn call<'s>(
+        scope: &mut v8::HandleScope<'s>,
+        function: &v8::Global<v8::Function>,
+        args: &[v8::Local<'s, v8::Value>],
+    ) -> Result<v8::Local<'s, v8::Value>, Error> {
+        let mut scope = v8::TryCatch::new(scope);
+        let function = v8::Local::new(&mut scope, function);
+        let undefined = v8::undefined(&mut scope).into();
+
+        match function.call(&mut scope, undefined, args) {
+            Some(value) => Ok(value),
+            None => {
+                let message = scope.message().map_or_else(
+                    || "Unknown error".to_string(),
+                    |m| m.get(&mut scope).to_rust_string_lossy(&mut scope),
+                );
+                Err(Error::Runtime(format!("Type conversion failed: {message}")))
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
 use std::path::Path;
 
 use crate::traits::ToModuleSpecifier;
-use crate::{Error, Module, ModuleWrapper, Runtime, RuntimeOptions};
+use crate::{
+    Error, ExportComparison, ExportSignature, Module, ModuleWrapper, Runtime, RuntimeOptions,
+};
+use deno_core::serde_json;
 
 /// Evaluate a piece of non-ECMAScript-module JavaScript code
 /// Effects on the global scope will not persist
@@ -54,11 +57,111 @@ pub fn validate(javascript: &str) -> Result<bool, Error> {
     let mut runtime = Runtime::new(RuntimeOptions::default())?;
     match runtime.load_modules(&module, vec![]) {
         Ok(_) => Ok(true),
-        Err(Error::Runtime(_) | Error::JsError(_)) => Ok(false),
+        Err(Error::Runtime(_) | Error::JsError(_) | Error::StackOverflow) => Ok(false),
         Err(e) => Err(e),
     }
 }
 
+/// Loads two versions of a module, each into a new runtime, and compares their exports
+/// Useful to decide if a reloaded module can safely replace the one in use
+///
+/// # Arguments
+/// * `old` - The version currently in use
+/// * `new` - The candidate replacement
+///
+/// # Returns
+/// A `Result` containing the differences between the export signatures of the two versions
+///
+/// # Errors
+/// Will return an error if either module fails to load, or the runtime
+/// cannot be started (usually due to extension issues)
+///
+/// # Example
+///
+/// ```rust
+/// use rustyscript::Module;
+///
+/// let old = Module::new("lib.js", "export const f = (a) => a;");
+/// let new = Module::new("lib.js", "export const f = (a) => a; export const g = () => 1;");
+///
+/// let comparison = rustyscript::compare_exports(&old, &new).expect("Something went wrong!");
+/// assert!(comparison.is_backward_compatible());
+/// ```
+pub fn compare_exports(old: &Module, new: &Module) -> Result<ExportComparison, Error> {
+    let signatures = |module: &Module| -> Result<Vec<ExportSignature>, Error> {
+        let mut runtime = Runtime::new(RuntimeOptions::default())?;
+        let handle = runtime.load_module(module)?;
+        runtime.get_export_signatures(&handle)
+    };
+
+    Ok(ExportComparison::new(&signatures(old)?, &signatures(new)?))
+}
+
+/// Loads several configuration modules and deep-merges them, later modules taking precedence
+/// Useful for layered configuration, such as defaults followed by overrides
+///
+/// `.json` modules are parsed as JSON; any other module is executed in a new runtime,
+/// and its default export is used. Objects are merged key by key, recursively;
+/// any other value (including arrays) replaces the one it overrides
+///
+/// # Arguments
+/// * `modules` - The configuration layers, from lowest to highest precedence
+///
+/// # Returns
+/// A `Result` containing the merged configuration
+///
+/// # Errors
+/// Will return an error if a module is not valid JSON, fails to execute, or has a default export
+/// that cannot be represented as JSON, or if the runtime cannot be started (usually due to extension issues)
+///
+/// # Example
+///
+/// ```rust
+/// use rustyscript::{ Module, serde_json::json };
+///
+/// let defaults = Module::new("defaults.json", r#"{ "server": { "port": 80, "host": "localhost" } }"#);
+/// let overrides = Module::new("local.js", "export default { server: { port: 8080 } };");
+///
+/// let config = rustyscript::merge_config_modules(&[defaults, overrides]).expect("Something went wrong!");
+/// assert_eq!(config, json!({ "server": { "port": 8080, "host": "localhost" } }));
+/// ```
+pub fn merge_config_modules(modules: &[Module]) -> Result<serde_json::Value, Error> {
+    let mut runtime = None;
+    let mut merged = serde_json::Value::Null;
+    for module in modules {
+        let is_json = module
+            .filename()
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+        let layer = if is_json {
+            serde_json::from_str(module.contents())?
+        } else {
+            let runtime = match &mut runtime {
+                Some(runtime) => runtime,
+                None => runtime.insert(Runtime::new(RuntimeOptions::default())?),
+            };
+            let handle = runtime.load_module(module)?;
+            runtime.get_value(Some(&handle), "default")?
+        };
+
+        deep_merge(&mut merged, layer);
+    }
+
+    Ok(merged)
+}
+
+/// Merges `overlay` into `base` - objects are merged recursively, anything else is replaced
+fn deep_merge(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                deep_merge(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 /// Imports a JS module into a new runtime
 ///
 /// # Arguments
@@ -122,6 +225,15 @@ mod runtime_macros {
     /// It simply builds a tuple reference from the provided arguments
     ///
     /// You can also just pass a &tuple directly, or an &array, or even a single value
+    /// A single array, whether passed directly or through this macro, is spread into separate arguments -
+    /// wrap it in a one-element tuple, `&(vec,)`, to pass the whole array as one argument
+    ///
+    /// Each argument can be anything implementing `serde::Serialize` - structs, `HashMap`s, `Vec`s,
+    /// and any nesting of them. Values are serialized straight into v8, with no intermediate `serde_json::Value`
+    ///
+    /// Structs arrive as objects with their fields in declaration order
+    /// Maps arrive as objects with their keys in the map's iteration order, which for `HashMap` is unspecified -
+    /// use a `BTreeMap` for a stable order. Either way, JS lists integer-like keys first, in ascending order
     ///
     /// # Example
     /// ```rust
@@ -151,6 +263,45 @@ mod runtime_macros {
         };
     }
 
+    /// Map a series of byte buffers into arguments that arrive in javascript as `Uint8Array`s
+    /// Accepts a maximum of 16 arguments, each anything that can be viewed as `&[u8]`
+    ///
+    /// The bytes are copied into v8 as-is, skipping the JSON array-of-numbers encoding
+    /// To mix binary and other arguments, wrap the bytes in [`deno_core::ToJsBuffer`] inside `json_args!`
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Runtime, Module, bin_args };
+    ///
+    /// # fn main() -> Result<(), rustyscript::Error> {
+    /// let module = Module::new("test.js", "
+    ///     export const checksum = (a, b) => [...a, ...b].reduce((sum, byte) => sum + byte, 0);
+    ///     export const isBinary = (a) => a instanceof Uint8Array;
+    /// ");
+    ///
+    /// let mut runtime = Runtime::new(Default::default())?;
+    /// let module = runtime.load_module(&module)?;
+    ///
+    /// let data = vec![1u8, 2, 3];
+    /// let sum: u32 = runtime.call_function(Some(&module), "checksum", bin_args!(&data, b"\x04"))?;
+    /// assert_eq!(10, sum);
+    ///
+    /// let binary: bool = runtime.call_function(Some(&module), "isBinary", bin_args!(data))?;
+    /// assert!(binary);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[macro_export]
+    macro_rules! bin_args {
+        ($($arg:expr),*) => {
+            &($(
+                $crate::deno_core::ToJsBuffer::from(
+                    <[u8]>::to_vec(::std::convert::AsRef::<[u8]>::as_ref(&$arg))
+                )
+            ),*)
+        };
+    }
+
     /// Map a series of values into a form which javascript functions can understand
     /// This forms a `Vec<serde_json::Value>` from the provided arguments
     ///
@@ -247,6 +398,113 @@ mod runtime_macros {
             })
         }
     }
+
+    /// Generates a strongly-typed facade over a module's exports
+    ///
+    /// Given a trait describing the functions a module is expected to export, this produces:
+    /// - The trait itself, with each method taking `&mut self` and returning `Result<T, Error>`
+    /// - A wrapper type around a [`crate::ModuleWrapper`] implementing the trait by calling the JS export of the same name
+    ///
+    /// Creating the wrapper (with `new`) checks that every function in the trait is exported and callable,
+    /// so a mismatched module is rejected up-front, instead of on first use
+    ///
+    /// Hosts can then interact with scripts through the trait (including as a `Box<dyn Trait>`),
+    /// instead of stringly-typed calls to [`crate::Runtime::call_function`]
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ module_facade, Module, ModuleWrapper, Error };
+    ///
+    /// module_facade! {
+    ///     /// A plugin able to do some arithmetic
+    ///     pub trait Calculator for CalculatorModule {
+    ///         /// Add two numbers
+    ///         fn add(a: i64, b: i64) -> i64;
+    ///
+    ///         /// Get the name of the plugin
+    ///         fn name() -> String;
+    ///     }
+    /// }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let module = Module::new("calculator.js", "
+    ///     export const add = (a, b) => a + b;
+    ///     export const name = () => 'calculator';
+    /// ");
+    /// let wrapper = ModuleWrapper::new_from_module(&module, Default::default())?;
+    ///
+    /// let mut plugin: Box<dyn Calculator> = Box::new(CalculatorModule::new(wrapper)?);
+    /// assert_eq!(5, plugin.add(2, 3)?);
+    /// assert_eq!("calculator", plugin.name()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[macro_export]
+    macro_rules! module_facade {
+        (
+            $(#[$meta:meta])*
+            $vis:vis trait $name:ident for $facade:ident {
+                $(
+                    $(#[$fn_meta:meta])*
+                    fn $fn_name:ident($($arg:ident: $arg_ty:ty),* $(,)?) -> $ret:ty;
+                )*
+            }
+        ) => {
+            $(#[$meta])*
+            $vis trait $name {
+                $(
+                    $(#[$fn_meta])*
+                    ///
+                    /// # Errors
+                    /// Will return an error if the function throws, or if the result cannot be deserialized
+                    fn $fn_name(&mut self, $($arg: $arg_ty),*) -> Result<$ret, $crate::Error>;
+                )*
+            }
+
+            #[doc = concat!("Implements [`", stringify!($name), "`] by calling the exports of a loaded module")]
+            $vis struct $facade($crate::ModuleWrapper);
+
+            #[allow(dead_code)]
+            impl $facade {
+                /// The names of the functions the module is expected to export
+                pub const EXPORTS: &'static [&'static str] = &[$(stringify!($fn_name)),*];
+
+                /// Wrap a loaded module, checking that every expected export is present and callable
+                ///
+                /// # Errors
+                /// Will return `Error::ValueNotCallable` naming the first missing or non-function export
+                pub fn new(mut module: $crate::ModuleWrapper) -> Result<Self, $crate::Error> {
+                    for name in Self::EXPORTS {
+                        if !module.is_callable(name) {
+                            return Err($crate::Error::ValueNotCallable((*name).to_string()));
+                        }
+                    }
+
+                    Ok(Self(module))
+                }
+
+                /// Returns a mutable reference to the underlying module
+                pub fn inner(&mut self) -> &mut $crate::ModuleWrapper {
+                    &mut self.0
+                }
+
+                /// Consumes the facade, returning the underlying module
+                #[must_use]
+                pub fn into_inner(self) -> $crate::ModuleWrapper {
+                    self.0
+                }
+            }
+
+            impl $name for $facade {
+                $(
+                    fn $fn_name(&mut self, $($arg: $arg_ty),*) -> Result<$ret, $crate::Error> {
+                        // Always a tuple, so a lone array argument is not spread into several
+                        self.0.call(stringify!($fn_name), &($($arg,)*))
+                    }
+                )*
+            }
+        };
+    }
 }
 
 #[cfg(test)]
@@ -271,6 +529,181 @@ mod test_runtime {
         assert_eq!(serde_json::Value::Number(10.into()), result);
     }
 
+    #[test]
+    fn test_bin_args() {
+        let module = Module::new(
+            "test.js",
+            "
+            export const describe = (...args) => args.map((a) => `${a.constructor.name}:${a.length}`);
+            export const mixed = (bytes, n) => bytes[0] + n;
+            ",
+        );
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        let described: Vec<String> = runtime
+            .call_function(Some(&module), "describe", bin_args!(bytes, [1u8, 2], b""))
+            .expect("Could not call function");
+        assert_eq!(
+            vec!["Uint8Array:256", "Uint8Array:2", "Uint8Array:0"],
+            described
+        );
+
+        let value: u32 = runtime
+            .call_function(
+                Some(&module),
+                "mixed",
+                json_args!(deno_core::ToJsBuffer::from(vec![40u8]), 2),
+            )
+            .expect("Could not call function");
+        assert_eq!(42, value);
+    }
+
+    #[test]
+    fn test_json_args_serialize() {
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(serde::Serialize)]
+        struct Inner {
+            tags: Vec<&'static str>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct Config {
+            name: String,
+            limits: HashMap<String, u32>,
+            inner: Inner,
+        }
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const describe = (config) => `${config.name}:${config.limits.max}:${config.inner.tags.join(',')}`;
+            export const keys = (map) => Object.keys(map).join(',');
+            export const count = (...args) => args.length;
+            ",
+        );
+        let mut runtime =
+            Runtime::new(RuntimeOptions::default()).expect("Could not create runtime");
+        let module = runtime.load_module(&module).expect("Could not load module");
+
+        let config = Config {
+            name: "app".to_string(),
+            limits: HashMap::from([("max".to_string(), 10)]),
+            inner: Inner {
+                tags: vec!["a", "b"],
+            },
+        };
+        let described: String = runtime
+            .call_function(Some(&module), "describe", json_args!(&config))
+            .expect("Could not call function");
+        assert_eq!("app:10:a,b", described);
+
+        let map = BTreeMap::from([("b", 2), ("a", 1), ("c", 3)]);
+        let keys: String = runtime
+            .call_function(Some(&module), "keys", json_args!(&map))
+            .expect("Could not call function");
+        assert_eq!("a,b,c", keys);
+
+        // A single array is spread, unless wrapped in a one-element tuple
+        let count: usize = runtime
+            .call_function(Some(&module), "count", json_args!(vec![1, 2, 3]))
+            .expect("Could not call function");
+        assert_eq!(3, count);
+        let count: usize = runtime
+            .call_function(Some(&module), "count", &(vec![1, 2, 3],))
+            .expect("Could not call function");
+        assert_eq!(1, count);
+    }
+
+    #[test]
+    fn test_compare_exports() {
+        let old = Module::new(
+            "lib.js",
+            "export function f(a, b) {}; export const x = 1; export class C {}",
+        );
+        let new = Module::new("lib.js", "export function f(a) {}; export const C = 1;");
+
+        let comparison = compare_exports(&old, &new).expect("Could not compare exports");
+        assert!(!comparison.is_backward_compatible());
+        assert_eq!(1, comparison.removed.len());
+
+        // A class replaced by a plain value
+        assert_eq!(crate::ExportKind::Class, comparison.changed[0].0.kind);
+        assert_eq!(crate::ExportKind::Value, comparison.changed[0].1.kind);
+
+        assert_eq!(Some(2), comparison.changed[1].0.arity);
+        assert_eq!(Some(1), comparison.changed[1].1.arity);
+    }
+
+    #[test]
+    fn test_merge_config_modules() {
+        let defaults = Module::new(
+            "defaults.json",
+            r#"{ "name": "app", "db": { "host": "localhost", "port": 5432 }, "tags": ["a", "b"] }"#,
+        );
+        let env = Module::new(
+            "env.ts",
+            "export default { db: { port: 6543 as number }, tags: ['c'] };",
+        );
+        let local = Module::new("local.json", r#"{ "db": { "host": "db.internal" } }"#);
+
+        let config =
+            merge_config_modules(&[defaults, env, local]).expect("Could not merge configuration");
+        assert_eq!(
+            serde_json::json!({
+                "name": "app",
+                "db": { "host": "db.internal", "port": 6543 },
+                "tags": ["c"]
+            }),
+            config
+        );
+
+        let invalid = Module::new("broken.json", "{ nope");
+        merge_config_modules(&[invalid]).expect_err("Invalid JSON was accepted");
+    }
+
+    #[test]
+    fn test_module_facade() {
+        module_facade! {
+            trait Greeter for GreeterModule {
+                fn greet(name: String) -> String;
+                fn sum(a: i64, b: i64, c: i64) -> i64;
+                fn count(items: Vec<i64>) -> usize;
+            }
+        }
+
+        let module = Module::new(
+            "test.js",
+            "
+            export const greet = (name) => `Hello, ${name}`;
+            export const sum = (a, b, c) => a + b + c;
+            export const count = (items) => items.length;
+        ",
+        );
+        let wrapper = ModuleWrapper::new_from_module(&module, RuntimeOptions::default())
+            .expect("Could not load module");
+        let mut greeter: Box<dyn Greeter> =
+            Box::new(GreeterModule::new(wrapper).expect("Could not create facade"));
+
+        assert_eq!(
+            "Hello, world",
+            greeter.greet("world".to_string()).expect("Could not call")
+        );
+        assert_eq!(6, greeter.sum(1, 2, 3).expect("Could not call"));
+        assert_eq!(3, greeter.count(vec![1, 2, 3]).expect("Could not call"));
+
+        let module = Module::new("test.js", "export const greet = 5;");
+        let wrapper = ModuleWrapper::new_from_module(&module, RuntimeOptions::default())
+            .expect("Could not load module");
+        assert!(matches!(
+            GreeterModule::new(wrapper),
+            Err(Error::ValueNotCallable(_))
+        ));
+    }
+
     #[test]
     fn test_evaluate() {
         assert_eq!(5, evaluate::<i64>("3 + 2").expect("invalid expression"));
@@ -17,10 +17,18 @@
 //! }
 
 use crate::{Error, RuntimeOptions};
+use deno_core::futures::{channel::mpsc::UnboundedReceiver, Stream, StreamExt};
 use std::cell::RefCell;
+use std::pin::Pin;
 use std::rc::Rc;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
 use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+/// How long a stream's forwarding thread waits on a worker's response channel before releasing it
+const RESPONSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// A pool of worker threads that can be used to run javascript code in parallel
 /// Uses a round-robin strategy to distribute work between workers
@@ -106,6 +114,97 @@ where
         self.next_worker().borrow().send_and_await(query)
     }
 
+    /// Send a batch of requests to the pool, and receive the responses as an async stream
+    /// Requests are distributed between workers round-robin, and responses
+    /// are yielded in the order they complete - not the order they were sent
+    ///
+    /// No other requests should be sent to the pool until the stream has been exhausted,
+    /// or their responses may be yielded by the stream instead
+    ///
+    /// Dropping the stream early blocks until the remaining responses have arrived, and discards them -
+    /// so none are left behind to be mistaken for the responses to later requests
+    ///
+    /// # Errors
+    /// Will return an error if the pool has no workers, or if a worker has already been stopped
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use rustyscript::deno_core::futures::{ executor::block_on, StreamExt };
+    /// use rustyscript::{ Error, worker::{ WorkerPool, DefaultWorker, DefaultWorkerQuery, DefaultWorkerResponse } };
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let mut pool = WorkerPool::<DefaultWorker>::new(Default::default(), 2)?;
+    /// let queries = (0..4).map(|i| DefaultWorkerQuery::Eval(format!("{i} * 2")));
+    /// let mut results = pool.stream_results(queries)?;
+    ///
+    /// block_on(async {
+    ///     while let Some(result) = results.next().await {
+    ///         assert!(matches!(result?, DefaultWorkerResponse::Value(_)));
+    ///     }
+    ///     Ok::<(), Error>(())
+    /// })?;
+    ///
+    /// // A stream dropped early leaves no responses behind for later requests
+    /// let queries = (0..4).map(|i| DefaultWorkerQuery::Eval(format!("{i}")));
+    /// drop(pool.stream_results(queries)?);
+    /// let response = pool.send_and_await(DefaultWorkerQuery::Eval("'next'".to_string()))?;
+    /// assert!(matches!(response, DefaultWorkerResponse::Value(v) if v == "next"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream_results(
+        &mut self,
+        queries: impl IntoIterator<Item = W::Query>,
+    ) -> Result<WorkerResultStream<W::Response>, Error> {
+        if self.is_empty() {
+            return Err(Error::Runtime("Worker pool has no workers".to_string()));
+        }
+
+        let mut pending = vec![0usize; self.workers.len()];
+        for query in queries {
+            pending[self.next_worker] += 1;
+            self.next_worker().borrow().send(query)?;
+        }
+
+        // Forward each worker's responses from its own thread, so none can hold up the others
+        let (tx, rx) = deno_core::futures::channel::mpsc::unbounded();
+        let mut forwarders = Vec::new();
+        for (worker, count) in self.workers.iter().zip(pending) {
+            if count == 0 {
+                continue;
+            }
+
+            let responses = worker.borrow().responses();
+            let tx = tx.clone();
+            forwarders.push(spawn(move || {
+                let mut remaining = count;
+                while remaining > 0 {
+                    // Locked only while polling, not for the whole wait
+                    let response = responses
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv_timeout(RESPONSE_POLL_INTERVAL);
+                    let response = match response {
+                        Ok(response) => Ok(response),
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => Err(Error::WorkerHasStopped),
+                    };
+                    remaining -= 1;
+
+                    // Still received once the stream is dropped, so the channel is left empty
+                    let stopped = response.is_err();
+                    tx.unbounded_send(response).ok();
+                    if stopped {
+                        break;
+                    }
+                }
+            }));
+        }
+
+        Ok(WorkerResultStream { rx, forwarders })
+    }
+
     /// Evaluate a string of non-ecma javascript code in a separate thread
     /// The code is evaluated in a new runtime instance, which is then destroyed
     /// Returns a handle to the thread that is running the code
@@ -122,6 +221,110 @@ where
     }
 }
 
+/// An async stream of responses from a [`WorkerPool`], in the order they complete
+/// Ends once every request has been answered
+///
+/// See [`WorkerPool::stream_results`]
+pub struct WorkerResultStream<R> {
+    rx: UnboundedReceiver<Result<R, Error>>,
+    forwarders: Vec<JoinHandle<()>>,
+}
+impl<R> Stream for WorkerResultStream<R> {
+    type Item = Result<R, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_next_unpin(cx)
+    }
+}
+impl<R> Drop for WorkerResultStream<R> {
+    fn drop(&mut self) {
+        // Responses still to come are drained by the forwarding threads
+        self.rx.close();
+        for forwarder in self.forwarders.drain(..) {
+            forwarder.join().ok();
+        }
+    }
+}
+
+/// Runs a set of independent tasks across several threads, each with its own runtime
+/// Threads take the next waiting task as soon as they finish one, so uneven tasks are balanced
+///
+/// This sidesteps the runtime not being `Send`: runtimes are created on, and never leave, their threads
+///
+/// # Arguments
+/// * `n_threads` - The number of threads (and runtimes) to use - at least 1, and at most one per task
+/// * `options` - Creates the options for each thread's runtime
+/// * `tasks` - The inputs to process
+/// * `task` - Processes one input using a runtime. Runtimes are reused, so state left by one task is seen by the next on that thread
+///
+/// # Returns
+/// The result of each task, in the same order as `tasks`
+/// If a thread's runtime could not be created, each task it picks up fails with that error
+///
+/// # Panics
+/// Panics if a task panics
+///
+/// # Example
+///
+/// ```rust
+/// use rustyscript::{ Error, worker::run_parallel };
+///
+/// # fn main() -> Result<(), Error> {
+/// let results = run_parallel(4, Default::default, 0..10, |runtime, x: i64| {
+///     runtime.eval::<i64>(&format!("{x} * {x}"))
+/// });
+///
+/// assert_eq!(results[3].clone()?, 9);
+/// # Ok(())
+/// # }
+/// ```
+pub fn run_parallel<T, R, O, F>(
+    n_threads: usize,
+    options: O,
+    tasks: impl IntoIterator<Item = T>,
+    task: F,
+) -> Vec<Result<R, Error>>
+where
+    T: Send,
+    R: Send,
+    O: Fn() -> RuntimeOptions + Sync,
+    F: Fn(&mut crate::Runtime, T) -> Result<R, Error> + Sync,
+{
+    let tasks: Vec<T> = tasks.into_iter().collect();
+    let n_tasks = tasks.len();
+    let n_threads = n_threads.clamp(1, n_tasks.max(1));
+    crate::init_platform(u32::try_from(n_threads).unwrap_or(u32::MAX), true);
+
+    let queue = Mutex::new(tasks.into_iter().enumerate());
+    let results = Mutex::new((0..n_tasks).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..n_threads {
+            scope.spawn(|| {
+                let mut runtime = crate::Runtime::new(options());
+                loop {
+                    let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                    let Some((index, input)) = next else {
+                        break;
+                    };
+
+                    let result = match &mut runtime {
+                        Ok(runtime) => task(runtime, input),
+                        Err(e) => Err(e.clone()),
+                    };
+                    results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(Error::Runtime("Task was not run".to_string()))))
+        .collect()
+}
+
 /// A worker thread that can be used to run javascript code in a separate thread
 /// Contains a channel pair for communication, and a single runtime instance
 ///
@@ -135,7 +338,7 @@ where
 {
     handle: Option<JoinHandle<()>>,
     tx: Option<Sender<W::Query>>,
-    rx: Receiver<W::Response>,
+    rx: Arc<Mutex<Receiver<W::Response>>>,
 }
 
 impl<W> Worker<W>
@@ -172,7 +375,7 @@ where
         let worker = Self {
             handle: Some(handle),
             tx: Some(qtx),
-            rx: rrx,
+            rx: Arc::new(Mutex::new(rrx)),
         };
 
         // Wait for initialization to complete
@@ -234,23 +437,32 @@ where
     /// This will not block the current thread
     ///
     /// # Errors
-    /// Will return an error if the worker has already been stopped, or if the worker thread panicked
+    /// Will return [`Error::WorkerHasStopped`] if the worker has already been stopped, or if the worker thread panicked
     pub fn send(&self, query: W::Query) -> Result<(), Error> {
         match &self.tx {
             None => return Err(Error::WorkerHasStopped),
             Some(tx) => tx,
         }
         .send(query)
-        .map_err(|e| Error::Runtime(e.to_string()))
+        .map_err(|_| Error::WorkerHasStopped)
     }
 
     /// Receive a response from the worker
     /// This will block the current thread until a response is received
     ///
     /// # Errors
-    /// Will return an error if the worker has already been stopped, or if the worker thread panicked
+    /// Will return [`Error::WorkerHasStopped`] if the worker has already been stopped, or if the worker thread panicked
     pub fn receive(&self) -> Result<W::Response, Error> {
-        self.rx.recv().map_err(|e| Error::Runtime(e.to_string()))
+        self.rx
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv()
+            .map_err(|_| Error::WorkerHasStopped)
+    }
+
+    /// A shared handle to the worker's response channel
+    fn responses(&self) -> Arc<Mutex<Receiver<W::Response>>> {
+        Arc::clone(&self.rx)
     }
 
     /// Send a request to the worker and wait for a response
@@ -456,8 +668,35 @@ impl DefaultWorker {
     /// Evaluate a string of javascript code
     /// Returns the result of the evaluation
     ///
+    /// Errors raised in the worker are returned as-is, so they carry the same variant and detail
+    /// (such as the stack frames of an [`Error::JsError`]) as they would in an in-process runtime
+    ///
     /// # Errors
     /// Can fail a runtime error occurs during evaluation, or if the return value cannot be deserialized into the requested type
+    ///
+    /// # Example
+    /// ```rust
+    /// use rustyscript::{ Error, worker::{ DefaultWorker, DefaultWorkerOptions } };
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Point { x: i32, y: i32 }
+    ///
+    /// # fn main() -> Result<(), Error> {
+    /// let worker = DefaultWorker::new(DefaultWorkerOptions {
+    ///     timeout: std::time::Duration::from_secs(5),
+    ///     ..Default::default()
+    /// })?;
+    ///
+    /// let point: Point = worker.eval("({ x: 1, y: 2 })".to_string())?;
+    /// assert_eq!((1, 2), (point.x, point.y));
+    ///
+    /// match worker.eval::<Point>("throw new RangeError('out of bounds')".to_string()) {
+    ///     Err(Error::JsError(e)) => assert_eq!("RangeError", e.name),
+    ///     _ => panic!("Expected a JsError"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
     pub fn eval<T>(&self, code: String) -> Result<T, Error>
     where
         T: serde::de::DeserializeOwned,